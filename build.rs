@@ -7,4 +7,10 @@ fn main() {
         .include(&dir)
         .file(dir.join("parser.c"))
         .compile("tree-sitter-vhdl");
+
+    // `gb self update` needs to know which release artifact it was built from
+    println!(
+        "cargo:rustc-env=GB_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
 }
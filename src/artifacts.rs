@@ -0,0 +1,145 @@
+//! What ghdl leaves next to gb.toml after analyzing, and how gb moves it into
+//! `build/<profile>/`.
+//!
+//! - ghdl's llvm and gcc backends write an object file per source file. the
+//!   mcode backend writes none at all. when `ghdl --version` doesn't tell the
+//!   backend, gb expects objects everywhere but on windows, where ghdl's
+//!   builds mostly use mcode.
+//! - the work library (`work-obj93.cf`) names every file relative to where
+//!   ghdl was run, `file . "src/demo.vhd"`, with `\` as separator on windows.
+//!   in the build directory those paths need `../../` in front, written with
+//!   the separator the library already uses. absolute paths stay as they are.
+//! - the work library is named after the library and the standard, which can
+//!   also come from flags gb doesn't look into. after analysis gb moves
+//!   whichever `*-obj*.cf` ghdl wrote.
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ghdl::{self, Backend},
+    profile, simulator, Check, GbError,
+};
+
+/// how ghdl starts the entry of a file in a work library
+pub const FILE_PREFIX: &str = "file . \"";
+
+/// whether ghdl is expected to write object files, nvc keeps its code in
+/// its library
+pub fn writes_objects() -> bool {
+    simulator::get().name() == "ghdl"
+        && ghdl::backend().map_or(cfg!(not(windows)), Backend::writes_objects)
+}
+
+/// the object file ghdl writes for `source`, relative to where it runs
+pub fn object_file(source: &str) -> Result<PathBuf, GbError> {
+    let stem = Path::new(source)
+        .file_stem()
+        .fatal(format!("could not get file stem for {source}"))?;
+    Ok(PathBuf::from(stem).with_extension("o"))
+}
+
+/// moves the object files of `files` into the build directory
+pub fn move_objects(files: &[&str]) -> Result<(), GbError> {
+    std::fs::create_dir_all(profile::dir()).fatal("could not create build directory")?;
+    for file in files {
+        let object = object_file(file)?;
+        if !object.exists() && !writes_objects() {
+            continue;
+        }
+        let backend = ghdl::backend().map_or(String::new(), |backend| format!("{backend} "));
+        std::fs::rename(&object, profile::dir().join(&object)).fatal(format!(
+            "could not move `{}`, which ghdl's {backend}backend should have written, to the build dir",
+            object.display()
+        ))?;
+    }
+    Ok(())
+}
+
+/// the work libraries in `dir`, `work-obj93.cf`, `mylib-obj08.cf` and the like
+pub fn work_libraries(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut libraries = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            let Some((library, std)) = name
+                .strip_suffix(".cf")
+                .and_then(|name| name.rsplit_once("-obj"))
+            else {
+                return false;
+            };
+            !library.is_empty() && std.len() == 2 && std.bytes().all(|c| c.is_ascii_digit())
+        })
+        .collect::<Vec<_>>();
+    libraries.sort();
+    libraries
+}
+
+fn is_absolute(path: &str) -> bool {
+    let drive = path
+        .as_bytes()
+        .get(..2)
+        .is_some_and(|drive| drive[0].is_ascii_alphabetic() && drive[1] == b':');
+    drive || path.starts_with(['/', '\\'])
+}
+
+/// the path of a work library entry, as seen from the build directory
+fn relocate(path: &str) -> Cow<'_, str> {
+    if is_absolute(path) {
+        Cow::Borrowed(path)
+    } else if path.contains('\\') && !path.contains('/') {
+        Cow::Owned(format!("..\\..\\{path}"))
+    } else {
+        Cow::Owned(format!("../../{path}"))
+    }
+}
+
+/// the path of a work library entry, as seen from the project root
+pub fn unrelocate(path: &str) -> &str {
+    path.strip_prefix("../../")
+        .or_else(|| path.strip_prefix("..\\..\\"))
+        .unwrap_or(path)
+}
+
+/// rewrites the `file . "<path>"` entries of a work library with `rewrite`
+fn rewrite_library<'l>(library: &'l str, rewrite: impl Fn(&str) -> Cow<'_, str>) -> String {
+    let lines = library
+        .lines()
+        .map(|line| match line.strip_prefix(FILE_PREFIX) {
+            Some(rest) => Cow::Owned(format!("{FILE_PREFIX}{}", rewrite(rest))),
+            None => Cow::Borrowed(line),
+        })
+        .collect::<Vec<Cow<'l, str>>>();
+    lines.join("\n") + "\n"
+}
+
+/// a work library written from the project root, for the build directory
+pub fn library_for_build_dir(library: &str) -> String {
+    rewrite_library(library, relocate)
+}
+
+/// a work library from the build directory, for ghdl run from `dir`, a
+/// directory relative to the project root
+pub fn library_for_dir(library: &str, dir: &Path) -> String {
+    let depth = dir.components().count();
+    rewrite_library(library, |rest| {
+        let path = unrelocate(rest);
+        if is_absolute(path) {
+            Cow::Borrowed(path)
+        } else if path.contains('\\') && !path.contains('/') {
+            Cow::Owned(format!("{}{path}", "..\\".repeat(depth)))
+        } else {
+            Cow::Owned(format!("{}{path}", "../".repeat(depth)))
+        }
+    })
+}
+
+/// a work library from the build directory, for ghdl run from the project root
+pub fn library_for_root(library: &str) -> String {
+    rewrite_library(library, |rest| Cow::Borrowed(unrelocate(rest)))
+}
@@ -0,0 +1,151 @@
+//! A warnings baseline, so a legacy codebase can be cleaned up bit by bit:
+//! `--warnings-baseline baseline.json --update-baseline` records the warnings
+//! ghdl gives for a target today, after which `--warnings-baseline baseline.json`
+//! only fails when analysis gives one that isn't in there.
+//!
+//! warnings are matched by file and message, not by line, so editing a file
+//! doesn't turn all of its old warnings into new ones.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{diagnostics::Diagnostic, Check, GbError, Level};
+
+/// the warnings seen during this invocation
+static SEEN: Mutex<Vec<Diagnostic>> = Mutex::new(Vec::new());
+
+pub fn note(diagnostic: Diagnostic) {
+    if let Ok(mut seen) = SEEN.lock() {
+        seen.push(diagnostic);
+    }
+}
+
+fn take_seen() -> Vec<Diagnostic> {
+    SEEN.lock()
+        .map(|mut seen| std::mem::take(&mut *seen))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Warning {
+    file: String,
+    message: String,
+}
+
+impl From<&Diagnostic> for Warning {
+    fn from(diagnostic: &Diagnostic) -> Warning {
+        Warning {
+            file: diagnostic.file.clone(),
+            message: diagnostic.message.clone(),
+        }
+    }
+}
+
+/// the accepted warnings of every target, kept apart so one file can serve them all
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct File {
+    targets: BTreeMap<String, Vec<Warning>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    pub path: PathBuf,
+    pub target: String,
+    /// record the current warnings instead of checking against them
+    pub update: bool,
+}
+
+fn read(path: &Path) -> Result<File, GbError> {
+    if !path.exists() {
+        return Ok(File::default());
+    }
+    let file = std::fs::read_to_string(path).fatal(format!(
+        "could not read the warnings baseline `{}`",
+        path.display()
+    ))?;
+    serde_json::from_str(&file).fatal(format!(
+        "`{}` is not a warnings baseline, record one with --update-baseline",
+        path.display()
+    ))
+}
+
+impl Baseline {
+    /// compares the warnings analysis gave with the baseline, or records them
+    pub fn check(&self) -> Result<(), GbError> {
+        let seen = take_seen();
+        let mut file = read(&self.path)?;
+
+        if self.update {
+            let mut warnings = seen.iter().map(Warning::from).collect::<Vec<_>>();
+            warnings.sort();
+            let count = warnings.len();
+            file.targets.insert(self.target.clone(), warnings);
+            let json =
+                serde_json::to_string_pretty(&file).fatal("could not serialize the baseline")?;
+            std::fs::write(&self.path, json + "\n").fatal(format!(
+                "could not write the warnings baseline `{}`",
+                self.path.display()
+            ))?;
+            eprintln!(
+                "  {}  {}",
+                "[baseline]".blue().bold(),
+                format!(
+                    "Recorded {count} warnings of {} in {}",
+                    self.target,
+                    self.path.display()
+                )
+                .green()
+                .bold()
+            );
+            return Ok(());
+        }
+
+        let accepted = file.targets.remove(&self.target).unwrap_or_default();
+        let mut allowance = BTreeMap::<Warning, usize>::new();
+        for warning in accepted {
+            *allowance.entry(warning).or_default() += 1;
+        }
+        let new = seen
+            .iter()
+            .filter(|diagnostic| {
+                let left = allowance.entry(Warning::from(*diagnostic)).or_default();
+                if *left > 0 {
+                    *left -= 1;
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            return Ok(());
+        }
+
+        eprintln!(
+            "{} new warnings not in the baseline `{}`:",
+            new.len(),
+            self.path.display()
+        );
+        for (pos, diagnostic) in new.iter().enumerate() {
+            eprintln!(
+                "  {}. {}:{}:{}: {}",
+                pos + 1,
+                diagnostic.file,
+                diagnostic.line,
+                diagnostic.column,
+                diagnostic.message
+            );
+        }
+        Err(GbError {
+            message: "fix the new warnings, or accept them with --update-baseline".to_owned(),
+            level: Level::Fatal,
+            source: None,
+        })
+    }
+}
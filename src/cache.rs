@@ -0,0 +1,180 @@
+//! Incremental analysis: remembers a hash of every analyzed file in
+//! `build/<profile>/.gb-cache.json`, so only files which changed since (and
+//! the files depending on them) are handed to `ghdl -a` again.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{order, sources, BuildOptions, Check, GbError};
+
+/// every profile has a library of its own, and so a cache of its own
+fn cache_file() -> PathBuf {
+    crate::profile::dir().join(".gb-cache.json")
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    /// the flags the files were analyzed with, changing them invalidates everything
+    flags: Vec<String>,
+    /// file path to the sha256 of its contents when it was last analyzed
+    files: BTreeMap<String, String>,
+}
+
+fn flags(build: &BuildOptions) -> Vec<String> {
+    let mut flags = build.common_flags();
+    flags.extend(build.analyze_flags.iter().cloned());
+    flags
+}
+
+/// a missing or unreadable cache just means analyzing everything
+fn load() -> Cache {
+    std::fs::read_to_string(cache_file())
+        .ok()
+        .and_then(|cache| serde_json::from_str(&cache).ok())
+        .unwrap_or_default()
+}
+
+/// the subset of `files` which needs analyzing, in the same order
+pub fn stale_files<'f>(files: &[&'f str], build: &BuildOptions) -> Result<Vec<&'f str>, GbError> {
+    let cache = load();
+    let work_library = crate::profile::dir().join(build.work_library_file());
+    if !work_library.exists() {
+        // whatever the cache says was analyzed, it's gone now
+        invalidate();
+        return Ok(files.to_vec());
+    }
+    if cache.flags != flags(build) {
+        return Ok(files.to_vec());
+    }
+
+    let mut changed = HashSet::new();
+    for file in files {
+        let fingerprint = sources::fingerprint(file.as_ref())?;
+        if cache.files.get(*file) != Some(&fingerprint) {
+            changed.insert(sources::normalize(file.as_ref()));
+        }
+    }
+
+    Ok(with_dependents(files, &changed))
+}
+
+/// the files of `files` which are `changed`, or depend on one that is, in
+/// the same order
+fn with_dependents<'f>(files: &[&'f str], changed: &HashSet<PathBuf>) -> Vec<&'f str> {
+    let mut stale = files
+        .iter()
+        .map(|file| changed.contains(&sources::normalize(file.as_ref())))
+        .collect::<Vec<_>>();
+    // anything using a changed unit, be it a package, an entity or a
+    // component, has to be analyzed again too. keep going until no more
+    // dependents turn up.
+    let dependencies = order::dependencies(files);
+    loop {
+        let dependents = (0..files.len())
+            .filter(|&file| !stale[file] && dependencies[file].iter().any(|&dep| stale[dep]))
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            break;
+        }
+        for file in dependents {
+            stale[file] = true;
+        }
+    }
+    files
+        .iter()
+        .zip(stale)
+        .filter(|(_, stale)| *stale)
+        .map(|(file, _)| *file)
+        .collect()
+}
+
+/// remembers `files` as analyzed, as they are right now
+pub fn record(files: &[&str], build: &BuildOptions) -> Result<(), GbError> {
+    let mut cache = load();
+    if cache.flags != flags(build) {
+        cache = Cache {
+            flags: flags(build),
+            files: BTreeMap::new(),
+        };
+    }
+    for file in files {
+        cache
+            .files
+            .insert(file.to_string(), sources::fingerprint(file.as_ref())?);
+    }
+
+    std::fs::create_dir_all(crate::profile::dir()).fatal("could not create build directory")?;
+    let cache =
+        serde_json::to_string_pretty(&cache).fatal("could not serialize the build cache")?;
+    std::fs::write(cache_file(), cache).fatal("could not write the build cache")
+}
+
+/// forgets `files`, so they're analyzed again when they come back
+pub fn forget(files: &HashSet<PathBuf>) -> Result<(), GbError> {
+    let mut cache = load();
+    let before = cache.files.len();
+    cache
+        .files
+        .retain(|file, _| !files.contains(&sources::normalize(file.as_ref())));
+    if cache.files.len() == before {
+        return Ok(());
+    }
+    let cache =
+        serde_json::to_string_pretty(&cache).fatal("could not serialize the build cache")?;
+    std::fs::write(cache_file(), cache).fatal("could not write the build cache")
+}
+
+/// forgets everything, so the next analysis starts from scratch
+pub fn invalidate() {
+    if cache_file().exists() {
+        let _ = std::fs::remove_file(cache_file());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{self, Design};
+
+    fn stale_after_editing(design: &Design, edited: &str) -> Vec<String> {
+        let files = design.files();
+        let changed = files
+            .iter()
+            .filter(|file| file.ends_with(edited))
+            .map(|file| sources::normalize(file.as_ref()))
+            .collect();
+        with_dependents(&files, &changed)
+            .into_iter()
+            .map(|file| fixture::name(file).to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn editing_a_package_makes_its_users_stale() {
+        let design = Design::new("cache-package");
+        assert_eq!(
+            stale_after_editing(&design, "pkg.vhd"),
+            ["top.vhd", "user.vhd", "pkg_body.vhd", "pkg.vhd"]
+        );
+    }
+
+    #[test]
+    fn editing_an_entity_makes_what_instantiates_it_stale() {
+        let design = Design::new("cache-entity");
+        assert_eq!(
+            stale_after_editing(&design, "user.vhd"),
+            ["top.vhd", "user.vhd"]
+        );
+    }
+
+    #[test]
+    fn editing_a_leaf_makes_nothing_else_stale() {
+        let design = Design::new("cache-leaf");
+        assert_eq!(stale_after_editing(&design, "other.vhd"), ["other.vhd"]);
+        assert_eq!(stale_after_editing(&design, "top.vhd"), ["top.vhd"]);
+    }
+}
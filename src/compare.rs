@@ -0,0 +1,285 @@
+//! `gb compare-targets a b`: what sets two targets apart, e.g. a `fast` and a
+//! `full` variant of the same design. the settings of each target are shown
+//! with whatever they take from `[default]` filled in, and only where they
+//! differ. `--results` also puts the last runs of both side by side: how they
+//! exited, how long they took, their assertions and their waveforms.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use colored::Colorize;
+use toml_edit::{Document, Item};
+
+use crate::{diagnostics::Diagnostic, vcd, wave, Check, GbError, Level};
+
+/// the keys of `[default]` a target falls back on
+const DEFAULTED: &[&str] = &[
+    "std",
+    "vcd-viewer",
+    "memory-limit",
+    "cpu-time-limit",
+    "timeout",
+    "uses",
+];
+
+/// `item` as `dotted.key = value` pairs
+fn flatten(prefix: &str, item: &Item, out: &mut BTreeMap<String, String>) {
+    let key = |name: &str| {
+        if prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{prefix}.{name}")
+        }
+    };
+    if let Some(table) = item.as_table_like() {
+        for (name, item) in table.iter() {
+            flatten(&key(name), item, out);
+        }
+    } else if let Some(tables) = item.as_array_of_tables() {
+        let names = tables
+            .iter()
+            .map(|table| {
+                table
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or("?")
+            })
+            .collect::<Vec<_>>();
+        out.insert(prefix.to_owned(), names.join(", "));
+    } else if let Some(value) = item.as_value() {
+        let value = match value.as_str() {
+            Some(string) => string.to_owned(),
+            None => value.to_string().trim().to_owned(),
+        };
+        out.insert(prefix.to_owned(), value);
+    }
+}
+
+struct Resolved {
+    settings: BTreeMap<String, String>,
+    files: Vec<String>,
+    waveform: Option<wave::Waveform>,
+}
+
+fn resolve(doc: &Document, target: &str) -> Result<Resolved, GbError> {
+    let target_info = doc
+        .get("target")
+        .and_then(|targets| targets.get(target))
+        .fatal(format!("there is no target named `{target}` in gb.toml"))?;
+    let mut settings = BTreeMap::new();
+    if let Some(default) = doc.get("default") {
+        for key in DEFAULTED {
+            if let Some(item) = default.get(key) {
+                flatten(key, item, &mut settings);
+            }
+        }
+    }
+    if let Some(table) = target_info.as_table_like() {
+        for (key, item) in table.iter().filter(|(key, _)| *key != "files") {
+            // a target's own table replaces the default one as a whole
+            settings
+                .retain(|setting, _| setting != key && !setting.starts_with(&format!("{key}.")));
+            flatten(key, item, &mut settings);
+        }
+    }
+    Ok(Resolved {
+        settings,
+        files: crate::resolve_target_files(target, target_info)?,
+        waveform: wave::from_manifest(target, target_info)?,
+    })
+}
+
+/// what can be told about the last run of a target from its log and dump
+#[derive(Default)]
+struct Summary {
+    exit: Option<String>,
+    runtime: Option<Duration>,
+    severities: BTreeMap<String, usize>,
+    signals: Option<usize>,
+    end_time: Option<u64>,
+}
+
+fn summarize(log: &Path, waveform: Option<&wave::Waveform>) -> Option<Summary> {
+    let text = std::fs::read_to_string(log).ok()?;
+    let mut summary = Summary::default();
+    let mut started = None;
+    for line in text.lines() {
+        if let Some(time) = line.strip_prefix("# started: ") {
+            started = humantime::parse_rfc3339(time).ok();
+        } else if let Some(rest) = line.strip_prefix("# finished: ") {
+            let (time, status) = rest.split_once(' ').unwrap_or((rest, ""));
+            let finished = humantime::parse_rfc3339(time).ok();
+            summary.runtime = started
+                .zip(finished)
+                .and_then(|(started, finished)| finished.duration_since(started).ok());
+            summary.exit = Some(status.trim_matches(|c| c == '(' || c == ')').to_owned());
+        } else {
+            // `[timestamp] [stream] <line>`
+            let output = line.splitn(3, "] ").nth(2).unwrap_or(line);
+            if let Some(diagnostic) = Diagnostic::parse(output).filter(|d| d.time.is_some()) {
+                *summary.severities.entry(diagnostic.severity).or_default() += 1;
+            }
+        }
+    }
+    let dump = waveform
+        .filter(|waveform| waveform.format == wave::WaveFormat::Vcd)
+        .map(|waveform| waveform.built_path());
+    if let Some(dump) = dump.filter(|dump| dump.exists()) {
+        if let Ok(dump) = vcd::Vcd::load(&dump) {
+            summary.signals = Some(dump.signals.len());
+            summary.end_time = Some(dump.end_time());
+        }
+    }
+    Some(summary)
+}
+
+fn header(a: &str, b: &str, width: usize) {
+    println!("    {:width$}  {:<24}  {}", "", a.bold(), b.bold());
+}
+
+fn row(key: &str, a: &str, b: &str, width: usize) {
+    let marker = if a == b { " " } else { "*" };
+    println!("  {marker} {key:<width$}  {a:<24}  {b}");
+}
+
+fn print_files(a: &str, b: &str, files_a: &[String], files_b: &[String]) {
+    let set_a = files_a.iter().collect::<BTreeSet<_>>();
+    let set_b = files_b.iter().collect::<BTreeSet<_>>();
+    let only_a = set_a.difference(&set_b).collect::<Vec<_>>();
+    let only_b = set_b.difference(&set_a).collect::<Vec<_>>();
+    if only_a.is_empty() && only_b.is_empty() {
+        if files_a != files_b {
+            println!("  the same files, in a different order");
+        }
+        return;
+    }
+    for (target, only) in [(a, only_a), (b, only_b)] {
+        if only.is_empty() {
+            continue;
+        }
+        println!("  files only in {}", target.bold());
+        for (pos, file) in only.iter().enumerate() {
+            println!("    {}. {file}", pos + 1);
+        }
+    }
+}
+
+fn print_results(a: (&str, &Resolved), b: (&str, &Resolved)) {
+    let log = |target: &str| PathBuf::from("build").join(target).join("run.log");
+    let first = summarize(&log(a.0), a.1.waveform.as_ref());
+    let second = summarize(&log(b.0), b.1.waveform.as_ref());
+    println!();
+    println!("{}", "last runs".bold());
+    for (target, summary) in [(a.0, &first), (b.0, &second)] {
+        if summary.is_none() {
+            println!("  {target} has not been run yet, try `gb run {target}`");
+        }
+    }
+    let (Some(first), Some(second)) = (first, second) else {
+        return;
+    };
+
+    let mut rows = vec![
+        (
+            "exit".to_owned(),
+            first.exit.clone().unwrap_or_default(),
+            second.exit.clone().unwrap_or_default(),
+        ),
+        (
+            "runtime".to_owned(),
+            first
+                .runtime
+                .map(|runtime| humantime::format_duration(runtime).to_string())
+                .unwrap_or_default(),
+            second
+                .runtime
+                .map(|runtime| humantime::format_duration(runtime).to_string())
+                .unwrap_or_default(),
+        ),
+    ];
+    let severities = first
+        .severities
+        .keys()
+        .chain(second.severities.keys())
+        .collect::<BTreeSet<_>>();
+    for severity in severities {
+        let count = |summary: &Summary| {
+            summary
+                .severities
+                .get(severity)
+                .copied()
+                .unwrap_or(0)
+                .to_string()
+        };
+        rows.push((
+            format!("{severity} assertions"),
+            count(&first),
+            count(&second),
+        ));
+    }
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_owned());
+    rows.push((
+        "signals dumped".to_owned(),
+        optional(first.signals.map(|signals| signals.to_string())),
+        optional(second.signals.map(|signals| signals.to_string())),
+    ));
+    rows.push((
+        "dump ends at".to_owned(),
+        optional(first.end_time.map(vcd::format_time)),
+        optional(second.end_time.map(vcd::format_time)),
+    ));
+
+    let width = rows.iter().map(|(key, _, _)| key.len()).max().unwrap_or(0);
+    header(a.0, b.0, width);
+    for (key, first, second) in &rows {
+        row(key, first, second, width);
+    }
+}
+
+pub fn compare(doc: &Document, a: &str, b: &str, results: bool) -> Result<(), GbError> {
+    if a == b {
+        Err(GbError {
+            message: format!("`{a}` would only be compared with itself, pass two targets"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let first = resolve(doc, a)?;
+    let second = resolve(doc, b)?;
+
+    println!("{}", "configuration".bold());
+    let keys = first
+        .settings
+        .keys()
+        .chain(second.settings.keys())
+        .collect::<BTreeSet<_>>();
+    let differing = keys
+        .into_iter()
+        .filter(|key| first.settings.get(*key) != second.settings.get(*key))
+        .collect::<Vec<_>>();
+    if differing.is_empty() && first.files == second.files {
+        println!("  no differences");
+    } else if !differing.is_empty() {
+        let width = differing.iter().map(|key| key.len()).max().unwrap_or(0);
+        header(a, b, width);
+        for key in differing {
+            let value = |resolved: &Resolved| {
+                resolved
+                    .settings
+                    .get(key)
+                    .cloned()
+                    .unwrap_or_else(|| "-".to_owned())
+            };
+            row(key, &value(&first), &value(&second), width);
+        }
+    }
+    print_files(a, b, &first.files, &second.files);
+
+    if results {
+        print_results((a, &first), (b, &second));
+    }
+    Ok(())
+}
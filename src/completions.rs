@@ -0,0 +1,94 @@
+//! `gb completions <shell>`: prints a completion script for the shell, to be
+//! sourced from its startup file:
+//!
+//! ```sh
+//! gb completions bash > ~/.local/share/bash-completion/completions/gb
+//! gb completions zsh > ~/.zfunc/_gb
+//! gb completions fish > ~/.config/fish/completions/gb.fish
+//! ```
+//!
+//! the targets aren't known until there's a gb.toml, so for bash, zsh and
+//! fish the script asks `gb list --names` in the current directory whenever a
+//! command taking a target is completed. powershell only gets the commands
+//! and flags.
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{Cli, GbError};
+
+/// a target argument or `--target` in clap's zsh script
+static TARGET: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(:?:target(?: -- [^:']*)?|:TARGET):_default").unwrap());
+
+/// the commands whose first argument is a target
+fn target_commands() -> Vec<String> {
+    Cli::command()
+        .get_subcommands()
+        .filter(|command| {
+            command
+                .get_positionals()
+                .next()
+                .is_some_and(|positional| positional.get_id() == "target")
+        })
+        .map(|command| command.get_name().to_owned())
+        .collect()
+}
+
+fn bash(commands: &str) -> String {
+    format!(
+        r#"
+_gb_with_targets() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [[ $COMP_CWORD -eq 2 && "$cur" != -* ]]; then
+        case "${{COMP_WORDS[1]}}" in
+            {commands})
+                COMPREPLY=($(compgen -W "$(gb list --names 2>/dev/null)" -- "$cur"))
+                return 0
+                ;;
+        esac
+    fi
+    _gb "$@"
+}}
+complete -F _gb_with_targets -o bashdefault -o default gb
+"#,
+        commands = commands.replace(' ', "|")
+    )
+}
+
+fn zsh() -> &'static str {
+    r#"_gb_targets() {
+    local -a targets
+    targets=(${(f)"$(gb list --names 2>/dev/null)"})
+    _describe 'target' targets
+}"#
+}
+
+fn fish(commands: &str) -> String {
+    format!(
+        "\ncomplete -c gb -n \"__fish_seen_subcommand_from {commands}\" -f -a \"(gb list --names 2>/dev/null)\"\n"
+    )
+}
+
+pub fn completions(shell: Shell) -> Result<(), GbError> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "gb", &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+
+    let commands = target_commands().join(" ");
+    match shell {
+        Shell::Bash => script.push_str(&bash(&commands)),
+        Shell::Zsh => {
+            // clap leaves a target to the default completion, of files
+            script = TARGET.replace_all(&script, "$1:_gb_targets").into_owned();
+            let start = "if [ \"$funcstack[1]\" = \"_gb\" ]";
+            script = script.replacen(start, &format!("{}\n\n{start}", zsh()), 1);
+        }
+        Shell::Fish => script.push_str(&fish(&commands)),
+        _ => {}
+    }
+    print!("{script}");
+    Ok(())
+}
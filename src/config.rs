@@ -0,0 +1,144 @@
+//! gb's own configuration, the defaults of a machine rather than of a
+//! project, in `~/.config/gb/config.toml` (`$XDG_CONFIG_HOME/gb/config.toml`
+//! when that's set, `%APPDATA%\gb\config.toml` on windows, or wherever
+//! `GB_CONFIG` points):
+//!
+//! ```toml
+//! color = "never"     # "auto", "always" or "never"
+//!
+//! [ghdl]
+//! path = "/opt/ghdl/bin/ghdl"
+//!
+//! [default]
+//! vcd-viewer = "surfer"
+//! jobs = 8
+//! ```
+//!
+//! a setting comes from the first of these that has it:
+//!
+//! 1. the command line, and the environment, like `GB_GHDL` or `NO_COLOR`
+//! 2. the target's table in gb.toml
+//! 3. `[default]`, `[ghdl]` or `[nvc]` in gb.toml
+//! 4. this file
+//! 5. gb's own default
+//!
+//! `[default]`, `[ghdl]` and `[nvc]` are merged underneath gb.toml's key by
+//! key, so the file only fills in what a project leaves out. `default.target`
+//! is a project's business and isn't read from here.
+
+use std::path::PathBuf;
+
+use once_cell::sync::OnceCell;
+use toml_edit::{Document, Item, Table};
+
+use crate::{report, Check, GbError, Level};
+
+pub const ENV: &str = "GB_CONFIG";
+
+/// the tables merged underneath gb.toml's
+const TABLES: &[&str] = &["default", "ghdl", "nvc"];
+
+static CONFIG: OnceCell<Option<Document>> = OnceCell::new();
+
+/// where the file is looked for
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(ENV).filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                std::env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        })?;
+    Some(base.join("gb").join("config.toml"))
+}
+
+fn read() -> Result<Option<Document>, GbError> {
+    let Some(path) = path() else {
+        return Ok(None);
+    };
+    let Ok(config) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let config = config
+        .parse::<Document>()
+        .fatal(format!("failed to parse `{}`", path.display()))?;
+    for (key, _) in config.iter() {
+        if key != "color" && !TABLES.contains(&key) {
+            report::emit(report::warning(format!(
+                "gb doesn't know `{key}` in `{}`, it's ignored",
+                path.display()
+            )))?;
+        }
+    }
+    Ok(Some(config))
+}
+
+/// the configuration file, read the first time it's needed
+pub fn get() -> Result<Option<&'static Document>, GbError> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config.as_ref());
+    }
+    let config = read()?;
+    Ok(CONFIG.get_or_init(|| config).as_ref())
+}
+
+/// applies `color`, unless the environment already decided
+pub fn apply_color() -> Result<(), GbError> {
+    let Some(color) = get()?.and_then(|config| config.get("color")) else {
+        return Ok(());
+    };
+    let decided = ["NO_COLOR", "CLICOLOR_FORCE"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some());
+    let color = match color.as_str() {
+        Some("auto") => None,
+        Some("always") => Some(true),
+        Some("never") => Some(false),
+        _ => Err(GbError {
+            message: "`color` in gb's configuration must be \"auto\", \"always\" or \"never\""
+                .to_owned(),
+            level: Level::Fatal,
+            source: None,
+        })?,
+    };
+    if let Some(color) = color.filter(|_| !decided) {
+        colored::control::set_override(color);
+    }
+    Ok(())
+}
+
+/// fills in the keys of `[default]`, `[ghdl]` and `[nvc]` gb.toml doesn't set
+pub fn merge_into(doc: &mut Document) -> Result<(), GbError> {
+    let Some(config) = get()? else {
+        return Ok(());
+    };
+    for name in TABLES {
+        let Some(table) = config.get(name) else {
+            continue;
+        };
+        let table = table
+            .as_table_like()
+            .fatal(format!("`{name}` in gb's configuration must be a table"))?;
+        if doc.get(name).is_none() {
+            let mut implicit = Table::new();
+            implicit.set_implicit(true);
+            doc.insert(name, Item::Table(implicit));
+        }
+        let Some(project) = doc.get_mut(name).and_then(Item::as_table_like_mut) else {
+            continue;
+        };
+        for (key, item) in table.iter() {
+            let project_only = *name == "default" && key == "target";
+            if !project_only && project.get(key).is_none() {
+                project.insert(key, item.clone());
+            }
+        }
+    }
+    Ok(())
+}
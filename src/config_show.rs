@@ -0,0 +1,357 @@
+//! `gb config show`: every setting of a target as gb ends up with it, and
+//! where it comes from:
+//!
+//! ```text
+//! target = "counter"              # gb use
+//! std = "08"                      # gb.toml [target.counter]
+//! vcd-viewer = "surfer"           # ~/.config/gb/config.toml [default]
+//! waveform = "build/debug/counter.vcd"   # vcd-name of the target
+//! ghdl.path = "/opt/ghdl/bin/ghdl"       # GB_GHDL
+//! ```
+//!
+//! a setting comes from the first source having it, in the order `config`
+//! lists them: the command line and the environment, the target, the rest
+//! of gb.toml with gb.local.toml over it, gb's configuration file and last
+//! gb's own defaults. `--json` prints the same as an array of `{key, value,
+//! source}`.
+
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+use serde::Serialize;
+use toml_edit::{Document, Item};
+
+use crate::{
+    config, local,
+    manifest::{join, Origins},
+    shell, state, wave, Check, GbError, GlobalOptions, Level,
+};
+
+/// the keys of `[default]` a target falls back on, and `jobs`
+const DEFAULTED: &[&str] = &[
+    "cpu-time-limit",
+    "jobs",
+    "memory-limit",
+    "std",
+    "timeout",
+    "uses",
+    "vcd-stream",
+    "vcd-viewer",
+];
+
+/// the top level keys that are a single setting
+const TOP_LEVEL: &[&str] = &["simulator", "strict", "werror"];
+
+#[derive(Debug, Clone, Serialize)]
+struct Setting {
+    value: String,
+    source: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Shown<'s> {
+    key: &'s str,
+    value: &'s str,
+    source: &'s str,
+}
+
+struct Settings<'o> {
+    values: BTreeMap<String, Setting>,
+    /// where what gb.toml has from includes and `extends` comes from
+    origins: &'o Origins,
+}
+
+impl Settings<'_> {
+    /// sets `key`, over whatever a source further down had
+    fn set(&mut self, key: &str, value: impl Into<String>, source: &str) {
+        self.values.insert(
+            key.to_owned(),
+            Setting {
+                value: value.into(),
+                source: source.to_owned(),
+            },
+        );
+    }
+
+    /// `item` under `prefix`, a table replacing the whole table set before.
+    /// `path` is where gb.toml has it, to look up where it really comes from
+    fn set_item(&mut self, prefix: &str, path: Option<&str>, item: &Item, source: &str) {
+        if let Some(table) = item.as_table_like() {
+            self.values
+                .retain(|key, _| !key.starts_with(&format!("{prefix}.")));
+            for (name, item) in table.iter() {
+                let path = path.map(|path| join(path, name));
+                self.set_item(&format!("{prefix}.{name}"), path.as_deref(), item, source);
+            }
+            return;
+        }
+        let origins = self.origins;
+        let source = path.and_then(|path| origins.of(path)).unwrap_or(source);
+        if let Some(tables) = item.as_array_of_tables() {
+            self.set(prefix, format!("{} tables", tables.len()), source);
+        } else if let Some(value) = item.as_value() {
+            self.set(prefix, value.to_string().trim(), source);
+        }
+    }
+
+    /// the keys of `table` among `keys`, every one without `keys`. `path` is
+    /// where gb.toml has the table, when it's gb.toml's
+    fn set_table(
+        &mut self,
+        prefix: Option<&str>,
+        (table, path): (Option<&Item>, Option<&str>),
+        keys: Option<&[&str]>,
+        source: &str,
+    ) {
+        let Some(table) = table.and_then(Item::as_table_like) else {
+            return;
+        };
+        for (key, item) in table.iter() {
+            if keys.is_some_and(|keys| !keys.contains(&key)) {
+                continue;
+            }
+            let path = path.map(|path| join(path, key));
+            let key = match prefix {
+                Some(prefix) => format!("{prefix}.{key}"),
+                None => key.to_owned(),
+            };
+            self.set_item(&key, path.as_deref(), item, source);
+        }
+    }
+
+    /// the top level settings and tables of gb.toml, or of gb.local.toml,
+    /// which `tracked` tells apart
+    fn set_manifest(&mut self, doc: &Document, name: &str, tracked: bool) {
+        let path = |path| tracked.then_some(path);
+        for key in TOP_LEVEL {
+            if let Some(item) = doc.get(key) {
+                self.set_item(key, path(*key), item, name);
+            }
+        }
+        self.set_table(
+            None,
+            (doc.get("default"), path("default")),
+            Some(DEFAULTED),
+            &format!("{name} [default]"),
+        );
+        for table in ["ghdl", "nvc"] {
+            self.set_table(
+                Some(table),
+                (doc.get(table), path(table)),
+                None,
+                &format!("{name} [{table}]"),
+            );
+        }
+    }
+
+    /// the keys of a target, but its files. `path` is where gb.toml has it
+    fn set_target(&mut self, target_info: Option<&Item>, path: Option<&str>, source: &str) {
+        if let Some(table) = target_info.and_then(Item::as_table_like) {
+            for (key, item) in table.iter().filter(|(key, _)| *key != "files") {
+                let path = path.map(|path| join(path, key));
+                self.set_item(key, path.as_deref(), item, source);
+            }
+        }
+    }
+}
+
+fn quoted(value: &str) -> String {
+    toml_edit::Value::from(value).to_string().trim().to_owned()
+}
+
+/// the target gb picks, and why
+fn pick_target(
+    doc: &Document,
+    origins: &Origins,
+    local: Option<&Document>,
+    target: Option<&str>,
+) -> Result<Option<(String, String)>, GbError> {
+    if let Some(target) = target {
+        return Ok(Some((target.to_owned(), "command line".to_owned())));
+    }
+    if let Some(target) = shell::session_target() {
+        return Ok(Some((target, "gb shell".to_owned())));
+    }
+    if let Some(target) = state::local_default_target()? {
+        return Ok(Some((target, "gb use".to_owned())));
+    }
+    let default_target = |doc: &Document| {
+        doc.get("default")
+            .and_then(|default| default.get("target"))
+            .and_then(|target| target.as_str())
+            .map(str::to_owned)
+    };
+    if let Some(target) = local.and_then(default_target) {
+        return Ok(Some((target, format!("{} [default]", local::FILE))));
+    }
+    Ok(default_target(doc).map(|target| (target, origins.of_or_manifest("default.target"))))
+}
+
+/// `doc` is gb.toml without the configuration merged in, `origins` where
+/// what it has from includes and `extends` comes from
+pub fn show(
+    doc: &Document,
+    origins: &Origins,
+    target: Option<&str>,
+    options: &GlobalOptions,
+    json: bool,
+) -> Result<(), GbError> {
+    let mut settings = Settings {
+        values: BTreeMap::new(),
+        origins,
+    };
+
+    // gb's own defaults
+    let default = "gb's default";
+    settings.set("simulator", quoted("ghdl"), default);
+    settings.set("ghdl.path", quoted("ghdl"), default);
+    settings.set("jobs", "1", default);
+    settings.set("color", quoted("auto"), default);
+    settings.set("std", quoted("93c"), "ghdl's default");
+
+    // gb's configuration file
+    if let Some(config) = config::get()? {
+        let source = config::path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| "gb's configuration".to_owned());
+        if let Some(color) = config.get("color") {
+            settings.set_item("color", None, color, &source);
+        }
+        settings.set_table(
+            None,
+            (config.get("default"), None),
+            Some(DEFAULTED),
+            &format!("{source} [default]"),
+        );
+        for table in ["ghdl", "nvc"] {
+            settings.set_table(
+                Some(table),
+                (config.get(table), None),
+                None,
+                &format!("{source} [{table}]"),
+            );
+        }
+    }
+
+    // gb.toml, then what gb.local.toml sets over it
+    let local = local::read()?;
+    settings.set_manifest(doc, "gb.toml", true);
+    if let Some(local) = &local {
+        settings.set_manifest(local, local::FILE, false);
+    }
+
+    let picked = pick_target(doc, origins, local.as_ref(), target)?;
+    let target_info = picked.as_ref().and_then(|(target, _)| {
+        doc.get("target")
+            .and_then(|targets| targets.get(target.as_str()))
+    });
+    if let Some((target, source)) = &picked {
+        if target_info.is_none() {
+            Err(GbError {
+                message: format!("there is no target `{target}` in gb.toml"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        settings.set("target", quoted(target), source);
+        let path = format!("target.{target}");
+        settings.set_target(
+            target_info,
+            Some(&path),
+            &format!("gb.toml [target.{target}]"),
+        );
+        let local_info = local
+            .as_ref()
+            .and_then(|local| local.get("target"))
+            .and_then(|targets| targets.get(target.as_str()));
+        settings.set_target(
+            local_info,
+            None,
+            &format!("{} [target.{target}]", local::FILE),
+        );
+    }
+
+    // the environment, then the command line
+    for (variable, key) in [("GB_GHDL", "ghdl.path"), ("GB_NVC", "nvc.path")] {
+        if let Some(value) = std::env::var_os(variable).filter(|value| !value.is_empty()) {
+            settings.set(key, quoted(&value.to_string_lossy()), variable);
+        }
+    }
+    if std::env::var_os("NO_COLOR").is_some() {
+        settings.set("color", quoted("never"), "NO_COLOR");
+    } else if std::env::var_os("CLICOLOR_FORCE").is_some() {
+        settings.set("color", quoted("always"), "CLICOLOR_FORCE");
+    }
+    let profile = if options.release {
+        settings.set("profile", quoted("release"), "--release");
+        "release".to_owned()
+    } else if let Some(profile) = &options.profile {
+        settings.set("profile", quoted(profile), "--profile");
+        profile.clone()
+    } else {
+        settings.set("profile", quoted("debug"), default);
+        "debug".to_owned()
+    };
+    if let Some(timeout) = options.timeout {
+        settings.set(
+            "timeout",
+            quoted(&humantime::format_duration(timeout).to_string()),
+            "--timeout",
+        );
+    }
+    if options.strict {
+        settings.set("strict", "true", "--strict");
+    }
+
+    // what the target's keys come down to
+    if let (Some((target, _)), Some(target_info)) = (&picked, target_info) {
+        let waveform = wave::from_manifest(target, target_info)?;
+        let (value, source) = match waveform {
+            Some(waveform) => {
+                let key = ["wave-name", "wave-format", "vcd-name"]
+                    .into_iter()
+                    .find(|key| target_info.get(key).is_some())
+                    .unwrap_or("vcd-name");
+                let path = std::path::Path::new("build")
+                    .join(&profile)
+                    .join(&waveform.path);
+                (
+                    quoted(&path.display().to_string()),
+                    format!("{key} of the target"),
+                )
+            }
+            None => (
+                "none".to_owned(),
+                "no vcd-name, wave-name or wave-format, only `--vcd` dumps".to_owned(),
+            ),
+        };
+        settings.set("waveform", value, &source);
+    }
+
+    if json {
+        let shown = settings
+            .values
+            .iter()
+            .map(|(key, setting)| Shown {
+                key,
+                value: &setting.value,
+                source: &setting.source,
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&shown).fatal("could not serialize the settings")?
+        );
+        return Ok(());
+    }
+    let lines = settings
+        .values
+        .iter()
+        .map(|(key, setting)| (format!("{key} = {}", setting.value), &setting.source))
+        .collect::<Vec<_>>();
+    let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+    for (line, source) in lines {
+        println!("{line:width$}  {}", format!("# {source}").dimmed());
+    }
+    Ok(())
+}
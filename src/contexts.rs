@@ -0,0 +1,60 @@
+//! VHDL-2008 context units, which bundle library and use clauses so that a
+//! design unit only needs `context work.project_ctx;`. The file declaring a
+//! context has to be analyzed before any file referencing it.
+//!
+//! context clauses are simple enough to pick out of the source text, so this
+//! doesn't go through tree-sitter.
+
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::sources;
+
+static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"--[^\n]*").unwrap());
+static DECLARATION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bcontext\s+([a-z][a-z0-9_]*)\s+is\b").unwrap());
+static REFERENCE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bcontext\s+work\s*\.\s*([a-z][a-z0-9_]*)\s*;").unwrap());
+
+fn names(path: &Path, regex: &Regex) -> Vec<String> {
+    let Ok(code_src) = std::fs::read_to_string(path) else {
+        return vec![];
+    };
+    let code_src = COMMENT.replace_all(&code_src, "");
+    let mut names = regex
+        .captures_iter(&code_src)
+        .map(|captures| captures[1].to_lowercase())
+        .collect::<Vec<_>>();
+    names.dedup();
+    names
+}
+
+/// the contexts `path` declares, lowercased since vhdl doesn't care
+pub fn declared(path: &Path) -> Vec<String> {
+    names(path, &DECLARATION)
+}
+
+/// the contexts of the work library `path` references
+pub fn referenced(path: &Path) -> Vec<String> {
+    names(path, &REFERENCE)
+}
+
+/// the files declaring the contexts `path` references, searched for in the whole project
+pub fn dependencies(path: &Path) -> Vec<PathBuf> {
+    let referenced = referenced(path);
+    if referenced.is_empty() {
+        return vec![];
+    }
+    let own = sources::normalize(path);
+    sources::find_vhdl_sources(Path::new("."))
+        .into_iter()
+        .filter(|source| *source != own)
+        .filter(|source| {
+            declared(source)
+                .iter()
+                .any(|context| referenced.contains(context))
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+//! `gb cover`: runs the testbenches like `gb test`, with the design
+//! instrumented for coverage, and renders which lines they reached as html in
+//! `build/coverage/html/`.
+//!
+//! only ghdl's gcc backend instruments code, and the report needs `lcov` and
+//! its `genhtml`. the instrumented build is kept apart in `build/coverage/`,
+//! so it doesn't end up in a normal one.
+//!
+//! gcc leaves its `.gcno` and `.gcda` files where ghdl analyzed, in the
+//! project's directory, gb moves them to `build/coverage/data/` afterwards.
+
+use std::{path::PathBuf, process::Command};
+
+use colored::Colorize;
+use toml_edit::Document;
+
+use crate::{ghdl, profile, simulator, test, verbosity, BuildOptions, Check, GbError, Level};
+
+/// flags instrumenting the design, when analyzing
+pub const ANALYZE_FLAGS: &[&str] = &["-fprofile-arcs", "-ftest-coverage"];
+
+/// and linking the runtime gathering the counts, when elaborating
+pub const ELABORATE_FLAGS: &[&str] = &["-Wl,-lgcov"];
+
+fn dir() -> PathBuf {
+    PathBuf::from("build").join(profile::COVERAGE)
+}
+
+/// moves gcc's coverage notes and counts out of the project's directory
+fn collect() -> Result<PathBuf, GbError> {
+    let data = dir().join("data");
+    std::fs::create_dir_all(&data).fatal(format!("could not create `{}`", data.display()))?;
+    let entries = std::fs::read_dir(".").fatal("could not read the project's directory")?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_coverage = path
+            .extension()
+            .is_some_and(|extension| extension == "gcno" || extension == "gcda");
+        if is_coverage {
+            let to = data.join(entry.file_name());
+            std::fs::rename(&path, &to).fatal(format!(
+                "could not move `{}` to `{}`",
+                path.display(),
+                to.display()
+            ))?;
+        }
+    }
+    Ok(data)
+}
+
+fn run(command: &mut Command, tool: &str) -> Result<(), GbError> {
+    verbosity::echo(command);
+    let status = command
+        .status()
+        .fatal(format!("couldn't spawn {tool}, is lcov installed?"))?;
+    if !status.success() {
+        Err(GbError {
+            message: format!("{tool} failed ({status})"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+pub fn cover(doc: &Document, build: &BuildOptions) -> Result<(), GbError> {
+    simulator::require_ghdl("coverage")?;
+    if ghdl::backend() != Some(ghdl::Backend::Gcc) {
+        let backend =
+            ghdl::backend().map_or("an unknown".to_owned(), |backend| format!("the {backend}"));
+        Err(GbError {
+            message: format!(
+                "coverage needs ghdl's gcc backend, `{}` has {backend} backend",
+                ghdl::path()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    // failing testbenches still covered something, the report comes first
+    let tested = test::run_tests(
+        doc,
+        build,
+        test::Seeds::default(),
+        &test::Selection::default(),
+        false,
+        &[],
+    );
+
+    verbosity::step("[cover]", "Collecting coverage...");
+    let data = collect()?;
+    let info = dir().join("coverage.info");
+    run(
+        Command::new("lcov")
+            .args(["--quiet", "--capture", "--base-directory", "."])
+            .arg("--directory")
+            .arg(&data)
+            .arg("--output-file")
+            .arg(&info),
+        "lcov",
+    )?;
+    let html = dir().join("html");
+    run(
+        Command::new("genhtml")
+            .arg("--quiet")
+            .arg(&info)
+            .arg("--output-directory")
+            .arg(&html),
+        "genhtml",
+    )?;
+    eprintln!(
+        "  {}  {}",
+        "[cover]".blue().bold(),
+        format!("Wrote {}", html.join("index.html").display())
+            .green()
+            .bold()
+    );
+    tested
+}
@@ -0,0 +1,139 @@
+//! Targets building on other targets of the same gb.toml:
+//!
+//! ```toml
+//! [target.common]
+//! files = ["src/fifo.vhd", "src/common_pkg.vhd"]
+//! library = "common"
+//!
+//! [target.top]
+//! files = ["src/top.vhd", "src/top_tb.vhd"]
+//! depends = ["common"]
+//! ```
+//!
+//! before `top` is analyzed, every target it depends on is, after the ones
+//! those depend on. each goes into a library named after its `library`, or
+//! after the target without one, in `build/<profile>/targets/<target>/`,
+//! which `top` then gets as `-P` and so can `use common.common_pkg.all`. a
+//! target none of whose files changed isn't analyzed again.
+
+use std::path::PathBuf;
+
+use toml_edit::Document;
+
+use crate::{exit, profile, simulator, verbosity, BuildOptions, Check, GbError, Level};
+
+/// the targets `target` names in `depends`
+fn depends(doc: &Document, target: &str) -> Result<Vec<String>, GbError> {
+    let Some(depends) = doc["target"][target].get("depends") else {
+        return Ok(vec![]);
+    };
+    let depends = depends
+        .as_array()
+        .and_then(|depends| {
+            depends
+                .iter()
+                .map(|depend| depend.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()
+        })
+        .fatal(format!(
+            "`depends` of {target} must be an array of target names"
+        ))?;
+    if let Some(missing) = depends
+        .iter()
+        .find(|depend| doc["target"].get(depend.as_str()).is_none())
+    {
+        Err(GbError {
+            message: format!("{target} depends on `{missing}`, which is not a target"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(depends)
+}
+
+fn visit(
+    doc: &Document,
+    target: &str,
+    visiting: &mut Vec<String>,
+    order: &mut Vec<String>,
+) -> Result<(), GbError> {
+    for depend in depends(doc, target)? {
+        if visiting.contains(&depend) {
+            Err(GbError {
+                message: format!("`{depend}` ends up depending on itself"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        if order.contains(&depend) {
+            continue;
+        }
+        visiting.push(depend.clone());
+        visit(doc, &depend, visiting, order)?;
+        visiting.pop();
+        order.push(depend);
+    }
+    Ok(())
+}
+
+/// every target `target` depends on, directly or not, each after the ones it
+/// depends on
+pub fn order(doc: &Document, target: &str) -> Result<Vec<String>, GbError> {
+    let mut order = Vec::new();
+    visit(doc, target, &mut vec![target.to_owned()], &mut order)?;
+    Ok(order)
+}
+
+/// analyzes the targets `target` depends on, unless they are up to date, and
+/// returns the directories of their libraries
+pub fn library_paths(
+    doc: &Document,
+    target: &str,
+    build: &BuildOptions,
+) -> Result<Vec<PathBuf>, GbError> {
+    let root = std::env::current_dir().fatal("could not find the current directory")?;
+    let mut paths = Vec::new();
+    for depend in order(doc, target)? {
+        let target_info = &doc["target"][depend.as_str()];
+        let files = crate::resolve_target_files(&depend, target_info)?;
+        let files = files.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let mut build = build.clone();
+        build.library = Some(
+            crate::parse_library(&depend, target_info.get("library"))?
+                .unwrap_or_else(|| depend.to_lowercase()),
+        );
+        build.library_paths.extend(paths.iter().cloned());
+        // absolute, elaboration runs in `build/<profile>/`
+        let dir = root.join(profile::dir()).join("targets").join(&depend);
+        paths.push(dir.clone());
+
+        if !crate::is_stale(&dir.join(build.work_library_file()), &files) {
+            continue;
+        }
+        verbosity::step(
+            "[depends]",
+            &format!(
+                "Analyzing target `{depend}` into library `{}`",
+                build.library.as_deref().unwrap_or_default()
+            ),
+        );
+        std::fs::create_dir_all(&dir).fatal(format!("could not create `{}`", dir.display()))?;
+        let mut command = simulator::get().analyze(&files, &build, Some(&dir));
+        exit::during(exit::Phase::Analysis, || {
+            let status = crate::filter::spawn(&mut command, &build.output.analyze)
+                .fatal("couldn't spawn ghdl subprocess")?
+                .wait()
+                .fatal("couldn't await ghdl analyze subprocess, is ghdl installed?")?;
+            if !status.success() {
+                Err(GbError {
+                    message: format!("analyzing target `{depend}` failed"),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(paths)
+}
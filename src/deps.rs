@@ -0,0 +1,322 @@
+//! Dependencies on other gb projects, built as ghdl libraries:
+//!
+//! ```toml
+//! [dependencies]
+//! my_ip = { path = "../my_ip" }
+//! uart = { git = "https://github.com/someone/uart", rev = "v1.2.0" }
+//! ```
+//!
+//! the default target of a dependency is analyzed into a library named after
+//! its key, in the dependency's own `build/lib/`, and that directory is passed
+//! to ghdl as `-P`, so the project can say `library my_ip;`. git dependencies
+//! are cloned into gb's cache directory first, once per url and commit, at
+//! the commit gb.lock pins them to, unless `gb vendor` copied that commit
+//! into `vendor/` already.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use toml_edit::{Document, Item};
+
+use crate::{
+    exit, libraries, lock, manifest, simulator, vendor, verbosity, BuildOptions, Check, GbError,
+    Level,
+};
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Path(PathBuf),
+    Git { url: String, rev: Option<String> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+}
+
+fn parse(name: &str, item: &Item) -> Result<Dependency, GbError> {
+    // the name is a directory of the dependency cache
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        Err(GbError {
+            message: format!("`{name}` can't be the name of a dependency"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let table = item.as_table_like().fatal(format!(
+        "dependency `{name}` must be a table, like `{name} = {{ path = \"../{name}\" }}`"
+    ))?;
+    let string = |key: &str| -> Result<Option<String>, GbError> {
+        table
+            .get(key)
+            .map(|value| {
+                value
+                    .as_str()
+                    .map(ToOwned::to_owned)
+                    .fatal(format!("`{key}` of dependency `{name}` must be a string"))
+            })
+            .transpose()
+    };
+    let source = match (string("path")?, string("git")?) {
+        (Some(path), None) => Source::Path(PathBuf::from(path)),
+        (None, Some(url)) => Source::Git {
+            url,
+            rev: string("rev")?.or(string("tag")?).or(string("branch")?),
+        },
+        _ => Err(GbError {
+            message: format!("dependency `{name}` needs exactly one of `path` or `git`"),
+            level: Level::Fatal,
+            source: None,
+        })?,
+    };
+    Ok(Dependency {
+        name: name.to_owned(),
+        source,
+    })
+}
+
+/// the `[dependencies]` of a manifest, in manifest order
+pub fn dependencies(doc: &Document) -> Result<Vec<Dependency>, GbError> {
+    let Some(dependencies) = doc.get("dependencies") else {
+        return Ok(vec![]);
+    };
+    dependencies
+        .as_table_like()
+        .fatal("`[dependencies]` must be a table")?
+        .iter()
+        .map(|(name, item)| parse(name, item))
+        .collect()
+}
+
+/// where gb keeps what it downloads, shared between projects
+pub fn cache_dir() -> Result<PathBuf, GbError> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .fatal("could not find a cache directory, set XDG_CACHE_HOME")?;
+    Ok(base.join("gb"))
+}
+
+fn git(args: &[&str], dir: Option<&Path>) -> Result<bool, GbError> {
+    let mut command = std::process::Command::new("git");
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let status = command
+        .status()
+        .fatal("could not run `git`, which gb needs for git dependencies")?;
+    Ok(status.success())
+}
+
+/// clones a git dependency into the cache, unless it's there already
+fn checkout(name: &str, url: &str, commit: &str) -> Result<PathBuf, GbError> {
+    let key = Sha256::digest(format!("{url}#{commit}"))
+        .iter()
+        .take(6)
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let dir = cache_dir()?.join("git").join(format!("{name}-{key}"));
+    if dir.join("gb.toml").exists() {
+        return Ok(dir);
+    }
+
+    verbosity::step("[deps]", &format!("Cloning `{name}` from {url}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).fatal("could not create the dependency cache")?;
+    let dir_arg = dir.to_string_lossy();
+    // `--`, whatever the url looks like it's not an option of git's
+    if !git(&["clone", "--quiet", "--", url, &dir_arg], None)? {
+        let _ = std::fs::remove_dir_all(&dir);
+        Err(GbError {
+            message: format!("could not clone `{name}` from {url}"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    if !git(&["checkout", "--quiet", commit], Some(&dir))? {
+        let _ = std::fs::remove_dir_all(&dir);
+        Err(GbError {
+            message: format!("`{url}` has no commit `{commit}`"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    if !dir.join("gb.toml").exists() {
+        Err(GbError {
+            message: format!("`{name}` from {url} is not a gb project, it has no gb.toml"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(dir)
+}
+
+impl Dependency {
+    /// the project directory of the dependency, relative paths are from `base`
+    pub fn dir(&self, base: &Path) -> Result<PathBuf, GbError> {
+        match &self.source {
+            Source::Path(path) => {
+                lock::path(&self.name, path)?;
+                let dir = base.join(path);
+                if !dir.join("gb.toml").exists() {
+                    Err(GbError {
+                        message: format!(
+                            "dependency `{}` points at `{}`, which has no gb.toml",
+                            self.name,
+                            dir.display()
+                        ),
+                        level: Level::Fatal,
+                        source: None,
+                    })?;
+                }
+                dir.canonicalize()
+                    .fatal(format!("could not find `{}`", dir.display()))
+            }
+            Source::Git { url, rev } => {
+                let commit = lock::commit(&self.name, url, rev.as_deref())?;
+                match vendor::vendored(&self.name, &commit)? {
+                    Some(dir) => Ok(dir),
+                    None => checkout(&self.name, url, &commit),
+                }
+            }
+        }
+    }
+}
+
+/// runs `f` inside `dir`, coming back afterwards whatever happens
+pub fn in_dir<T>(dir: &Path, f: impl FnOnce() -> Result<T, GbError>) -> Result<T, GbError> {
+    let back = std::env::current_dir().fatal("cannot get the current directory")?;
+    std::env::set_current_dir(dir).fatal(format!("could not enter `{}`", dir.display()))?;
+    let result = f();
+    std::env::set_current_dir(&back).fatal("could not return to the project directory")?;
+    result
+}
+
+/// the library directories of the dependencies of the project in the current
+/// directory, building any that are out of date
+pub fn library_paths(doc: &Document, build: &BuildOptions) -> Result<Vec<PathBuf>, GbError> {
+    let paths = library_paths_within(doc, build, &mut vec![])?;
+    lock::save()?;
+    Ok(paths)
+}
+
+/// `building` holds the libraries being built further up, to catch cycles
+fn library_paths_within(
+    doc: &Document,
+    build: &BuildOptions,
+    building: &mut Vec<PathBuf>,
+) -> Result<Vec<PathBuf>, GbError> {
+    let mut paths = Vec::new();
+    for dependency in dependencies(doc)? {
+        let dir = dependency.dir(Path::new("."))?;
+        for path in library_within(&dependency.name, &dir, build, building)? {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// the manifest of the project in the current directory, which is `dir`, and
+/// the files of its default target, which make up the library `name`
+fn default_target(name: &str, dir: &Path) -> Result<(Document, Vec<String>), GbError> {
+    let mut doc = std::fs::read_to_string("gb.toml")
+        .fatal(format!("could not read `{}/gb.toml`", dir.display()))?
+        .parse::<Document>()
+        .fatal(format!("failed to parse `{}/gb.toml`", dir.display()))?;
+    manifest::resolve(&mut doc)?;
+    let target = doc
+        .get("default")
+        .and_then(|default| default.get("target"))
+        .and_then(|target| target.as_str())
+        .fatal(format!(
+            "`{name}` is used as a library, but has no `default.target` to build it from"
+        ))?;
+    let target_info = doc
+        .get("target")
+        .and_then(|targets| targets.get(target))
+        .fatal(format!(
+            "the default target `{target}` of `{name}` does not exist"
+        ))?;
+    let files = crate::resolve_target_files(target, target_info)?;
+    Ok((doc, files))
+}
+
+/// the files the library `name` is analyzed from, found from `dir`
+pub fn library_files(name: &str, dir: &Path) -> Result<Vec<PathBuf>, GbError> {
+    let (_, files) = in_dir(dir, || default_target(name, dir))?;
+    Ok(files.iter().map(|file| dir.join(file)).collect())
+}
+
+/// analyzes the default target of the project in `dir` into a library called
+/// `name`, after its own dependencies, unless it's up to date. returns the
+/// directory holding it and those of its dependencies.
+pub fn library(name: &str, dir: &Path, build: &BuildOptions) -> Result<Vec<PathBuf>, GbError> {
+    library_within(name, dir, build, &mut vec![])
+}
+
+fn library_within(
+    name: &str,
+    dir: &Path,
+    build: &BuildOptions,
+    building: &mut Vec<PathBuf>,
+) -> Result<Vec<PathBuf>, GbError> {
+    if building.iter().any(|above| above == dir) {
+        Err(GbError {
+            message: format!("`{name}` ends up depending on itself"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    building.push(dir.to_owned());
+    let paths = in_dir(dir, || {
+        let (doc, files) = default_target(name, dir)?;
+        let files = files.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let mut build = build.clone();
+        build.library = Some(name.to_owned());
+        // on top of the `[libraries]` of the project it's built for
+        for path in libraries::paths(&doc)?
+            .into_iter()
+            .chain(library_paths_within(&doc, &build, building)?)
+        {
+            if !build.library_paths.contains(&path) {
+                build.library_paths.push(path);
+            }
+        }
+        lock::flags(name, &build)?;
+        let mut paths = build.library_paths.clone();
+        let lib = PathBuf::from("build/lib");
+        paths.insert(0, dir.join(&lib));
+
+        let library_file = lib.join(build.work_library_file());
+        if !crate::is_stale(&library_file, &files) {
+            return Ok(paths);
+        }
+
+        verbosity::step("[deps]", &format!("Analyzing library `{name}`"));
+        std::fs::create_dir_all(&lib).fatal("could not create the library directory")?;
+        let mut command = simulator::get().analyze(&files, &build, Some(&lib));
+        exit::during(exit::Phase::Analysis, || {
+            let status = crate::filter::spawn(&mut command, &build.output.analyze)
+                .fatal("couldn't spawn ghdl subprocess")?
+                .wait()
+                .fatal("couldn't await ghdl analyze subprocess, is ghdl installed?")?;
+            if !status.success() {
+                Err(GbError {
+                    message: format!("analyzing the library `{name}` failed"),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+            Ok(())
+        })?;
+        Ok(paths)
+    });
+    building.pop();
+    paths
+}
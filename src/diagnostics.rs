@@ -0,0 +1,62 @@
+//! ghdl's `file:line:col: message` diagnostics, picked out of its output so
+//! that `--message-format=json` can hand them to editors and CI wrappers as
+//! one json object per line on stdout.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+/// how ghdl's diagnostics are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// as ghdl prints them
+    #[default]
+    Human,
+    /// as json lines on stdout, everything else goes to stderr
+    Json,
+}
+
+// analysis and elaboration print `file:1:2: msg` or `file:1:2:warning: msg`,
+// a simulation prints `file:1:2:@10ns:(assertion error): msg`
+static DIAGNOSTIC: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?P<column>\d+):\s*(?:@(?P<time>[^:]+):)?(?:\((?:assertion|report) (?P<report>\w+)\):|(?P<severity>error|warning|note):)?\s*(?P<message>.*)$",
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub severity: String,
+    /// simulation time, for diagnostics reported while running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn parse(line: &str) -> Option<Diagnostic> {
+        let captures = DIAGNOSTIC.captures(line)?;
+        let severity = captures
+            .name("severity")
+            .or(captures.name("report"))
+            .map(|severity| severity.as_str().to_lowercase())
+            // ghdl only tags warnings and notes, untagged diagnostics are errors
+            .unwrap_or_else(|| "error".to_owned());
+        Some(Diagnostic {
+            file: captures["file"].to_owned(),
+            line: captures["line"].parse().ok()?,
+            column: captures["column"].parse().ok()?,
+            severity,
+            time: captures.name("time").map(|time| time.as_str().to_owned()),
+            message: captures["message"].trim().to_owned(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("diagnostics always serialize")
+    }
+}
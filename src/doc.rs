@@ -0,0 +1,523 @@
+//! `gb doc`: documentation for the entities of a target, from their ports,
+//! generics and the comments right above them, written to `build/doc/` as
+//! html (the default) or `--format markdown`. a comment on the same line as a
+//! port or generic describes that one:
+//!
+//! ```vhdl
+//! -- counts the rising edges of `clk`, wrapping around at the top
+//! entity counter is
+//!   generic (WIDTH : natural := 8);  -- bits of the count
+//!   port (
+//!     clk   : in  std_logic;
+//!     count : out unsigned(WIDTH - 1 downto 0)  -- the edges seen so far
+//!   );
+//! end entity counter;
+//! ```
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+
+use crate::{tree_sitter, Check, GbError, Level};
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Html,
+    Markdown,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// a port or a generic
+#[derive(Debug, Default)]
+struct Interface {
+    name: String,
+    /// `in`, `out`, ..., empty for generics
+    mode: String,
+    ty: String,
+    default: Option<String>,
+    comment: String,
+}
+
+#[derive(Debug)]
+struct Entity {
+    name: String,
+    file: PathBuf,
+    comment: String,
+    generics: Vec<Interface>,
+    ports: Vec<Interface>,
+}
+
+/// the text of a comment without its `--`, or doxygen's `--!`
+fn comment_text(comment: &str) -> &str {
+    let text = comment.trim_start_matches("--");
+    let text = text.strip_prefix('!').unwrap_or(text);
+    text.strip_prefix(' ').unwrap_or(text).trim_end()
+}
+
+struct Source<'s> {
+    code_src: &'s str,
+    leaves: Vec<tree_sitter::Leaf>,
+}
+
+impl<'s> Source<'s> {
+    fn text(&self, pos: usize) -> &'s str {
+        &self.code_src[self.leaves[pos].start..self.leaves[pos].end]
+    }
+
+    fn is(&self, pos: usize, text: &str) -> bool {
+        pos < self.leaves.len() && self.text(pos).eq_ignore_ascii_case(text)
+    }
+
+    /// the next token after `pos` that isn't a comment
+    fn next(&self, pos: usize) -> usize {
+        (pos + 1..self.leaves.len())
+            .find(|pos| !self.leaves[*pos].comment)
+            .unwrap_or(self.leaves.len())
+    }
+
+    fn previous(&self, pos: usize) -> Option<usize> {
+        (0..pos).rev().find(|pos| !self.leaves[*pos].comment)
+    }
+
+    /// the comment lines directly above `pos`, without blank lines in between
+    fn comment_above(&self, pos: usize) -> String {
+        let mut lines = Vec::new();
+        let mut row = self.leaves[pos].row;
+        for leaf in self.leaves[..pos].iter().rev() {
+            if !leaf.comment || leaf.end_row + 1 != row {
+                break;
+            }
+            lines.push(comment_text(&self.code_src[leaf.start..leaf.end]));
+            row = leaf.row;
+        }
+        lines.reverse();
+        lines.join("\n")
+    }
+
+    /// the declarations inside the parentheses opening at `open`, and where they end
+    fn interfaces(&self, open: usize) -> (Vec<Interface>, usize) {
+        let mut declarations = Vec::new();
+        let mut current = Vec::new();
+        let mut depth = 0;
+        let mut pos = open;
+        while pos < self.leaves.len() {
+            let text = self.text(pos);
+            if self.leaves[pos].comment {
+                current.push(pos);
+            } else if text == "(" {
+                depth += 1;
+                if depth > 1 {
+                    current.push(pos);
+                }
+            } else if text == ")" {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                current.push(pos);
+            } else if text == ";" && depth == 1 {
+                declarations.push(std::mem::take(&mut current));
+            } else {
+                current.push(pos);
+            }
+            pos += 1;
+        }
+        // or after the `);` closing the last one
+        let mut after = pos + 1;
+        if after < self.leaves.len() && self.text(after) == ";" {
+            after += 1;
+        }
+        if after < self.leaves.len()
+            && self.leaves[after].comment
+            && pos < self.leaves.len()
+            && self.leaves[after].row == self.leaves[pos].row
+        {
+            current.push(after);
+        }
+        declarations.push(current);
+
+        // a comment trailing a declaration can come after its `;`
+        for index in 1..declarations.len() {
+            let Some(last) = declarations[index - 1]
+                .iter()
+                .rev()
+                .find(|pos| !self.leaves[**pos].comment)
+                .map(|pos| self.leaves[*pos].end_row)
+            else {
+                continue;
+            };
+            let trailing = declarations[index]
+                .iter()
+                .take_while(|pos| self.leaves[**pos].comment && self.leaves[**pos].row == last)
+                .count();
+            let moved = declarations[index].drain(..trailing).collect::<Vec<_>>();
+            declarations[index - 1].extend(moved);
+        }
+
+        let interfaces = declarations
+            .iter()
+            .flat_map(|declaration| self.declaration(declaration))
+            .collect();
+        (interfaces, pos)
+    }
+
+    /// `a, b : in std_logic := '0'`, as one interface per name
+    fn declaration(&self, tokens: &[usize]) -> Vec<Interface> {
+        let code = tokens
+            .iter()
+            .copied()
+            .filter(|pos| !self.leaves[*pos].comment)
+            .collect::<Vec<_>>();
+        let Some(colon) = code.iter().position(|pos| self.text(*pos) == ":") else {
+            return vec![];
+        };
+        let names = code[..colon]
+            .iter()
+            .map(|pos| self.text(*pos))
+            .filter(|name| {
+                *name != ","
+                    && !["signal", "constant", "variable", "file"]
+                        .iter()
+                        .any(|class| name.eq_ignore_ascii_case(class))
+            })
+            .collect::<Vec<_>>();
+        let mut rest = &code[colon + 1..];
+        let mut mode = String::new();
+        if let Some(first) = rest.first() {
+            let text = self.text(*first);
+            if ["in", "out", "inout", "buffer", "linkage"]
+                .iter()
+                .any(|known| text.eq_ignore_ascii_case(known))
+            {
+                mode = text.to_lowercase();
+                rest = &rest[1..];
+            }
+        }
+        let assign = rest.iter().position(|pos| self.text(*pos) == ":=");
+        let slice = |tokens: &[usize]| match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => {
+                self.code_src[self.leaves[*first].start..self.leaves[*last].end].to_owned()
+            }
+            _ => String::new(),
+        };
+        let (ty, default) = match assign {
+            Some(assign) => (slice(&rest[..assign]), Some(slice(&rest[assign + 1..]))),
+            None => (slice(rest), None),
+        };
+
+        let first_row = self.leaves[code[0]].row;
+        let last_row = self.leaves[*code.last().unwrap_or(&code[0])].end_row;
+        // a trailing comment beats the ones above the declaration
+        let (trailing, above): (Vec<usize>, Vec<usize>) = tokens
+            .iter()
+            .copied()
+            .filter(|pos| self.leaves[*pos].comment)
+            .partition(|pos| self.leaves[*pos].row >= first_row);
+        let comments = if trailing.is_empty() { above } else { trailing };
+        let comment = comments
+            .into_iter()
+            .filter(|pos| self.leaves[*pos].row <= last_row)
+            .map(|pos| comment_text(self.text(pos)))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        names
+            .into_iter()
+            .map(|name| Interface {
+                name: name.to_owned(),
+                mode: mode.clone(),
+                ty: ty.split_whitespace().collect::<Vec<_>>().join(" "),
+                default: default.clone(),
+                comment: comment.clone(),
+            })
+            .collect()
+    }
+}
+
+fn entities(file: &Path) -> Result<Vec<Entity>, GbError> {
+    let code_src =
+        std::fs::read_to_string(file).fatal(format!("could not read `{}`", file.display()))?;
+    let leaves = tree_sitter::leaves(&code_src).map_err(|error| GbError {
+        message: format!("could not parse `{}`: {error}", file.display()),
+        level: Level::Fatal,
+        source: None,
+    })?;
+    let source = Source {
+        code_src: &code_src,
+        leaves,
+    };
+
+    let mut entities = Vec::new();
+    for pos in 0..source.leaves.len() {
+        let starts_entity = source.leaves[pos].keyword
+            && source.is(pos, "entity")
+            // `end entity` and `u0 : entity work.x` are no declarations
+            && !source
+                .previous(pos)
+                .is_some_and(|previous| source.is(previous, "end") || source.is(previous, ":"));
+        if !starts_entity {
+            continue;
+        }
+        let name = source.next(pos);
+        if name >= source.leaves.len() {
+            break;
+        }
+        let mut entity = Entity {
+            name: source.text(name).to_owned(),
+            file: file.to_owned(),
+            comment: source.comment_above(pos),
+            generics: vec![],
+            ports: vec![],
+        };
+        let mut at = source.next(name);
+        while at < source.leaves.len() && !source.is(at, "end") && !source.is(at, "begin") {
+            let open = source.next(at);
+            if source.is(at, "generic") && source.is(open, "(") {
+                let (generics, close) = source.interfaces(open);
+                entity.generics = generics;
+                at = close;
+            } else if source.is(at, "port") && source.is(open, "(") {
+                let (ports, close) = source.interfaces(open);
+                entity.ports = ports;
+                at = close;
+            }
+            at = source.next(at);
+        }
+        entities.push(entity);
+    }
+    Ok(entities)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         td, th {{ border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }}\n\
+         code {{ background: #f4f4f4; }}\n\
+         </style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+fn html_table(title: &str, columns: &[&str], rows: &[Vec<String>]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let mut html = format!("<h2>{title}</h2>\n<table>\n<tr>");
+    for column in columns {
+        let _ = write!(html, "<th>{column}</th>");
+    }
+    html.push_str("</tr>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for (pos, cell) in row.iter().enumerate() {
+            // everything but the description is vhdl
+            if pos + 1 < row.len() && !cell.is_empty() {
+                let _ = write!(html, "<td><code>{}</code></td>", escape(cell));
+            } else {
+                let _ = write!(html, "<td>{}</td>", escape(cell));
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn markdown_table(title: &str, columns: &[&str], rows: &[Vec<String>]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let mut markdown = format!("## {title}\n\n| {} |\n|", columns.join(" | "));
+    markdown.push_str(&"---|".repeat(columns.len()));
+    markdown.push('\n');
+    for row in rows {
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(pos, cell)| {
+                let cell = cell.replace('|', "\\|");
+                if pos + 1 < row.len() && !cell.is_empty() {
+                    format!("`{cell}`")
+                } else {
+                    cell
+                }
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(markdown, "| {} |", cells.join(" | "));
+    }
+    markdown.push('\n');
+    markdown
+}
+
+fn page(entity: &Entity, format: Format) -> String {
+    let generics = entity
+        .generics
+        .iter()
+        .map(|generic| {
+            vec![
+                generic.name.clone(),
+                generic.ty.clone(),
+                generic.default.clone().unwrap_or_default(),
+                generic.comment.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let ports = entity
+        .ports
+        .iter()
+        .map(|port| {
+            vec![
+                port.name.clone(),
+                port.mode.clone(),
+                port.ty.clone(),
+                port.comment.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let generic_columns = ["name", "type", "default", "description"];
+    let port_columns = ["name", "mode", "type", "description"];
+    let file = entity.file.display().to_string();
+
+    match format {
+        Format::Html => {
+            let mut body = format!(
+                "<p><a href=\"index.html\">index</a></p>\n<h1>{}</h1>\n<p>defined in <code>{}</code></p>\n",
+                escape(&entity.name),
+                escape(&file)
+            );
+            for paragraph in entity
+                .comment
+                .split("\n\n")
+                .filter(|p| !p.trim().is_empty())
+            {
+                let _ = writeln!(body, "<p>{}</p>", escape(paragraph));
+            }
+            body.push_str(&html_table("Generics", &generic_columns, &generics));
+            body.push_str(&html_table("Ports", &port_columns, &ports));
+            html_page(&entity.name, &body)
+        }
+        Format::Markdown => {
+            let mut markdown = format!("# {}\n\ndefined in `{file}`\n\n", entity.name);
+            if !entity.comment.is_empty() {
+                let _ = write!(markdown, "{}\n\n", entity.comment);
+            }
+            markdown.push_str(&markdown_table("Generics", &generic_columns, &generics));
+            markdown.push_str(&markdown_table("Ports", &port_columns, &ports));
+            markdown
+        }
+    }
+}
+
+fn index(target: &str, entities: &[Entity], format: Format) -> String {
+    let summary = |entity: &Entity| {
+        entity
+            .comment
+            .split("\n\n")
+            .next()
+            .unwrap_or_default()
+            .replace('\n', " ")
+    };
+    match format {
+        Format::Html => {
+            let mut body = format!("<h1>{}</h1>\n<table>\n", escape(target));
+            for entity in entities {
+                let _ = writeln!(
+                    body,
+                    "<tr><td><a href=\"{}.html\"><code>{}</code></a></td><td>{}</td></tr>",
+                    escape(&entity.name.to_lowercase()),
+                    escape(&entity.name),
+                    escape(&summary(entity))
+                );
+            }
+            body.push_str("</table>\n");
+            html_page(target, &body)
+        }
+        Format::Markdown => {
+            let mut markdown = format!("# {target}\n\n");
+            for entity in entities {
+                let line = format!(
+                    "- [`{}`]({}.md) {}",
+                    entity.name,
+                    entity.name.to_lowercase(),
+                    summary(entity)
+                );
+                let _ = writeln!(markdown, "{}", line.trim_end());
+            }
+            markdown
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<(), GbError> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener).arg(path).spawn().fatal(format!(
+        "could not run `{opener}` to open the documentation"
+    ))?;
+    Ok(())
+}
+
+pub fn doc(target: &str, files: &[&str], format: Format, open_it: bool) -> Result<(), GbError> {
+    let mut documented = Vec::new();
+    for file in files {
+        documented.extend(entities(Path::new(file))?);
+    }
+    documented.sort_by_key(|entity| entity.name.to_lowercase());
+
+    let dir = PathBuf::from("build/doc");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).fatal("could not create build/doc")?;
+    for entity in &documented {
+        let path = dir
+            .join(entity.name.to_lowercase())
+            .with_extension(format.extension());
+        std::fs::write(&path, page(entity, format))
+            .fatal(format!("could not write `{}`", path.display()))?;
+    }
+    let index_path = dir.join("index").with_extension(format.extension());
+    std::fs::write(&index_path, index(target, &documented, format))
+        .fatal(format!("could not write `{}`", index_path.display()))?;
+
+    eprintln!(
+        "  {}  {}",
+        "[doc]".blue().bold(),
+        format!(
+            "Documented {} entities in {}",
+            documented.len(),
+            index_path.display()
+        )
+        .green()
+        .bold()
+    );
+    if open_it {
+        open(&index_path)?;
+    }
+    Ok(())
+}
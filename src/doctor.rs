@@ -0,0 +1,428 @@
+//! `gb doctor`: checks what a build is going to need before it needs it, and
+//! says how to fix what's missing.
+//!
+//! - the ghdl gb runs (`ghdl.path`, `GB_GHDL` or the path), its version and
+//!   backend
+//! - the waveform viewers gb.toml names, and `shmidcat` for gtkwave
+//! - the fpga tools, when gb.toml has an `[fpga]` table
+//! - gb.toml itself, going through the same checks a build does, but for
+//!   every target at once instead of stopping at the first problem, and the
+//!   keys gb doesn't know
+//!
+//! problems make `gb doctor` fail, warnings don't.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+use toml_edit::{Document, Item};
+
+use crate::{
+    config, depends, filter, foreign, fpga, ghdl, hooks, libraries, lint, manifest, naming, nvc,
+    order, profile, scenario, schema, sim, simulator, synth, warnings, wave, BuildOptions, GbError,
+    Level,
+};
+
+enum Finding {
+    Ok(String),
+    Warning { what: String, fix: String },
+    Problem { what: String, fix: String },
+}
+
+#[derive(Default)]
+struct Report {
+    findings: Vec<(&'static str, Finding)>,
+}
+
+impl Report {
+    fn ok(&mut self, section: &'static str, what: impl Into<String>) {
+        self.findings.push((section, Finding::Ok(what.into())));
+    }
+
+    fn warn(&mut self, section: &'static str, what: impl Into<String>, fix: impl Into<String>) {
+        let (what, fix) = (what.into(), fix.into());
+        self.findings
+            .push((section, Finding::Warning { what, fix }));
+    }
+
+    fn problem(&mut self, section: &'static str, what: impl Into<String>, fix: impl Into<String>) {
+        let (what, fix) = (what.into(), fix.into());
+        self.findings
+            .push((section, Finding::Problem { what, fix }));
+    }
+
+    /// a failed check of gb.toml, its message already says what to change
+    fn check(&mut self, result: Result<(), GbError>) {
+        if let Err(error) = result {
+            self.problem("gb.toml", error.message, "");
+        }
+    }
+
+    fn print(&self) -> usize {
+        let mut problems = 0;
+        for (section, finding) in &self.findings {
+            let section = format!("[{section}]");
+            match finding {
+                Finding::Ok(what) => {
+                    eprintln!(
+                        "  {}  {}  {what}",
+                        section.blue().bold(),
+                        "ok".green().bold()
+                    )
+                }
+                Finding::Warning { what, fix } => {
+                    eprintln!(
+                        "  {}  {}  {what}",
+                        section.blue().bold(),
+                        "warning".yellow().bold()
+                    );
+                    if !fix.is_empty() {
+                        eprintln!("      fix: {fix}");
+                    }
+                }
+                Finding::Problem { what, fix } => {
+                    problems += 1;
+                    eprintln!(
+                        "  {}  {}  {what}",
+                        section.blue().bold(),
+                        "problem".red().bold()
+                    );
+                    if !fix.is_empty() {
+                        eprintln!("      fix: {fix}");
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// where `program` would be started from, like `which`
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.exists().then(|| path.to_owned());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .flat_map(|candidate| {
+            [
+                candidate.with_extension(std::env::consts::EXE_EXTENSION),
+                candidate,
+            ]
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+fn check_ghdl(report: &mut Report) {
+    let path = ghdl::path();
+    let fix =
+        "install ghdl and put it on the PATH, or point `ghdl.path` in gb.toml or `GB_GHDL` at it";
+    let output = match Command::new(path).arg("--version").output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            report.problem(
+                "ghdl",
+                format!("`{path} --version` failed ({})", output.status),
+                fix,
+            );
+            return;
+        }
+        Err(_) => {
+            report.problem("ghdl", format!("`{path}` was not found"), fix);
+            return;
+        }
+    };
+    let version = String::from_utf8_lossy(&output.stdout);
+    let version = version.lines().next().unwrap_or_default().trim();
+    match ghdl::backend() {
+        Some(backend) => report.ok("ghdl", format!("{version}, {backend} backend")),
+        None => report.warn(
+            "ghdl",
+            format!("{version}, but gb doesn't know its backend"),
+            "gb assumes it writes object files, `gb clean` after a failed build",
+        ),
+    }
+}
+
+fn check_nvc(report: &mut Report) {
+    let path = nvc::path();
+    let fix =
+        "install nvc and put it on the PATH, or point `nvc.path` in gb.toml or `GB_NVC` at it";
+    match Command::new(path).arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            report.ok("nvc", version.lines().next().unwrap_or_default().trim());
+        }
+        Ok(output) => report.problem(
+            "nvc",
+            format!("`{path} --version` failed ({})", output.status),
+            fix,
+        ),
+        Err(_) => report.problem("nvc", format!("`{path}` was not found"), fix),
+    }
+}
+
+/// the viewers named in `[default]` and the targets, without repeats
+fn viewers(doc: &Document, key: &str) -> Vec<String> {
+    let mut tables = vec![doc.get("default")];
+    if let Some(targets) = doc.get("target").and_then(Item::as_table_like) {
+        tables.extend(targets.iter().map(|(_, info)| Some(info)));
+    }
+    let mut viewers = Vec::new();
+    for viewer in tables
+        .into_iter()
+        .flatten()
+        .filter_map(|table| table.get(key)?.as_str())
+    {
+        if !viewers.iter().any(|seen| seen == viewer) {
+            viewers.push(viewer.to_owned());
+        }
+    }
+    viewers
+}
+
+fn check_viewers(doc: &Document, report: &mut Report) {
+    let viewers = viewers(doc, "vcd-viewer");
+    if viewers.is_empty() {
+        report.warn(
+            "viewer",
+            "no `vcd-viewer` is set, `gb wave` has nothing to open",
+            "set `default.vcd-viewer = \"gtkwave\"` in gb.toml",
+        );
+    }
+    for viewer in viewers.iter().chain(&self::viewers(doc, "vcd-stream")) {
+        let Some(program) = viewer.split_whitespace().next() else {
+            continue;
+        };
+        match find_program(program) {
+            Some(found) => report.ok("viewer", format!("`{program}` at {}", found.display())),
+            // builds don't need the viewer, only `gb wave` does
+            None => report.warn(
+                "viewer",
+                format!("`{program}` is set as a viewer, but isn't installed"),
+                format!("install `{program}` or set a viewer that is"),
+            ),
+        }
+        let is_gtkwave = Path::new(program)
+            .file_stem()
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("gtkwave"));
+        if is_gtkwave && find_program("shmidcat").is_none() {
+            report.warn(
+                "viewer",
+                "`shmidcat` isn't installed, `gb wave --stream` needs it for gtkwave",
+                "install gtkwave's tools, which come with `shmidcat`",
+            );
+        }
+    }
+}
+
+fn check_target(target: &str, info: &Item, report: &mut Report) {
+    let files = match crate::resolve_target_files(target, info) {
+        Ok(files) => files,
+        Err(error) => {
+            report.check(Err(error));
+            return;
+        }
+    };
+    let missing = files
+        .iter()
+        .filter(|file| !Path::new(file).exists())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        let missing = missing
+            .iter()
+            .map(|file| format!("`{file}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        report.problem(
+            "gb.toml",
+            format!("target `{target}` lists files that don't exist: {missing}"),
+            "create them, or take them out of `files`",
+        );
+    }
+    match info.get("execute").map(|execute| execute.as_str()) {
+        Some(Some(execute)) if !Path::new(execute).exists() => report.problem(
+            "gb.toml",
+            format!("the `execute` file of target `{target}`, `{execute}`, doesn't exist"),
+            "point `execute` at the testbench of the target",
+        ),
+        Some(None) => report.problem(
+            "gb.toml",
+            format!("`execute` of target `{target}` must be a path"),
+            "",
+        ),
+        None => {
+            if let Err(error) = order::top(target, &files) {
+                report.warn(
+                    "gb.toml",
+                    error.message,
+                    "point `execute` at the testbench of the target",
+                );
+            }
+        }
+        _ => {}
+    }
+    report.check(crate::parse_std(info.get("std")).map(drop));
+    report.check(crate::parse_library(target, info.get("library")).map(drop));
+    report.check(crate::parse_ieee(target, info.get("ieee")).map(drop));
+    report.check(hooks::Hooks::from_manifest(target, info).map(drop));
+    report.check(foreign::Foreign::from_manifest(target, info).map(drop));
+    report.check(BuildOptions::default().limits.read(Some(info)).map(drop));
+    report.check(scenario::key_values(info.get("generics"), "generics").map(drop));
+    report.check(scenario::scenarios(target, info).map(drop));
+    report.check(sim::run_flags(target, info).map(drop));
+    for key in ["analyze-flags", "elab-flags", "run-flags"] {
+        report.check(sim::passthrough(target, info, key).map(drop));
+    }
+    match synth::Synth::from_manifest(target, info) {
+        Ok(Some(synth)) if synth.yosys && find_program("yosys").is_none() => report.warn(
+            "gb.toml",
+            format!("target `{target}` synthesizes through yosys, which isn't installed"),
+            "install yosys and its ghdl plugin, or take out `yosys = true`",
+        ),
+        result => report.check(result.map(drop)),
+    }
+    report.check(wave::DumpWindow::from_manifest(target, info).map(drop));
+    report.check(wave::from_manifest(target, info).map(drop));
+}
+
+/// the tools `gb pnr` and `gb flash` go through, which only matter to them
+fn check_fpga(fpga: &fpga::Fpga, report: &mut Report) {
+    if !fpga.constraints.exists() {
+        report.problem(
+            "gb.toml",
+            format!(
+                "the fpga constraints `{}` don't exist",
+                fpga.constraints.display()
+            ),
+            "point `fpga.constraints` at the pin constraints of the board",
+        );
+    }
+    for tool in fpga.family.tools().iter().chain(&["openFPGALoader"]) {
+        if find_program(tool).is_none() {
+            report.warn(
+                "fpga",
+                format!("`{tool}` isn't installed, `gb pnr` and `gb flash` need it"),
+                "install the oss-cad-suite, which comes with all of them",
+            );
+        }
+    }
+}
+
+fn check_manifest(report: &mut Report) -> Option<Document> {
+    let Ok(manifest) = std::fs::read_to_string("gb.toml") else {
+        report.problem(
+            "gb.toml",
+            "there is no gb.toml in this directory",
+            "run `gb init`, or `gb new <name>` for a new project",
+        );
+        return None;
+    };
+    let mut doc = match manifest.parse::<Document>() {
+        Ok(doc) => doc,
+        Err(error) => {
+            let error = error.to_string();
+            report.problem("gb.toml", "gb.toml is not valid toml", error.trim());
+            return None;
+        }
+    };
+    report.check(manifest::resolve(&mut doc).map(drop));
+    // gb's own configuration fills in what gb.toml leaves out
+    report.check(config::merge_into(&mut doc));
+    for finding in schema::findings(&doc) {
+        if finding.level == Level::Warning {
+            report.warn("gb.toml", finding.message, "");
+        }
+    }
+    report.check(simulator::configure(&doc));
+    report.check(
+        crate::parse_std(doc.get("default").and_then(|default| default.get("std"))).map(drop),
+    );
+    report.check(filter::OutputFilters::parse(&doc).map(drop));
+    report.check(warnings::werror(&doc).map(drop));
+    report.check(libraries::paths(&doc).map(drop));
+    report.check(warnings::analyze_flags(&doc).map(drop));
+    report.check(naming::Convention::parse(&doc).map(drop));
+    report.check(lint::check(&doc));
+    report.check(profile::check(&doc));
+    report.check(
+        BuildOptions::default()
+            .limits
+            .read(doc.get("default"))
+            .map(drop),
+    );
+
+    let targets = doc.get("target").and_then(Item::as_table_like);
+    match targets {
+        Some(targets) if !targets.is_empty() => {
+            for (target, info) in targets.iter() {
+                check_target(target, info, report);
+                report.check(depends::order(&doc, target).map(drop));
+            }
+        }
+        _ if doc.get("workspace").is_some() => {}
+        _ => report.problem(
+            "gb.toml",
+            "gb.toml has no targets",
+            "add a `[target.<name>]` with its `files` and the file to `execute`",
+        ),
+    }
+    match fpga::Fpga::from_manifest(&doc) {
+        Ok(Some(fpga)) => check_fpga(&fpga, report),
+        result => report.check(result.map(drop)),
+    }
+    match crate::default_target(&doc) {
+        Ok(Some(target)) if targets.and_then(|targets| targets.get(&target)).is_none() => report
+            .problem(
+                "gb.toml",
+                format!("the default target `{target}` doesn't exist"),
+                "set `default.target` to one of the targets, or `gb use --clear`",
+            ),
+        Err(error) => report.check(Err(error)),
+        _ => {}
+    }
+    Some(doc)
+}
+
+pub fn doctor() -> Result<(), GbError> {
+    // gb.toml is read first, it may pick the ghdl to check, but printed last
+    let mut manifest = Report::default();
+    let doc = check_manifest(&mut manifest);
+    if doc.is_some()
+        && !manifest
+            .findings
+            .iter()
+            .any(|(_, finding)| matches!(finding, Finding::Problem { .. }))
+    {
+        manifest.ok("gb.toml", "gb.toml and all of its targets check out");
+    }
+
+    let mut report = Report::default();
+    if simulator::get().name() == "nvc" {
+        check_nvc(&mut report);
+    } else {
+        check_ghdl(&mut report);
+    }
+    if let Some(doc) = &doc {
+        check_viewers(doc, &mut report);
+    }
+    report.findings.append(&mut manifest.findings);
+
+    let problems = report.print();
+    if problems > 0 {
+        Err(GbError {
+            message: format!("gb doctor found {problems} problem(s)"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    eprintln!(
+        "  {}  {}",
+        "[doctor]".blue().bold(),
+        "Everything gb needs is in place.".green().bold()
+    );
+    Ok(())
+}
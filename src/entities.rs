@@ -0,0 +1,86 @@
+//! `gb entities`: the design units every file of a target declares, as
+//! tree-sitter reads them. when ghdl can't find a unit, this shows which file
+//! it's actually in, or that it isn't in any of them.
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::{tree_sitter, Check, GbError, Level};
+
+/// the tree-sitter node kinds of design units, and what vhdl calls them
+const KINDS: &[(&str, &str)] = &[
+    ("context_declaration", "context"),
+    ("entity_declaration", "entity"),
+    ("architecture_body", "architecture"),
+    ("package_declaration", "package"),
+    ("package_body", "package body"),
+    ("configuration_declaration", "configuration"),
+    ("component_declaration", "component"),
+];
+
+#[derive(Debug, Serialize)]
+pub struct Unit {
+    pub kind: &'static str,
+    pub name: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct File {
+    pub path: String,
+    pub units: Vec<Unit>,
+}
+
+/// the units declared in `path`, in source order
+fn units(path: &str) -> Result<Vec<Unit>, GbError> {
+    let anything = glob::Pattern::new("*").fatal("`*` is a valid pattern")?;
+    let mut units = Vec::new();
+    for (kind, name) in KINDS {
+        let found =
+            tree_sitter::find_nodes(path, Some(kind), &anything).map_err(|err| GbError {
+                message: format!("could not parse `{path}`: {err}"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        units.extend(found.into_iter().map(|node| Unit {
+            kind: name,
+            name: node.name,
+            line: node.line,
+        }));
+    }
+    units.sort_by_key(|unit| unit.line);
+    Ok(units)
+}
+
+pub fn entities(files: &[&str], json: bool) -> Result<(), GbError> {
+    let files = files
+        .iter()
+        .map(|path| {
+            Ok(File {
+                path: path.to_string(),
+                units: units(path)?,
+            })
+        })
+        .collect::<Result<Vec<_>, GbError>>()?;
+
+    if json {
+        let json = serde_json::to_string_pretty(&files).fatal("could not serialize the units")?;
+        println!("{json}");
+        return Ok(());
+    }
+    for file in files {
+        println!("{}", file.path.blue().bold());
+        if file.units.is_empty() {
+            println!("  no design units");
+        }
+        for unit in file.units {
+            println!(
+                "  {:<13} {}  (line {})",
+                unit.kind,
+                unit.name.bold(),
+                unit.line
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! What gb's exit code means, so scripts and IDE tasks can tell a broken
+//! design from a broken setup:
+//!
+//! | code | meaning                                               |
+//! |------|-------------------------------------------------------|
+//! | 0    | success                                               |
+//! | 1    | any other failure, like a file gb couldn't write      |
+//! | 2    | bad command line or gb.toml                           |
+//! | 3    | a tool gb needs, like ghdl, is not installed          |
+//! | 4    | analysis failed                                       |
+//! | 5    | elaboration failed                                    |
+//! | 6    | the simulation failed an assertion                    |
+//! | 7    | testbenches failed                                    |
+//! | 8    | the simulation hit a runtime error, like an index out |
+//! |      | of range, crashed or went over a resource limit       |
+//! | 9    | synthesis failed                                      |
+//! | 10   | the simulation ran past its `timeout` and was killed  |
+//! | 70   | gb itself crashed                                     |
+//!
+//! the code follows from the phase gb was in when it failed, so a phase only
+//! has to be marked where it starts and ends, not at every error. clap already
+//! exits with 2 on a bad command line.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::Serialize;
+
+use crate::GbError;
+
+pub const HELP: &str = "\
+Exit codes:
+  0   success
+  1   any other failure
+  2   bad command line or gb.toml
+  3   a tool gb needs, like ghdl, is not installed
+  4   analysis failed
+  5   elaboration failed
+  6   the simulation failed an assertion
+  7   testbenches failed
+  8   the simulation hit a runtime error, crashed or went over a limit
+  9   synthesis failed
+  10  the simulation ran past its timeout
+  70  gb itself crashed";
+
+pub const INTERNAL: i32 = 70;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Phase {
+    Config,
+    Analysis,
+    Elaboration,
+    Simulation,
+    Tests,
+    /// the simulation failed, but not on one of the design's assertions
+    Runtime,
+    Synthesis,
+    /// the simulation was killed for running past its `timeout`
+    Timeout,
+    Other,
+}
+
+static PHASE: AtomicU8 = AtomicU8::new(Phase::Config as u8);
+
+fn current() -> Phase {
+    match PHASE.load(Ordering::Relaxed) {
+        0 => Phase::Config,
+        1 => Phase::Analysis,
+        2 => Phase::Elaboration,
+        3 => Phase::Simulation,
+        4 => Phase::Tests,
+        5 => Phase::Runtime,
+        6 => Phase::Synthesis,
+        7 => Phase::Timeout,
+        _ => Phase::Other,
+    }
+}
+
+fn set(phase: Phase) {
+    PHASE.store(phase as u8, Ordering::Relaxed);
+}
+
+/// the manifest and command line were fine, whatever fails from here on isn't about them
+pub fn configured() {
+    set(Phase::Other);
+}
+
+/// reading the manifest (again), whatever fails until `configured` is about it
+pub fn configuring() {
+    set(Phase::Config);
+}
+
+/// runs `f` as `phase`. when it fails, gb exits with the code of the phase.
+pub fn during<T>(phase: Phase, f: impl FnOnce() -> Result<T, GbError>) -> Result<T, GbError> {
+    let before = current();
+    set(phase);
+    let result = f();
+    if result.is_ok() {
+        set(before);
+    }
+    result
+}
+
+/// starting a program that isn't there fails with `NotFound`, as does reading
+/// a missing file, so only errors about starting something count
+fn is_tool_missing(error: &GbError) -> bool {
+    error
+        .source
+        .as_ref()
+        .and_then(|source| source.downcast_ref::<std::io::Error>())
+        .is_some_and(|source| source.kind() == std::io::ErrorKind::NotFound)
+        && (error.message.contains("spawn") || error.message.contains("could not run"))
+}
+
+fn kind(error: &GbError) -> (&'static str, i32) {
+    if is_tool_missing(error) {
+        return ("tool-missing", 3);
+    }
+    match current() {
+        Phase::Config => ("config", 2),
+        Phase::Analysis => ("analysis", 4),
+        Phase::Elaboration => ("elaboration", 5),
+        Phase::Simulation => ("simulation", 6),
+        Phase::Tests => ("tests", 7),
+        Phase::Runtime => ("runtime", 8),
+        Phase::Synthesis => ("synthesis", 9),
+        Phase::Timeout => ("timeout", 10),
+        Phase::Other => ("other", 1),
+    }
+}
+
+/// how a failure is printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// the message alone
+    #[default]
+    Short,
+    /// the message and what caused it
+    Full,
+    /// one json object, with the kind of failure and the exit code
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonError<'e> {
+    kind: &'static str,
+    exit_code: i32,
+    message: &'e str,
+    causes: Vec<String>,
+}
+
+fn causes(error: &GbError) -> Vec<String> {
+    let mut causes = Vec::new();
+    let mut source = error
+        .source
+        .as_deref()
+        .map(|source| source as &(dyn std::error::Error + 'static));
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    causes
+}
+
+/// prints `error` and returns the code gb should exit with
+pub fn report(error: &GbError, format: ErrorFormat) -> i32 {
+    let (kind, code) = kind(error);
+    match format {
+        ErrorFormat::Short => eprintln!("{error}"),
+        ErrorFormat::Full => {
+            eprintln!("{error}");
+            for cause in causes(error) {
+                eprintln!("  caused by: {cause}");
+            }
+        }
+        ErrorFormat::Json => {
+            let json = JsonError {
+                kind,
+                exit_code: code,
+                message: &error.message,
+                causes: causes(error),
+            };
+            if let Ok(json) = serde_json::to_string(&json) {
+                eprintln!("{json}");
+            }
+        }
+    }
+    if code == 3 && format != ErrorFormat::Json {
+        eprintln!("  `gb doctor` checks everything gb needs, and how to get it");
+    }
+    code
+}
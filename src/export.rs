@@ -0,0 +1,161 @@
+//! `gb export`: hand what gb does to people and tools that don't use gb.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::Serialize;
+
+use crate::{Check, GbError};
+
+/// how `gb export files` writes the file list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FilesFormat {
+    /// one path per line, with a `// library` comment starting each library
+    #[default]
+    FFile,
+    /// `library,file` rows
+    Csv,
+    Json,
+}
+
+/// a file of the list, with the library it's analyzed into
+#[derive(Debug, Clone, Serialize)]
+pub struct Source {
+    pub library: String,
+    pub file: String,
+}
+
+fn is_shell_safe(arg: &str) -> bool {
+    !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_./=+:,@%-".contains(c))
+}
+
+fn sh_quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    if is_shell_safe(&arg) {
+        arg.into_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+fn ps_quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    if is_shell_safe(&arg) {
+        arg.into_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "''"))
+    }
+}
+
+fn command_line(command: &Command, quote: fn(&OsStr) -> String) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn sh_script(target: &str, steps: &[Command]) -> String {
+    let mut script = format!(
+        "#!/bin/sh\n\
+         # reproduces `gb run {target}` without gb, generated by gb {}.\n\
+         # run it from the directory containing gb.toml.\n\
+         set -e\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    for step in steps {
+        script.push_str(&command_line(step, sh_quote));
+        script.push('\n');
+    }
+    script
+}
+
+fn ps1_script(target: &str, steps: &[Command]) -> String {
+    let mut script = format!(
+        "# reproduces `gb run {target}` without gb, generated by gb {}.\n\
+         # run it from the directory containing gb.toml.\n\
+         $ErrorActionPreference = \"Stop\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    for step in steps {
+        script.push_str("& ");
+        script.push_str(&command_line(step, ps_quote));
+        script.push_str("\nif ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }\n");
+    }
+    script
+}
+
+/// writes `run.sh` and `run.ps1` into `dir`, each running `steps` in order.
+///
+/// gb shuffles the analysis artifacts into `build/<profile>/` between the steps,
+/// the scripts don't bother and just let ghdl work in place, which
+/// behaves the same as far as ghdl is concerned.
+pub fn write_scripts(dir: &Path, target: &str, steps: &[Command]) -> Result<Vec<PathBuf>, GbError> {
+    std::fs::create_dir_all(dir).fatal(format!(
+        "could not create the export directory `{}`",
+        dir.display()
+    ))?;
+
+    let sh = dir.join("run.sh");
+    std::fs::write(&sh, sh_script(target, steps))
+        .fatal(format!("could not write `{}`", sh.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&sh, std::fs::Permissions::from_mode(0o755))
+            .fatal(format!("could not make `{}` executable", sh.display()))?;
+    }
+
+    let ps1 = dir.join("run.ps1");
+    std::fs::write(&ps1, ps1_script(target, steps))
+        .fatal(format!("could not write `{}`", ps1.display()))?;
+
+    Ok(vec![sh, ps1])
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// `sources`, in the order they're analyzed in, as `format`
+pub fn files(sources: &[Source], format: FilesFormat) -> Result<String, GbError> {
+    let mut out = String::new();
+    match format {
+        FilesFormat::FFile => {
+            let mut library = None;
+            for source in sources {
+                if library != Some(&source.library) {
+                    out.push_str(&format!("// library {}\n", source.library));
+                    library = Some(&source.library);
+                }
+                out.push_str(&source.file);
+                out.push('\n');
+            }
+        }
+        FilesFormat::Csv => {
+            out.push_str("library,file\n");
+            for source in sources {
+                out.push_str(&format!(
+                    "{},{}\n",
+                    csv_field(&source.library),
+                    csv_field(&source.file)
+                ));
+            }
+        }
+        FilesFormat::Json => {
+            out = serde_json::to_string_pretty(sources).fatal("could not serialize the files")?;
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
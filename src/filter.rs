@@ -0,0 +1,214 @@
+//! Output filters: regexes in gb.toml hiding known-benign ghdl chatter and
+//! highlighting the lines that matter, per phase:
+//!
+//! ```toml
+//! [output]
+//! highlight = ["error"]                 # every phase
+//!
+//! [output.analyze]
+//! suppress = ["warning: .* is never read"]
+//! ```
+//!
+//! only what is shown on the terminal is filtered, run logs keep everything.
+//! with `--message-format=json` diagnostics skip the filters and go to stdout
+//! as json.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::JoinHandle,
+};
+
+use colored::Colorize;
+use regex::Regex;
+use toml_edit::{Document, Item};
+
+use crate::{
+    baseline,
+    diagnostics::{Diagnostic, MessageFormat},
+    Check, GbError,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputFilter {
+    suppress: Vec<Regex>,
+    highlight: Vec<Regex>,
+    json: bool,
+    /// hand warnings to the baseline
+    collect_warnings: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputFilters {
+    pub analyze: OutputFilter,
+    pub elaborate: OutputFilter,
+    pub run: OutputFilter,
+}
+
+fn regexes(table: Option<&Item>, key: &str, at: &str) -> Result<Vec<Regex>, GbError> {
+    let Some(item) = table.and_then(|table| table.get(key)) else {
+        return Ok(vec![]);
+    };
+    item.as_array()
+        .fatal(format!("`{at}.{key}` must be an array of regexes"))?
+        .iter()
+        .map(|regex| {
+            let regex = regex
+                .as_str()
+                .fatal(format!("`{at}.{key}` must only contain strings"))?;
+            Regex::new(regex).fatal(format!("`{regex}` in `{at}.{key}` is not a valid regex"))
+        })
+        .collect()
+}
+
+impl OutputFilter {
+    fn parse(table: Option<&Item>, at: &str) -> Result<OutputFilter, GbError> {
+        Ok(OutputFilter {
+            suppress: regexes(table, "suppress", at)?,
+            highlight: regexes(table, "highlight", at)?,
+            json: false,
+            collect_warnings: false,
+        })
+    }
+
+    fn extend(mut self, other: &OutputFilter) -> OutputFilter {
+        self.suppress.extend(other.suppress.iter().cloned());
+        self.highlight.extend(other.highlight.iter().cloned());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.suppress.is_empty()
+            && self.highlight.is_empty()
+            && !self.json
+            && !self.collect_warnings
+    }
+
+    /// whether everything that isn't a diagnostic belongs on stderr
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// the line as it should be shown, or `None` if it's suppressed
+    pub fn apply(&self, line: &str) -> Option<String> {
+        if self.suppress.iter().any(|regex| regex.is_match(line)) {
+            return None;
+        }
+        if self.highlight.iter().any(|regex| regex.is_match(line)) {
+            return Some(line.yellow().bold().to_string());
+        }
+        Some(line.to_owned())
+    }
+
+    /// writes `line` to `sink` as it should be shown, diagnostics to stdout
+    /// instead when they're wanted as json
+    pub fn show(&self, line: &str, sink: &mut impl Write) {
+        if self.json || self.collect_warnings {
+            if let Some(diagnostic) = Diagnostic::parse(line) {
+                if self.collect_warnings && diagnostic.severity == "warning" {
+                    baseline::note(diagnostic.clone());
+                }
+                if self.json {
+                    println!("{}", diagnostic.to_json());
+                    return;
+                }
+            }
+        }
+        if let Some(line) = self.apply(line) {
+            let _ = writeln!(sink, "{line}");
+        }
+    }
+}
+
+impl OutputFilters {
+    /// reads the `[output]` table, whose top level rules apply to every phase
+    pub fn parse(doc: &Document) -> Result<OutputFilters, GbError> {
+        let output = doc.get("output");
+        let every = OutputFilter::parse(output, "output")?;
+        let phase = |name: &str| {
+            OutputFilter::parse(
+                output.and_then(|output| output.get(name)),
+                &format!("output.{name}"),
+            )
+            .map(|filter| filter.extend(&every))
+        };
+        Ok(OutputFilters {
+            analyze: phase("analyze")?,
+            elaborate: phase("elaborate")?,
+            run: phase("run")?,
+        })
+    }
+
+    /// collects the warnings of analysis for a baseline
+    pub fn collect_warnings(&mut self) {
+        self.analyze.collect_warnings = true;
+    }
+
+    pub fn set_message_format(&mut self, format: MessageFormat) {
+        let json = format == MessageFormat::Json;
+        for filter in [&mut self.analyze, &mut self.elaborate, &mut self.run] {
+            filter.json = json;
+        }
+    }
+}
+
+/// copies `stream` line by line into `sink`, through `filter`
+pub fn pump(
+    stream: impl Read + Send + 'static,
+    mut sink: impl Write + Send + 'static,
+    filter: OutputFilter,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            filter.show(&line, &mut sink);
+        }
+    })
+}
+
+/// a process whose output goes through a filter on its way to the terminal
+pub struct Filtered {
+    child: Child,
+    pumps: Vec<JoinHandle<()>>,
+}
+
+impl Filtered {
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        for pump in self.pumps.drain(..) {
+            let _ = pump.join();
+        }
+        Ok(status)
+    }
+}
+
+/// spawns `command`, only capturing its output when there is something to
+/// filter, so ghdl keeps its colours otherwise.
+pub fn spawn(command: &mut Command, filter: &OutputFilter) -> std::io::Result<Filtered> {
+    crate::verbosity::echo(command);
+    if filter.is_empty() {
+        return Ok(Filtered {
+            child: command.spawn()?,
+            pumps: vec![],
+        });
+    }
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut pumps = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        if filter.is_json() {
+            pumps.push(pump(stdout, std::io::stderr(), filter.clone()));
+        } else {
+            pumps.push(pump(stdout, std::io::stdout(), filter.clone()));
+        }
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pumps.push(pump(stderr, std::io::stderr(), filter.clone()));
+    }
+    Ok(Filtered { child, pumps })
+}
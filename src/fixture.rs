@@ -0,0 +1,51 @@
+//! A small design on disk for the tests that need vhdl to scan: a package,
+//! its body, an entity using it, a top instantiating that entity directly and
+//! an entity nothing uses. it's removed again when the fixture is dropped.
+
+use std::path::PathBuf;
+
+pub struct Design {
+    dir: PathBuf,
+    files: Vec<String>,
+}
+
+impl Design {
+    /// writes the design into a directory of its own, `name` tells apart the
+    /// tests running at the same time
+    pub fn new(name: &str) -> Design {
+        let dir = std::env::temp_dir().join(format!("gb-design-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("top.vhd", "entity top is\nend entity;\narchitecture sim of top is\nbegin\n    dut: entity work.user;\nend architecture;\n"),
+            ("user.vhd", "use work.pkg.all;\nentity user is\nend entity;\narchitecture rtl of user is\nbegin\nend architecture;\n"),
+            ("pkg_body.vhd", "package body pkg is\nend package body;\n"),
+            ("pkg.vhd", "package pkg is\n    constant WIDTH : integer := 8;\nend package;\n"),
+            ("other.vhd", "entity other is\nend entity;\n"),
+        ]
+        .iter()
+        .map(|(file, contents)| {
+            let path = dir.join(file);
+            std::fs::write(&path, contents).unwrap();
+            path.display().to_string()
+        })
+        .collect();
+        Design { dir, files }
+    }
+
+    /// the files, top first and the package last
+    pub fn files(&self) -> Vec<&str> {
+        self.files.iter().map(String::as_str).collect()
+    }
+}
+
+impl Drop for Design {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// the name of `file`, without the directory of the design
+pub fn name(file: &str) -> &str {
+    file.rsplit(['/', '\\']).next().unwrap_or(file)
+}
@@ -0,0 +1,520 @@
+//! `gb fmt`: reformats vhdl sources in the style `gb new` writes them in, on
+//! top of the tokens tree-sitter finds, so comments and strings are left alone.
+//!
+//! ```toml
+//! [fmt]
+//! keyword-case = "lower"   # or "upper", "preserve"
+//! indent = 2
+//! align-ports = true
+//! ```
+//!
+//! it re-indents every line, re-cases reserved words and lines up the `:` and
+//! modes of port and generic clauses. line breaks stay where they are. files
+//! which don't parse are left as they are. `--check` only reports the files
+//! which would change, for CI.
+
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use toml_edit::Document;
+
+use crate::{
+    sources,
+    tree_sitter::{self, Leaf},
+    Check, GbError, Level,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordCase {
+    Lower,
+    Upper,
+    Preserve,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub keyword_case: KeywordCase,
+    pub indent: usize,
+    pub align_ports: bool,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            keyword_case: KeywordCase::Lower,
+            indent: 2,
+            align_ports: true,
+        }
+    }
+}
+
+impl Style {
+    /// the `[fmt]` table of gb.toml
+    pub fn parse(doc: Option<&Document>) -> Result<Style, GbError> {
+        let mut style = Style::default();
+        let Some(fmt) = doc.and_then(|doc| doc.get("fmt")) else {
+            return Ok(style);
+        };
+        if let Some(case) = fmt.get("keyword-case") {
+            style.keyword_case = match case.as_str() {
+                Some("lower") => KeywordCase::Lower,
+                Some("upper") => KeywordCase::Upper,
+                Some("preserve") => KeywordCase::Preserve,
+                _ => Err(GbError {
+                    message: "`fmt.keyword-case` must be \"lower\", \"upper\" or \"preserve\""
+                        .to_owned(),
+                    level: Level::Fatal,
+                    source: None,
+                })?,
+            };
+        }
+        if let Some(indent) = fmt.get("indent") {
+            style.indent = indent
+                .as_integer()
+                .and_then(|indent| usize::try_from(indent).ok())
+                .filter(|indent| *indent <= 16)
+                .fatal("`fmt.indent` must be a number of spaces, up to 16")?;
+        }
+        if let Some(align) = fmt.get("align-ports") {
+            style.align_ports = align
+                .as_bool()
+                .fatal("`fmt.align-ports` must be true or false")?;
+        }
+        Ok(style)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// opened by `is`, `process`, `loop`, `generate`, `record`, ...
+    Block,
+    /// the statements after `begin`
+    Begin,
+    /// the branch of an `if` after `then` or `else`
+    If,
+    /// the alternatives of a `case`
+    Case,
+    /// the statements of one `when ... =>` alternative
+    When,
+    /// `clause` for the parentheses of `port (...)` and `generic (...)`
+    Paren { clause: bool },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Open {
+    kind: Kind,
+    row: usize,
+}
+
+/// a port or generic declaration on a line of its own, to be aligned
+#[derive(Debug, Clone)]
+struct Declaration {
+    row: usize,
+    /// the clause it belongs to, as the row the clause was opened on
+    clause: usize,
+    names: String,
+    mode: Option<String>,
+    rest: String,
+}
+
+/// what the formatter knows about where it is in the file
+#[derive(Default)]
+struct State {
+    stack: Vec<Open>,
+    /// the next token starts a statement
+    statement_start: bool,
+    first_keyword: Option<String>,
+    /// `process`, `block` or `component` opened the block, their `is` doesn't
+    skip_is: bool,
+    /// inside `end ...;`, where `loop`, `generate` and the like open nothing
+    after_end: bool,
+    /// a case `when` was seen, its `=>` opens the alternative
+    pending_arrow: bool,
+    /// `else` opened the branch already, an `else generate` mustn't open another
+    after_else: bool,
+    /// the depth of the stack inside a configuration, whose `for`s open blocks
+    configuration: Option<usize>,
+    previous: Option<String>,
+}
+
+/// reserved words that open a block whatever comes before them
+const OPENERS: &[&str] = &["loop", "generate", "record", "units", "protected"];
+
+/// declarations whose `is` doesn't open a block
+const NO_BLOCK_IS: &[&str] = &["type", "subtype", "alias", "attribute", "file", "group"];
+
+const MODES: &[&str] = &["in", "out", "inout", "buffer", "linkage"];
+
+impl State {
+    fn top(&self) -> Option<Kind> {
+        self.stack.last().map(|open| open.kind)
+    }
+
+    fn pop_if(&mut self, matches: impl Fn(Kind) -> bool) -> bool {
+        if self.top().is_some_and(matches) {
+            self.stack.pop();
+            if self.configuration > Some(self.stack.len()) {
+                self.configuration = None;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// whether `word` closes something, so its line is indented like the opener
+    fn closes(&self, word: &str) -> bool {
+        match word {
+            ")" | "end" | "begin" | "elsif" => true,
+            "else" => self.statement_start && matches!(self.top(), Some(Kind::If | Kind::Block)),
+            "when" => self.statement_start && matches!(self.top(), Some(Kind::When)),
+            _ => false,
+        }
+    }
+
+    /// the indentation level of a line starting on `row`: one per row
+    /// something is still open from
+    fn level(&self, row: usize) -> usize {
+        let mut rows = self
+            .stack
+            .iter()
+            .map(|open| open.row)
+            .filter(|opened| *opened < row)
+            .collect::<Vec<_>>();
+        rows.dedup();
+        rows.len()
+    }
+
+    fn end_statement(&mut self) {
+        self.statement_start = true;
+        self.first_keyword = None;
+        self.skip_is = false;
+        self.after_end = false;
+        self.pending_arrow = false;
+        self.after_else = false;
+    }
+
+    /// opens a block, after which a new statement starts
+    fn open(&mut self, kind: Kind, row: usize) {
+        self.stack.push(Open { kind, row });
+        self.end_statement();
+    }
+
+    fn step(&mut self, word: &str, keyword: bool, row: usize) {
+        let at_start = self.statement_start;
+        self.statement_start = false;
+        if keyword && self.first_keyword.is_none() {
+            self.first_keyword = Some(word.to_owned());
+        }
+        let after_else = std::mem::take(&mut self.after_else);
+        let previous = self.previous.replace(word.to_owned());
+
+        match word {
+            // the `;`s between parameters don't end the declaration
+            ";" if matches!(self.top(), Some(Kind::Paren { .. })) => {}
+            ";" => self.end_statement(),
+            "(" => {
+                let clause = matches!(previous.as_deref(), Some("port" | "generic"));
+                self.stack.push(Open {
+                    kind: Kind::Paren { clause },
+                    row,
+                });
+            }
+            ")" => {
+                self.pop_if(|kind| matches!(kind, Kind::Paren { .. }));
+            }
+            "=>" if self.pending_arrow => self.open(Kind::When, row),
+            _ if !keyword || self.after_end => {}
+            "end" => {
+                while self.pop_if(|kind| kind == Kind::When) {}
+                self.pop_if(|kind| !matches!(kind, Kind::Paren { .. }));
+                self.after_end = true;
+            }
+            "begin" => {
+                self.pop_if(|kind| kind == Kind::Block);
+                self.open(Kind::Begin, row);
+            }
+            "then" => self.open(Kind::If, row),
+            "elsif" => {
+                self.pop_if(|kind| matches!(kind, Kind::If | Kind::Block));
+            }
+            "else" if at_start && matches!(self.top(), Some(Kind::If | Kind::Block)) => {
+                self.pop_if(|_| true);
+                self.open(Kind::If, row);
+                self.after_else = true;
+            }
+            "when" if at_start && matches!(self.top(), Some(Kind::Case | Kind::When)) => {
+                self.pop_if(|kind| kind == Kind::When);
+                self.pending_arrow = true;
+            }
+            "generate" if after_else => {}
+            "is" if self.skip_is => {
+                self.skip_is = false;
+                self.statement_start = true;
+            }
+            "is" => match self.first_keyword.as_deref().unwrap_or_default() {
+                "case" => self.open(Kind::Case, row),
+                "configuration" => {
+                    self.open(Kind::Block, row);
+                    self.configuration = Some(self.stack.len());
+                }
+                first if NO_BLOCK_IS.contains(&first) => {}
+                _ => self.open(Kind::Block, row),
+            },
+            // `package p is new ...` instantiates, it has no body
+            "new" if previous.as_deref() == Some("is") => {
+                self.pop_if(|kind| kind == Kind::Block);
+                self.statement_start = false;
+            }
+            "process" | "block" => {
+                self.open(Kind::Block, row);
+                self.skip_is = true;
+            }
+            "component" if at_start => {
+                self.open(Kind::Block, row);
+                self.skip_is = true;
+            }
+            // the block configurations of a configuration, up to `end for`
+            "for" if at_start && self.configuration.is_some() => self.open(Kind::Block, row),
+            _ if OPENERS.contains(&word) => self.open(Kind::Block, row),
+            _ => {}
+        }
+        // the `is` after the name of a component is optional
+        if self.skip_is && previous.as_deref() == Some("component") {
+            self.statement_start = true;
+        }
+    }
+}
+
+fn recase(source: &str, leaves: &[Leaf], case: KeywordCase) -> String {
+    let mut cased = source.to_owned();
+    if case == KeywordCase::Preserve {
+        return cased;
+    }
+    for leaf in leaves.iter().filter(|leaf| leaf.keyword) {
+        let word = &source[leaf.start..leaf.end];
+        let word = match case {
+            KeywordCase::Lower => word.to_ascii_lowercase(),
+            _ => word.to_ascii_uppercase(),
+        };
+        // same length, only ascii letters change
+        cased.replace_range(leaf.start..leaf.end, &word);
+    }
+    cased
+}
+
+/// `source` formatted in `style`, given the tokens tree-sitter found in it
+fn format_tokens(source: &str, leaves: &[Leaf], style: Style) -> String {
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let cased = recase(source, leaves, style.keyword_case);
+    let lines = cased.split('\n').collect::<Vec<_>>();
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in &lines {
+        line_starts.push(offset);
+        offset += line.len() + 1;
+    }
+
+    let mut state = State {
+        statement_start: true,
+        ..Default::default()
+    };
+    let mut levels = vec![None; lines.len()];
+    // rows continuing a token started further up, like a block comment
+    let mut verbatim = vec![false; lines.len()];
+    let mut declarations = Vec::new();
+
+    let mut next = 0;
+    // the last row reached by a token seen so far
+    let mut reach = 0;
+    for row in 0..lines.len() {
+        verbatim[row] = next > 0 && reach >= row;
+        let mut on_row = Vec::new();
+        while next < leaves.len() && leaves[next].row == row {
+            on_row.push(&leaves[next]);
+            reach = reach.max(leaves[next].end_row);
+            next += 1;
+        }
+        if on_row.is_empty() {
+            continue;
+        }
+
+        let continues = !state.statement_start && !matches!(state.top(), Some(Kind::Paren { .. }));
+        let mut level = None;
+        let mut colons = Vec::new();
+        let clause = match state.top() {
+            Some(Kind::Paren { clause: true }) => state.stack.last().map(|open| open.row),
+            _ => None,
+        };
+        let depth = state.stack.len();
+        for (index, leaf) in on_row.iter().enumerate() {
+            let word = cased[leaf.start..leaf.end].to_ascii_lowercase();
+            if leaf.comment {
+                if level.is_none() && index == 0 {
+                    level = Some(state.level(row));
+                }
+                continue;
+            }
+            if level.is_none() && !state.closes(&word) {
+                level = Some(state.level(row) + usize::from(continues && index == 0));
+            }
+            if word == ":" && state.stack.len() == depth {
+                colons.push(index);
+            }
+            state.step(&word, leaf.keyword, row);
+        }
+        levels[row] = Some(level.unwrap_or_else(|| state.level(row)));
+
+        // `names : [mode] type`, alone on its line in a port or generic clause
+        if let (Some(clause), &[colon]) = (clause, colons.as_slice()) {
+            let is_mode = |leaf: &&&Leaf| {
+                leaf.keyword
+                    && MODES.contains(&cased[leaf.start..leaf.end].to_ascii_lowercase().as_str())
+            };
+            let mode = on_row.get(colon + 1).filter(is_mode);
+            let rest = on_row.get(colon + 1 + usize::from(mode.is_some()));
+            let line_end = line_starts[row] + lines[row].len();
+            if let (false, Some(rest)) = (on_row[0].comment || colon == 0, rest) {
+                declarations.push(Declaration {
+                    row,
+                    clause,
+                    names: cased[on_row[0].start..on_row[colon].start]
+                        .trim_end()
+                        .to_owned(),
+                    mode: mode.map(|mode| cased[mode.start..mode.end].to_owned()),
+                    rest: cased[rest.start..line_end].trim_end().to_owned(),
+                });
+            }
+        }
+    }
+
+    let mut aligned = vec![None; lines.len()];
+    if style.align_ports {
+        let mut start = 0;
+        while start < declarations.len() {
+            let clause = declarations[start].clause;
+            let end = declarations[start..]
+                .iter()
+                .position(|declaration| declaration.clause != clause)
+                .map_or(declarations.len(), |len| start + len);
+            let group = &declarations[start..end];
+            let names = group.iter().map(|d| d.names.len()).max().unwrap_or(0);
+            let modes = group
+                .iter()
+                .filter_map(|d| d.mode.as_ref().map(String::len))
+                .max();
+            for declaration in group {
+                let mode = match (modes, &declaration.mode) {
+                    (Some(width), Some(mode)) => format!("{mode:<width$} "),
+                    (Some(width), None) => " ".repeat(width + 1),
+                    (None, _) => String::new(),
+                };
+                aligned[declaration.row] = Some(format!(
+                    "{:<names$} : {mode}{}",
+                    declaration.names, declaration.rest
+                ));
+            }
+            start = end;
+        }
+    }
+
+    let mut formatted = Vec::with_capacity(lines.len());
+    for (row, line) in lines.iter().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if verbatim[row] {
+            formatted.push(line.to_owned());
+            continue;
+        }
+        let content = aligned[row].as_deref().unwrap_or(line.trim());
+        if content.is_empty() {
+            formatted.push(String::new());
+            continue;
+        }
+        let level = levels[row].unwrap_or(0);
+        formatted.push(format!("{}{content}", " ".repeat(level * style.indent)));
+    }
+    while formatted.last().is_some_and(|line| line.is_empty()) {
+        formatted.pop();
+    }
+    let mut formatted = formatted.join(newline);
+    if !formatted.is_empty() {
+        formatted.push_str(newline);
+    }
+    formatted
+}
+
+/// `source` formatted in `style`, or why it can't be
+pub fn format(source: &str, style: Style) -> Result<String, String> {
+    let leaves = tree_sitter::leaves(source).map_err(|err| err.to_string())?;
+    Ok(format_tokens(source, &leaves, style))
+}
+
+pub fn fmt(doc: Option<&Document>, files: &[PathBuf], check: bool) -> Result<(), GbError> {
+    let style = Style::parse(doc)?;
+    crate::exit::configured();
+    let files = if files.is_empty() {
+        sources::find_vhdl_sources(Path::new("."))
+    } else {
+        files.to_vec()
+    };
+
+    let mut changed = Vec::new();
+    let mut unparsed = Vec::new();
+    for file in &files {
+        let source =
+            std::fs::read_to_string(file).fatal(format!("could not read `{}`", file.display()))?;
+        let formatted = match format(&source, style) {
+            Ok(formatted) => formatted,
+            Err(reason) => {
+                unparsed.push((file, reason));
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if !check {
+            std::fs::write(file, formatted)
+                .fatal(format!("could not write `{}`", file.display()))?;
+        }
+        changed.push(file);
+    }
+
+    if !unparsed.is_empty() {
+        eprintln!("The following files were left as they are, they don't parse");
+        for (pos, (file, reason)) in unparsed.iter().enumerate() {
+            eprintln!("  {}. {}: {reason}", pos + 1, file.display());
+        }
+    }
+    if check {
+        if !changed.is_empty() {
+            eprintln!("The following files are not formatted");
+            for (pos, file) in changed.iter().enumerate() {
+                eprintln!("  {}. {}", pos + 1, file.display());
+            }
+        }
+        if !changed.is_empty() || !unparsed.is_empty() {
+            Err(GbError {
+                message: format!(
+                    "{} of {} files are not formatted, run `gb fmt`",
+                    changed.len() + unparsed.len(),
+                    files.len()
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        return Ok(());
+    }
+    eprintln!(
+        "  {}  {}",
+        "[fmt]".blue().bold(),
+        format!("Formatted {} of {} files", changed.len(), files.len())
+            .green()
+            .bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! C code a target co-simulates with, either through VHPIDIRECT, linked into
+//! the simulation when it's elaborated, or as VPI plugins the simulation
+//! loads:
+//!
+//! ```toml
+//! [target.cosim]
+//! foreign = ["src/c/dpi.c", "lib/libmodel.a"]  # for `attribute foreign`
+//! vpi = ["src/c/trace.c"]                      # loaded with `--vpi=`
+//! ```
+//!
+//! C sources are compiled into `build/<profile>/foreign/` when they changed,
+//! with `$CC`, or `cc` when it isn't set. `vpi` sources go through ghdl's
+//! `--vpi-compile` and `--vpi-link`, so they find `vpi_user.h`. objects,
+//! archives and shared libraries are used as they are.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use toml_edit::Item;
+
+use crate::{ghdl, profile, verbosity, Check, GbError, Level};
+
+/// the foreign code of a target, none when it has no `foreign` or `vpi`
+#[derive(Debug, Clone, Default)]
+pub struct Foreign {
+    link: Vec<PathBuf>,
+    vpi: Vec<PathBuf>,
+}
+
+fn paths(target: &str, key: &str, item: Option<&Item>) -> Result<Vec<PathBuf>, GbError> {
+    let Some(item) = item else {
+        return Ok(vec![]);
+    };
+    item.as_array()
+        .and_then(|paths| {
+            paths
+                .iter()
+                .map(|path| path.as_str().map(PathBuf::from))
+                .collect::<Option<Vec<_>>>()
+        })
+        .fatal(format!(
+            "`target.{target}.{key}` must be a list of paths, like `{key} = [\"src/c/model.c\"]`"
+        ))
+}
+
+fn is_source(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("c" | "cc" | "cpp")
+    )
+}
+
+fn is_shared_library(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("so" | "dylib" | "dll" | "vpi")
+    )
+}
+
+/// where a C source is compiled to, with the extension of what it becomes
+fn output(source: &Path, extension: &str) -> PathBuf {
+    let stem = source.file_stem().unwrap_or_default();
+    profile::dir()
+        .join("foreign")
+        .join(stem)
+        .with_extension(extension)
+}
+
+/// ghdl elaborates and runs in the profile's directory, so paths given to it
+/// have to be absolute
+fn absolute(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| path.to_owned())
+}
+
+fn compiler() -> String {
+    std::env::var("CC").unwrap_or_else(|_| "cc".to_owned())
+}
+
+fn run(command: &mut Command, source: &Path) -> Result<(), GbError> {
+    verbosity::echo(command);
+    let status = command.status().fatal(format!(
+        "couldn't spawn the C compiler for `{}`",
+        source.display()
+    ))?;
+    if !status.success() {
+        Err(GbError {
+            message: format!("`{}` didn't compile ({status})", source.display()),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+impl Foreign {
+    pub fn from_manifest(target: &str, target_info: &Item) -> Result<Foreign, GbError> {
+        let foreign = Foreign {
+            link: paths(target, "foreign", target_info.get("foreign"))?,
+            vpi: paths(target, "vpi", target_info.get("vpi"))?,
+        };
+        for path in foreign.link.iter().chain(&foreign.vpi) {
+            if !path.exists() {
+                Err(GbError {
+                    message: format!(
+                        "the foreign code `{}` of target `{target}` was not found",
+                        path.display()
+                    ),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+        }
+        Ok(foreign)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.link.is_empty() && self.vpi.is_empty()
+    }
+
+    /// compiles the C sources that changed since they were last compiled
+    pub fn compile(&self) -> Result<(), GbError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let links_statically = self.link.iter().any(|path| !is_shared_library(path));
+        if links_statically && ghdl::backend() == Some(ghdl::Backend::Mcode) {
+            Err(GbError {
+                message: "ghdl's mcode backend can't link `foreign` code into the simulation, use the llvm or gcc backend, or a shared library".to_owned(),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        let dir = profile::dir().join("foreign");
+        std::fs::create_dir_all(&dir).fatal(format!("could not create `{}`", dir.display()))?;
+
+        for source in self.link.iter().filter(|path| is_source(path)) {
+            let object = output(source, "o");
+            if crate::is_stale(&object, &[&source.to_string_lossy()]) {
+                verbosity::step("[foreign]", &format!("Compiling {}", source.display()));
+                run(
+                    Command::new(compiler())
+                        .args(["-c", "-fPIC"])
+                        .arg(source)
+                        .arg("-o")
+                        .arg(&object),
+                    source,
+                )?;
+            }
+        }
+        for source in self.vpi.iter().filter(|path| is_source(path)) {
+            let (object, plugin) = (output(source, "o"), output(source, "vpi"));
+            if crate::is_stale(&plugin, &[&source.to_string_lossy()]) {
+                verbosity::step("[foreign]", &format!("Compiling {}", source.display()));
+                run(
+                    Command::new(ghdl::path())
+                        .args(["--vpi-compile", &compiler(), "-c"])
+                        .arg(source)
+                        .arg("-o")
+                        .arg(&object),
+                    source,
+                )?;
+                run(
+                    Command::new(ghdl::path())
+                        .args(["--vpi-link", &compiler()])
+                        .arg(&object)
+                        .arg("-o")
+                        .arg(&plugin),
+                    source,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// linker flags putting the `foreign` code into the simulation
+    pub fn elaborate_flags(&self) -> Vec<String> {
+        self.link
+            .iter()
+            .map(|path| {
+                if is_source(path) {
+                    output(path, "o")
+                } else {
+                    path.clone()
+                }
+            })
+            .map(|path| format!("-Wl,{}", absolute(&path).display()))
+            .collect()
+    }
+
+    /// `--vpi=` for every plugin
+    pub fn run_flags(&self) -> Vec<String> {
+        self.vpi
+            .iter()
+            .map(|path| {
+                if is_source(path) {
+                    output(path, "vpi")
+                } else {
+                    path.clone()
+                }
+            })
+            .map(|path| format!("--vpi={}", absolute(&path).display()))
+            .collect()
+    }
+}
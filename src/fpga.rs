@@ -0,0 +1,264 @@
+//! `gb pnr` and `gb flash`: takes a target's design all the way onto an fpga,
+//! through yosys, nextpnr, the family's packer and openFPGALoader. the design
+//! is the top of `[target.<name>.synth]`, the board is set once:
+//!
+//! ```toml
+//! [fpga]
+//! board = "icebreaker"             # a board gb knows sets everything else
+//! constraints = "icebreaker.pcf"   # .pcf for ice40, .lpf for ecp5
+//!
+//! # or, for a board gb doesn't know
+//! family = "ecp5"                  # "ice40" or "ecp5"
+//! device = "25k"                   # nextpnr's `--25k`
+//! package = "CABGA256"
+//! ```
+//!
+//! everything is written to `build/fpga/`, named after the top: the json
+//! netlist, the placed and routed design and the bitstream. `gb flash` gives
+//! `board` to openFPGALoader's `-b`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+use toml_edit::Document;
+
+use crate::{exit, synth, verbosity, BuildOptions, Check, GbError, Level};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    Ice40,
+    Ecp5,
+}
+
+impl Family {
+    fn parse(family: &str) -> Option<Family> {
+        match family {
+            "ice40" => Some(Family::Ice40),
+            "ecp5" => Some(Family::Ecp5),
+            _ => None,
+        }
+    }
+
+    /// the programs a build for the family goes through, in order
+    pub fn tools(self) -> &'static [&'static str] {
+        match self {
+            Family::Ice40 => &["yosys", "nextpnr-ice40", "icepack"],
+            Family::Ecp5 => &["yosys", "nextpnr-ecp5", "ecppack"],
+        }
+    }
+}
+
+/// boards gb knows the family, device and package of
+const BOARDS: &[(&str, Family, &str, &str)] = &[
+    ("icebreaker", Family::Ice40, "up5k", "sg48"),
+    ("icestick", Family::Ice40, "hx1k", "tq144"),
+    ("tinyfpga-bx", Family::Ice40, "lp8k", "cm81"),
+    ("ulx3s", Family::Ecp5, "85k", "CABGA381"),
+    ("orangecrab", Family::Ecp5, "25k", "CSFBGA285"),
+];
+
+/// the `[fpga]` table
+#[derive(Debug, Clone)]
+pub struct Fpga {
+    pub board: Option<String>,
+    pub family: Family,
+    pub device: String,
+    pub package: String,
+    pub constraints: PathBuf,
+}
+
+impl Fpga {
+    pub fn from_manifest(doc: &Document) -> Result<Option<Fpga>, GbError> {
+        let Some(fpga) = doc.get("fpga") else {
+            return Ok(None);
+        };
+        let fpga = fpga.as_table_like().fatal("`fpga` must be a table")?;
+        let string = |key: &str| -> Result<Option<String>, GbError> {
+            fpga.get(key)
+                .map(|value| {
+                    value
+                        .as_str()
+                        .map(ToOwned::to_owned)
+                        .fatal(format!("`fpga.{key}` must be a string"))
+                })
+                .transpose()
+        };
+
+        let board = string("board")?;
+        let known = board
+            .as_deref()
+            .and_then(|board| BOARDS.iter().find(|(name, ..)| *name == board));
+        let family = match string("family")? {
+            Some(family) => Family::parse(&family).fatal(format!(
+                "unknown fpga family `{family}`, expected \"ice40\" or \"ecp5\""
+            ))?,
+            None => known
+                .map(|(_, family, ..)| *family)
+                .fatal(unknown_board(board.as_deref()))?,
+        };
+        let device = match string("device")? {
+            Some(device) => device,
+            None => known
+                .map(|(_, _, device, _)| device.to_string())
+                .fatal(unknown_board(board.as_deref()))?,
+        };
+        let package = match string("package")? {
+            Some(package) => package,
+            None => known
+                .map(|(.., package)| package.to_string())
+                .fatal(unknown_board(board.as_deref()))?,
+        };
+        let constraints = string("constraints")?
+            .fatal("`fpga.constraints` must point at the pin constraints, like `constraints = \"pins.pcf\"`")?;
+        Ok(Some(Fpga {
+            board,
+            family,
+            device,
+            package,
+            constraints: PathBuf::from(constraints),
+        }))
+    }
+
+    /// the file nextpnr writes and the packer reads, and the bitstream
+    fn outputs(&self, top: &str) -> (PathBuf, PathBuf) {
+        let dir = PathBuf::from("build").join("fpga");
+        match self.family {
+            Family::Ice40 => (
+                dir.join(format!("{top}.asc")),
+                dir.join(format!("{top}.bin")),
+            ),
+            Family::Ecp5 => (
+                dir.join(format!("{top}.config")),
+                dir.join(format!("{top}.bit")),
+            ),
+        }
+    }
+}
+
+fn unknown_board(board: Option<&str>) -> String {
+    let boards = BOARDS
+        .iter()
+        .map(|(name, ..)| *name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    match board {
+        Some(board) => format!(
+            "gb doesn't know the board `{board}`, set `fpga.family`, `fpga.device` and `fpga.package`, or use one of {boards}"
+        ),
+        None => format!(
+            "set `fpga.board` to one of {boards}, or `fpga.family`, `fpga.device` and `fpga.package`"
+        ),
+    }
+}
+
+fn run(command: &mut Command, tool: &str) -> Result<(), GbError> {
+    verbosity::echo(command);
+    let status = command
+        .status()
+        .fatal(format!("couldn't spawn {tool}, is it installed?"))?;
+    if !status.success() {
+        Err(GbError {
+            message: format!("{tool} failed ({status})"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+/// synthesizes, places and routes `top` and packs it into a bitstream,
+/// returning the bitstream
+pub fn pnr(
+    fpga: &Fpga,
+    top: &str,
+    build: &BuildOptions,
+    steps: [&str; 3],
+) -> Result<PathBuf, GbError> {
+    if !fpga.constraints.exists() {
+        Err(GbError {
+            message: format!(
+                "the constraints `{}` were not found",
+                fpga.constraints.display()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let dir = PathBuf::from("build").join("fpga");
+    std::fs::create_dir_all(&dir).fatal(format!("could not create `{}`", dir.display()))?;
+    let cwd = std::env::current_dir().fatal("cannot get the current directory")?;
+    let netlist = dir.join(format!("{top}.json"));
+    let (routed, bitstream) = fpga.outputs(top);
+
+    exit::during(exit::Phase::Synthesis, || {
+        verbosity::step(steps[0], "Synthesizing...");
+        let synth = match fpga.family {
+            Family::Ice40 => "synth_ice40",
+            Family::Ecp5 => "synth_ecp5",
+        };
+        // yosys runs in the profile's directory, so it needs the full path
+        let then = format!("{synth} -top {top} -json {}", cwd.join(&netlist).display());
+        run(&mut synth::yosys_command(top, build, &then), "yosys")?;
+
+        verbosity::step(steps[1], "Placing and Routing...");
+        let mut nextpnr = match fpga.family {
+            Family::Ice40 => Command::new("nextpnr-ice40"),
+            Family::Ecp5 => Command::new("nextpnr-ecp5"),
+        };
+        let (constraints, output) = match fpga.family {
+            Family::Ice40 => ("--pcf", "--asc"),
+            Family::Ecp5 => ("--lpf", "--textcfg"),
+        };
+        nextpnr
+            .arg(format!("--{}", fpga.device))
+            .args(["--package", &fpga.package])
+            .arg(constraints)
+            .arg(&fpga.constraints)
+            .arg("--json")
+            .arg(&netlist)
+            .arg(output)
+            .arg(&routed);
+        run(&mut nextpnr, "nextpnr")?;
+
+        verbosity::step(steps[2], "Packing...");
+        let mut pack = match fpga.family {
+            Family::Ice40 => Command::new("icepack"),
+            Family::Ecp5 => Command::new("ecppack"),
+        };
+        pack.arg(&routed).arg(&bitstream);
+        run(&mut pack, "the packer")
+    })?;
+
+    eprintln!(
+        "  {}  {}",
+        "[fpga]".blue().bold(),
+        format!("Wrote {}", bitstream.display()).green().bold()
+    );
+    Ok(bitstream)
+}
+
+/// loads `bitstream` onto the board with openFPGALoader
+pub fn flash(fpga: &Fpga, bitstream: &Path, step: &str) -> Result<(), GbError> {
+    let board = fpga
+        .board
+        .as_deref()
+        .fatal("flashing needs `fpga.board`, it's what openFPGALoader is told with `-b`")?;
+    verbosity::step(step, "Flashing...");
+    run(
+        Command::new("openFPGALoader")
+            .args(["-b", board])
+            .arg(bitstream),
+        "openFPGALoader",
+    )?;
+    eprintln!(
+        "  {}  {}",
+        "[fpga]".blue().bold(),
+        format!("Flashed {} onto the {board}", bitstream.display())
+            .green()
+            .bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,297 @@
+//! Which ghdl gb runs, for when more than one version is installed:
+//!
+//! ```toml
+//! [ghdl]
+//! path = "/opt/ghdl/bin/ghdl"
+//! flags = ["-frelaxed"]       # passed to every ghdl command
+//! ```
+//!
+//! `GB_GHDL` takes precedence over `ghdl.path`, so a single build can try
+//! another version without touching gb.toml. without either, gb runs the
+//! `ghdl` found on the path.
+//!
+//! which backend that ghdl was built with is asked once, with `ghdl --version`.
+//! it decides what analysis and elaboration leave behind, see `artifacts`.
+//!
+//! ghdl is the `Simulator` unless gb.toml picks another one, and the one
+//! synthesis goes through either way.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use once_cell::sync::OnceCell;
+use toml_edit::Document;
+
+use crate::{
+    artifacts, profile, simulator::Simulator, wave::Waveform, BuildOptions, Check, GbError,
+};
+
+pub const ENV: &str = "GB_GHDL";
+
+/// the code generator ghdl was built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// compiles in memory, no object files and no executable
+    Mcode,
+    Llvm,
+    Gcc,
+}
+
+impl Backend {
+    /// whether analysis writes an object file per source file, and
+    /// elaboration an executable
+    pub fn writes_objects(self) -> bool {
+        self != Backend::Mcode
+    }
+
+    /// the backend named by the output of `ghdl --version`
+    fn parse(version: &str) -> Option<Backend> {
+        let version = version.to_ascii_lowercase();
+        let generator = version
+            .lines()
+            .find(|line| line.contains("code generator"))?;
+        if generator.contains("mcode") {
+            Some(Backend::Mcode)
+        } else if generator.contains("llvm") {
+            Some(Backend::Llvm)
+        } else if generator.contains("gcc") {
+            Some(Backend::Gcc)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Backend::Mcode => "mcode",
+            Backend::Llvm => "llvm",
+            Backend::Gcc => "gcc",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    path: String,
+    flags: Vec<String>,
+}
+
+static CONFIGURED: OnceCell<Config> = OnceCell::new();
+
+static BACKEND: OnceCell<Option<Backend>> = OnceCell::new();
+
+/// reads `[ghdl]` from gb.toml, before the first ghdl command is made
+pub fn configure(doc: &Document) -> Result<(), GbError> {
+    let table = doc.get("ghdl");
+    let path = match std::env::var(ENV) {
+        Ok(path) if !path.is_empty() => path,
+        _ => match table.and_then(|table| table.get("path")) {
+            Some(path) => path
+                .as_str()
+                .fatal("`ghdl.path` must be the path to the ghdl binary")?
+                .to_owned(),
+            None => "ghdl".to_owned(),
+        },
+    };
+    let mut flags = Vec::new();
+    if let Some(extra) = table.and_then(|table| table.get("flags")) {
+        let extra = extra
+            .as_array()
+            .fatal("`ghdl.flags` must be an array of strings")?;
+        for flag in extra {
+            let flag = flag
+                .as_str()
+                .fatal("`ghdl.flags` must only contain strings")?;
+            flags.push(flag.to_owned());
+        }
+    }
+    // gb only reads one manifest per run, the first configuration sticks
+    let _ = CONFIGURED.set(Config { path, flags });
+    Ok(())
+}
+
+/// the ghdl gb runs
+pub fn path() -> &'static str {
+    CONFIGURED.get().map_or("ghdl", |ghdl| ghdl.path.as_str())
+}
+
+/// the flags from gb.toml passed to every ghdl command
+pub fn flags() -> &'static [String] {
+    CONFIGURED.get().map_or(&[], |ghdl| ghdl.flags.as_slice())
+}
+
+/// `ghdl <command>` with the flags from gb.toml, ghdl wants its options after
+/// the command
+pub fn command(command: &str) -> Command {
+    let mut ghdl = Command::new(path());
+    ghdl.arg(command).args(flags());
+    ghdl
+}
+
+/// the backend of the configured ghdl, `None` when `ghdl --version` doesn't
+/// run or names none gb knows
+pub fn backend() -> Option<Backend> {
+    *BACKEND.get_or_init(|| {
+        let output = Command::new(path()).arg("--version").output().ok()?;
+        Backend::parse(&String::from_utf8_lossy(&output.stdout))
+    })
+}
+
+/// ghdl as the simulator of the build
+/// what ghdl names a library file after for `--std=<std>`, every standard
+/// from 93 to 02 shares `-obj93`
+fn library_suffix(std: &str) -> &str {
+    match std {
+        "87" => "87",
+        "93" | "93c" | "00" | "02" => "93",
+        "08" => "08",
+        "19" => "19",
+        _ => std.get(..2).unwrap_or(std),
+    }
+}
+
+pub struct Ghdl;
+
+impl Simulator for Ghdl {
+    fn name(&self) -> &'static str {
+        "ghdl"
+    }
+
+    fn analyze(&self, files: &[&str], build: &BuildOptions, workdir: Option<&Path>) -> Command {
+        let mut ghdl = command("-a");
+        ghdl.args(build.common_flags())
+            .args(&build.analyze_flags)
+            .args(workdir.map(|dir| format!("--workdir={}", dir.display())))
+            .args(files);
+        ghdl
+    }
+
+    fn elaborate(&self, unit: &str, build: &BuildOptions) -> Result<Command, GbError> {
+        let mut ghdl = command("-e");
+        ghdl.args(build.common_flags())
+            .args(&build.elaborate_flags)
+            .args(build.foreign.elaborate_flags())
+            .args(crate::platform_elaborate_args())
+            .arg(unit)
+            .current_dir(build.run_dir());
+        Ok(ghdl)
+    }
+
+    fn run(
+        &self,
+        unit: &str,
+        waveform: Option<&Waveform>,
+        build: &BuildOptions,
+    ) -> Result<Command, GbError> {
+        let mut ghdl = command("-r");
+        ghdl.args(build.common_flags())
+            .current_dir(build.run_dir())
+            .arg(unit)
+            .args(waveform.map(Waveform::run_flag))
+            .args(build.foreign.run_flags())
+            .args(
+                build
+                    .generics
+                    .iter()
+                    .map(|(name, value)| format!("-g{name}={value}")),
+            )
+            .args(&build.run_flags);
+        Ok(ghdl)
+    }
+
+    /// named after the library and the standard, e.g. `work-obj08.cf`
+    fn library_file(&self, build: &BuildOptions) -> String {
+        // a `--std` or `--work` among the flags is the one ghdl goes by
+        let flag = |prefix: &str| {
+            flags()
+                .iter()
+                .chain(&build.analyze_flags)
+                .filter_map(|flag| flag.strip_prefix(prefix))
+                .next_back()
+        };
+        let std = flag("--std=").or(build.std.as_deref()).unwrap_or("93");
+        let library = flag("--work=")
+            .or(build.library.as_deref())
+            .unwrap_or("work");
+        format!("{library}-obj{}.cf", library_suffix(std))
+    }
+
+    fn executable(&self, unit: &str) -> Option<PathBuf> {
+        Some(
+            profile::dir()
+                .join(unit.to_lowercase())
+                .with_extension(std::env::consts::EXE_EXTENSION),
+        )
+    }
+
+    fn collect(&self, files: &[&str]) -> Result<(), GbError> {
+        crate::cleanup_build_dir(files.to_vec())
+    }
+
+    fn restore(&self) -> Result<(), GbError> {
+        for work_library in artifacts::work_libraries(&profile::dir()) {
+            crate::restore_work_library_from_build_directory(&work_library)?;
+        }
+        Ok(())
+    }
+
+    /// the work libraries, with their entries pointing back at the sources
+    /// from `dir`, and the object files elaboration links
+    fn isolate(&self, dir: &Path) -> Result<(), GbError> {
+        let built = profile::dir();
+        std::fs::create_dir_all(dir).fatal(format!("could not create `{}`", dir.display()))?;
+        for work_library in artifacts::work_libraries(&built) {
+            let library = std::fs::read_to_string(built.join(&work_library))
+                .fatal(format!("could not read `{work_library}`"))?;
+            std::fs::write(
+                dir.join(&work_library),
+                artifacts::library_for_dir(&library, dir),
+            )
+            .fatal(format!(
+                "could not copy `{work_library}` to `{}`",
+                dir.display()
+            ))?;
+        }
+        for object in std::fs::read_dir(&built)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "o"))
+        {
+            if let Some(name) = object.file_name() {
+                std::fs::copy(&object, dir.join(name))
+                    .fatal(format!("could not copy `{}`", object.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn analyzes_in_parallel(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::library_suffix;
+
+    #[test]
+    fn names_libraries_like_ghdl_does() {
+        for (std, suffix) in [
+            ("87", "87"),
+            ("93", "93"),
+            ("93c", "93"),
+            ("00", "93"),
+            ("02", "93"),
+            ("08", "08"),
+            ("19", "19"),
+        ] {
+            assert_eq!(library_suffix(std), suffix, "--std={std}");
+        }
+    }
+}
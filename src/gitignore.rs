@@ -0,0 +1,74 @@
+//! Keeps the project's .gitignore covering everything gb writes, without
+//! duplicating entries the user (or an older gb) already added.
+
+use std::path::Path;
+
+use crate::{verbosity, Check, GbError};
+
+/// paths gb writes into the project which never belong in version control,
+/// and `gb.local.toml`, which is personal
+pub const GB_ARTIFACTS: &[&str] = &["/build", "/.gb", "/gb.local.toml"];
+
+const HEADER: &str = "# gb build artifacts";
+
+/// `/build`, `build`, `build/` and `/build/` all ignore the build directory
+fn normalize(entry: &str) -> &str {
+    entry.trim().trim_start_matches('/').trim_end_matches('/')
+}
+
+/// the entries of `GB_ARTIFACTS` (or any others) that `.gitignore` doesn't cover yet
+pub fn missing_entries<'e>(contents: &str, entries: &[&'e str]) -> Vec<&'e str> {
+    let existing = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(normalize)
+        .collect::<Vec<_>>();
+    entries
+        .iter()
+        .copied()
+        .filter(|entry| !existing.contains(&normalize(entry)))
+        .collect()
+}
+
+/// appends whichever of `entries` are missing from `.gitignore`, creating it if
+/// needed, and returns the ones that were added.
+pub fn ensure_ignored<'e>(entries: &[&'e str]) -> Result<Vec<&'e str>, GbError> {
+    ensure_ignored_in(Path::new("."), entries)
+}
+
+/// `ensure_ignored` for the `.gitignore` of a project in `dir`
+pub fn ensure_ignored_in<'e>(dir: &Path, entries: &[&'e str]) -> Result<Vec<&'e str>, GbError> {
+    let path = &dir.join(".gitignore");
+    let mut contents = if path.exists() {
+        std::fs::read_to_string(path).fatal("could not read .gitignore")?
+    } else {
+        String::new()
+    };
+
+    let missing = missing_entries(&contents, entries);
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    if !contents.lines().any(|line| line.trim() == HEADER) {
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        contents.push_str(HEADER);
+        contents.push('\n');
+    }
+    for entry in &missing {
+        contents.push_str(entry);
+        contents.push('\n');
+    }
+    std::fs::write(path, contents).fatal("could not update .gitignore")?;
+
+    for entry in &missing {
+        verbosity::step("[gitignore]", &format!("Added `{entry}` to .gitignore"));
+    }
+    Ok(missing)
+}
@@ -0,0 +1,261 @@
+//! `gb test --golden`: regression tests against a waveform known to be right.
+//! a target running a testbench names the dump the testbench should produce:
+//!
+//! ```toml
+//! [target.alu]
+//! execute = "src/alu_tb.vhd"
+//! golden-vcd = "golden/alu.vcd"
+//!
+//! [target.alu.golden]
+//! signals = ["alu_tb.dut.*"]   # the signals compared, every one by default
+//! ignore = ["*.debug_*"]       # left out of the comparison
+//! time-tolerance = "2ns"       # how far a change may move from the golden one
+//! ```
+//!
+//! the testbench then dumps a vcd whenever `gb test --golden` runs it, and
+//! fails when a signal of the golden waveform is missing, takes other values,
+//! or changes further from the golden time than the tolerance allows. the
+//! comparison goes by the values a signal takes one after the other, so a
+//! change dumped again with the same value doesn't count.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    sources,
+    test::TestBench,
+    vcd::{self, Signal, Vcd},
+    Check, GbError, Level,
+};
+
+/// what a testbench's dump is checked against
+#[derive(Debug, Clone)]
+pub struct Golden {
+    target: String,
+    pub vcd: PathBuf,
+    signals: Vec<String>,
+    ignore: Vec<String>,
+    time_tolerance: u64,
+}
+
+fn patterns(
+    target: &str,
+    golden: Option<&toml_edit::Item>,
+    key: &str,
+) -> Result<Vec<String>, GbError> {
+    match golden.and_then(|golden| golden.get(key)) {
+        Some(patterns) => patterns
+            .as_array()
+            .and_then(|patterns| {
+                patterns
+                    .iter()
+                    .map(|pattern| pattern.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .fatal(format!(
+                "`golden.{key}` of {target} must be an array of signal patterns"
+            )),
+        None => Ok(vec![]),
+    }
+}
+
+impl Golden {
+    fn from_manifest(
+        target: &str,
+        target_info: &toml_edit::Item,
+    ) -> Result<Option<Golden>, GbError> {
+        let Some(vcd) = target_info.get("golden-vcd") else {
+            return Ok(None);
+        };
+        let vcd = vcd.as_str().fatal(format!(
+            "`golden-vcd` of {target} must be the path to a vcd file"
+        ))?;
+        let golden = target_info.get("golden");
+        let time_tolerance = match golden.and_then(|golden| golden.get("time-tolerance")) {
+            Some(time) => time.as_str().and_then(vcd::parse_time).fatal(format!(
+                "`golden.time-tolerance` of {target} must be a time, like \"2ns\""
+            ))?,
+            None => 0,
+        };
+        Ok(Some(Golden {
+            target: target.to_owned(),
+            vcd: PathBuf::from(vcd),
+            signals: patterns(target, golden, "signals")?,
+            ignore: patterns(target, golden, "ignore")?,
+            time_tolerance,
+        }))
+    }
+
+    fn compared<'v>(&self, golden: &'v Vcd) -> Result<Vec<&'v Signal>, GbError> {
+        let selected = if self.signals.is_empty() {
+            golden.signals.iter().collect()
+        } else {
+            golden.select(&self.signals)?
+        };
+        let ignored = self
+            .ignore
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .fatal(format!("`{pattern}` is not a valid signal pattern"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(selected
+            .into_iter()
+            .filter(|signal| !ignored.iter().any(|pattern| pattern.matches(&signal.name)))
+            .collect())
+    }
+
+    /// what sets the dump at `path` apart from the golden one, nothing when
+    /// they match
+    pub fn compare(&self, path: &Path) -> Result<Vec<String>, GbError> {
+        let golden = Vcd::load(&self.vcd)?;
+        let dump = Vcd::load(path)?;
+
+        let mut mismatches = Vec::new();
+        for signal in self.compared(&golden)? {
+            let Some(dumped) = dump
+                .signals
+                .iter()
+                .find(|dumped| dumped.name == signal.name)
+            else {
+                mismatches.push(format!("`{}` is not in the waveform", signal.name));
+                continue;
+            };
+            if let Some(mismatch) = self.difference(
+                &signal.name,
+                &values(golden.changes(signal), signal.width),
+                &values(dump.changes(dumped), dumped.width),
+            ) {
+                mismatches.push(mismatch);
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// the first place `dumped` parts from `golden`
+    fn difference(
+        &self,
+        name: &str,
+        golden: &[(u64, String)],
+        dumped: &[(u64, String)],
+    ) -> Option<String> {
+        let time = vcd::format_time;
+        for index in 0..golden.len().max(dumped.len()) {
+            match (golden.get(index), dumped.get(index)) {
+                (Some((expected_at, expected)), Some((at, value))) => {
+                    if value != expected {
+                        return Some(format!(
+                            "`{name}` changes to {value} at {}, the golden waveform to {expected} at {}",
+                            time(*at),
+                            time(*expected_at)
+                        ));
+                    }
+                    if at.abs_diff(*expected_at) > self.time_tolerance {
+                        return Some(format!(
+                            "`{name}` changes to {value} at {}, the golden waveform at {}",
+                            time(*at),
+                            time(*expected_at)
+                        ));
+                    }
+                }
+                (Some((expected_at, expected)), None) => {
+                    return Some(format!(
+                        "`{name}` never changes to {expected}, the golden waveform does at {}",
+                        time(*expected_at)
+                    ));
+                }
+                (None, Some((at, value))) => {
+                    return Some(format!(
+                        "`{name}` changes to {value} at {}, the golden waveform doesn't",
+                        time(*at)
+                    ));
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+        None
+    }
+}
+
+/// a vector's value with the bits a vcd may leave out put back: a value
+/// starting with 1 is extended with 0, anything else with its first bit.
+/// reals are left as they are.
+fn widen(value: &str, width: u32) -> String {
+    let width = width as usize;
+    let bits = value.chars().all(|bit| "01xzuwlh-".contains(bit));
+    let Some(first) = value.chars().next().filter(|_| bits && value.len() < width) else {
+        return value.to_owned();
+    };
+    let fill = if first == '1' { '0' } else { first };
+    std::iter::repeat_n(fill, width - value.len())
+        .chain(value.chars())
+        .collect()
+}
+
+/// the values a signal takes one after the other, leaving out the changes
+/// to the value it already had
+fn values(changes: &[(u64, String)], width: u32) -> Vec<(u64, String)> {
+    let mut values: Vec<(u64, String)> = Vec::new();
+    for (time, value) in changes {
+        let value = widen(&value.to_lowercase(), width);
+        match values.last_mut() {
+            // several values at the same time only leave the last one
+            Some(last) if last.0 == *time => last.1 = value,
+            Some(last) if last.1 == value => {}
+            _ => values.push((*time, value)),
+        }
+    }
+    values.dedup_by(|next, previous| next.1 == previous.1);
+    values
+}
+
+/// the golden waveform of every testbench that has one, found through the
+/// target executing it
+pub fn goldens(
+    doc: &toml_edit::Document,
+    benches: &[TestBench],
+) -> Result<Vec<(String, Golden)>, GbError> {
+    let mut goldens: Vec<(String, Golden)> = Vec::new();
+    for target in crate::list_targets(doc) {
+        let target_info = &doc["target"][target];
+        let Some(golden) = Golden::from_manifest(target, target_info)? else {
+            continue;
+        };
+        if !golden.vcd.exists() {
+            Err(GbError {
+                message: format!(
+                    "`{}`, the `golden-vcd` of target `{target}`, doesn't exist",
+                    golden.vcd.display()
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        let execute = target_info
+            .get("execute")
+            .and_then(|execute| execute.as_str())
+            .fatal(format!(
+                "target `{target}` has a `golden-vcd`, but no `execute` testbench to compare it with"
+            ))?;
+        let bench = benches
+            .iter()
+            .find(|bench| {
+                sources::normalize(bench.file.as_ref()) == sources::normalize(execute.as_ref())
+            })
+            .fatal(format!(
+                "`{execute}`, the testbench of target `{target}`, is not one `gb test` runs"
+            ))?;
+        if let Some((_, other)) = goldens.iter().find(|(name, _)| *name == bench.name) {
+            Err(GbError {
+                message: format!(
+                    "targets `{}` and `{target}` both set a `golden-vcd` for `{}`",
+                    other.target, bench.name
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        goldens.push((bench.name.clone(), golden));
+    }
+    Ok(goldens)
+}
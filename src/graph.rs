@@ -0,0 +1,109 @@
+//! `gb graph`: the dependency graph of a target's files, as found by the
+//! tree-sitter component analysis, printed as Graphviz DOT or Mermaid:
+//!
+//! ```sh
+//! gb graph counter | dot -Tsvg > counter.svg
+//! ```
+//!
+//! an edge points from a file to the files of the components and contexts it
+//! uses. the file the target executes is drawn in bold.
+
+use std::path::{Path, PathBuf};
+
+use crate::{sources, tree_sitter};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Dot,
+    Mermaid,
+}
+
+pub struct Graph {
+    /// the files, in the order of the target
+    nodes: Vec<PathBuf>,
+    /// indices into `nodes`, from user to used
+    edges: Vec<(usize, usize)>,
+    top: Option<usize>,
+}
+
+fn index_of(nodes: &mut Vec<PathBuf>, path: PathBuf) -> usize {
+    match nodes.iter().position(|node| *node == path) {
+        Some(index) => index,
+        None => {
+            nodes.push(path);
+            nodes.len() - 1
+        }
+    }
+}
+
+/// the graph of `files` and whatever they pull in that isn't listed
+pub fn graph(files: &[&str], top: Option<&str>) -> Graph {
+    let mut nodes = files
+        .iter()
+        .map(|file| sources::normalize(file.as_ref()))
+        .collect::<Vec<_>>();
+    let mut edges = Vec::new();
+    let mut next = 0;
+    while next < nodes.len() {
+        for dependency in tree_sitter::direct_dependencies(&nodes[next]) {
+            let to = index_of(&mut nodes, sources::normalize(&dependency));
+            if !edges.contains(&(next, to)) {
+                edges.push((next, to));
+            }
+        }
+        next += 1;
+    }
+    let top = top.and_then(|top| {
+        let top = sources::normalize(top.as_ref());
+        nodes.iter().position(|node| *node == top)
+    });
+    Graph { nodes, edges, top }
+}
+
+fn label(path: &Path) -> String {
+    path.display().to_string().replace('"', "\\\"")
+}
+
+impl Graph {
+    pub fn dot(&self, target: &str) -> String {
+        let mut out = format!("digraph \"{target}\" {{\n    node [shape=box];\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let style = if Some(index) == self.top {
+                ", style=bold"
+            } else {
+                ""
+            };
+            out += &format!("    n{index} [label=\"{}\"{style}];\n", label(node));
+        }
+        for (from, to) in &self.edges {
+            out += &format!("    n{from} -> n{to};\n");
+        }
+        out + "}\n"
+    }
+
+    pub fn mermaid(&self) -> String {
+        let mut out = "graph TD\n".to_owned();
+        for (index, node) in self.nodes.iter().enumerate() {
+            out += &format!(
+                "    n{index}[\"{}\"]\n",
+                label(node).replace("\\\"", "#quot;")
+            );
+        }
+        for (from, to) in &self.edges {
+            out += &format!("    n{from} --> n{to}\n");
+        }
+        if let Some(top) = self.top {
+            out += &format!("    style n{top} stroke-width:3px\n");
+        }
+        out
+    }
+}
+
+pub fn print(target: &str, files: &[&str], top: Option<&str>, format: Format) {
+    let graph = graph(files, top);
+    match format {
+        Format::Dot => print!("{}", graph.dot(target)),
+        Format::Mermaid => print!("{}", graph.mermaid()),
+    }
+}
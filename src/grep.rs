@@ -0,0 +1,39 @@
+//! `gb grep`: searching a target's files by what things are, rather than by
+//! how they're spelled, e.g. only the declarations of a signal and not its uses.
+
+use colored::Colorize;
+
+use crate::{tree_sitter, Check, GbError, Level};
+
+pub fn grep(files: &[&str], kind: Option<&str>, pattern: &str) -> Result<(), GbError> {
+    let compiled =
+        glob::Pattern::new(pattern).fatal(format!("`{pattern}` is not a valid pattern"))?;
+
+    let mut found = 0;
+    for file in files {
+        let matches = tree_sitter::find_nodes(file, kind, &compiled).map_err(|err| GbError {
+            message: format!("could not parse `{file}`: {err}"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+        let source = std::fs::read_to_string(file).fatal(format!("could not read `{file}`"))?;
+        let lines = source.lines().collect::<Vec<_>>();
+
+        for found_match in &matches {
+            let line = lines.get(found_match.line - 1).copied().unwrap_or_default();
+            println!(
+                "{}:{}:{}: {}",
+                file.blue().bold(),
+                found_match.line,
+                found_match.column,
+                line.trim()
+            );
+        }
+        found += matches.len();
+    }
+
+    if found == 0 {
+        eprintln!("no {} matching `{pattern}`", kind.unwrap_or("identifier"));
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Commands a target runs around the steps of its build, for code generation
+//! before analysis or for checking what the simulation wrote after it:
+//!
+//! ```toml
+//! [target.cpu.hooks]
+//! pre-analyze = "python gen_tables.py"
+//! post-run = "./check_output.sh"
+//! ```
+//!
+//! the stages are `pre-analyze`, `post-analyze`, `pre-elaborate`,
+//! `post-elaborate`, `pre-run` and `post-run`. a hook goes through the shell,
+//! from the project's directory, with the build's environment exported:
+//! `GB_TARGET`, `GB_HOOK` (the stage), `GB_BUILD_DIR`, `GB_GHDL`, `GB_STD` and
+//! `GB_LIBRARY` when they're set, and whatever the simulation gets, like
+//! `GB_SEED`. a hook failing fails the build, and a `post-` hook only runs
+//! once its step succeeded, even when the step had nothing to do.
+
+use std::process::Command;
+
+use toml_edit::Item;
+
+use crate::{ghdl, profile, verbosity, BuildOptions, Check, GbError, Level};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    PreAnalyze,
+    PostAnalyze,
+    PreElaborate,
+    PostElaborate,
+    PreRun,
+    PostRun,
+}
+
+const STAGES: &[(Stage, &str)] = &[
+    (Stage::PreAnalyze, "pre-analyze"),
+    (Stage::PostAnalyze, "post-analyze"),
+    (Stage::PreElaborate, "pre-elaborate"),
+    (Stage::PostElaborate, "post-elaborate"),
+    (Stage::PreRun, "pre-run"),
+    (Stage::PostRun, "post-run"),
+];
+
+impl Stage {
+    fn name(self) -> &'static str {
+        STAGES
+            .iter()
+            .find(|(stage, _)| *stage == self)
+            .map(|(_, name)| *name)
+            .unwrap_or_default()
+    }
+}
+
+/// the hooks of a target, none when it has no `hooks` table
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    target: String,
+    commands: Vec<(Stage, String)>,
+}
+
+impl Hooks {
+    pub fn from_manifest(target: &str, target_info: &Item) -> Result<Hooks, GbError> {
+        let mut hooks = Hooks {
+            target: target.to_owned(),
+            commands: vec![],
+        };
+        let Some(table) = target_info.get("hooks") else {
+            return Ok(hooks);
+        };
+        let table = table
+            .as_table_like()
+            .fatal(format!("`target.{target}.hooks` must be a table"))?;
+        for (key, command) in table.iter() {
+            let Some((stage, _)) = STAGES.iter().find(|(_, name)| *name == key) else {
+                return Err(GbError {
+                    message: format!(
+                        "unknown hook `{key}` in `target.{target}.hooks`, expected one of {}",
+                        STAGES
+                            .iter()
+                            .map(|(_, name)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    level: Level::Fatal,
+                    source: None,
+                });
+            };
+            let command = command.as_str().fatal(format!(
+                "`target.{target}.hooks.{key}` must be a command, like `{key} = \"./gen.sh\"`"
+            ))?;
+            hooks.commands.push((*stage, command.to_owned()));
+        }
+        Ok(hooks)
+    }
+
+    /// runs the hook of `stage`, if the target has one
+    pub fn run(&self, stage: Stage, build: &BuildOptions) -> Result<(), GbError> {
+        let Some((_, hook)) = self.commands.iter().find(|(at, _)| *at == stage) else {
+            return Ok(());
+        };
+        let name = stage.name();
+        verbosity::step("[hook]", &format!("Running {name} `{hook}`"));
+
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        command
+            .arg(hook)
+            .env("GB_TARGET", &self.target)
+            .env("GB_HOOK", name)
+            .env("GB_BUILD_DIR", profile::dir())
+            .env("GB_GHDL", ghdl::path())
+            .envs(build.run_env.iter().map(|(name, value)| (name, value)));
+        if let Some(std) = &build.std {
+            command.env("GB_STD", std);
+        }
+        if let Some(library) = &build.library {
+            command.env("GB_LIBRARY", library);
+        }
+        verbosity::echo(&command);
+
+        let status = command
+            .status()
+            .fatal(format!("couldn't start the {name} hook `{hook}`"))?;
+        if !status.success() {
+            Err(GbError {
+                message: format!("the {name} hook `{hook}` failed ({status})"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+//! What many targets share, written once:
+//!
+//! ```toml
+//! include = ["common.toml"]     # its tables and keys, under gb.toml's own
+//!
+//! [target.base]
+//! files = ["src/pkg.vhd", "src/fifo.vhd"]
+//! std = "08"
+//! run-flags = ["--ieee-asserts=disable"]
+//!
+//! [target.fifo-test]
+//! extends = "base"
+//! files = ["test/fifo_tb.vhd"]  # after base's files
+//! ```
+//!
+//! an included file is read like gb.toml, relative to the file including it,
+//! and can include files itself. what gb.toml sets wins over it, as does a
+//! later include over an earlier one; tables are merged key by key.
+//!
+//! a target that `extends` another gets every key of it it doesn't set
+//! itself. arrays, like `files` and the flags, are the base's followed by the
+//! target's own, tables are merged, and the target's own value wins for
+//! everything else. a base can extend another target in turn.
+//!
+//! where every key gb.toml didn't set itself comes from goes into `Origins`,
+//! and the included files into its `files`.
+
+use std::path::{Path, PathBuf};
+
+use toml_edit::{Document, Item, TableLike};
+
+use crate::{
+    manifest::{join, Origins},
+    Check, GbError, Level,
+};
+
+/// fills in what `into`, at `path`, doesn't have from `from`, at `from_path`,
+/// table by table. `origin` tells where a key of `from` comes from.
+fn fill(
+    into: &mut dyn TableLike,
+    from: &dyn TableLike,
+    (path, from_path): (&str, &str),
+    origin: &dyn Fn(&str) -> String,
+    origins: &mut Origins,
+) {
+    for (key, item) in from.iter() {
+        let (path, from_path) = (join(path, key), join(from_path, key));
+        match into.get_mut(key) {
+            None => {
+                into.insert(key, item.clone());
+                origins.record(&path, &from_path, item, origin);
+            }
+            Some(existing) => {
+                if let (Some(existing), Some(item)) =
+                    (existing.as_table_like_mut(), item.as_table_like())
+                {
+                    fill(existing, item, (&path, &from_path), origin, origins);
+                }
+            }
+        }
+    }
+}
+
+fn included(doc: &Document, file: &Path) -> Result<Vec<String>, GbError> {
+    let Some(include) = doc.get("include") else {
+        return Ok(vec![]);
+    };
+    include
+        .as_array()
+        .and_then(|include| {
+            include
+                .iter()
+                .map(|file| file.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()
+        })
+        .fatal(format!(
+            "`include` in `{}` must be an array of files",
+            file.display()
+        ))
+}
+
+/// fills `doc`, read from `file`, in with the files it includes, `including`
+/// are the files on the way there
+fn include(
+    doc: &mut Document,
+    file: &Path,
+    including: &mut Vec<PathBuf>,
+    origins: &mut Origins,
+) -> Result<(), GbError> {
+    let dir = file.parent().unwrap_or(Path::new("")).to_owned();
+    // the last include is filled in first, so it wins over the ones before it
+    for name in included(doc, file)?.iter().rev() {
+        let path = dir.join(name);
+        let canonical = path.canonicalize().fatal(format!(
+            "could not find `{}`, included from `{}`",
+            path.display(),
+            file.display()
+        ))?;
+        if including.contains(&canonical) {
+            Err(GbError {
+                message: format!("`{}` ends up including itself", path.display()),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        let mut other = std::fs::read_to_string(&path)
+            .fatal(format!("could not read `{}`", path.display()))?
+            .parse::<Document>()
+            .fatal(format!("failed to parse `{}`", path.display()))?;
+        let mut other_origins = Origins::default();
+        including.push(canonical);
+        include(&mut other, &path, including, &mut other_origins)?;
+        including.pop();
+
+        origins.files.push(path.clone());
+        origins.files.extend(other_origins.files.iter().cloned());
+        let name = path.display().to_string();
+        let origin = |from: &str| {
+            other_origins
+                .of(from)
+                .map(str::to_owned)
+                .unwrap_or_else(|| Origins::label(&name, from))
+        };
+        fill(
+            doc.as_table_mut(),
+            other.as_table(),
+            ("", ""),
+            &origin,
+            origins,
+        );
+    }
+    Ok(())
+}
+
+/// the target `target` extends, if any
+fn base(doc: &Document, target: &str) -> Result<Option<String>, GbError> {
+    let Some(extends) = doc["target"][target].get("extends") else {
+        return Ok(None);
+    };
+    let base = extends.as_str().fatal(format!(
+        "`extends` of {target} must be the name of a target"
+    ))?;
+    if doc["target"].get(base).is_none() {
+        Err(GbError {
+            message: format!("{target} extends `{base}`, which is not a target"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(Some(base.to_owned()))
+}
+
+/// `target` with what it inherits, after the targets it extends are
+fn extend(
+    doc: &mut Document,
+    target: &str,
+    extending: &mut Vec<String>,
+    origins: &mut Origins,
+) -> Result<(), GbError> {
+    let Some(base) = base(doc, target)? else {
+        return Ok(());
+    };
+    if extending.contains(&base) {
+        Err(GbError {
+            message: format!("`{base}` ends up extending itself"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    extending.push(base.clone());
+    extend(doc, &base, extending, origins)?;
+    extending.pop();
+
+    let base_info = doc["target"][base.as_str()]
+        .as_table_like()
+        .fatal(format!("`target.{base}` must be a table"))?
+        .iter()
+        .filter(|(key, _)| *key != "extends")
+        .map(|(key, item)| (key.to_owned(), item.clone()))
+        .collect::<Vec<_>>();
+    let target_info = doc["target"][target]
+        .as_table_like_mut()
+        .fatal(format!("`target.{target}` must be a table"))?;
+    // the base's keys are where they are before any of them is inherited
+    let known = origins.clone();
+    let origin = |from: &str| known.of_or_manifest(from);
+    for (key, item) in base_info {
+        let path = format!("target.{target}.{key}");
+        let from_path = format!("target.{base}.{key}");
+        let Some(own) = target_info.get_mut(&key) else {
+            origins.record(&path, &from_path, &item, &origin);
+            target_info.insert(&key, item);
+            continue;
+        };
+        if let (Some(own), Some(inherited)) = (own.as_array_mut(), item.as_array()) {
+            let mut merged = inherited.clone();
+            merged.extend(own.iter().cloned());
+            merged.fmt();
+            *own = merged;
+            origins.set(&path, format!("{} + {}", origin(&from_path), origin(&path)));
+        } else if let (Some(own), Some(inherited)) = (own.as_table_like_mut(), item.as_table_like())
+        {
+            fill(own, inherited, (&path, &from_path), &origin, origins);
+        }
+    }
+    target_info.remove("extends");
+    Ok(())
+}
+
+/// resolves the `include`s of `doc`, read from gb.toml, and the `extends` of
+/// its targets
+pub fn resolve(doc: &mut Document, origins: &mut Origins) -> Result<(), GbError> {
+    let manifest = Path::new("gb.toml");
+    let canonical = manifest.canonicalize().unwrap_or(manifest.to_owned());
+    include(doc, manifest, &mut vec![canonical], origins)?;
+
+    let targets = doc
+        .get("target")
+        .and_then(Item::as_table_like)
+        .map(|targets| {
+            targets
+                .iter()
+                .map(|(name, _)| name.to_owned())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    for target in targets {
+        extend(doc, &target, &mut vec![target.clone()], origins)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,190 @@
+//! Environment variables in gb.toml's strings:
+//!
+//! ```toml
+//! [target.top]
+//! files = ["${env:IP_DIR}/fifo.vhd", "src/top.vhd"]
+//! vcd-name = "${env:OUT_DIR:-build}/top.vcd"
+//! run-flags = ["-gSEED=${env:SEED:-1}"]
+//! ```
+//!
+//! `${env:VAR}` is replaced by the variable, and is an error when it isn't
+//! set. `${env:VAR:-default}` falls back on `default` when the variable isn't
+//! set or is empty, like the shell does. `$${` is a literal `${`. every string
+//! of gb.toml is interpolated once it's read, before gb looks at any of them.
+
+use toml_edit::{Document, Item, Value};
+
+use crate::{GbError, Level};
+
+/// `text` with its `${env:...}`s replaced, `key` says where it's from
+fn interpolate_str(text: &str, key: &str) -> Result<String, GbError> {
+    let error = |message: String| GbError {
+        message: format!("in `{key}`: {message}"),
+        level: Level::Fatal,
+        source: None,
+    };
+    let mut interpolated = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            interpolated.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(after) = rest.strip_prefix("${") else {
+            interpolated.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let end = after
+            .find('}')
+            .ok_or_else(|| error(format!("`{rest}` is missing its closing `}}`")))?;
+        let reference = &after[..end];
+        let Some(reference) = reference.strip_prefix("env:") else {
+            Err(error(format!(
+                "`${{{reference}}}` isn't `${{env:VAR}}`, write `$${{` for a literal `${{`"
+            )))?
+        };
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            Err(error(format!(
+                "`{name}` can't name an environment variable"
+            )))?;
+        }
+        let value = std::env::var(name).ok();
+        match (value, default) {
+            (Some(value), Some(default)) if value.is_empty() => interpolated.push_str(default),
+            (Some(value), _) => interpolated.push_str(&value),
+            (None, Some(default)) => interpolated.push_str(default),
+            (None, None) => Err(error(format!(
+                "the environment variable `{name}` isn't set, give it a default with `${{env:{name}:-default}}`"
+            )))?,
+        }
+        rest = &after[end + 1..];
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+fn interpolate_value(value: &mut Value, key: &str) -> Result<(), GbError> {
+    match value {
+        Value::String(string) if string.value().contains('$') => {
+            let interpolated = interpolate_str(string.value(), key)?;
+            let decor = string.decor().clone();
+            *value = Value::from(interpolated);
+            *value.decor_mut() = decor;
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter_mut().enumerate() {
+                interpolate_value(value, &format!("{key}[{index}]"))?;
+            }
+        }
+        Value::InlineTable(table) => {
+            for (name, value) in table.iter_mut() {
+                interpolate_value(value, &format!("{key}.{}", name.get()))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_item(item: &mut Item, key: &str) -> Result<(), GbError> {
+    match item {
+        Item::Value(value) => interpolate_value(value, key)?,
+        Item::Table(table) => {
+            for (name, item) in table.iter_mut() {
+                interpolate_item(item, &format!("{key}.{}", name.get()))?;
+            }
+        }
+        Item::ArrayOfTables(tables) => {
+            for (index, table) in tables.iter_mut().enumerate() {
+                for (name, item) in table.iter_mut() {
+                    interpolate_item(item, &format!("{key}[{index}].{}", name.get()))?;
+                }
+            }
+        }
+        Item::None => {}
+    }
+    Ok(())
+}
+
+/// replaces the `${env:...}`s of every string in `doc`
+pub fn interpolate(doc: &mut Document) -> Result<(), GbError> {
+    for (name, item) in doc.as_table_mut().iter_mut() {
+        interpolate_item(item, name.get())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every test sets variables of its own, tests run in parallel
+    fn interpolated(text: &str) -> Result<String, String> {
+        interpolate_str(text, "key").map_err(|error| error.message)
+    }
+
+    #[test]
+    fn replaces_a_set_variable() {
+        std::env::set_var("GB_TEST_SET", "/opt/ip");
+        assert_eq!(
+            interpolated("${env:GB_TEST_SET}/fifo.vhd").unwrap(),
+            "/opt/ip/fifo.vhd"
+        );
+        assert_eq!(
+            interpolated("${env:GB_TEST_SET:-build}").unwrap(),
+            "/opt/ip"
+        );
+    }
+
+    #[test]
+    fn falls_back_on_the_default() {
+        std::env::remove_var("GB_TEST_UNSET");
+        assert_eq!(
+            interpolated("${env:GB_TEST_UNSET:-build}/top.vcd").unwrap(),
+            "build/top.vcd"
+        );
+        assert_eq!(interpolated("-g${env:GB_TEST_UNSET:-}").unwrap(), "-g");
+    }
+
+    #[test]
+    fn an_empty_variable_takes_the_default_only_with_one() {
+        std::env::set_var("GB_TEST_EMPTY", "");
+        assert_eq!(interpolated("${env:GB_TEST_EMPTY:-1}").unwrap(), "1");
+        assert_eq!(interpolated("[${env:GB_TEST_EMPTY}]").unwrap(), "[]");
+    }
+
+    #[test]
+    fn an_unset_variable_without_default_is_an_error() {
+        std::env::remove_var("GB_TEST_MISSING");
+        let error = interpolated("${env:GB_TEST_MISSING}").unwrap_err();
+        assert!(error.starts_with("in `key`:"), "{error}");
+        assert!(error.contains("`GB_TEST_MISSING` isn't set"), "{error}");
+    }
+
+    #[test]
+    fn escapes_and_plain_dollars_stay() {
+        assert_eq!(interpolated("$${env:HOME}").unwrap(), "${env:HOME}");
+        assert_eq!(interpolated("cost: $5, $$").unwrap(), "cost: $5, $$");
+    }
+
+    #[test]
+    fn rejects_malformed_references() {
+        assert!(interpolated("${env:GB_TEST_SET")
+            .unwrap_err()
+            .contains("missing its closing `}`"));
+        assert!(interpolated("${HOME}")
+            .unwrap_err()
+            .contains("isn't `${env:VAR}`"));
+        assert!(interpolated("${env:NOT-A-NAME}")
+            .unwrap_err()
+            .contains("can't name an environment variable"));
+    }
+}
@@ -0,0 +1,257 @@
+//! Libraries analyzed outside of gb, like the vendor libraries ghdl's
+//! `--vendor` scripts build:
+//!
+//! ```toml
+//! [libraries]
+//! paths = ["~/ghdl-libs/xilinx-vivado/unisim/v08"]
+//! ```
+//!
+//! every ghdl command gets a `-P` for each path, nvc a `-L`. a path may start
+//! with `~`, and a relative one is relative to gb.toml.
+//!
+//! `gb vendor-libs xilinx-vivado` compiles a vendor's libraries with the
+//! script ghdl ships for it, into gb's cache where every project can use
+//! them, and adds the vendor to gb.toml:
+//!
+//! ```toml
+//! [libraries]
+//! vendor = ["xilinx-vivado"]
+//! ```
+//!
+//! every build gets its `-P` from then on. the scripts look for the sources
+//! where the vendor's tools install them, `--source` points them elsewhere,
+//! like at a checkout of osvvm.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use clap::ValueEnum;
+use colored::Colorize;
+use toml_edit::{Array, Document};
+
+use crate::{deps, doctor, ghdl, manifest_edit, simulator, verbosity, Check, GbError, Level};
+
+/// the libraries ghdl has a compile script for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Vendor {
+    XilinxVivado,
+    XilinxIse,
+    Intel,
+    Lattice,
+    Osvvm,
+    Uvvm,
+}
+
+impl Vendor {
+    /// what gb.toml and the command line call it, e.g. `xilinx-vivado`
+    fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_owned())
+            .unwrap_or_default()
+    }
+
+    fn parse(name: &str) -> Result<Vendor, GbError> {
+        Vendor::from_str(name, true).map_err(|_| GbError {
+            message: format!(
+                "unknown vendor `{name}` in `libraries.vendor`, expected one of {}",
+                Vendor::value_variants()
+                    .iter()
+                    .map(|vendor| vendor.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            level: Level::Fatal,
+            source: None,
+        })
+    }
+
+    /// where its compiled libraries go, shared by every project
+    fn dir(self) -> Result<PathBuf, GbError> {
+        Ok(deps::cache_dir()?.join("vendor-libs").join(self.name()))
+    }
+}
+
+/// `path` with a leading `~` expanded, and absolute, the simulation runs
+/// somewhere below the project
+fn resolve(path: &str) -> Result<PathBuf, GbError> {
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            let home = std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .fatal(format!("can't expand `{path}`, `HOME` isn't set"))?;
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path),
+    };
+    Ok(std::env::current_dir()
+        .fatal("could not find the current directory")?
+        .join(path))
+}
+
+fn strings<'d>(doc: &'d Document, key: &str, what: &str) -> Result<Vec<&'d str>, GbError> {
+    let Some(strings) = doc
+        .get("libraries")
+        .and_then(|libraries| libraries.get(key))
+    else {
+        return Ok(vec![]);
+    };
+    strings
+        .as_array()
+        .and_then(|strings| {
+            strings
+                .iter()
+                .map(|string| string.as_str())
+                .collect::<Option<Vec<_>>>()
+        })
+        .fatal(format!("`libraries.{key}` must be an array of {what}"))
+}
+
+fn vendors(doc: &Document) -> Result<Vec<Vendor>, GbError> {
+    strings(doc, "vendor", "vendor names")?
+        .into_iter()
+        .map(Vendor::parse)
+        .collect()
+}
+
+/// the directories of `[libraries] paths`, then those of `[libraries] vendor`
+pub fn paths(doc: &Document) -> Result<Vec<PathBuf>, GbError> {
+    let mut resolved = Vec::new();
+    for path in strings(doc, "paths", "directories")? {
+        let dir = resolve(path)?;
+        if !dir.is_dir() {
+            Err(GbError {
+                message: format!("`{path}`, in `libraries.paths`, is not a directory"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        resolved.push(dir);
+    }
+    for vendor in vendors(doc)? {
+        let dir = vendor.dir()?;
+        if !dir.is_dir() {
+            Err(GbError {
+                message: format!(
+                    "the {0} libraries aren't compiled yet, run `gb vendor-libs {0}`",
+                    vendor.name()
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        resolved.push(dir);
+    }
+    Ok(resolved)
+}
+
+/// where ghdl keeps its vendor scripts, next to its own libraries
+fn scripts_dir() -> Result<PathBuf, GbError> {
+    let ghdl = doctor::find_program(ghdl::path()).fatal(format!(
+        "could not find `{}` to find its vendor scripts, pass `--scripts`",
+        ghdl::path()
+    ))?;
+    let ghdl = ghdl.canonicalize().unwrap_or(ghdl);
+    ghdl.parent()
+        .and_then(Path::parent)
+        .map(|prefix| prefix.join("lib/ghdl/vendors"))
+        .fatal("could not find ghdl's vendor scripts, pass `--scripts`")
+}
+
+/// the script's option for the standard, it compiles for both without one
+fn std_flag(std: &str) -> Result<&'static str, GbError> {
+    match std {
+        "93" | "93c" => Ok("--vhdl93"),
+        "08" => Ok("--vhdl2008"),
+        _ => Err(GbError {
+            message: format!("ghdl's vendor scripts compile for 93 or 08, not `{std}`"),
+            level: Level::Fatal,
+            source: None,
+        }),
+    }
+}
+
+/// `gb vendor-libs`: compiles the libraries of `vendor` and adds it to
+/// `[libraries] vendor`
+pub fn compile(
+    vendor: Vendor,
+    std: Option<&str>,
+    source: Option<&Path>,
+    scripts: Option<&Path>,
+) -> Result<(), GbError> {
+    simulator::require_ghdl("`gb vendor-libs`")?;
+    let scripts = match scripts {
+        Some(scripts) => scripts.to_owned(),
+        None => scripts_dir()?,
+    };
+    let script = scripts.join(format!("compile-{}.sh", vendor.name()));
+    if !script.is_file() {
+        Err(GbError {
+            message: format!(
+                "`{}` doesn't exist, pass `--scripts` with where ghdl's vendor scripts are",
+                script.display()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let out = vendor.dir()?;
+    std::fs::create_dir_all(&out).fatal(format!("could not create `{}`", out.display()))?;
+
+    verbosity::step(
+        "[vendor-libs]",
+        &format!("Compiling the {} libraries...", vendor.name()),
+    );
+    let mut command = Command::new("bash");
+    command
+        .arg(&script)
+        .arg("--all")
+        .args(["--ghdl", ghdl::path()])
+        .arg("--output")
+        .arg(&out)
+        .args(std.map(std_flag).transpose()?);
+    if let Some(source) = source {
+        command.arg("--source").arg(source);
+    }
+    verbosity::echo(&command);
+    let status = command
+        .status()
+        .fatal("couldn't run ghdl's vendor script, is bash installed?")?;
+    if !status.success() {
+        Err(GbError {
+            message: format!(
+                "compiling the {} libraries failed, see the script's output above",
+                vendor.name()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let (mut doc, formatted) = manifest_edit::read()?;
+    if !vendors(&doc)?.contains(&vendor) {
+        doc.entry("libraries")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .fatal("`[libraries]` must be a table")?
+            .entry("vendor")
+            .or_insert(toml_edit::value(Array::new()))
+            .as_array_mut()
+            .fatal("`libraries.vendor` must be an array of vendor names")?
+            .push(vendor.name());
+        manifest_edit::write(doc, formatted)?;
+    }
+    eprintln!(
+        "  {}  {}",
+        "[vendor-libs]".blue().bold(),
+        format!(
+            "Compiled the {} libraries into {}, every build gets them from now on",
+            vendor.name(),
+            out.display()
+        )
+        .green()
+        .bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,251 @@
+//! Resource limits for simulations, so a runaway testbench can't take down a
+//! shared machine:
+//!
+//! ```toml
+//! [target.counter]
+//! memory-limit = "2GiB"
+//! cpu-time-limit = "5min"
+//! ```
+//!
+//! both can also be set in `[default]`. They're enforced with rlimits, so only on unix.
+//!
+//! `timeout = "60s"` limits the wall-clock time instead, for a testbench
+//! that never stops and hardly uses any cpu while it waits. gb kills the
+//! simulation when it runs over, and `--timeout` overrides gb.toml.
+
+use std::{
+    process::{Child, Command, ExitStatus},
+    time::{Duration, Instant},
+};
+
+use crate::{Check, GbError, Level};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// bytes of address space
+    pub memory: Option<u64>,
+    pub cpu_time: Option<Duration>,
+    /// wall-clock time, which gb enforces itself
+    pub timeout: Option<Duration>,
+}
+
+/// `512MiB`, `2G`, `1500000kB` or a plain number of bytes
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let scale: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(scale)
+}
+
+impl Limits {
+    /// reads `memory-limit` and `cpu-time-limit` from a `[default]` or `[target.X]`
+    /// table, keeping whatever was set before for keys the table doesn't have.
+    pub fn read(mut self, table: Option<&toml_edit::Item>) -> Result<Limits, GbError> {
+        let Some(table) = table else {
+            return Ok(self);
+        };
+        if let Some(memory) = table.get("memory-limit") {
+            let memory = memory
+                .as_str()
+                .and_then(parse_size)
+                .fatal("`memory-limit` must be a size like \"2GiB\" or \"512MB\"")?;
+            self.memory = Some(memory);
+        }
+        if let Some(cpu_time) = table.get("cpu-time-limit") {
+            let cpu_time = cpu_time
+                .as_str()
+                .and_then(|cpu_time| humantime::parse_duration(cpu_time).ok())
+                .fatal("`cpu-time-limit` must be a duration like \"30s\" or \"5min\"")?;
+            self.cpu_time = Some(cpu_time);
+        }
+        if let Some(timeout) = table.get("timeout") {
+            let timeout = timeout
+                .as_str()
+                .and_then(|timeout| humantime::parse_duration(timeout).ok())
+                .fatal("`timeout` must be a duration like \"60s\" or \"5min\"")?;
+            self.timeout = Some(timeout);
+        }
+        Ok(self)
+    }
+
+    /// whether there are no rlimits to set
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.cpu_time.is_none()
+    }
+
+    /// waits for `child`, killing it once it has run for longer than the
+    /// timeout. whether it was killed comes along with how it exited.
+    pub fn wait(&self, child: &mut Child) -> std::io::Result<(ExitStatus, bool)> {
+        let Some(timeout) = self.timeout else {
+            return Ok((child.wait()?, false));
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((status, false));
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                return Ok((child.wait()?, true));
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// what a simulation killed by `wait` is told apart with
+    pub fn timed_out(&self) -> String {
+        format!(
+            "the simulation ran longer than its timeout of {} and was killed",
+            humantime::format_duration(self.timeout.unwrap_or_default())
+        )
+    }
+
+    /// makes `command` run under these limits
+    #[cfg(unix)]
+    pub fn apply(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if self.is_empty() {
+            return;
+        }
+        let limits = *self;
+        let set = |resource, soft: u64, hard: u64| {
+            let limit = libc::rlimit {
+                rlim_cur: soft as libc::rlim_t,
+                rlim_max: hard as libc::rlim_t,
+            };
+            // SAFETY: setrlimit is async-signal-safe and only reads `limit`
+            if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        };
+        // SAFETY: the closure only calls setrlimit, which is fine between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(memory) = limits.memory {
+                    set(libc::RLIMIT_AS, memory, memory)?;
+                }
+                if let Some(cpu_time) = limits.cpu_time {
+                    // SIGXCPU at the soft limit, a second later SIGKILL
+                    let seconds = cpu_time.as_secs().max(1);
+                    set(libc::RLIMIT_CPU, seconds, seconds + 1)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _command: &mut Command) {
+        if !self.is_empty() {
+            eprintln!("resource limits are only enforced on unix, running without them");
+        }
+    }
+
+    /// when a simulation failed, whether it was because it ran into a limit.
+    /// only SIGXCPU is the cpu limit's own, a SIGKILL may as well be the oom
+    /// killer's or someone's `kill -9`
+    pub fn explain(&self, status: &std::process::ExitStatus, output: &[String]) -> Option<String> {
+        #[cfg(unix)]
+        if let Some(cpu_time) = self.cpu_time {
+            use std::os::unix::process::ExitStatusExt;
+            if status.signal() == Some(libc::SIGXCPU) {
+                return Some(format!(
+                    "the simulation exceeded its cpu-time-limit of {}",
+                    humantime::format_duration(cpu_time)
+                ));
+            }
+        }
+        if let Some(memory) = self.memory {
+            // what ghdl's and nvc's runtimes print when an allocation fails
+            let out_of_memory = output.iter().any(|line| {
+                let line = line.to_lowercase();
+                line.contains("storage_error") || line.contains("cannot allocate")
+            });
+            if !status.success() && out_of_memory {
+                return Some(format!(
+                    "the simulation ran out of memory under its memory-limit of {memory} bytes"
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// the error for a simulation stopped by a limit
+pub fn exceeded(explanation: String, log: &std::path::Path) -> GbError {
+    GbError {
+        message: format!("{explanation}, see `{}`", log.display()),
+        level: Level::Fatal,
+        source: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512MiB"), Some(512 << 20));
+        assert_eq!(parse_size("2G"), Some(2_000_000_000));
+        assert_eq!(parse_size(" 1500 kB "), Some(1_500_000));
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("2 parsecs"), None);
+        assert_eq!(parse_size("GiB"), None);
+    }
+
+    #[cfg(unix)]
+    fn exited(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[cfg(unix)]
+    fn signaled(signal: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(signal)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn out_of_memory_is_only_an_allocation_failure() {
+        let limits = Limits {
+            memory: Some(1 << 30),
+            ..Default::default()
+        };
+        let assertion =
+            vec!["tb.vhd:10:5:@100ns:(assertion error): memory read mismatch".to_owned()];
+        assert_eq!(limits.explain(&exited(1), &assertion), None);
+        let storage_error = vec!["raised STORAGE_ERROR : heap exhausted".to_owned()];
+        assert!(limits.explain(&exited(1), &storage_error).is_some());
+        let cannot_allocate = vec!["fatal: cannot allocate 4096 bytes".to_owned()];
+        assert!(limits.explain(&exited(1), &cannot_allocate).is_some());
+        assert_eq!(limits.explain(&exited(0), &storage_error), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_sigxcpu_is_the_cpu_limit() {
+        let limits = Limits {
+            cpu_time: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        assert!(limits.explain(&signaled(libc::SIGXCPU), &[]).is_some());
+        assert_eq!(limits.explain(&signaled(libc::SIGKILL), &[]), None);
+        assert_eq!(limits.explain(&exited(1), &[]), None);
+    }
+}
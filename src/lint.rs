@@ -0,0 +1,502 @@
+//! `gb lint`: problems gb can spot in a target's files without ghdl, from the
+//! tokens tree-sitter splits them into. every rule has a level, `deny` fails
+//! the lint, `warn` only reports and `allow` turns the rule off:
+//!
+//! ```toml
+//! [lint]
+//! allow = ["unused-signal"]
+//! deny = ["undriven-output", "missing-default-case"]
+//! ```
+//!
+//! the rules, and their level when gb.toml doesn't set one:
+//!
+//! | rule                   | default | finds                                          |
+//! |------------------------|---------|------------------------------------------------|
+//! | `file-naming`          | deny    | files not named after their entity             |
+//! | `unused-signal`        | warn    | signals of an architecture nothing refers to   |
+//! | `undriven-output`      | warn    | `out` ports their architecture never assigns   |
+//! | `missing-default-case` | warn    | `case` statements without a `when others` arm  |
+//!
+//! these are read off the tokens, not elaborated, so an output driven only
+//! through a procedure's parameter looks undriven. `allow` it where that bites.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use colored::Colorize;
+use toml_edit::Document;
+
+use crate::{naming, tree_sitter, Check, GbError, Level};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    FileNaming,
+    UnusedSignal,
+    UndrivenOutput,
+    MissingDefaultCase,
+}
+
+impl Rule {
+    const ALL: [Rule; 4] = [
+        Rule::FileNaming,
+        Rule::UnusedSignal,
+        Rule::UndrivenOutput,
+        Rule::MissingDefaultCase,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Rule::FileNaming => "file-naming",
+            Rule::UnusedSignal => "unused-signal",
+            Rule::UndrivenOutput => "undriven-output",
+            Rule::MissingDefaultCase => "missing-default-case",
+        }
+    }
+
+    fn default_level(self) -> Option<Level> {
+        match self {
+            Rule::FileNaming => Some(Level::Error),
+            _ => Some(Level::Warning),
+        }
+    }
+}
+
+/// the level of every rule, none for the allowed ones
+struct Levels(Vec<(Rule, Option<Level>)>);
+
+impl Levels {
+    fn parse(doc: &Document) -> Result<Levels, GbError> {
+        let mut levels = Rule::ALL
+            .iter()
+            .map(|rule| (*rule, rule.default_level()))
+            .collect::<Vec<_>>();
+        let lint = doc.get("lint");
+        for (key, level) in [
+            ("allow", None),
+            ("warn", Some(Level::Warning)),
+            ("deny", Some(Level::Error)),
+        ] {
+            let Some(rules) = lint.and_then(|lint| lint.get(key)) else {
+                continue;
+            };
+            let rules = rules
+                .as_array()
+                .fatal(format!("`lint.{key}` must be an array of rule names"))?;
+            for name in rules {
+                let name = name
+                    .as_str()
+                    .fatal(format!("`lint.{key}` must only contain strings"))?;
+                let (_, set) = levels
+                    .iter_mut()
+                    .find(|(rule, _)| rule.name() == name)
+                    .fatal(format!(
+                        "`lint.{key}` names an unknown rule `{name}`, the rules are {}",
+                        Rule::ALL.map(Rule::name).join(", ")
+                    ))?;
+                *set = level;
+            }
+        }
+        Ok(Levels(levels))
+    }
+
+    fn of(&self, rule: Rule) -> Option<Level> {
+        self.0
+            .iter()
+            .find(|(set, _)| *set == rule)
+            .and_then(|(_, level)| *level)
+    }
+}
+
+#[derive(Debug)]
+struct Finding {
+    rule: Rule,
+    file: PathBuf,
+    /// 1-based, like editors count them
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+#[derive(Debug)]
+struct Token {
+    /// lowercased, vhdl doesn't care about case
+    text: String,
+    line: usize,
+    column: usize,
+    keyword: bool,
+}
+
+impl Token {
+    fn is(&self, text: &str) -> bool {
+        self.text == text
+    }
+
+    fn is_identifier(&self) -> bool {
+        !self.keyword
+            && self
+                .text
+                .starts_with(|c: char| c.is_ascii_alphabetic() || c == '\\')
+    }
+}
+
+fn tokens(code_src: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+    let line_starts = std::iter::once(0)
+        .chain(code_src.match_indices('\n').map(|(pos, _)| pos + 1))
+        .collect::<Vec<_>>();
+    Ok(tree_sitter::leaves(code_src)?
+        .into_iter()
+        .filter(|leaf| !leaf.comment)
+        .map(|leaf| Token {
+            text: code_src[leaf.start..leaf.end].to_lowercase(),
+            line: leaf.row + 1,
+            column: leaf.start - line_starts[leaf.row] + 1,
+            keyword: leaf.keyword,
+        })
+        .collect())
+}
+
+/// an entity, architecture or package, from its first token to the next unit
+struct Unit {
+    kind: String,
+    name: String,
+    /// the entity of an architecture
+    of: Option<String>,
+    start: usize,
+    end: usize,
+}
+
+fn units(tokens: &[Token]) -> Vec<Unit> {
+    let mut units: Vec<Unit> = Vec::new();
+    for (pos, token) in tokens.iter().enumerate() {
+        let starts_unit = ["entity", "architecture", "package", "configuration", "context"]
+            .contains(&token.text.as_str())
+            && token.keyword
+            // `end entity` and `u0 : entity work.x` don't start a unit
+            && !(pos > 0 && (tokens[pos - 1].is("end") || tokens[pos - 1].is(":")));
+        if !starts_unit {
+            continue;
+        }
+        if let Some(last) = units.last_mut() {
+            last.end = pos;
+        }
+        let mut name_at = pos + 1;
+        if token.is("package") && tokens.get(name_at).is_some_and(|next| next.is("body")) {
+            name_at += 1;
+        }
+        let of = (token.is("architecture")
+            && tokens.get(name_at + 1).is_some_and(|next| next.is("of")))
+        .then(|| tokens.get(name_at + 2).map(|entity| entity.text.clone()))
+        .flatten();
+        units.push(Unit {
+            kind: token.text.clone(),
+            name: tokens
+                .get(name_at)
+                .map(|name| name.text.clone())
+                .unwrap_or_default(),
+            of,
+            start: pos,
+            end: tokens.len(),
+        });
+    }
+    units
+}
+
+/// the names a token refers to, `a.b` being two of them
+fn names(token: &Token) -> Vec<&str> {
+    if !token.is_identifier() {
+        return vec![];
+    }
+    token
+        .text
+        .split('.')
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn unused_signals<'t>(
+    tokens: &'t [Token],
+    units: &[Unit],
+    found: &mut Vec<(Rule, &'t Token, String)>,
+) {
+    let mut uses = HashMap::<&str, usize>::new();
+    for token in tokens {
+        for name in names(token) {
+            *uses.entry(name).or_default() += 1;
+        }
+    }
+    for unit in units.iter().filter(|unit| unit.kind == "architecture") {
+        let mut depth = 0usize;
+        let mut pos = unit.start;
+        while pos < unit.end {
+            match tokens[pos].text.as_str() {
+                "(" => depth += 1,
+                ")" => depth = depth.saturating_sub(1),
+                // `signal` inside parentheses is a parameter of a subprogram
+                "signal" if depth == 0 && tokens[pos].keyword => {
+                    pos += 1;
+                    while pos < unit.end && !tokens[pos].is(":") {
+                        let token = &tokens[pos];
+                        if token.is_identifier() && uses.get(token.text.as_str()) == Some(&1) {
+                            found.push((
+                                Rule::UnusedSignal,
+                                token,
+                                format!("signal `{}` is never used", token.text),
+                            ));
+                        }
+                        pos += 1;
+                    }
+                }
+                _ => {}
+            }
+            pos += 1;
+        }
+    }
+}
+
+/// the index of the matching `)` of the `(` at `open`
+fn closing(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (pos, token) in tokens.iter().enumerate().skip(open) {
+        match token.text.as_str() {
+            "(" => depth += 1,
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return pos;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// the `out` ports declared by the `port (...)` of an entity
+fn outputs<'t>(tokens: &'t [Token], entity: &Unit) -> Vec<&'t Token> {
+    let Some(port) = (entity.start..entity.end).find(|pos| {
+        tokens[*pos].is("port") && tokens.get(pos + 1).is_some_and(|next| next.is("("))
+    }) else {
+        return vec![];
+    };
+    let end = closing(tokens, port + 1);
+    let mut outputs = Vec::new();
+    let mut names = Vec::new();
+    let mut pos = port + 2;
+    while pos < end {
+        let token = &tokens[pos];
+        if token.is(":") {
+            let mode = tokens[pos + 1..end]
+                .iter()
+                .find(|token| !token.is("signal"));
+            if mode.is_some_and(|mode| mode.is("out") || mode.is("buffer")) {
+                outputs.append(&mut names);
+            }
+            names.clear();
+            // skip the type, which can have parentheses of its own
+            while pos < end && !tokens[pos].is(";") {
+                if tokens[pos].is("(") {
+                    pos = closing(tokens, pos);
+                }
+                pos += 1;
+            }
+        } else if token.is_identifier() {
+            names.push(token);
+        }
+        pos += 1;
+    }
+    outputs
+}
+
+/// whether `tokens[pos]` is assigned, or connected to an instance's port
+fn drives(tokens: &[Token], pos: usize, port_maps: &[(usize, usize)]) -> bool {
+    if port_maps
+        .iter()
+        .any(|(open, close)| (*open..*close).contains(&pos))
+    {
+        return true;
+    }
+    let statement_start = pos == 0
+        || [";", "begin", "then", "else", "loop", "generate", "=>", ":"]
+            .contains(&tokens[pos - 1].text.as_str());
+    let mut next = pos + 1;
+    if tokens.get(next).is_some_and(|token| token.is("(")) {
+        next = closing(tokens, next) + 1;
+    }
+    statement_start && tokens.get(next).is_some_and(|token| token.is("<="))
+}
+
+fn undriven_outputs<'t>(
+    tokens: &'t [Token],
+    units: &[Unit],
+    found: &mut Vec<(Rule, &'t Token, String)>,
+) {
+    for entity in units.iter().filter(|unit| unit.kind == "entity") {
+        let architectures = units
+            .iter()
+            .filter(|unit| unit.of.as_deref() == Some(entity.name.as_str()))
+            .collect::<Vec<_>>();
+        // the architecture lives in another file, which isn't looked at
+        if architectures.is_empty() {
+            continue;
+        }
+        let port_maps = (0..tokens.len())
+            .filter(|pos| {
+                tokens[*pos].is("port")
+                    && tokens.get(pos + 1).is_some_and(|next| next.is("map"))
+                    && tokens.get(pos + 2).is_some_and(|next| next.is("("))
+            })
+            .map(|pos| (pos + 2, closing(tokens, pos + 2)))
+            .collect::<Vec<_>>();
+        for output in outputs(tokens, entity) {
+            let driven = architectures.iter().any(|architecture| {
+                (architecture.start..architecture.end).any(|pos| {
+                    names(&tokens[pos]).contains(&output.text.as_str())
+                        && drives(tokens, pos, &port_maps)
+                })
+            });
+            if !driven {
+                found.push((
+                    Rule::UndrivenOutput,
+                    output,
+                    format!(
+                        "output `{}` of `{}` is never driven",
+                        output.text, entity.name
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn missing_default_cases<'t>(tokens: &'t [Token], found: &mut Vec<(Rule, &'t Token, String)>) {
+    // every open `case`, and whether it has a `when others` yet
+    let mut open = Vec::<(&Token, bool)>::new();
+    for (pos, token) in tokens.iter().enumerate() {
+        let after_end = pos > 0 && tokens[pos - 1].is("end");
+        if token.is("case") && token.keyword && !after_end {
+            open.push((token, false));
+        } else if token.is("case") && after_end {
+            if let Some((case, false)) = open.pop() {
+                found.push((
+                    Rule::MissingDefaultCase,
+                    case,
+                    "this case has no `when others` arm".to_owned(),
+                ));
+            }
+        } else if token.is("others") && pos > 0 && tokens[pos - 1].is("when") {
+            if let Some((_, others)) = open.last_mut() {
+                *others = true;
+            }
+        }
+    }
+}
+
+fn lint_file(file: &Path, levels: &Levels) -> Result<Vec<Finding>, GbError> {
+    let code_src =
+        std::fs::read_to_string(file).fatal(format!("could not read `{}`", file.display()))?;
+    let tokens = match tokens(&code_src) {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            eprintln!(
+                "  {}  skipped `{}`, {error}",
+                "[lint]".yellow().bold(),
+                file.display()
+            );
+            return Ok(vec![]);
+        }
+    };
+    let units = units(&tokens);
+
+    let mut found = Vec::new();
+    if levels.of(Rule::UnusedSignal).is_some() {
+        unused_signals(&tokens, &units, &mut found);
+    }
+    if levels.of(Rule::UndrivenOutput).is_some() {
+        undriven_outputs(&tokens, &units, &mut found);
+    }
+    if levels.of(Rule::MissingDefaultCase).is_some() {
+        missing_default_cases(&tokens, &mut found);
+    }
+    Ok(found
+        .into_iter()
+        .map(|(rule, token, message)| Finding {
+            rule,
+            file: file.to_owned(),
+            line: token.line,
+            column: token.column,
+            message,
+        })
+        .collect())
+}
+
+/// whether `[lint]` of gb.toml is valid
+pub fn check(doc: &Document) -> Result<(), GbError> {
+    Levels::parse(doc).map(drop)
+}
+
+pub fn lint(doc: &Document, files: &[&str], convention: naming::Convention) -> Result<(), GbError> {
+    let levels = Levels::parse(doc)?;
+
+    let mut findings = Vec::new();
+    if levels.of(Rule::FileNaming).is_some() {
+        findings.extend(
+            naming::mismatches(files, convention)
+                .into_iter()
+                .map(|mismatch| Finding {
+                    rule: Rule::FileNaming,
+                    line: 1,
+                    column: 1,
+                    message: format!(
+                        "declares `{}`, rename it: mv {} {}",
+                        mismatch.entity,
+                        mismatch.file.display(),
+                        mismatch.expected.display()
+                    ),
+                    file: mismatch.file,
+                }),
+        );
+    }
+    for file in files {
+        findings.extend(lint_file(Path::new(file), &levels)?);
+    }
+    findings.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+
+    let mut errors = 0;
+    for finding in &findings {
+        let tag = match levels.of(finding.rule) {
+            Some(Level::Error) => {
+                errors += 1;
+                "[error]".red().bold()
+            }
+            _ => "[warning]".yellow().bold(),
+        };
+        eprintln!(
+            "  {tag}  {}:{}:{}: {} ({})",
+            finding.file.display(),
+            finding.line,
+            finding.column,
+            finding.message,
+            finding.rule.name()
+        );
+    }
+
+    if errors > 0 {
+        Err(GbError {
+            message: format!(
+                "{errors} lint error(s) and {} warning(s), see `[lint]` in gb.toml",
+                findings.len() - errors
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let summary = if findings.is_empty() {
+        "No problems found.".to_owned()
+    } else {
+        format!("{} warning(s), no errors.", findings.len())
+    };
+    eprintln!("  {}  {}", "[lint]".blue().bold(), summary.green().bold());
+    Ok(())
+}
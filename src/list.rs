@@ -0,0 +1,83 @@
+//! `gb list`: the targets of gb.toml, what they execute, how many files they
+//! have and which one is used when none is passed. `--json` is for editors
+//! and other tools.
+
+use colored::Colorize;
+use serde::Serialize;
+use toml_edit::Document;
+
+use crate::{order, Check, GbError};
+
+#[derive(Debug, Serialize)]
+pub struct Target {
+    pub name: String,
+    pub execute: Option<String>,
+    /// none when the files can't be resolved, e.g. a broken `files = "auto"`
+    pub files: Option<usize>,
+    pub default: bool,
+}
+
+pub fn targets(doc: &Document) -> Result<Vec<Target>, GbError> {
+    let default = crate::default_target(doc)?;
+    let Some(targets) = doc
+        .get("target")
+        .and_then(|targets| targets.as_table_like())
+    else {
+        return Ok(vec![]);
+    };
+    Ok(targets
+        .iter()
+        .map(|(name, info)| {
+            let files = crate::resolve_target_files(name, info).ok();
+            Target {
+                name: name.to_owned(),
+                // the entity run in place of a missing `execute`, when there's one
+                execute: info
+                    .get("execute")
+                    .and_then(|execute| execute.as_str())
+                    .map(ToOwned::to_owned)
+                    .or_else(|| order::top(name, files.as_ref()?).ok()),
+                files: files.map(|files| files.len()),
+                default: default.as_deref() == Some(name),
+            }
+        })
+        .collect())
+}
+
+pub fn list(doc: &Document, json: bool, names: bool) -> Result<(), GbError> {
+    let targets = targets(doc)?;
+    if names {
+        for target in &targets {
+            println!("{}", target.name);
+        }
+        return Ok(());
+    }
+    if json {
+        let json =
+            serde_json::to_string_pretty(&targets).fatal("could not serialize the targets")?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    if targets.is_empty() {
+        eprintln!("gb.toml has no targets");
+        return Ok(());
+    }
+    for (pos, target) in targets.iter().enumerate() {
+        let name = if target.default {
+            format!("{} (default)", target.name).green().bold()
+        } else {
+            target.name.bold()
+        };
+        let files = match target.files {
+            Some(1) => "1 file".to_owned(),
+            Some(files) => format!("{files} files"),
+            None => "files unresolved".to_owned(),
+        };
+        match &target.execute {
+            Some(execute) => println!("  {}. {name}  executes {execute}, {files}", pos + 1),
+            None => println!("  {}. {name}  {files}", pos + 1),
+        }
+    }
+    Ok(())
+}
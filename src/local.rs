@@ -0,0 +1,58 @@
+//! `gb.local.toml`, next to gb.toml and ignored by git, for what's only
+//! yours to decide, without touching the manifest everyone shares:
+//!
+//! ```toml
+//! default.target = "alu"
+//! default.vcd-viewer = "surfer"
+//!
+//! [ghdl]
+//! path = "/opt/ghdl-nightly/bin/ghdl"
+//! ```
+//!
+//! it's laid over gb.toml key by key, tables are merged and everything else
+//! it sets wins. `gb init` adds it to .gitignore.
+
+use std::path::Path;
+
+use toml_edit::{Document, TableLike};
+
+use crate::{Check, GbError};
+
+pub const FILE: &str = "gb.local.toml";
+
+/// `gb.local.toml`, if there is one
+pub fn read() -> Result<Option<Document>, GbError> {
+    if !Path::new(FILE).exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(FILE)
+        .fatal(format!("could not read `{FILE}`"))?
+        .parse::<Document>()
+        .fatal(format!("failed to parse `{FILE}`"))
+        .map(Some)
+}
+
+fn overlay(into: &mut dyn TableLike, from: &dyn TableLike) {
+    for (key, item) in from.iter() {
+        match into.get_mut(key) {
+            Some(existing) if existing.is_table_like() && item.is_table_like() => {
+                if let (Some(existing), Some(item)) =
+                    (existing.as_table_like_mut(), item.as_table_like())
+                {
+                    overlay(existing, item);
+                }
+            }
+            _ => {
+                into.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// lays `gb.local.toml` over `doc`
+pub fn merge_into(doc: &mut Document) -> Result<(), GbError> {
+    if let Some(local) = read()? {
+        overlay(doc.as_table_mut(), local.as_table());
+    }
+    Ok(())
+}
@@ -0,0 +1,387 @@
+//! `gb.lock`: the exact commit every git dependency is built from, and the
+//! flags each dependency was analyzed with, so that a checkout of the project
+//! builds the same libraries a year from now:
+//!
+//! ```toml
+//! # written by gb, `gb update` refreshes it
+//!
+//! [[dependency]]
+//! name = "uart"
+//! git = "https://github.com/someone/uart"
+//! rev = "v1.2.0"
+//! commit = "3f4c2a9d0e6b7c1f8a5d2e9b4c7f0a3d6e1b8c5f"
+//! flags = ["--std=08", "--work=uart"]
+//! ```
+//!
+//! gb writes it the first time it builds the dependencies, and goes by it
+//! afterwards: a git dependency is checked out at its locked commit, even
+//! when its branch has moved on, and a dependency analyzed with other flags
+//! than it was locked with gets a warning. the dependencies of dependencies
+//! are locked in the project's gb.lock as well.
+//!
+//! `gb update` resolves every `rev` again and rewrites the lock, `gb update
+//! uart` only does so for uart. under `strict` gb never writes gb.lock, a
+//! dependency it doesn't lock yet fails the build instead.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use colored::Colorize;
+use once_cell::sync::Lazy;
+use toml_edit::{value, Array, ArrayOfTables, Document, Item, Table};
+
+use crate::{deps, report, BuildOptions, Check, GbError, Level};
+
+const FILE: &str = "gb.lock";
+
+const HEADER: &str = "# written by gb, `gb update` refreshes it";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Entry {
+    name: String,
+    git: Option<String>,
+    path: Option<String>,
+    rev: Option<String>,
+    commit: Option<String>,
+    flags: Option<Vec<String>>,
+}
+
+#[derive(Debug)]
+struct Lock {
+    /// gb.lock of the project, found before any dependency's directory is entered
+    file: PathBuf,
+    entries: Vec<Entry>,
+    /// the names of the entries the build went through, the others are stale
+    used: Vec<String>,
+    changed: bool,
+    frozen: bool,
+}
+
+static LOCK: Lazy<Mutex<Option<Lock>>> = Lazy::new(|| Mutex::new(None));
+
+fn parse(file: &Path) -> Result<Vec<Entry>, GbError> {
+    let Ok(lock) = std::fs::read_to_string(file) else {
+        return Ok(vec![]);
+    };
+    let lock = lock
+        .parse::<Document>()
+        .fatal("failed to parse gb.lock, `gb update` writes it again")?;
+    let Some(entries) = lock.get("dependency") else {
+        return Ok(vec![]);
+    };
+    let entries = entries
+        .as_array_of_tables()
+        .fatal("`dependency` in gb.lock must be an array of tables")?;
+    let string =
+        |table: &Table, key: &str| table.get(key).and_then(|v| v.as_str()).map(str::to_owned);
+    entries
+        .iter()
+        .map(|table| {
+            let flags = table
+                .get("flags")
+                .and_then(|flags| flags.as_array())
+                .map(|flags| {
+                    flags
+                        .iter()
+                        .filter_map(|flag| flag.as_str().map(str::to_owned))
+                        .collect()
+                });
+            Ok(Entry {
+                name: string(table, "name").fatal("every dependency in gb.lock needs a `name`")?,
+                git: string(table, "git"),
+                path: string(table, "path"),
+                rev: string(table, "rev"),
+                commit: string(table, "commit"),
+                flags,
+            })
+        })
+        .collect()
+}
+
+/// runs `f` on the lock, reading gb.lock the first time
+fn with_lock<T>(f: impl FnOnce(&mut Lock) -> Result<T, GbError>) -> Result<T, GbError> {
+    let mut lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if lock.is_none() {
+        let file = std::env::current_dir()
+            .fatal("cannot get the current directory")?
+            .join(FILE);
+        *lock = Some(Lock {
+            entries: parse(&file)?,
+            file,
+            used: vec![],
+            changed: false,
+            frozen: false,
+        });
+    }
+    f(lock.as_mut().expect("the lock was just read"))
+}
+
+impl Lock {
+    /// replaces `entry`'s namesake, unless gb.lock may not change
+    fn set(&mut self, entry: Entry) -> Result<(), GbError> {
+        if self.frozen {
+            Err(GbError {
+                message: format!(
+                    "dependency `{}` isn't locked in gb.lock, and strict builds don't write it, run `gb update`",
+                    entry.name
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        self.entries.retain(|locked| locked.name != entry.name);
+        self.entries.push(entry);
+        self.changed = true;
+        Ok(())
+    }
+
+    fn get(&mut self, name: &str) -> Option<&Entry> {
+        if !self.used.iter().any(|used| used == name) {
+            self.used.push(name.to_owned());
+        }
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+}
+
+/// the directory of the project gb.lock belongs to, wherever gb is at the moment
+pub fn root() -> Result<PathBuf, GbError> {
+    with_lock(|lock| {
+        Ok(lock
+            .file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default())
+    })
+}
+
+/// strict builds go by gb.lock without ever writing it
+pub fn freeze() {
+    let _ = with_lock(|lock| {
+        lock.frozen = true;
+        Ok(())
+    });
+}
+
+/// the commit `rev` of `url` points at on the remote, `HEAD` without one
+fn resolve(name: &str, url: &str, rev: Option<&str>) -> Result<String, GbError> {
+    let output = std::process::Command::new("git")
+        .args(["ls-remote", "--", url, rev.unwrap_or("HEAD")])
+        .output()
+        .fatal("could not run `git`, which gb needs for git dependencies")?;
+    if !output.status.success() {
+        Err(GbError {
+            message: format!("could not reach `{url}` for dependency `{name}`"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let refs = String::from_utf8_lossy(&output.stdout);
+    let refs = refs
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect::<Vec<_>>();
+    // an annotated tag is listed once more, peeled to the commit it tags
+    let commit = refs
+        .iter()
+        .find(|(_, name)| name.ends_with("^{}"))
+        .or(refs.first())
+        .map(|(commit, _)| commit.to_string());
+    match (commit, rev) {
+        (Some(commit), _) => Ok(commit),
+        // a commit isn't a ref, `git checkout` finds out whether it exists
+        (None, Some(rev)) if rev.len() >= 7 && rev.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(rev.to_owned())
+        }
+        (None, rev) => Err(GbError {
+            message: format!("`{url}` has no revision `{}`", rev.unwrap_or("HEAD")),
+            level: Level::Fatal,
+            source: None,
+        }),
+    }
+}
+
+/// the commit a git dependency is built from, resolving and locking it when
+/// gb.lock has none for this `url` and `rev`
+pub fn commit(name: &str, url: &str, rev: Option<&str>) -> Result<String, GbError> {
+    with_lock(|lock| {
+        let locked = lock
+            .get(name)
+            .filter(|entry| entry.git.as_deref() == Some(url) && entry.rev.as_deref() == rev);
+        if let Some(commit) = locked.and_then(|entry| entry.commit.clone()) {
+            return Ok(commit);
+        }
+        let commit = resolve(name, url, rev)?;
+        lock.set(Entry {
+            name: name.to_owned(),
+            git: Some(url.to_owned()),
+            rev: rev.map(str::to_owned),
+            commit: Some(commit.clone()),
+            ..Default::default()
+        })?;
+        Ok(commit)
+    })
+}
+
+/// locks a path dependency, which has no commit but its flags
+pub fn path(name: &str, path: &Path) -> Result<(), GbError> {
+    let path = path.display().to_string();
+    with_lock(|lock| {
+        let locked = lock
+            .get(name)
+            .is_some_and(|entry| entry.path.as_ref() == Some(&path));
+        if !locked {
+            lock.set(Entry {
+                name: name.to_owned(),
+                path: Some(path),
+                ..Default::default()
+            })?;
+        }
+        Ok(())
+    })
+}
+
+/// the flags a dependency is analyzed with, which every machine has to agree on
+fn locked_flags(build: &BuildOptions) -> Vec<String> {
+    build
+        .common_flags()
+        .into_iter()
+        .chain(build.analyze_flags.iter().cloned())
+        // where the libraries are differs from one checkout to the next, and
+        // which warnings are shown or fatal doesn't change what's analyzed
+        .filter(|flag| {
+            !["-P", "--workdir=", "--warn-", "-W"]
+                .iter()
+                .any(|prefix| flag.starts_with(prefix))
+        })
+        .collect()
+}
+
+/// compares the flags the library `name` is about to be analyzed with to the
+/// locked ones, locking them if there are none yet
+pub fn flags(name: &str, build: &BuildOptions) -> Result<(), GbError> {
+    let flags = locked_flags(build);
+    with_lock(|lock| {
+        // workspace members are built the same way, but aren't dependencies
+        let Some(entry) = lock.get(name).cloned() else {
+            return Ok(());
+        };
+        match &entry.flags {
+            None => lock.set(Entry {
+                flags: Some(flags),
+                ..entry
+            }),
+            Some(locked) if *locked != flags => report::emit(report::warning(format!(
+                "dependency `{name}` is analyzed with `{}`, but gb.lock has `{}`, `gb update {name}` locks the new flags",
+                flags.join(" "),
+                locked.join(" ")
+            ))),
+            Some(_) => Ok(()),
+        }
+    })
+}
+
+fn write(lock: &Lock) -> Result<(), GbError> {
+    let mut tables = ArrayOfTables::new();
+    for entry in &lock.entries {
+        let mut table = Table::new();
+        table["name"] = value(&entry.name);
+        let strings = [
+            ("git", &entry.git),
+            ("path", &entry.path),
+            ("rev", &entry.rev),
+            ("commit", &entry.commit),
+        ];
+        for (key, string) in strings {
+            if let Some(string) = string {
+                table[key] = value(string);
+            }
+        }
+        if let Some(flags) = &entry.flags {
+            table["flags"] = value(flags.iter().collect::<Array>());
+        }
+        tables.push(table);
+    }
+    let mut doc = Document::new();
+    doc["dependency"] = Item::ArrayOfTables(tables);
+    std::fs::write(&lock.file, format!("{HEADER}\n\n{doc}")).fatal("could not write gb.lock")
+}
+
+/// writes gb.lock if the build changed it, dropping the dependencies it
+/// didn't come across
+pub fn save() -> Result<(), GbError> {
+    let mut lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(lock) = lock.as_mut() else {
+        return Ok(());
+    };
+    let before = lock.entries.len();
+    let used = std::mem::take(&mut lock.used);
+    lock.entries.retain(|entry| used.contains(&entry.name));
+    if (lock.changed || lock.entries.len() != before) && !lock.frozen {
+        write(lock)?;
+        lock.changed = false;
+    }
+    Ok(())
+}
+
+fn short(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+/// `gb update`: resolves the dependencies named, or all of them, again and
+/// builds them, so that gb.lock holds their latest commits and flags
+pub fn update(doc: &Document, build: &BuildOptions, names: &[String]) -> Result<(), GbError> {
+    let before = with_lock(|lock| {
+        // asking for it is what `strict` wants done instead of writing gb.lock
+        lock.frozen = false;
+        let unknown = names
+            .iter()
+            .find(|name| !lock.entries.iter().any(|entry| entry.name == **name));
+        if let Some(name) = unknown {
+            let direct = deps::dependencies(doc)?;
+            if !direct.iter().any(|dependency| dependency.name == *name) {
+                Err(GbError {
+                    message: format!("there is no dependency named `{name}`"),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+        }
+        let before = lock.entries.clone();
+        lock.entries
+            .retain(|entry| !names.is_empty() && !names.contains(&entry.name));
+        lock.changed = true;
+        Ok(before)
+    })?;
+
+    deps::library_paths(doc, build)?;
+
+    let after = with_lock(|lock| Ok(lock.entries.clone()))?;
+    for entry in &after {
+        let Some(commit) = &entry.commit else {
+            continue;
+        };
+        let previous = before
+            .iter()
+            .find(|locked| locked.name == entry.name)
+            .and_then(|locked| locked.commit.as_deref());
+        let message = match previous {
+            Some(previous) if previous == commit => continue,
+            Some(previous) => format!(
+                "Updated `{}` from {} to {}",
+                entry.name,
+                short(previous),
+                short(commit)
+            ),
+            None => format!("Locked `{}` at {}", entry.name, short(commit)),
+        };
+        eprintln!("  {}  {}", "[update]".blue().bold(), message.green().bold());
+    }
+    eprintln!(
+        "  {}  {}",
+        "[update]".blue().bold(),
+        "Wrote gb.lock".green().bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! `gb lsp-config`: writes a `vhdl_ls.toml` for rust_hdl's language server,
+//! so that an editor sees the same libraries gb builds:
+//!
+//! ```toml
+//! standard = "2008"
+//!
+//! [libraries]
+//! blinky.files = ["src/blinky.vhd", "src/blinky_tb.vhd"]
+//! uart.files = ["/home/me/uart/src/uart.vhd"]
+//! ```
+//!
+//! every target's files go into its `library`. vhdl_ls doesn't accept `work`
+//! as a name, so the files gb analyzes into `work` get a library named after
+//! the project's directory, which `work.` still refers to from inside of it.
+//! `[dependencies]` get a library under their own name, with the files of
+//! their default target.
+//!
+//! gb only overwrites a `vhdl_ls.toml` it wrote itself, unless it's forced to.
+
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use toml_edit::{value, Array, Document, Item, Table};
+
+use crate::{deps, scaffold, Check, GbError, Level};
+
+const HEADER: &str =
+    "# written by `gb lsp-config` from gb.toml, run it again after changing targets";
+
+const PATH: &str = "vhdl_ls.toml";
+
+/// vhdl_ls's name for a `std`, none for those it doesn't know
+fn standard(std: &str) -> Option<&'static str> {
+    match std {
+        "93" | "93c" => Some("1993"),
+        "08" => Some("2008"),
+        "19" => Some("2019"),
+        _ => None,
+    }
+}
+
+/// the library for the files analyzed into `work`
+fn project_library() -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .and_then(|name| scaffold::entity_name(&name).ok())
+        .unwrap_or_else(|| "design".to_owned())
+}
+
+/// the libraries and their files, in the order gb.toml brings them up
+fn libraries(doc: &Document) -> Result<Vec<(String, Vec<String>)>, GbError> {
+    let mut libraries: Vec<(String, Vec<String>)> = Vec::new();
+    let mut add = |library: String, files: Vec<String>| {
+        let index = match libraries.iter().position(|(name, _)| *name == library) {
+            Some(index) => index,
+            None => {
+                libraries.push((library, vec![]));
+                libraries.len() - 1
+            }
+        };
+        for file in files {
+            if !libraries[index].1.contains(&file) {
+                libraries[index].1.push(file);
+            }
+        }
+    };
+
+    let targets = doc
+        .get("target")
+        .and_then(|targets| targets.as_table_like())
+        .map(|targets| targets.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    for (target, info) in targets {
+        let library = crate::parse_library(target, info.get("library"))?;
+        add(
+            library.unwrap_or_else(project_library),
+            crate::resolve_target_files(target, info)?,
+        );
+    }
+    for dependency in deps::dependencies(doc)? {
+        let dir = dependency.dir(Path::new("."))?;
+        let files = deps::library_files(&dependency.name, &dir)?
+            .into_iter()
+            .map(|file: PathBuf| file.display().to_string())
+            .collect();
+        add(dependency.name.to_lowercase(), files);
+    }
+    Ok(libraries)
+}
+
+pub fn lsp_config(doc: &Document, force: bool) -> Result<(), GbError> {
+    if let Ok(existing) = std::fs::read_to_string(PATH) {
+        if !existing.starts_with(HEADER) && !force {
+            Err(GbError {
+                message: format!(
+                    "`{PATH}` wasn't written by gb, pass `--force` to replace it anyway"
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+    }
+
+    let mut config = Document::new();
+    let std = doc
+        .get("default")
+        .and_then(|default| default.get("std"))
+        .and_then(|std| std.as_str());
+    if let Some(standard) = std.and_then(standard) {
+        config["standard"] = value(standard);
+    }
+    let mut table = Table::new();
+    for (name, files) in libraries(doc)? {
+        let mut library = Table::new();
+        library.set_dotted(true);
+        library["files"] = value(files.into_iter().collect::<Array>());
+        table.insert(&name, Item::Table(library));
+    }
+    config["libraries"] = Item::Table(table);
+
+    std::fs::write(PATH, format!("{HEADER}\n{config}"))
+        .fatal(format!("could not write `{PATH}`"))?;
+    eprintln!(
+        "  {}  {}",
+        "[lsp]".blue().bold(),
+        format!("Wrote {PATH}").green().bold()
+    );
+    Ok(())
+}
@@ -1,18 +1,92 @@
 #![allow(dead_code)]
 
+mod artifacts;
+mod baseline;
+mod cache;
+mod compare;
+mod completions;
+mod config;
+mod config_show;
+mod contexts;
+mod coverage;
+mod depends;
+mod deps;
+mod diagnostics;
+mod doc;
+mod doctor;
+mod entities;
+mod exit;
+mod export;
+mod filter;
+#[cfg(test)]
+mod fixture;
+mod fmt;
+mod foreign;
+mod fpga;
+mod ghdl;
+mod gitignore;
+mod golden;
+mod graph;
+mod grep;
+mod hooks;
+mod inherit;
+mod interpolate;
+mod libraries;
+mod limits;
+mod lint;
+mod list;
+mod local;
+mod lock;
+mod lsp;
+mod manifest;
+mod manifest_edit;
+mod manifest_fmt;
+mod naming;
+mod nvc;
+mod order;
+mod orphans;
+mod package;
+mod parallel;
+mod plan;
+mod probe;
+mod profile;
+mod publish;
+mod render;
+mod report;
+mod scaffold;
+mod scenario;
+mod schema;
+mod shell;
+mod sim;
+mod simulator;
+mod sources;
+mod state;
+mod stream;
+mod synth;
+mod test;
+mod test_report;
+mod transcript;
+mod tree;
 mod tree_sitter;
-
-use std::{
-    borrow::Cow, error::Error, fs::OpenOptions, io::Write, path::PathBuf, process::Command,
-    str::FromStr,
-};
+mod update;
+mod vcd;
+mod vcd_export;
+mod vendor;
+mod verbosity;
+mod warnings;
+mod watch;
+mod wave;
+mod wizard;
+mod workspace;
+
+use std::{error::Error, path::PathBuf, process::Command};
 
 use crate::tree_sitter::generate_sources_for;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
 use toml_edit::Document;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 
 pub enum Level {
     Fatal,
@@ -79,17 +153,101 @@ impl std::error::Error for GbError {
     }
 }
 
+/// A TOML based build tool using GHDL + VHDL
 #[derive(Debug, Clone, Parser)]
+#[command(after_help = exit::HELP)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Commands,
 
-/// A TOML based build tool using GHDL + VHDL
+    #[command(flatten)]
+    options: GlobalOptions,
+}
+
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct GlobalOptions {
+    /// enforce a fully specified manifest, same as `strict = true` in gb.toml
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// show ghdl's output as it is, ignoring the `[output]` filters in gb.toml
+    #[arg(long, global = true)]
+    raw_output: bool,
+
+    /// how ghdl's diagnostics are shown, `json` prints one object per line on stdout
+    #[arg(long, global = true, value_enum, default_value_t)]
+    message_format: diagnostics::MessageFormat,
+
+    /// how gb's own errors are shown, `json` prints one object on stderr
+    #[arg(long, global = true, value_enum, default_value_t)]
+    error_format: exit::ErrorFormat,
+
+    /// fail only on warnings that are not in this baseline file
+    #[arg(long, global = true, value_name = "FILE")]
+    warnings_baseline: Option<PathBuf>,
+
+    /// run in this member of the workspace, wherever in the workspace gb is started
+    #[arg(short, long = "package", global = true, value_name = "MEMBER")]
+    package: Option<String>,
+
+    /// record the current warnings into the --warnings-baseline file
+    #[arg(long, global = true, requires = "warnings_baseline")]
+    update_baseline: bool,
+
+    /// leave out the step banners, only ghdl's output, warnings and errors are shown
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// also print every ghdl command before it runs
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// fail on gb's warnings about gb.toml and the project, like unknown keys
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    /// build with `[profile.release]`, into build/release/
+    #[arg(long, global = true)]
+    release: bool,
+
+    /// build with `[profile.<name>]`, into build/<name>/
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "release")]
+    profile: Option<String>,
+
+    /// kill a simulation running longer than this, like `60s`, overriding
+    /// `timeout` in gb.toml
+    #[arg(long, global = true, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    timeout: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
 pub enum Commands {
     /// fully analyze, elaborate, and run
     Run {
         /// choose a target to run with vhdl
         target: Option<String>,
         /// output a vcd file
-        #[arg(long)]
+        #[arg(long, value_name = "FILE")]
         vcd: Option<std::path::PathBuf>,
+        /// output a ghw file, which keeps the full vhdl types
+        #[arg(long = "wave", value_name = "FILE", conflicts_with = "vcd")]
+        ghw: Option<std::path::PathBuf>,
+        /// output an fst file, which is a lot smaller than a vcd
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["vcd", "ghw"])]
+        fst: Option<std::path::PathBuf>,
+        /// run one of the target's `[[target.<name>.scenario]]` entries
+        #[arg(long)]
+        scenario: Option<String>,
+        /// set a top level generic, e.g. `--generic WIDTH=8`, overriding gb.toml
+        #[arg(long = "generic", value_name = "NAME=VALUE", value_parser = parse_generic)]
+        generics: Vec<(String, String)>,
+        /// analyze up to this many independent files at once, `default.jobs` or 1 when not given
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// passed on to the simulation, after `--`, e.g.
+        /// `gb run counter -- --assert-level=error --disp-time`
+        #[arg(last = true, value_name = "SIM_ARGS")]
+        sim_args: Vec<String>,
     },
 
     ListPaths {
@@ -104,34 +262,505 @@ pub enum Commands {
     #[clap(alias = "build")]
     Compile {
         target: Option<String>,
+        /// analyze up to this many independent files at once, `default.jobs` or 1 when not given
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+
+    /// synthesize a target's `[target.<name>.synth]` top into a netlist in
+    /// build/synth/
+    Synth {
+        target: Option<String>,
+    },
+
+    /// synthesize, place and route a target for the `[fpga]` board, and pack
+    /// it into a bitstream in build/fpga/
+    Pnr {
+        target: Option<String>,
+    },
+
+    /// build a target's bitstream like `gb pnr`, and load it onto the
+    /// `[fpga]` board with openFPGALoader
+    Flash {
+        target: Option<String>,
+    },
+
+    /// only elaborate a target and print the path of the executable.
+    /// analysis is only redone if a source changed since the last one
+    Elab {
+        target: Option<String>,
     },
 
     /// analyzes a configuration (useful for errors!), only analyzes
     Analyze {
         /// compile a specific target
         target: Option<String>,
+        /// analyze up to this many independent files at once, `default.jobs` or 1 when not given
+        #[arg(short, long)]
+        jobs: Option<usize>,
     },
 
-    /// Use a waveform viewer, default.vcd-viewer to specify.
+    /// Use a waveform viewer, the target's vcd-viewer or default.vcd-viewer to specify.
     /// will do a run and then view the wave, in a detached
-    /// process
+    /// process. `--no-run` reopens the last run's waveform while it's up to date
     Wave {
         target: Option<String>,
-        #[arg(long)]
+        /// output a vcd file
+        #[arg(long, value_name = "FILE")]
         vcd: Option<std::path::PathBuf>,
+        /// output a ghw file, which keeps the full vhdl types
+        #[arg(long = "wave", value_name = "FILE", conflicts_with = "vcd")]
+        ghw: Option<std::path::PathBuf>,
+        /// output an fst file, which is a lot smaller than a vcd
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["vcd", "ghw"])]
+        fst: Option<std::path::PathBuf>,
+        /// show the waveform while the simulation is still running,
+        /// through `vcd-stream` or gtkwave's `shmidcat`
+        #[arg(long, conflicts_with_all = ["ghw", "fst", "export_svg", "export_png"])]
+        stream: bool,
+        /// draw the waveform into an svg instead of opening the viewer
+        #[arg(long, value_name = "FILE")]
+        export_svg: Option<PathBuf>,
+        /// draw the waveform into a png (needs rsvg-convert or imagemagick)
+        #[arg(long, value_name = "FILE")]
+        export_png: Option<PathBuf>,
+        /// the signals to draw when exporting, as glob patterns like `*.count`
+        #[arg(long, value_delimiter = ',', default_value = "*")]
+        signals: Vec<String>,
+        /// where the exported image starts, like `100ns`
+        #[arg(long)]
+        from: Option<String>,
+        /// where the exported image ends, the end of the dump by default
+        #[arg(long)]
+        to: Option<String>,
+        /// set a top level generic, e.g. `--generic WIDTH=8`, overriding gb.toml
+        #[arg(long = "generic", value_name = "NAME=VALUE", value_parser = parse_generic)]
+        generics: Vec<(String, String)>,
+        /// open the waveform of the last run instead of simulating again,
+        /// unless a source or gb.toml changed since
+        #[arg(long, conflicts_with_all = ["stream", "generics"])]
+        no_run: bool,
+        /// simulate even when `--no-run` is given
+        #[arg(long, overrides_with = "no_run")]
+        force_run: bool,
+    },
+
+    /// print the build plan of a target in the order it would run,
+    /// without running any of it
+    Plan {
+        target: Option<String>,
+        /// print the plan as json, with fingerprints of every input
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// print the dependency graph of a target's files, e.g. for `dot -Tsvg`
+    Graph {
+        target: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: graph::Format,
+    },
+
+    /// print the instantiation hierarchy of a target, from the unit it executes down
+    Tree {
+        target: Option<String>,
+        /// how many levels below the top to print
+        #[arg(long)]
+        depth: Option<usize>,
+        /// print what instantiates this unit instead, up to the top
+        #[arg(long, value_name = "UNIT")]
+        invert: Option<String>,
+    },
+
+    /// write documentation of a target's entities, their ports and generics, to build/doc
+    Doc {
+        target: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: doc::Format,
+        /// open the documentation in a browser afterwards
+        #[arg(long)]
+        open: bool,
+    },
+
+    /// work with a vcd dump without a waveform viewer
+    Vcd {
+        #[command(subcommand)]
+        command: VcdCommands,
+    },
+
+    /// print the values of signals at the given times, without a waveform viewer.
+    /// the simulation is only re-run when a source changed since the last dump
+    Probe {
+        target: Option<String>,
+        /// the simulation times to look at, e.g. `--at 150ns,200ns`
+        #[arg(long, required = true, value_delimiter = ',')]
+        at: Vec<String>,
+        /// the signals to print, `*` matches anything, e.g. `top.dut.*`
+        #[arg(long, value_delimiter = ',', default_value = "*")]
+        signals: Vec<String>,
+        /// always re-run the simulation, even if the dump is up to date
+        #[arg(long)]
+        rerun: bool,
+    },
+
+    /// search a target's files by tree-sitter node kind, printing `file:line:column` matches,
+    /// e.g. `gb grep --kind signal_declaration 'count*'`
+    Grep {
+        /// the name to look for, `*` matches anything, case insensitive like vhdl
+        pattern: String,
+        /// the kind of node to look for, any identifier when it isn't given
+        #[arg(long)]
+        kind: Option<String>,
+        /// search this target's files instead of the default target's
+        #[arg(long)]
+        target: Option<String>,
+    },
+
+    /// list the entities, architectures, packages and components each of a target's files declares
+    Entities {
+        target: Option<String>,
+        /// print them as json
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// run every testbench (`*_tb.vhd`, or `[test] testbenches`) and report which failed
+    Test {
+        /// only run the testbenches with this in their name, or matching it
+        /// as a glob like `uart_*`
+        pattern: Option<String>,
+        /// only run the testbenches that failed the last time they ran
+        #[arg(long)]
+        failed: bool,
+        /// run every testbench this many times, each with another seed, to find flaky ones
+        #[arg(long, value_name = "N")]
+        repeat: Option<usize>,
+        /// stop repeating after the first round with a failure
+        #[arg(long, requires = "repeat")]
+        until_failure: bool,
+        /// the seed of the first run, e.g. to reproduce a failure `--repeat` found
+        #[arg(long)]
+        seed: Option<u64>,
+        /// also compare the waveform of every testbench a target sets a
+        /// `golden-vcd` for with it, failing the testbench on a mismatch
+        #[arg(long)]
+        golden: bool,
+        /// also write a report for CI, `junit` or `tap`, to stdout or to a
+        /// file like `junit:build/report.xml`
+        #[arg(long = "report", value_name = "FORMAT[:PATH]", value_parser = test_report::parse)]
+        reports: Vec<test_report::Report>,
+        /// analyze up to this many files and run up to this many testbenches
+        /// at once, `default.jobs` or 1 when not given
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+
+    /// run every testbench like `gb test`, instrumented for coverage, and
+    /// write an html report to build/coverage/html/. needs ghdl's gcc backend
+    /// and lcov
+    Cover,
+
+    /// an interactive prompt taking gb commands, with tab completion
+    /// over targets and entities
+    Shell,
+
+    /// check a target's files for problems gb can spot without ghdl,
+    /// like files not named after the entity they declare
+    Lint {
+        target: Option<String>,
+    },
+
+    /// re-run a target whenever a vhdl source or gb.toml changes
+    Watch {
+        target: Option<String>,
+        /// a shell command to run after every successful run,
+        /// e.g. a plotting script or a notification
+        #[arg(long)]
+        run_on_success: Option<String>,
+        /// clear the screen before every run
+        #[arg(long)]
+        clear: bool,
+        /// how long the sources have to stay untouched before a run starts,
+        /// so that saving several files at once only runs once
+        #[arg(long, value_name = "MILLISECONDS", default_value_t = 200)]
+        debounce: u64,
+    },
+
+    /// export what gb would do, for use outside of gb
+    Export {
+        #[command(subcommand)]
+        export: ExportCommands,
+    },
+
+    /// add, remove and pick the default of gb.toml's targets
+    Target {
+        #[command(subcommand)]
+        command: TargetCommands,
+    },
+
+    /// resolve the dependencies again and rewrite gb.lock with their latest
+    /// commits, only those of the dependencies named when any are
+    Update {
+        dependencies: Vec<String>,
+    },
+
+    /// copy the git dependencies into vendor/, which gb builds them from
+    /// afterwards, without the network
+    Vendor,
+
+    /// compile a vendor's libraries, like xilinx' unisim, with the script
+    /// ghdl ships for it, and build against them from now on
+    VendorLibs {
+        vendor: libraries::Vendor,
+        /// only compile for this standard, 93 or 08, `default.std` when not
+        /// given, and both without that
+        #[arg(long)]
+        std: Option<String>,
+        /// where the vendor's sources are, when the script doesn't find them
+        #[arg(long)]
+        source: Option<PathBuf>,
+        /// where ghdl's vendor scripts are, `lib/ghdl/vendors/` next to ghdl
+        /// when not given
+        #[arg(long)]
+        scripts: Option<PathBuf>,
+    },
+
+    /// manage the gb installation itself
+    #[command(name = "self")]
+    SelfCommand {
+        #[command(subcommand)]
+        command: SelfCommands,
+    },
+
+    /// remove build artifacts: `build/`, and any `*-obj*.cf` or `.o`
+    /// files ghdl left behind in the project root
+    Clean {
+        /// only remove the artifacts of this target, in `build/<target>/`:
+        /// its run log and exported scripts. what's built into a profile's
+        /// directory is shared by every target, and stays
+        #[arg(long)]
+        target: Option<String>,
+    },
+
+    /// check ghdl, the waveform viewers and gb.toml, and say how to fix
+    /// what's wrong with them
+    Doctor,
+
+    /// Initilize a ghdl project with gb as the build system. on a terminal,
+    /// without `--template`, gb asks how to set it up.
+    Init {
+        /// what to start the project with, `empty` only writes a gb.toml
+        #[arg(long, value_enum)]
+        template: Option<scaffold::Template>,
+    },
+
+    /// create a new project directory with a starter entity and testbench
+    New {
+        /// the directory to create, also used to name the target and entity
+        name: String,
+        /// what the starter entity is
+        #[arg(long, value_enum, default_value_t)]
+        template: scaffold::Template,
+    },
+
+    /// add files to a target's `files` in gb.toml, creating the target if
+    /// there's none by that name
+    Add {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// the target to add them to, instead of the one used when none is passed
+        #[arg(long)]
+        target: Option<String>,
     },
 
-    /// Initilize a ghdl project with gb as the build system.
-    Init,
+    /// copy a target's build outputs and a build-info.json to a directory,
+    /// `s3://bucket/prefix` or `ssh://host/path`
+    Publish {
+        target: Option<String>,
+        /// where to publish to, instead of `publish.dest` of the target
+        #[arg(long)]
+        dest: Option<String>,
+    },
+
+    /// inspect gb's settings for this project
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// bundle a target's sources, gb.toml and `[package]` metadata into
+    /// build/package/, for handing in or sharing
+    Package {
+        target: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: package::Format,
+    },
+
+    /// reformat vhdl sources: indentation, keyword case and port alignment.
+    /// formats every vhdl file of the project when no files are given
+    Fmt {
+        files: Vec<PathBuf>,
+        /// only list the files which aren't formatted, failing if there are any
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// normalize the layout of gb.toml: key order, arrays and indentation, keeping comments
+    FmtManifest {
+        /// only check whether gb.toml is formatted, failing if it isn't
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// show where two targets differ, e.g. a fast and a full variant of a design
+    CompareTargets {
+        first: String,
+        second: String,
+        /// also compare their last runs: exit, runtime, assertions and waveform
+        #[arg(long)]
+        results: bool,
+    },
+
+    /// list the targets of gb.toml, marking the one used when none is passed
+    #[clap(alias = "targets")]
+    List {
+        /// print the targets as json
+        #[arg(long)]
+        json: bool,
+        /// print only their names, one per line, like shell completion wants them
+        #[arg(long, conflicts_with = "json")]
+        names: bool,
+    },
+
+    /// print a completion script for the shell, which completes targets from gb.toml
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// write a vhdl_ls.toml with gb's targets and libraries, for rust_hdl's language server
+    LspConfig {
+        /// replace a vhdl_ls.toml gb didn't write
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// lock in the target used when none is passed, for this directory only.
+    /// the choice is stored in `.gb/state`, so gb.toml is left untouched.
+    Use {
+        /// the target to use, prints the current one if omitted
+        target: Option<String>,
+        /// forget the locked target and go back to `default.target`
+        #[arg(long, conflicts_with = "target")]
+        clear: bool,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ExportCommands {
+    /// write `run.sh` and `run.ps1`, which reproduce the exact analyze,
+    /// elaborate and run commands of a target without needing gb
+    Script {
+        target: Option<String>,
+        /// where to put the scripts, `build/<target>/` by default
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// print the files of a target in the order gb analyzes them, with the
+    /// library of each, for vendor tools and other scripts
+    Files {
+        target: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: export::FilesFormat,
+        /// write the list to this file instead of printing it
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCommands {
+    /// print every setting of a target as gb resolves it, and where it
+    /// comes from
+    Show {
+        target: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum VcdCommands {
+    /// write the value changes of signals as csv or json, for scripts and plots
+    Export {
+        /// the dump, e.g. build/debug/top.vcd
+        file: PathBuf,
+        /// the signals to export, as glob patterns like `*.count`
+        #[arg(long, value_delimiter = ',', default_value = "*")]
+        signals: Vec<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: vcd_export::Format,
+        /// write the export to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum TargetCommands {
+    /// add a target to gb.toml
+    Add {
+        name: String,
+        /// the testbench or top the target runs, which is also its first file
+        #[arg(long)]
+        execute: Option<String>,
+        /// the target's files, e.g. `--files src/alu.vhd,src/alu_tb.vhd`
+        #[arg(long, value_delimiter = ',')]
+        files: Vec<String>,
+    },
+    /// remove a target from gb.toml
+    Remove { name: String },
+    /// make a target `default.target`, the one used when none is passed
+    SetDefault { name: String },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SelfCommands {
+    /// replace this gb with the latest release, after verifying its checksum
+    Update {
+        /// only tell whether a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
 }
 
 impl Commands {
     pub fn target(&self) -> Option<&str> {
         match self {
-            Commands::Run { target, vcd: _ } => target.as_ref().map(|i| i.as_ref()),
-            Commands::Compile { target } => target.as_ref().map(|i| i.as_ref()),
-            Commands::Analyze { target } => target.as_ref().map(|i| i.as_ref()),
-            Commands::Wave { target, vcd: _ } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Run { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Compile { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Analyze { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Elab { target } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Synth { target } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Pnr { target } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Flash { target } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Probe { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Grep { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Entities { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Lint { target } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Plan { target, json: _ } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Graph { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Tree { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Doc { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Publish { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Package { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Wave { target, .. } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Export {
+                export: ExportCommands::Script { target, out: _ },
+            } => target.as_ref().map(|i| i.as_ref()),
+            Commands::Export {
+                export: ExportCommands::Files { target, .. },
+            } => target.as_ref().map(|i| i.as_ref()),
             _ => None,
         }
     }
@@ -139,11 +768,16 @@ impl Commands {
 
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
-    let commands = Commands::parse();
-
-    if let Err(e) = validate(&commands) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    let cli = Cli::parse();
+
+    // a panic is reported by color_eyre's hook, but should still exit with its own code
+    let validated = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        validate(&cli.command, &cli.options)
+    }));
+    match validated {
+        Ok(Ok(())) => report::summary(),
+        Ok(Err(e)) => std::process::exit(exit::report(&e, cli.options.error_format)),
+        Err(_) => std::process::exit(exit::INTERNAL),
     }
 
     Ok(())
@@ -159,10 +793,88 @@ fn list_targets(doc: &Document) -> Vec<&str> {
         .collect::<Vec<_>>()
 }
 
-fn validate(commands: &Commands) -> Result<(), GbError> {
-    if let Commands::Init = commands {
-        init()?;
-        return Ok(());
+fn validate(commands: &Commands, options: &GlobalOptions) -> Result<(), GbError> {
+    verbosity::set(options.quiet, options.verbose);
+    if options.deny_warnings {
+        report::deny_warnings();
+    }
+    config::apply_color()?;
+    if let Some(package) = &options.package {
+        workspace::enter(package)?;
+    }
+    // the commands up to reading gb.toml fail on their own terms, whatever
+    // manifest they read says so for itself
+    exit::configured();
+    if let Commands::Init { template } = commands {
+        return scaffold::init(*template);
+    }
+    if let Commands::New { name, template } = commands {
+        return scaffold::new(name, *template);
+    }
+    if let Commands::SelfCommand {
+        command: SelfCommands::Update { check },
+    } = commands
+    {
+        return update::update(*check);
+    }
+    if let Commands::Shell = commands {
+        return shell::shell();
+    }
+    if let Commands::Completions { shell } = commands {
+        return completions::completions(*shell);
+    }
+    if let Commands::Doctor = commands {
+        return doctor::doctor();
+    }
+    if let Commands::Watch {
+        target,
+        run_on_success,
+        clear,
+        debounce,
+    } = commands
+    {
+        return watch::watch(
+            target.as_deref(),
+            run_on_success.as_deref(),
+            *clear,
+            std::time::Duration::from_millis(*debounce),
+            options,
+        );
+    }
+    if let Commands::Fmt { files, check } = commands {
+        // formatting works without a manifest, it only reads `[fmt]` from it
+        let doc = exit::during(exit::Phase::Config, || {
+            std::fs::read_to_string("gb.toml")
+                .ok()
+                .map(|manifest| manifest.parse::<Document>())
+                .transpose()
+                .fatal("failed to parse manifest file")
+        })?;
+        return fmt::fmt(doc.as_ref(), files, *check);
+    }
+    if let Commands::Add { files, target } = commands {
+        return manifest_edit::add(files, target.as_deref());
+    }
+    if let Commands::Target { command } = commands {
+        return manifest_edit::target(command);
+    }
+    if let Commands::Vcd {
+        command:
+            VcdCommands::Export {
+                file,
+                signals,
+                format,
+                out,
+            },
+    } = commands
+    {
+        return vcd_export::export(file, signals, *format, out.as_deref());
+    }
+    if let Commands::FmtManifest { check } = commands {
+        return manifest_fmt::fmt_manifest(*check);
+    }
+    if let Commands::Clean { target } = commands {
+        return clean(target.as_deref());
     }
     if let Commands::Chase { path } = commands {
         let files = tree_sitter::generate_sources_for(path);
@@ -178,26 +890,165 @@ fn validate(commands: &Commands) -> Result<(), GbError> {
         return Ok(());
     }
 
-    // let pwd = current_dir().error("cannot get the current directory")?;
-    let manifest = std::fs::read_to_string("gb.toml")
-        .fatal("manifest file `gb.toml` not found in the current directory")?;
-    let doc = manifest
-        .parse::<Document>()
-        .fatal("failed to parse manifest file")?;
+    exit::configuring();
+    let (mut doc, origins) = manifest::load()?;
+    // before the configuration is merged in, to tell the two apart
+    if let Commands::Config {
+        command: ConfigCommands::Show { target, json },
+    } = commands
+    {
+        exit::configured();
+        return config_show::show(&doc, &origins, target.as_deref(), options, *json);
+    }
+    config::merge_into(&mut doc)?;
+    if doc.get("target").is_none() {
+        if let Some(members) = workspace::members(&doc)? {
+            return Err(workspace::pick_member(&members));
+        }
+    }
     list_targets(&doc);
-    let default_target = doc
-        .as_item()
-        .get("default")
-        .and_then(|default| default.get("target"))
-        .and_then(|default_target| default_target.as_str());
-    let default_vcd_viewer = doc
-        .as_item()
-        .get("default")
-        .and_then(|default| default.get("vcd-viewer"))
-        .and_then(|default_target| default_target.as_str());
+    if let Commands::Use { target, clear } = commands {
+        exit::configured();
+        return use_target(&doc, target.as_deref(), *clear);
+    }
+    if let Commands::List { json, names } = commands {
+        exit::configured();
+        return list::list(&doc, *json, *names);
+    }
+    if let Commands::Vendor = commands {
+        exit::configured();
+        return vendor::vendor(&doc);
+    }
+    if let Commands::LspConfig { force } = commands {
+        exit::configured();
+        return lsp::lsp_config(&doc, *force);
+    }
+    if let Commands::CompareTargets {
+        first,
+        second,
+        results,
+    } = commands
+    {
+        exit::configured();
+        return compare::compare(&doc, first, second, *results);
+    }
+    if std::path::Path::new(".git").exists() {
+        // keep up with whatever new artifacts this version of gb writes
+        gitignore::ensure_ignored(gitignore::GB_ARTIFACTS)?;
+    }
+    let strict = options.strict
+        || doc
+            .get("strict")
+            .and_then(|strict| strict.as_bool())
+            .unwrap_or(false);
+    if strict {
+        report::deny_warnings();
+        check_strict(&doc)?;
+        lock::freeze();
+    }
+    for finding in schema::findings(&doc) {
+        report::emit(finding)?;
+    }
+    simulator::configure(&doc)?;
+    // before `[libraries]` is read, which may list the vendor already
+    if let Commands::VendorLibs {
+        vendor,
+        std,
+        source,
+        scripts,
+    } = commands
+    {
+        let std = match std {
+            Some(std) => Some(std.clone()),
+            None => parse_std(doc.get("default").and_then(|default| default.get("std")))?,
+        };
+        exit::configured();
+        return libraries::compile(
+            *vendor,
+            std.as_deref(),
+            source.as_deref(),
+            scripts.as_deref(),
+        );
+    }
+    let mut build = BuildOptions {
+        std: parse_std(doc.get("default").and_then(|default| default.get("std")))?,
+        library_paths: libraries::paths(&doc)?,
+        ..Default::default()
+    };
+    build.analyze_flags.extend(warnings::analyze_flags(&doc)?);
+    if strict || warnings::werror(&doc)? {
+        build.analyze_flags.push("--warn-error".to_owned());
+    }
+    if let Commands::Cover = commands {
+        profile::select_coverage();
+        build
+            .analyze_flags
+            .extend(coverage::ANALYZE_FLAGS.iter().map(|flag| flag.to_string()));
+        build.elaborate_flags.extend(
+            coverage::ELABORATE_FLAGS
+                .iter()
+                .map(|flag| flag.to_string()),
+        );
+    }
+    profile::check(&doc)?;
+    profile::select(
+        &doc,
+        options.release,
+        options.profile.as_deref(),
+        &mut build,
+    )?;
+    if !options.raw_output {
+        build.output = filter::OutputFilters::parse(&doc)?;
+    }
+    build.output.set_message_format(options.message_format);
+    build.limits = build.limits.read(doc.get("default"))?;
+    build.limits.timeout = options.timeout.or(build.limits.timeout);
+    build.file_naming = naming::Convention::parse(&doc)?;
+    if let Commands::Compile { jobs, .. }
+    | Commands::Run { jobs, .. }
+    | Commands::Analyze { jobs, .. }
+    | Commands::Test { jobs, .. } = commands
+    {
+        build.jobs = match jobs {
+            Some(jobs) => *jobs,
+            None => parse_jobs(doc.get("default").and_then(|default| default.get("jobs")))?,
+        };
+    }
+    if let Commands::Update { dependencies } = commands {
+        exit::configured();
+        return lock::update(&doc, &build, dependencies);
+    }
+    if let Commands::Test {
+        pattern,
+        failed,
+        repeat,
+        until_failure,
+        seed,
+        golden,
+        reports,
+        ..
+    } = commands
+    {
+        let seeds = test::Seeds {
+            first: *seed,
+            repeat: *repeat,
+            until_failure: *until_failure,
+        };
+        let selection = test::Selection {
+            pattern: pattern.clone(),
+            failed: *failed,
+        };
+        exit::configured();
+        return test::run_tests(&doc, &build, seeds, &selection, *golden, reports);
+    }
+    if let Commands::Cover = commands {
+        exit::configured();
+        return coverage::cover(&doc, &build);
+    }
+    let default_target = default_target(&doc)?;
     let target = commands
         .target()
-        .or(default_target)
+        .or(default_target.as_deref())
         .fatal("No target was passed and no default target was set")?;
     let target_info = doc
         .as_item()
@@ -207,19 +1058,99 @@ fn validate(commands: &Commands) -> Result<(), GbError> {
         .fatal(format!(
             "Attempted to run target `{target}` but it was not found in gb.toml"
         ))?;
-    let files = target_info
-        .get("files")
-        .fatal(format!(
-            "a files key is required for every target but it was not supplied for {target}"
-        ))?
-        .as_array()
-        .fatal("the files list must be an array")?
-        .into_iter()
-        .map(|f| f.as_str())
-        .collect::<Option<Vec<&str>>>()
-        .fatal("all the files in the files list, must be listed by their path as a string")?;
+    let target_files = resolve_target_files(target, target_info)?;
+    let files = target_files.iter().map(String::as_str).collect::<Vec<_>>();
 
-    let file_to_execute = target_info.get("execute").and_then(|file| file.as_str());
+    // without an `execute` file, the target runs the entity nothing instantiates
+    let file_to_execute = match target_info.get("execute").and_then(|file| file.as_str()) {
+        Some(file) => Ok(file.to_owned()),
+        None => order::top(target, &target_files),
+    };
+    if let Commands::Package { format, .. } = commands {
+        exit::configured();
+        return package::package(
+            &doc,
+            target,
+            &target_files,
+            file_to_execute.as_deref().ok(),
+            *format,
+        );
+    }
+    // a target can pick its own viewer, e.g. one that understands its wave-format
+    let vcd_viewer = target_info
+        .get("vcd-viewer")
+        .or_else(|| {
+            doc.as_item()
+                .get("default")
+                .and_then(|default| default.get("vcd-viewer"))
+        })
+        .and_then(|viewer| viewer.as_str());
+    if let Some(std) = parse_std(target_info.get("std"))? {
+        build.std = Some(std);
+    }
+    build.library = parse_library(target, target_info.get("library"))?;
+    build.ieee = parse_ieee(target, target_info.get("ieee"))?;
+    build.hooks = hooks::Hooks::from_manifest(target, target_info)?;
+    build.foreign = foreign::Foreign::from_manifest(target, target_info)?;
+    let declares_libraries = doc
+        .get("target")
+        .and_then(|targets| targets.as_table_like())
+        .is_some_and(|targets| {
+            targets
+                .iter()
+                .any(|(_, info)| info.get("library").is_some())
+        });
+    if declares_libraries {
+        // the libraries of other targets end up in the profile's directory,
+        // analysis runs next to it and wouldn't find them otherwise
+        build
+            .analyze_flags
+            .push(format!("-P{}", profile::dir().display()));
+    }
+    build.limits = build.limits.read(Some(target_info))?;
+    // `--timeout` wins over the target's as well
+    build.limits.timeout = options.timeout.or(build.limits.timeout);
+    let mut library_paths = deps::library_paths(&doc, &build)?;
+    for member in workspace::uses(&doc, target_info)? {
+        library_paths.extend(workspace::library(&member, &build)?);
+    }
+    for path in library_paths {
+        if !build.library_paths.contains(&path) {
+            build.library_paths.push(path);
+        }
+    }
+    // after the other libraries, which the targets depended on may use too
+    for path in depends::library_paths(&doc, target, &build)? {
+        if !build.library_paths.contains(&path) {
+            build.library_paths.push(path);
+        }
+    }
+    for (name, value) in scenario::key_values(target_info.get("generics"), "generics")? {
+        build.set_generic(&name, &value);
+    }
+    build.run_flags.extend(sim::run_flags(target, target_info)?);
+    build
+        .analyze_flags
+        .extend(sim::passthrough(target, target_info, "analyze-flags")?);
+    build
+        .elaborate_flags
+        .extend(sim::passthrough(target, target_info, "elab-flags")?);
+    build
+        .run_flags
+        .extend(sim::passthrough(target, target_info, "run-flags")?);
+    build.dump_window = wave::DumpWindow::from_manifest(target, target_info)?;
+    if let Some(path) = &options.warnings_baseline {
+        build.output.collect_warnings();
+        build.warnings_baseline = Some(baseline::Baseline {
+            path: path.clone(),
+            target: target.to_owned(),
+            update: options.update_baseline,
+        });
+        // recording needs every file's warnings, not just those of the changed ones
+        if options.update_baseline {
+            cache::invalidate();
+        }
+    }
 
     let missing_files = files
         .iter()
@@ -237,84 +1168,617 @@ fn validate(commands: &Commands) -> Result<(), GbError> {
         })?;
     }
 
-    let vcd_output_name = target_info
-        .get("vcd-name")
-        .and_then(|i| i.as_str())
-        .map(std::path::PathBuf::from);
+    let manifest_waveform = wave::from_manifest(target, target_info)?;
+    let manifest_synth = synth::Synth::from_manifest(target, target_info)?;
+    let manifest_fpga = fpga::Fpga::from_manifest(&doc)?;
+    exit::configured();
+    orphans::prune(&doc)?;
 
     match commands {
-        Commands::Compile { target: _ } => {
-            analyze_vhdl(files, " [1/2] ")?;
+        Commands::Compile { .. } => {
+            let file_to_exec = file_to_execute?;
+            analyze_vhdl(files, &build, " [1/2] ")?;
 
-            elaborate_vhdl_solution(file_to_execute, " [2/2] ")?;
+            elaborate_vhdl_solution(&file_to_exec, &build, " [2/2] ")?;
         }
         Commands::ListPaths { path } => {
             let srcs = generate_sources_for(path);
 
             dbg!(srcs.iter().map(|src| src.to_str()).collect::<Vec<_>>());
         }
-        Commands::Run { target: _, vcd } => {
-            analyze_vhdl(files, " [1/3] ")?;
+        Commands::Run {
+            vcd,
+            ghw,
+            fst,
+            scenario,
+            generics,
+            sim_args,
+            ..
+        } => {
+            // each scenario keeps its own log, next to the target's
+            let mut log_dir = target.to_owned();
+            if let Some(scenario) = scenario {
+                let scenario = scenario::find(target, target_info, scenario)?;
+                verbosity::step(
+                    "[scenario]",
+                    &format!("Running scenario `{}`", scenario.name),
+                );
+                scenario.apply(&mut build);
+                log_dir = format!("{target}/{}", scenario.name);
+            }
+            for (name, value) in generics {
+                build.set_generic(name, value);
+            }
+            // after gb.toml's `sim` flags, so they can override them
+            build.run_flags.extend(sim_args.iter().cloned());
+            let file_to_exec = file_to_execute?;
+
+            analyze_vhdl(files, &build, " [1/3] ")?;
+
+            elaborate_vhdl_solution(&file_to_exec, &build, " [2/3] ")?;
+
+            execute_vhdl_solution(
+                &log_dir,
+                &file_to_exec,
+                wave::from_flags(vcd.as_ref(), ghw.as_ref(), fst.as_ref()).or(manifest_waveform),
+                &build,
+                " [3/3]",
+            )?;
+        }
+        Commands::Analyze { .. } => {
+            analyze_vhdl(files, &build, " [1/1] ")?;
+        }
+        Commands::Plan { target: _, json } => {
+            let file_to_exec = file_to_execute?;
+            let plan = plan::plan(target, &files, &build, &file_to_exec, manifest_waveform)?;
+            plan::print(&plan, *json)?;
+        }
+        Commands::Publish { target: _, dest } => {
+            let mut outputs = vec![PathBuf::from("build").join(target).join("run.log")];
+            if let Some(waveform) = &manifest_waveform {
+                outputs.push(waveform.built_path());
+            }
+            if let Ok(file_to_execute) = file_to_execute {
+                outputs.extend(executable_path(&file_to_execute)?);
+            }
+            publish::publish(
+                target,
+                target_info,
+                &files,
+                outputs,
+                dest.as_deref(),
+                &build,
+            )?;
+        }
+        Commands::Graph { target: _, format } => {
+            graph::print(target, &files, file_to_execute.ok().as_deref(), *format);
+        }
+        Commands::Tree {
+            target: _,
+            depth,
+            invert,
+        } => match invert {
+            Some(unit) => tree::tree(&files, unit, *depth, true)?,
+            None => {
+                let file_to_exec = file_to_execute?;
+                let top = unit_name(&file_to_exec)?.to_string_lossy();
+                tree::tree(&files, &top, *depth, false)?;
+            }
+        },
+        Commands::Doc {
+            target: _,
+            format,
+            open,
+        } => {
+            doc::doc(target, &files, *format, *open)?;
+        }
+        Commands::Probe {
+            target: _,
+            at,
+            signals,
+            rerun,
+        } => {
+            let vcd = wave::readable(manifest_waveform, target);
+            let dump = vcd.built_path();
+            if *rerun || is_stale(&dump, &files) {
+                let file_to_exec = file_to_execute?;
+                analyze_vhdl(files, &build, " [1/3] ")?;
+                elaborate_vhdl_solution(&file_to_exec, &build, " [2/3] ")?;
+                execute_vhdl_solution(target, &file_to_exec, Some(vcd), &build, " [3/3]")?;
+            }
+            probe::probe(&dump, at, signals)?;
+        }
+        Commands::Grep { pattern, kind, .. } => {
+            grep::grep(&files, kind.as_deref(), pattern)?;
+        }
+        Commands::Entities { json, .. } => {
+            entities::entities(&files, *json)?;
+        }
+        Commands::Lint { .. } => {
+            lint::lint(&doc, &files, build.file_naming)?;
+        }
+        Commands::Elab { target: _ } => {
+            let file_to_exec = file_to_execute?;
+            let work_library = profile::dir().join(build.work_library_file());
+            if is_stale(&work_library, &files) {
+                analyze_vhdl(files, &build, " [1/2] ")?;
+            }
+            elaborate_vhdl_solution(&file_to_exec, &build, " [2/2] ")?;
+
+            let unit = unit_name(&file_to_exec)?.to_string_lossy();
+            match executable_path(&file_to_exec)? {
+                Some(executable) if executable.exists() => println!("{}", executable.display()),
+                None => eprintln!(
+                    "elaborated `{unit}`, {} keeps it in its library rather than an executable",
+                    simulator::get().name()
+                ),
+                Some(_) if ghdl::backend() == Some(ghdl::Backend::Mcode) => eprintln!(
+                    "elaborated `{unit}`, ghdl's mcode backend doesn't produce an executable"
+                ),
+                Some(_) => eprintln!(
+                    "elaborated `{unit}`, but ghdl did not produce an executable (is it using the mcode backend?)"
+                ),
+            }
+        }
+        Commands::Synth { target: _ } => {
+            let synth = manifest_synth.fatal(format!(
+                "target `{target}` has no `[target.{target}.synth]` table, set its `top` there"
+            ))?;
+            // `--synth` reads the libraries ghdl analyzed
+            simulator::require_ghdl("synthesis")?;
+            analyze_vhdl(files, &build, " [1/2] ")?;
+            synth::synth(&synth, &build, " [2/2] ")?;
+        }
+        Commands::Pnr { target: _ } | Commands::Flash { target: _ } => {
+            let fpga = manifest_fpga
+                .fatal("gb.toml has no `[fpga]` table, set the `board` and `constraints` there")?;
+            let synth = manifest_synth.fatal(format!(
+                "target `{target}` has no `[target.{target}.synth]` table, set its `top` there"
+            ))?;
+            simulator::require_ghdl("synthesis")?;
+            if let Commands::Pnr { .. } = commands {
+                analyze_vhdl(files, &build, " [1/4] ")?;
+                fpga::pnr(&fpga, &synth.top, &build, [" [2/4] ", " [3/4] ", " [4/4] "])?;
+            } else {
+                analyze_vhdl(files, &build, " [1/5] ")?;
+                let bitstream =
+                    fpga::pnr(&fpga, &synth.top, &build, [" [2/5] ", " [3/5] ", " [4/5] "])?;
+                fpga::flash(&fpga, &bitstream, " [5/5] ")?;
+            }
+        }
+        Commands::Wave {
+            vcd,
+            ghw,
+            fst,
+            stream,
+            export_svg,
+            export_png,
+            signals,
+            from,
+            to,
+            generics,
+            no_run,
+            force_run,
+            ..
+        } => {
+            for (name, value) in generics {
+                build.set_generic(name, value);
+            }
+            let exports = [
+                export_svg.as_ref().map(|out| (out, render::Format::Svg)),
+                export_png.as_ref().map(|out| (out, render::Format::Png)),
+            ];
+            let exporting = exports.iter().any(Option::is_some);
+            let mut waveform =
+                wave::from_flags(vcd.as_ref(), ghw.as_ref(), fst.as_ref()).or(manifest_waveform);
+            if exporting {
+                waveform = Some(wave::readable(waveform, target));
+            }
+            let reused = waveform
+                .as_ref()
+                .map(wave::Waveform::built_path)
+                .filter(|dump| *no_run && !*force_run && !is_stale(dump, &files));
+            if let Some(dump) = &reused {
+                verbosity::step(
+                    " [1/1] ",
+                    &format!("Up to date, opening {} of the last run", dump.display()),
+                );
+            } else {
+                if *no_run && !*force_run {
+                    eprintln!("the sources changed since the last run, or it left no waveform, simulating again");
+                }
+                let file_to_exec = file_to_execute?;
+                analyze_vhdl(files, &build, " [1/3] ")?;
+
+                elaborate_vhdl_solution(&file_to_exec, &build, " [2/3] ")?;
+
+                if *stream {
+                    let vcd_stream = target_info
+                        .get("vcd-stream")
+                        .or_else(|| {
+                            doc.get("default")
+                                .and_then(|default| default.get("vcd-stream"))
+                        })
+                        .and_then(|command| command.as_str());
+                    let waveform = wave::readable(waveform, target);
+                    return stream::stream(
+                        &file_to_exec,
+                        &waveform,
+                        vcd_viewer,
+                        vcd_stream,
+                        &wave::Gtkwave::from_manifest(target, target_info)?,
+                        &build,
+                        " [3/3]",
+                    );
+                }
+                execute_vhdl_solution(target, &file_to_exec, waveform.clone(), &build, " [3/3]")?;
+            }
+
+            if !exporting {
+                let gtkwave = wave::Gtkwave::from_manifest(target, target_info)?;
+                launch_vcd_viewer(waveform, vcd_viewer, &gtkwave)?;
+            } else if let Some(waveform) = waveform {
+                let dump = waveform.built_path();
+                for (out, format) in exports.into_iter().flatten() {
+                    render::export(&dump, out, format, signals, from.as_deref(), to.as_deref())?;
+                }
+            }
+        }
+        Commands::Export {
+            export: ExportCommands::Script { target: _, out },
+        } => {
+            let file_to_exec = file_to_execute?;
+            let steps = [
+                analyze_command(&files, &build),
+                elaborate_command(&file_to_exec, &build)?,
+                run_command(&file_to_exec, manifest_waveform, &build)?,
+            ];
+            let out = out
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("build").join(target));
+            for script in export::write_scripts(&out, target, &steps)? {
+                eprintln!(
+                    "  {}  {}",
+                    "[export]".blue().bold(),
+                    format!("Wrote {}", script.display()).green().bold()
+                );
+            }
+        }
+        Commands::Export {
+            export: ExportCommands::Files { format, out, .. },
+        } => {
+            // dependencies are analyzed into their own libraries first
+            let mut sources = Vec::new();
+            for dependency in deps::dependencies(&doc)? {
+                let dir = dependency.dir(std::path::Path::new("."))?;
+                for file in deps::library_files(&dependency.name, &dir)? {
+                    sources.push(export::Source {
+                        library: dependency.name.to_lowercase(),
+                        file: file.display().to_string(),
+                    });
+                }
+            }
+            let library = build.library.as_deref().unwrap_or("work");
+            sources.extend(files.iter().map(|file| export::Source {
+                library: library.to_owned(),
+                file: file.to_string(),
+            }));
+            let list = export::files(&sources, *format)?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, list)
+                        .fatal(format!("could not write `{}`", out.display()))?;
+                    eprintln!(
+                        "  {}  {}",
+                        "[export]".blue().bold(),
+                        format!("Wrote {}", out.display()).green().bold()
+                    );
+                }
+                None => print!("{list}"),
+            }
+        }
+        Commands::Init { .. } => unreachable!(),
+        Commands::Chase { path: _ } => unreachable!(),
+        Commands::Use { .. } => unreachable!(),
+        Commands::List { .. } => unreachable!(),
+        Commands::Completions { .. } => unreachable!(),
+        Commands::LspConfig { .. } => unreachable!(),
+        Commands::CompareTargets { .. } => unreachable!(),
+        Commands::New { .. } => unreachable!(),
+        Commands::SelfCommand { .. } => unreachable!(),
+        Commands::Watch { .. } => unreachable!(),
+        Commands::Shell => unreachable!(),
+        Commands::Clean { .. } => unreachable!(),
+        Commands::Doctor => unreachable!(),
+        Commands::Fmt { .. } => unreachable!(),
+        Commands::FmtManifest { .. } => unreachable!(),
+        Commands::Vcd { .. } => unreachable!(),
+        Commands::Add { .. } => unreachable!(),
+        Commands::Target { .. } => unreachable!(),
+        Commands::Update { .. } => unreachable!(),
+        Commands::Vendor => unreachable!(),
+        Commands::VendorLibs { .. } => unreachable!(),
+        Commands::Package { .. } => unreachable!(),
+        Commands::Config { .. } => unreachable!(),
+        Commands::Test { .. } => unreachable!(),
+        Commands::Cover => unreachable!(),
+    }
+
+    Ok(())
+}
 
-            let file_to_exec = elaborate_vhdl_solution(file_to_execute, " [2/3] ")?;
+fn use_target(doc: &Document, target: Option<&str>, clear: bool) -> Result<(), GbError> {
+    if clear {
+        state::set_local_default_target(None)?;
+        eprintln!(
+            "  {}  {}",
+            "[use]".blue().bold(),
+            "Cleared the locked target, falling back to `default.target`."
+                .green()
+                .bold()
+        );
+        return Ok(());
+    }
 
-            execute_vhdl_solution(file_to_exec, vcd.clone().or(vcd_output_name), " [3/3]")?;
-        }
-        Commands::Analyze { target: _ } => {
-            analyze_vhdl(files, " [1/1] ")?;
+    let Some(target) = target else {
+        match state::local_default_target()? {
+            Some(target) => println!("{target}"),
+            None => eprintln!("no target is locked in, `default.target` from gb.toml is used"),
         }
-        Commands::Wave { target: _, vcd } => {
-            let vcd = vcd.clone().or(vcd_output_name);
-            analyze_vhdl(files, " [1/3] ")?;
+        return Ok(());
+    };
 
-            let file_to_exec = elaborate_vhdl_solution(file_to_execute, " [2/3] ")?;
+    if !list_targets(doc).contains(&target) {
+        Err(GbError {
+            message: format!("cannot use target `{target}`, it was not found in gb.toml"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    state::set_local_default_target(Some(target))?;
+    eprintln!(
+        "  {}  {}",
+        "[use]".blue().bold(),
+        format!("Now using target `{target}` in this directory.")
+            .green()
+            .bold()
+    );
+    Ok(())
+}
 
-            execute_vhdl_solution(file_to_exec, vcd.clone(), " [3/3]")?;
+/// the target to use when none was passed: the one picked with `gb use`,
+/// otherwise `default.target`
+fn default_target(doc: &Document) -> Result<Option<String>, GbError> {
+    if let Some(target) = shell::session_target() {
+        return Ok(Some(target));
+    }
+    if let Some(target) = state::local_default_target()? {
+        return Ok(Some(target));
+    }
+    Ok(doc
+        .get("default")
+        .and_then(|default| default.get("target"))
+        .and_then(|target| target.as_str())
+        .map(ToOwned::to_owned))
+}
 
-            launch_vcd_viewer(vcd, default_vcd_viewer)?;
+/// the files of a target, in the order they are analyzed. `files = "auto"`
+/// discovers them by following the components used from the `execute` file.
+/// a pattern like `src/**/*.vhd` in the list stands for the files it matches,
+/// and the target's `exclude` patterns take files back out. listed files are
+/// put in dependency order, so they don't have to be listed bottom-up.
+fn resolve_target_files(
+    target: &str,
+    target_info: &toml_edit::Item,
+) -> Result<Vec<String>, GbError> {
+    let files = target_info.get("files").fatal(format!(
+        "a files key is required for every target but it was not supplied for {target}"
+    ))?;
+
+    if files.as_str() == Some("auto") {
+        let execute = target_info
+            .get("execute")
+            .and_then(|file| file.as_str())
+            .fatal(format!(
+                "`files = \"auto\"` starts from the `execute` file, but {target} doesn't set one"
+            ))?;
+        if !std::path::Path::new(execute).exists() {
+            Err(GbError {
+                message: format!("the execute file `{execute}` of {target} was not found"),
+                level: Level::Fatal,
+                source: None,
+            })?;
         }
-        Commands::Init => init()?,
-        Commands::Chase { path: _ } => unreachable!(),
+        return Ok(tree_sitter::generate_ordered_sources_for(execute)
+            .into_iter()
+            .map(|file| file.display().to_string())
+            .collect());
     }
 
-    Ok(())
+    let files = files
+        .as_array()
+        .fatal("the files list must be an array, or \"auto\"")?
+        .into_iter()
+        .map(|f| f.as_str().map(ToOwned::to_owned))
+        .collect::<Option<Vec<String>>>()
+        .fatal("all the files in the files list, must be listed by their path as a string")?;
+    let mut expanded = Vec::new();
+    for entry in files {
+        if sources::is_glob(&entry) {
+            expanded.extend(sources::expand(&entry)?);
+        } else {
+            expanded.push(entry);
+        }
+    }
+    let exclude = match target_info.get("exclude") {
+        Some(exclude) => exclude
+            .as_array()
+            .and_then(|patterns| {
+                patterns
+                    .iter()
+                    .map(|pattern| pattern.as_str())
+                    .collect::<Option<Vec<_>>>()
+            })
+            .fatal(format!(
+                "`exclude` of {target} must be an array of patterns, like `exclude = [\"src/old/*\"]`"
+            ))?
+            .into_iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).fatal(format!("`{pattern}` is not a valid pattern"))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![],
+    };
+    let files = expanded.into_iter().filter(|file| {
+        let normalized = sources::normalize(file.as_ref());
+        !exclude
+            .iter()
+            .any(|pattern| pattern.matches_path(&normalized))
+    });
+
+    // files declaring vhdl-2008 contexts, which are analyzed before everything else
+    let mut resolved = match target_info.get("contexts") {
+        Some(contexts) => contexts
+            .as_array()
+            .fatal(format!("`contexts` of {target} must be an array of paths"))?
+            .into_iter()
+            .map(|f| f.as_str().map(ToOwned::to_owned))
+            .collect::<Option<Vec<String>>>()
+            .fatal(
+                "all the files in the contexts list, must be listed by their path as a string",
+            )?,
+        None => vec![],
+    };
+    for file in files {
+        let normalized = sources::normalize(file.as_ref());
+        if !resolved
+            .iter()
+            .any(|context| sources::normalize(context.as_ref()) == normalized)
+        {
+            resolved.push(file);
+        }
+    }
+    Ok(order::order(resolved))
 }
 
-fn launch_vcd_viewer(
-    vcd: Option<std::path::PathBuf>,
-    default_vcd_viewer: Option<&str>,
-) -> Result<(), GbError> {
-    if vcd.is_none() {
+/// strict mode is for courses and CI: every target must be fully specified,
+/// and every vhdl file in the project has to belong to some target.
+fn check_strict(doc: &Document) -> Result<(), GbError> {
+    let mut violations = Vec::new();
+    let mut listed = std::collections::HashSet::new();
+
+    for target in list_targets(doc) {
+        let target_info = &doc["target"][target];
+        if target_info
+            .get("execute")
+            .and_then(|value| value.as_str())
+            .is_none()
+        {
+            violations.push(format!("target `{target}` does not set `execute`"));
+        }
+        if !matches!(wave::from_manifest(target, target_info), Ok(Some(_))) {
+            violations.push(format!(
+                "target `{target}` does not set `wave-name` or `vcd-name`"
+            ));
+        }
+        let globbed = target_info
+            .get("files")
+            .and_then(|files| files.as_array())
+            .is_some_and(|files| {
+                files
+                    .iter()
+                    .any(|file| file.as_str().is_some_and(sources::is_glob))
+            });
+        if globbed {
+            violations.push(format!(
+                "target `{target}` lists its files with a pattern instead of one by one"
+            ));
+        }
+        let files = resolve_target_files(target, target_info).unwrap_or_default();
+        listed.extend(files.iter().map(|file| sources::normalize(file.as_ref())));
+    }
+
+    for source in sources::find_vhdl_sources(".".as_ref()) {
+        if !listed.contains(&source) {
+            violations.push(format!("`{}` is not used by any target", source.display()));
+        }
+    }
+
+    if !violations.is_empty() {
+        eprintln!("The manifest does not satisfy strict mode");
+        for (pos, violation) in violations.iter().enumerate() {
+            eprintln!("  {}. {violation}", pos + 1)
+        }
         Err(GbError {
-            message: "vcd-name not set in target for toml. Cannot launch vcd viewer".to_owned(),
+            message: "strict mode is enabled and the manifest is not fully specified".to_owned(),
             level: Level::Fatal,
             source: None,
         })?;
     }
-    if default_vcd_viewer.is_none() {
+    Ok(())
+}
+
+/// a viewer like `gtkwave` or `surfer` gets the dump as its only argument,
+/// a command template like `"myviewer --reload {file}"` gets it wherever
+/// `{file}` is.
+fn viewer_command(viewer: &str, file: &std::path::Path) -> Result<Command, GbError> {
+    let file = file.to_string_lossy();
+    let mut words = viewer.split_whitespace();
+    let program = words
+        .next()
+        .fatal("`vcd-viewer` is empty, set it to a viewer like \"gtkwave\"")?;
+
+    let mut command = Command::new(program);
+    let mut mentions_file = false;
+    for word in words {
+        mentions_file |= word.contains("{file}");
+        command.arg(word.replace("{file}", &file));
+    }
+    if !mentions_file {
+        command.arg(file.as_ref());
+    }
+    Ok(command)
+}
+
+fn launch_vcd_viewer(
+    waveform: Option<wave::Waveform>,
+    vcd_viewer: Option<&str>,
+    gtkwave: &wave::Gtkwave,
+) -> Result<(), GbError> {
+    if waveform.is_none() {
         Err(GbError {
-            message: "top level `default.vcd-viewer` not set in target for toml. Cannot launch vcd viewer"
-                .to_owned(),
+            message:
+                "neither wave-name nor vcd-name is set in target for toml. Cannot launch vcd viewer"
+                    .to_owned(),
             level: Level::Fatal,
             source: None,
         })?;
     }
-    if default_vcd_viewer != Some("gtkwave") {
+    if vcd_viewer.is_none() {
         Err(GbError {
-            message: "The only supported wave viewer is gtkwave. Please set `default.vcd-viewer = \"gtkwave\"`"
+            message: "neither the target's `vcd-viewer` nor `default.vcd-viewer` is set in toml. Cannot launch vcd viewer"
                 .to_owned(),
             level: Level::Fatal,
             source: None,
         })?;
     }
+    let file = waveform.unwrap().built_path();
+    let mut command = viewer_command(vcd_viewer.unwrap(), &file)?;
+    let viewer = command.get_program().to_string_lossy().into_owned();
+    if wave::is_gtkwave(&viewer) {
+        command.args(gtkwave.args()?);
+    } else if !gtkwave.is_empty() {
+        report::emit(report::warning(format!(
+            "`gtkw` and `gtkwave-script` are for gtkwave, `{viewer}` is opened without them"
+        )))?;
+    }
     eprintln!("launching waveform viewer");
 
-    Command::new("gtkwave")
-        .arg(PathBuf::from("build/root/").join(vcd.unwrap()))
+    command
         .spawn()
-        .fatal("could not create gtkwave")?
+        .fatal(format!("could not create {viewer}"))?
         .wait()
-        .fatal("failed to await gtkwave")?;
+        .fatal(format!("failed to await {viewer}"))?;
 
     Ok(())
 }
@@ -337,8 +1801,7 @@ fn get_macos_version() -> String {
         let str = String::from_utf8_lossy(output.stdout.as_slice());
 
         str.lines()
-            .filter(|line| line.starts_with("ProductVersion:"))
-            .next()
+            .find(|line| line.starts_with("ProductVersion:"))
             .map(|line| line.trim_start_matches("ProductVersion:").trim())
             .expect("failed to parse macos version")
             .to_owned()
@@ -346,100 +1809,408 @@ fn get_macos_version() -> String {
         panic!("could not access macos version")
     }
 }
+
+/// the vhdl standards ghdl understands, as passed to `--std=`
+const VHDL_STANDARDS: &[&str] = &["87", "93", "93c", "00", "02", "08", "19"];
+
+/// how ghdl gets invoked for a target. every step of the build gets these,
+/// since ghdl refuses to mix, say, units analyzed for different standards.
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// the vhdl standard, ghdl defaults to `93c` when it isn't set
+    pub std: Option<String>,
+    /// the library the target is analyzed into, `work` when it isn't set
+    pub library: Option<String>,
+    /// which ieee library ghdl provides, its own default when it isn't set
+    pub ieee: Option<Ieee>,
+    /// flags only passed when analyzing
+    pub analyze_flags: Vec<String>,
+    /// flags only passed when elaborating
+    pub elaborate_flags: Vec<String>,
+    /// flags passed to the simulation, after the unit name
+    pub run_flags: Vec<String>,
+    /// top level generics of the simulation, passed as `-gNAME=VALUE`
+    pub generics: Vec<(String, String)>,
+    /// environment variables the simulation runs with
+    pub run_env: Vec<(String, String)>,
+    /// how many ghdl processes may analyze at once, one when it isn't set
+    pub jobs: usize,
+    /// what of ghdl's output is shown, per phase
+    pub output: filter::OutputFilters,
+    /// rlimits the simulation runs under, and its timeout
+    pub limits: limits::Limits,
+    /// the warnings analysis is allowed to give
+    pub warnings_baseline: Option<baseline::Baseline>,
+    /// how files have to be named after their entity
+    pub file_naming: naming::Convention,
+    /// the part of the simulation kept in its vcd
+    pub dump_window: wave::DumpWindow,
+    /// directories of other libraries, like those of workspace members or
+    /// `[libraries] paths`, passed as `-P`
+    pub library_paths: Vec<PathBuf>,
+    /// commands the target runs around the steps of its build
+    pub hooks: hooks::Hooks,
+    /// C code the simulation is linked with, or loads as vpi plugins
+    pub foreign: foreign::Foreign,
+    /// where elaboration and the simulation run, `build/<profile>/` when it
+    /// isn't set
+    pub workdir: Option<PathBuf>,
+}
+
+impl BuildOptions {
+    /// sets a generic, replacing an earlier value for it
+    pub fn set_generic(&mut self, name: &str, value: &str) {
+        // vhdl names are case insensitive
+        self.generics
+            .retain(|(set, _)| !set.eq_ignore_ascii_case(name));
+        self.generics.push((name.to_owned(), value.to_owned()));
+    }
+
+    /// flags that every ghdl invocation needs to agree on
+    fn common_flags(&self) -> Vec<String> {
+        self.std
+            .iter()
+            .map(|std| format!("--std={std}"))
+            .chain(
+                self.library
+                    .iter()
+                    .map(|library| format!("--work={library}")),
+            )
+            .chain(self.ieee.map(|ieee| ieee.flag().to_owned()))
+            .chain(
+                self.library_paths
+                    .iter()
+                    .map(|path| format!("-P{}", path.display())),
+            )
+            .collect()
+    }
+
+    /// the directory elaboration and the simulation run in
+    pub fn run_dir(&self) -> PathBuf {
+        self.workdir.clone().unwrap_or_else(profile::dir)
+    }
+
+    /// the file analysis writes for the library being analyzed into, e.g.
+    /// ghdl's `work-obj08.cf`
+    fn work_library_file(&self) -> String {
+        simulator::get().library_file(self)
+    }
+}
+
+/// `--generic WIDTH=8`
+fn parse_generic(generic: &str) -> Result<(String, String), String> {
+    match generic.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_owned(), value.trim().to_owned()))
+        }
+        _ => Err(format!("expected NAME=VALUE, got `{generic}`")),
+    }
+}
+
+/// a dotted generic like `dut.fifo.DEPTH` is one of an instance below the
+/// top entity. nvc overrides it, ghdl's `-g` only reaches the top entity's
+/// generics and would silently ignore it.
+fn check_generics(generics: &[(String, String)]) -> Result<(), GbError> {
+    if simulator::get().overrides_nested_generics() {
+        return Ok(());
+    }
+    if let Some((name, _)) = generics.iter().find(|(name, _)| name.contains('.')) {
+        let generic = name.rsplit('.').next().unwrap_or(name);
+        Err(GbError {
+            message: format!(
+                "`{name}` is a generic below the top entity, which ghdl can't override. \
+                 add a `{generic}` generic to the top entity and pass it down, or use \
+                 `simulator = \"nvc\"`, which can"
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+/// reads a `library = "my_lib"` key, which has to be a vhdl identifier
+fn parse_library(target: &str, item: Option<&toml_edit::Item>) -> Result<Option<String>, GbError> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let library = item
+        .as_str()
+        .fatal(format!("`library` of {target} must be a string"))?;
+    let is_identifier = library.starts_with(|c: char| c.is_ascii_alphabetic())
+        && library
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        Err(GbError {
+            message: format!(
+                "`library = \"{library}\"` of {target} is not a valid vhdl library name"
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    // vhdl names are case insensitive, ghdl names the library file in lowercase
+    Ok(Some(library.to_lowercase()))
+}
+
+/// reads `default.jobs`, 1 when it isn't set
+fn parse_jobs(item: Option<&toml_edit::Item>) -> Result<usize, GbError> {
+    let Some(item) = item else {
+        return Ok(1);
+    };
+    item.as_integer()
+        .filter(|jobs| *jobs > 0)
+        .and_then(|jobs| usize::try_from(jobs).ok())
+        .fatal("`default.jobs` must be a number of jobs, at least 1")
+}
+
+/// `ieee = "synopsys"`, the ieee library a target is built against. old
+/// course material tends to need synopsys' `std_logic_arith` and friends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ieee {
+    /// the standard packages along with synopsys' non-standard ones
+    Synopsys,
+    Standard,
+    /// no ieee library at all
+    None,
+}
+
+impl Ieee {
+    /// the ghdl option picking it, `--ieee=synopsys` is deprecated
+    fn flag(self) -> &'static str {
+        match self {
+            Ieee::Synopsys => "-fsynopsys",
+            Ieee::Standard => "--ieee=standard",
+            Ieee::None => "--ieee=none",
+        }
+    }
+}
+
+fn parse_ieee(target: &str, item: Option<&toml_edit::Item>) -> Result<Option<Ieee>, GbError> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    match item.as_str() {
+        Some("synopsys") => Ok(Some(Ieee::Synopsys)),
+        Some("standard") => Ok(Some(Ieee::Standard)),
+        Some("none") => Ok(Some(Ieee::None)),
+        _ => Err(GbError {
+            message: format!("`ieee` of {target} must be \"synopsys\", \"standard\" or \"none\""),
+            level: Level::Fatal,
+            source: None,
+        }),
+    }
+}
+
+/// reads a `std = "08"` key, which has to be one of the standards ghdl knows
+fn parse_std(item: Option<&toml_edit::Item>) -> Result<Option<String>, GbError> {
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let std = item
+        .as_str()
+        .fatal("`std` must be a string, like `std = \"08\"`")?;
+    if !VHDL_STANDARDS.contains(&std) {
+        Err(GbError {
+            message: format!(
+                "unknown vhdl standard `{std}`, expected one of {}",
+                VHDL_STANDARDS.join(", ")
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(Some(std.to_owned()))
+}
+
+/// linker flags the elaboration step needs on the current platform
+fn platform_elaborate_args() -> Vec<String> {
+    #[cfg(target_os = "macos")]
+    {
+        vec![format!("-Wl,-mmacosx-version-min={}", get_macos_version())]
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        vec![]
+    }
+}
+
+/// the name of the design unit ghdl knows the file to execute by
+fn unit_name(file_to_exec: &str) -> Result<&std::ffi::OsStr, GbError> {
+    std::path::Path::new(file_to_exec)
+        .file_stem()
+        .fatal("could not get base filename")
+}
+
+fn analyze_command(files: &[&str], build: &BuildOptions) -> Command {
+    simulator::get().analyze(files, build, None)
+}
+
+fn elaborate_command(file_to_exec: &str, build: &BuildOptions) -> Result<Command, GbError> {
+    simulator::get().elaborate(&unit_name(file_to_exec)?.to_string_lossy(), build)
+}
+
+fn run_command(
+    file_to_exec: &str,
+    waveform: Option<wave::Waveform>,
+    build: &BuildOptions,
+) -> Result<Command, GbError> {
+    check_generics(&build.generics)?;
+    let unit = unit_name(file_to_exec)?.to_string_lossy();
+    let mut command = simulator::get().run(&unit, waveform.as_ref(), build)?;
+    command.envs(build.run_env.iter().cloned());
+    build.limits.apply(&mut command);
+    Ok(command)
+}
+
+/// the transcript of the run ends up in `build/<log_dir>/run.log`
 fn execute_vhdl_solution(
+    log_dir: &str,
     file_to_exec: &str,
-    vcd: Option<std::path::PathBuf>,
+    waveform: Option<wave::Waveform>,
+    build: &BuildOptions,
     step: &str,
 ) -> Result<(), GbError> {
-    eprintln!(
-        "  {}  {}",
-        step.blue().bold(),
-        "Executing Solution...".green().bold()
-    );
-    let child = Command::new("ghdl")
-        .arg("-r")
-        .current_dir("build/root/")
-        .arg(
-            std::path::Path::new(file_to_exec)
-                .file_stem()
-                .fatal("could not get base filename")?,
-        )
-        .args(match vcd {
-            Some(vcd) => [format!("--vcd={}", vcd.to_string_lossy())].to_vec(),
-            None => vec![],
+    verbosity::step(step, "Executing Solution...");
+    build.hooks.run(hooks::Stage::PreRun, build)?;
+    let mut command = run_command(file_to_exec, waveform.clone(), build)?;
+    let log = PathBuf::from("build").join(log_dir).join("run.log");
+    exit::during(exit::Phase::Simulation, || {
+        let transcript =
+            transcript::run_teed(&mut command, &log, Some(&build.output.run), &build.limits)?;
+        if transcript.timed_out {
+            exit::during(exit::Phase::Timeout, || {
+                Err(limits::exceeded(build.limits.timed_out(), &log))
+            })?;
+        }
+        if transcript.status.success() {
+            return Ok(());
+        }
+        // a failed assertion is the design's fault, anything else ghdl's
+        // runtime stopped on, like a bound check or a limit, is told apart
+        // for scripts
+        if transcript.lines.iter().any(|line| test::is_failure(line)) {
+            Err(GbError {
+                message: format!(
+                    "the simulation failed an assertion, see `{}`",
+                    log.display()
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        if let Some(explanation) = build.limits.explain(&transcript.status, &transcript.lines) {
+            exit::during(exit::Phase::Runtime, || {
+                Err(limits::exceeded(explanation, &log))
+            })?;
+        }
+        exit::during(exit::Phase::Runtime, || {
+            Err(GbError {
+                message: format!(
+                    "the simulation did not finish successfully ({}), see `{}`",
+                    transcript.status,
+                    log.display()
+                ),
+                level: Level::Fatal,
+                source: None,
+            })
         })
-        .spawn()
-        .fatal("couldn't spawn ghdl run subprocess, is ghdl installed?")?;
-    await_vhdl_process(child, "couldn't await ghdl run subprocess, is ghdl installed correctly, and do you have run permissions?")?;
-    Ok(())
+    })?;
+    if let Some(waveform) = waveform {
+        build.dump_window.apply(&waveform)?;
+    }
+    build.hooks.run(hooks::Stage::PostRun, build)
 }
 
-fn elaborate_vhdl_solution<'s>(
-    file_to_execute: Option<&'s str>,
-    step: &str,
-) -> Result<&'s str, GbError> {
-    eprintln!(
-        "  {}  {}",
-        step.blue().bold(),
-        "Elaborating Solution...".green().bold()
-    );
-    let file_to_exec = file_to_execute.fatal("must have a file chosen to execute in order to elaborate. Please set `execute = \"<YOUR_FILE>\" in gb.toml")?;
+/// where the simulator leaves the executable produced by elaborating
+/// `file_to_exec`, if it makes one
+fn executable_path(file_to_exec: &str) -> Result<Option<PathBuf>, GbError> {
+    let unit = unit_name(file_to_exec)?.to_string_lossy();
+    Ok(simulator::get().executable(&unit))
+}
 
-    let args = if cfg!(target_os = "macos") {
-        vec![
-            "-e".to_owned(),
-            format!("-Wl,-mmacosx-version-min={}", get_macos_version()),
-        ]
-    } else {
-        vec!["-e".to_owned()]
+/// whether the manifest or any of the files was modified since `artifact`
+/// was last written, or if it was never produced in the first place.
+fn is_stale(artifact: &std::path::Path, files: &[&str]) -> bool {
+    let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|m| m.modified());
+    let Ok(produced) = modified(artifact) else {
+        return true;
     };
-    let child = Command::new("ghdl")
-        .args(&args)
-        .arg(
-            std::path::Path::new(file_to_exec)
-                .file_stem()
-                .fatal("could not get base filename")?,
-        )
-        .current_dir("build/root/")
-        .spawn()
-        .fatal("couldn't spawn ghdl elaborate subprocess, is ghdl installed?")?;
-    await_vhdl_process(child, "couldn't await ghdl elaborate subprocess, is ghdl installed correctly, and do you have run permissions?")?;
+    files
+        .iter()
+        .chain(&["gb.toml"])
+        .any(|file| modified(file.as_ref()).map_or(true, |changed| changed > produced))
+}
 
-    eprintln!(
-        "  {}  {}",
-        step.blue().bold(),
-        "Successfully Elaborated.".green().bold()
-    );
-    Ok(file_to_exec)
+/// the file to execute, or a helpful error explaining how to set one
+fn elaborate_vhdl_solution(
+    file_to_exec: &str,
+    build: &BuildOptions,
+    step: &str,
+) -> Result<(), GbError> {
+    verbosity::step(step, "Elaborating Solution...");
+    build.hooks.run(hooks::Stage::PreElaborate, build)?;
+
+    let mut command = elaborate_command(file_to_exec, build)?;
+    exit::during(exit::Phase::Elaboration, || {
+        build.foreign.compile()?;
+        let child = filter::spawn(&mut command, &build.output.elaborate)
+            .fatal("couldn't spawn ghdl elaborate subprocess, is ghdl installed?")?;
+        await_vhdl_process(child, "couldn't await ghdl elaborate subprocess, is ghdl installed correctly, and do you have run permissions?")
+    })?;
+
+    verbosity::step(step, "Successfully Elaborated.");
+    build.hooks.run(hooks::Stage::PostElaborate, build)
 }
 
-fn analyze_vhdl(files: Vec<&str>, steps: &str) -> Result<(), GbError> {
-    eprintln!(
-        "  {}  {}",
-        steps.blue().bold(),
-        "Analyzing Solution...".green().bold()
-    );
-    compile_vhd_files(files)?;
-    eprintln!(
-        "  {}  {}",
-        steps.blue().bold(),
-        "Successfully Analyzed.".green().bold()
-    );
-    Ok(())
+fn analyze_vhdl(files: Vec<&str>, build: &BuildOptions, steps: &str) -> Result<(), GbError> {
+    verbosity::step(steps, "Analyzing Solution...");
+    // a hook generating sources runs before gb looks at what changed
+    build.hooks.run(hooks::Stage::PreAnalyze, build)?;
+    let stale = cache::stale_files(&files, build)?;
+    naming::warn(&naming::mismatches(&stale, build.file_naming))?;
+    if stale.is_empty() {
+        verbosity::step(steps, "Up to date.");
+        return build.hooks.run(hooks::Stage::PostAnalyze, build);
+    }
+
+    // ghdl starts a fresh work library in the project root, so bring back the
+    // units analyzed earlier, otherwise they'd be lost when it's moved back.
+    simulator::get().restore()?;
+    let analyzed = exit::during(exit::Phase::Analysis, || {
+        if build.jobs > 1 && simulator::get().analyzes_in_parallel() {
+            parallel::analyze(stale, build)
+        } else {
+            compile_vhd_files(stale, build)
+        }
+    });
+    if let Err(err) = analyzed {
+        cache::invalidate();
+        return Err(err);
+    }
+    cache::record(&files, build)?;
+    if let Some(baseline) = &build.warnings_baseline {
+        if let Err(err) = exit::during(exit::Phase::Analysis, || baseline.check()) {
+            // analyze these files again next time, so the new warnings show up again
+            cache::invalidate();
+            return Err(err);
+        }
+    }
+
+    verbosity::step(steps, "Successfully Analyzed.");
+    build.hooks.run(hooks::Stage::PostAnalyze, build)
 }
 
-fn compile_vhd_files(files: Vec<&str>) -> Result<(), GbError> {
-    let child = Command::new("ghdl")
-        .arg("-a")
-        .args(&files)
-        .spawn()
+fn compile_vhd_files(files: Vec<&str>, build: &BuildOptions) -> Result<(), GbError> {
+    let child = filter::spawn(&mut analyze_command(&files, build), &build.output.analyze)
         .fatal("couldn't spawn ghdl subprocess")?;
     {
         let mut child = child;
         let waiting = child
             .wait()
             .fatal("couldn't await ghdl analyze subprocess, is ghdl installed?")?;
-        cleanup_build_dir(files)?;
+        simulator::get().collect(&files)?;
         Ok(if !waiting.success() {
             Err(GbError {
                 message: "GHDL didn't compile successfully.".to_owned(),
@@ -453,31 +2224,15 @@ fn compile_vhd_files(files: Vec<&str>) -> Result<(), GbError> {
 }
 
 fn cleanup_build_dir(files: Vec<&str>) -> Result<(), GbError> {
-    move_work_obj93_to_build_directory()?;
-    move_artifacts_to_build_directory(files)?;
-    Ok(())
-}
-
-fn move_artifacts_to_build_directory(files: Vec<&str>) -> Result<(), GbError> {
-    std::fs::create_dir_all("build/root/").fatal("could not create build directory")?;
-    for file_str in files {
-        let file = std::path::Path::new(file_str);
-
-        let stem = file
-            .file_stem()
-            .fatal(format!("could not get file stem for {file_str}"))?;
-        // TODO: Might need to not add the .o on some systems.
-
-        let path = std::path::PathBuf::from(stem).with_extension("o");
-
-        std::fs::rename(&path, std::path::PathBuf::from("build/root/").join(&path)).fatal(
-            format!("could not move generated build artifact `{path:?}` to build dir"),
-        )?;
+    // whichever standard ghdl went by, it's in the name of the library
+    for work_library in artifacts::work_libraries(std::path::Path::new(".")) {
+        move_work_library_to_build_directory(&work_library)?;
     }
+    artifacts::move_objects(&files)?;
     Ok(())
 }
 
-fn move_work_obj93_to_build_directory() -> Result<(), GbError> {
+fn move_work_library_to_build_directory(work_library: &str) -> Result<(), GbError> {
     // this method is actually a little more complicated than you might *initially* think, since
     // we need to "fix-up" some of the file paths inside of the file, so that we can still compile
     // the sources. The goal of gb is to be opinionated and flexible while hiding away the details
@@ -485,77 +2240,122 @@ fn move_work_obj93_to_build_directory() -> Result<(), GbError> {
     // Like for instance in C, most of the time it's build, link, run. But generally we just think of build
     // and run. It's like that.
 
-    let file = std::fs::read_to_string("work-obj93.cf").fatal("could not load work-obj93.cf, which is a necessary compliation artifact to move it to the build dir")?;
+    let file = std::fs::read_to_string(work_library).fatal(format!("could not load {work_library}, which is a necessary compliation artifact to move it to the build dir"))?;
 
-    let mut lines = file.lines().map(Cow::Borrowed).collect::<Vec<Cow<str>>>();
+    let full = artifacts::library_for_build_dir(&file);
 
-    const PREFIX: &str = "file . \"";
-    for line in &mut lines {
-        if line.starts_with(PREFIX) {
-            let mut string: String = line.clone().into_owned();
+    std::fs::create_dir_all(profile::dir())
+        .fatal("could not create the build directory, but it is necessary to run ghdl")?;
 
-            string.insert_str(PREFIX.len(), "../../");
+    std::fs::write(profile::dir().join(work_library), full).fatal(format!(
+        "could not move modified {work_library}, but it is necessary to build ghdl"
+    ))?;
 
-            *line = Cow::Owned(string);
-        }
-    }
+    std::fs::remove_file(work_library).fatal(format!("could not remove {work_library}"))?;
 
-    let full = lines.join("\n");
-
-    std::fs::create_dir_all("build/root/")
-        .fatal("could not create the build directory, but it is necessary to run ghdl")?;
+    Ok(())
+}
 
-    std::fs::write("build/root/work-obj93.cf", full)
-        .fatal("could not move modified work-obj93.cf, but it is necessary to build ghdl")?;
+/// the reverse of `move_work_library_to_build_directory`, leaving the build copy in place
+fn restore_work_library_from_build_directory(work_library: &str) -> Result<(), GbError> {
+    let built = profile::dir().join(work_library);
+    if !built.exists() {
+        return Ok(());
+    }
 
-    std::fs::remove_file("work-obj93.cf").fatal("could not remove work-obj93.cf")?;
+    let file = std::fs::read_to_string(&built).fatal(format!("could not load {built:?}"))?;
 
+    std::fs::write(work_library, artifacts::library_for_root(&file))
+        .fatal(format!("could not restore {work_library} for analysis"))?;
     Ok(())
 }
 
-fn init() -> Result<(), GbError> {
-    let exists = PathBuf::from_str("gb.toml").unwrap().exists();
-
-    if exists {
-        eprintln!("already inited!");
-        return Ok(());
+/// `target` must be a target of gb.toml, and nothing that leads out of `build/`
+fn check_clean_target(target: &str) -> Result<(), GbError> {
+    if target.is_empty()
+        || target.contains(['/', '\\'])
+        || target.contains("..")
+        || std::path::Path::new(target).is_absolute()
+    {
+        Err(GbError {
+            message: format!("`{target}` can't be the name of a target"),
+            level: Level::Fatal,
+            source: None,
+        })?;
     }
+    let (doc, _) = exit::during(exit::Phase::Config, manifest::load)?;
+    if doc
+        .get("target")
+        .and_then(|targets| targets.get(target))
+        .is_none()
+    {
+        Err(GbError {
+            message: format!("there is no target named `{target}` in gb.toml"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    // `build/debug/` is the debug profile's, whatever target is named like it
+    profile::check_target_name(&doc, target)
+}
+
+fn clean(target: Option<&str>) -> Result<(), GbError> {
+    let mut removed = Vec::new();
 
-    let mut ignore = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(".gitignore")
-        .fatal("could not create .gitignore file")?;
-
-    ignore
-        .write_all("/build".as_bytes())
-        .fatal("could not write '/build' into .gitignore")?;
-
-    std::fs::write(
-        "gb.toml",
-        r#"
-    default.target = "default-target"
-    default.vcd-viewer = "gtkwave"
-    
-    [target.default-target]
-    files = []
-    
-    # execute = "your-file-to-execute"
-    # vcd-name = "your-vcd-name.vcd"
-    "#,
-    )
-    .fatal("could not write sample gb.toml")?;
-
-    std::fs::create_dir_all("src/").fatal("could not create src/ dir")?;
+    match target {
+        Some(target) => {
+            check_clean_target(target)?;
+            removed.push(PathBuf::from("build").join(target));
+        }
+        None => {
+            removed.push(PathBuf::from("build"));
+            // stray artifacts from an analysis that was interrupted before gb could move them
+            for entry in std::fs::read_dir(".")
+                .fatal("could not read the current directory")?
+                .flatten()
+            {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let is_work_library = name.contains("-obj") && name.ends_with(".cf");
+                let is_object = path.extension().is_some_and(|ext| ext == "o");
+                if path.is_file() && (is_work_library || is_object) {
+                    removed.push(path.strip_prefix(".").unwrap_or(&path).to_owned());
+                }
+            }
+        }
+    }
+    removed.retain(|path| path.exists());
 
+    if removed.is_empty() {
+        eprintln!(
+            "  {}  {}",
+            "[clean]".blue().bold(),
+            "Nothing to clean.".green().bold()
+        );
+        return Ok(());
+    }
+    for path in removed {
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        }
+        .fatal(format!("could not remove `{}`", path.display()))?;
+        eprintln!(
+            "  {}  {}",
+            "[clean]".blue().bold(),
+            format!("Removed {}", path.display()).green().bold()
+        );
+    }
     Ok(())
 }
+
 fn create_build_src() -> Result<(), GbError> {
     std::fs::create_dir_all("build/src/")
         .fatal("could not construct directory for build source files")
 }
 
-fn await_vhdl_process(mut child: std::process::Child, message: &str) -> Result<(), GbError> {
+fn await_vhdl_process(mut child: filter::Filtered, message: &str) -> Result<(), GbError> {
     let waiting = child.wait().fatal(message)?;
     Ok(if !waiting.success() {
         Err(GbError {
@@ -0,0 +1,93 @@
+//! Reading gb.toml the way every part of gb sees it: `gb.local.toml` laid
+//! over it, its `include`s and `extends` resolved and the environment
+//! variables in its strings interpolated. gb's own configuration is merged in
+//! later, by whoever wants it.
+//!
+//! what gb.toml ends up with without setting it itself is remembered in
+//! `Origins`, for `gb config show` to tell where it's from, and the files
+//! read besides gb.toml, for `gb watch` to watch.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use toml_edit::{Document, Item};
+
+use crate::{inherit, interpolate, local, Check, GbError};
+
+#[derive(Debug, Clone, Default)]
+pub struct Origins {
+    /// a dotted key, like `target.top.files`, to where it comes from, like
+    /// `common.toml [target.base]`
+    keys: BTreeMap<String, String>,
+    /// the files read besides gb.toml
+    pub files: Vec<PathBuf>,
+}
+
+impl Origins {
+    /// where the key at `path` comes from, unless it's gb.toml itself
+    pub fn of(&self, path: &str) -> Option<&str> {
+        self.keys.get(path).map(String::as_str)
+    }
+
+    /// how `gb config show` names the key at `path` of `file`: the file and
+    /// the table the key is in, a target's for anything below it
+    pub fn label(file: &str, path: &str) -> String {
+        let components = path.split('.').collect::<Vec<_>>();
+        match components.as_slice() {
+            [_] => file.to_owned(),
+            ["target", target, _, ..] => format!("{file} [target.{target}]"),
+            [table, ..] => format!("{file} [{table}]"),
+            [] => file.to_owned(),
+        }
+    }
+
+    /// the origin of the key at `path`, which is gb.toml's without one
+    pub fn of_or_manifest(&self, path: &str) -> String {
+        self.of(path)
+            .map(str::to_owned)
+            .unwrap_or_else(|| Origins::label("gb.toml", path))
+    }
+
+    pub fn set(&mut self, path: &str, origin: String) {
+        self.keys.insert(path.to_owned(), origin);
+    }
+
+    /// `item`, now at `path`, came from `from`; `origin` tells where each
+    /// key below `from` comes from
+    pub fn record(&mut self, path: &str, from: &str, item: &Item, origin: &dyn Fn(&str) -> String) {
+        match item.as_table_like() {
+            Some(table) => {
+                for (key, item) in table.iter() {
+                    self.record(&join(path, key), &join(from, key), item, origin);
+                }
+            }
+            None => self.set(path, origin(from)),
+        }
+    }
+}
+
+/// `key` below `path`, which is empty for the top level
+pub fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_owned(),
+        _ => format!("{path}.{key}"),
+    }
+}
+
+/// resolves `doc`, as read from gb.toml
+pub fn resolve(doc: &mut Document) -> Result<Origins, GbError> {
+    let mut origins = Origins::default();
+    local::merge_into(doc)?;
+    inherit::resolve(doc, &mut origins)?;
+    interpolate::interpolate(doc)?;
+    Ok(origins)
+}
+
+/// gb.toml of the current directory, resolved
+pub fn load() -> Result<(Document, Origins), GbError> {
+    let mut doc = std::fs::read_to_string("gb.toml")
+        .fatal("manifest file `gb.toml` not found in the current directory")?
+        .parse::<Document>()
+        .fatal("failed to parse manifest file")?;
+    let origins = resolve(&mut doc)?;
+    Ok((doc, origins))
+}
@@ -0,0 +1,340 @@
+//! gb's own edits of gb.toml, like `gb add`. they go through toml_edit, so
+//! the comments and layout of the rest of the file stay as they were:
+//!
+//! ```sh
+//! gb add src/uart_rx.vhd src/uart_tx.vhd --target uart
+//! ```
+//!
+//! appends the files to the `files` of `uart`, creating `[target.uart]` if
+//! there's no such target yet. without `--target` they go into the target
+//! used when none is passed. a gb.toml `gb fmt-manifest` formatted stays
+//! formatted, otherwise only the edited array is touched, and a multi-line
+//! array gets its new elements on lines of their own.
+//!
+//! `gb target add <name>`, `gb target remove <name>` and
+//! `gb target set-default <name>` manage the targets themselves, so that
+//! gb.toml never has to be opened by hand:
+//!
+//! ```sh
+//! gb target add alu --execute src/alu_tb.vhd --files src/alu.vhd
+//! gb target set-default alu
+//! ```
+//!
+//! gb.toml is read as it is, without gb's configuration merged underneath.
+
+use std::path::Path;
+
+use colored::Colorize;
+use toml_edit::{value, Array, Document, Item, Table, TableLike, Value};
+
+use crate::{
+    exit, manifest_fmt, profile, report, sources, state, Check, GbError, Level, TargetCommands,
+};
+
+const PATH: &str = "gb.toml";
+
+/// gb.toml as it's written, with whether `gb fmt-manifest` would leave it alone
+pub fn read() -> Result<(Document, bool), GbError> {
+    let manifest = std::fs::read_to_string(PATH)
+        .fatal("manifest file `gb.toml` not found in the current directory")?;
+    let doc = exit::during(exit::Phase::Config, || {
+        manifest
+            .parse::<Document>()
+            .fatal("failed to parse manifest file")
+    })?;
+    let formatted = manifest_fmt::format(&manifest)? == manifest;
+    Ok((doc, formatted))
+}
+
+/// writes gb.toml back, formatting it again if it was formatted before
+pub fn write(mut doc: Document, formatted: bool) -> Result<(), GbError> {
+    if formatted {
+        manifest_fmt::normalize(&mut doc);
+    }
+    let manifest = doc.to_string();
+    let manifest = if formatted {
+        format!("{}\n", manifest.trim_matches('\n'))
+    } else {
+        manifest
+    };
+    std::fs::write(PATH, manifest).fatal("could not write gb.toml")
+}
+
+/// the `[target]` table, created when gb.toml has none yet
+pub fn targets_mut(doc: &mut Document) -> Result<&mut dyn TableLike, GbError> {
+    if doc.get("target").is_none() {
+        let mut targets = Table::new();
+        targets.set_implicit(true);
+        doc.insert("target", Item::Table(targets));
+    }
+    doc.get_mut("target")
+        .and_then(Item::as_table_like_mut)
+        .fatal("`target` in gb.toml must be a table of targets")
+}
+
+fn last_position(table: &Table) -> usize {
+    table
+        .iter()
+        .filter_map(|(_, item)| item.as_table())
+        .map(|table| {
+            table
+                .position()
+                .unwrap_or_default()
+                .max(last_position(table))
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// a new table for gb.toml, which goes after every table there is. toml_edit
+/// would put it first otherwise.
+pub fn new_table(doc: &Document) -> Table {
+    let mut table = Table::new();
+    table.set_position(last_position(doc.as_table()) + 1);
+    table
+}
+
+/// appends `entry` the way the array's last element was written, on a line
+/// of its own in a multi-line array
+fn push(array: &mut Array, entry: &str) {
+    let Some(decor) = array.iter().last().map(|last| last.decor().clone()) else {
+        array.push(entry);
+        return;
+    };
+    let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or_default();
+    // only the indentation, not a comment the line above ends with
+    let Some((_, indentation)) = prefix.rsplit_once('\n') else {
+        array.push(entry);
+        return;
+    };
+    let prefix = format!("\n{indentation}");
+    // the last element's suffix is what closes the line before `]`
+    let suffix = decor.suffix().and_then(|s| s.as_str()).unwrap_or_default();
+    if let Some(last) = array.iter_mut().last() {
+        last.decor_mut().set_suffix("");
+    }
+    array.push_formatted(Value::from(entry).decorated(prefix, suffix));
+}
+
+/// the entry of `files` covering `file` already, the path itself or a pattern
+/// matching it
+fn covering<'a>(files: &'a Array, file: &str) -> Option<&'a str> {
+    let normalized = sources::normalize(Path::new(file));
+    files.iter().filter_map(Value::as_str).find(|entry| {
+        if sources::is_glob(entry) {
+            glob::Pattern::new(entry).is_ok_and(|pattern| pattern.matches_path(&normalized))
+        } else {
+            sources::normalize(Path::new(entry)) == normalized
+        }
+    })
+}
+
+/// `file` relative to the project, with forward slashes like gb.toml uses
+fn manifest_path(file: &str) -> String {
+    let path = Path::new(file);
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf());
+    sources::normalize(&relative)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+pub fn add(files: &[String], target: Option<&str>) -> Result<(), GbError> {
+    let (mut doc, formatted) = read()?;
+    let target = match target {
+        Some(target) => target.to_owned(),
+        None => crate::default_target(&doc)?
+            .fatal("No target was passed with `--target` and no default target was set")?,
+    };
+
+    let mut table = new_table(&doc);
+    let targets = targets_mut(&mut doc)?;
+    let created = !targets.contains_key(&target);
+    if created {
+        table["files"] = value(Array::new());
+        targets.insert(&target, Item::Table(table));
+    }
+    let info = targets
+        .get_mut(&target)
+        .and_then(Item::as_table_like_mut)
+        .fatal(format!("target `{target}` in gb.toml must be a table"))?;
+    if info.get("files").and_then(Item::as_str) == Some("auto") {
+        Err(GbError {
+            message: format!(
+                "target `{target}` has `files = \"auto\"`, gb finds its files from `execute` itself"
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    if info.get("files").is_none() {
+        info.insert("files", value(Array::new()));
+    }
+    let array = info
+        .get_mut("files")
+        .and_then(Item::as_array_mut)
+        .fatal("the files list must be an array, or \"auto\"")?;
+
+    let mut added = 0;
+    for file in files {
+        let file = manifest_path(file);
+        if let Some(entry) = covering(array, &file) {
+            let message = if entry == file {
+                format!("`{file}` is already in target `{target}`")
+            } else {
+                format!("`{file}` is already in target `{target}` through `{entry}`")
+            };
+            report::emit(report::warning(message))?;
+            continue;
+        }
+        if !Path::new(&file).exists() {
+            report::emit(report::warning(format!(
+                "`{file}` doesn't exist yet, gb will fail to build `{target}` until it does"
+            )))?;
+        }
+        push(array, &file);
+        added += 1;
+    }
+
+    if added == 0 && !created {
+        return Ok(());
+    }
+    write(doc, formatted)?;
+    let message = if created {
+        format!("Created target `{target}` with {added} file(s)")
+    } else {
+        format!("Added {added} file(s) to target `{target}`")
+    };
+    eprintln!("  {}  {}", "[add]".blue().bold(), message.green().bold());
+    Ok(())
+}
+
+fn find_target<'a>(doc: &'a mut Document, name: &str) -> Result<&'a mut dyn TableLike, GbError> {
+    let targets = targets_mut(doc)?;
+    if !targets.contains_key(name) {
+        Err(GbError {
+            message: format!("there is no target named `{name}` in gb.toml"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(targets)
+}
+
+fn target_add(name: &str, execute: Option<&str>, files: &[String]) -> Result<(), GbError> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        Err(GbError {
+            message: format!(
+                "`{name}` can't be the name of a target, its outputs go to build/<target>"
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let (mut doc, formatted) = read()?;
+    profile::check_target_name(&doc, name)?;
+    let mut table = new_table(&doc);
+    let targets = targets_mut(&mut doc)?;
+    if targets.contains_key(name) {
+        Err(GbError {
+            message: format!("there is a target named `{name}` in gb.toml already"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let mut array = Array::new();
+    for file in execute.into_iter().chain(files.iter().map(String::as_str)) {
+        let file = manifest_path(file);
+        if covering(&array, &file).is_none() {
+            if !Path::new(&file).exists() {
+                report::emit(report::warning(format!(
+                    "`{file}` doesn't exist yet, gb will fail to build `{name}` until it does"
+                )))?;
+            }
+            array.push(file);
+        }
+    }
+    table["files"] = value(array);
+    if let Some(execute) = execute {
+        table["execute"] = value(manifest_path(execute));
+    }
+    targets.insert(name, Item::Table(table));
+
+    write(doc, formatted)?;
+    eprintln!(
+        "  {}  {}",
+        "[target]".blue().bold(),
+        format!("Added target `{name}`").green().bold()
+    );
+    Ok(())
+}
+
+fn target_remove(name: &str) -> Result<(), GbError> {
+    let (mut doc, formatted) = read()?;
+    find_target(&mut doc, name)?.remove(name);
+    let default = doc.get_mut("default").and_then(Item::as_table_like_mut);
+    if let Some(default) = default {
+        if default.get("target").and_then(Item::as_str) == Some(name) {
+            default.remove("target");
+            report::emit(report::warning(format!(
+                "`{name}` was `default.target`, there's no default target now"
+            )))?;
+        }
+    }
+    if state::local_default_target()?.as_deref() == Some(name) {
+        // `gb use` would keep pointing at a target that's gone
+        state::set_local_default_target(None)?;
+    }
+
+    write(doc, formatted)?;
+    eprintln!(
+        "  {}  {}",
+        "[target]".blue().bold(),
+        format!("Removed target `{name}`").green().bold()
+    );
+    Ok(())
+}
+
+fn set_default(name: &str) -> Result<(), GbError> {
+    let (mut doc, formatted) = read()?;
+    find_target(&mut doc, name)?;
+    if doc.get("default").is_none() {
+        // `default.target = ...` on the first line, like `gb init` writes it
+        let mut default = Table::new();
+        default.set_dotted(true);
+        doc.insert("default", Item::Table(default));
+    }
+    let default = doc
+        .get_mut("default")
+        .and_then(Item::as_table_like_mut)
+        .fatal("`default` in gb.toml must be a table")?;
+    default.insert("target", value(name));
+    if state::local_default_target()?.is_some_and(|locked| locked != name) {
+        report::emit(report::warning(
+            "`gb use` locked in another target for this directory, which still takes precedence, `gb use --clear` forgets it",
+        ))?;
+    }
+
+    write(doc, formatted)?;
+    eprintln!(
+        "  {}  {}",
+        "[target]".blue().bold(),
+        format!("`{name}` is the default target now").green().bold()
+    );
+    Ok(())
+}
+
+pub fn target(command: &TargetCommands) -> Result<(), GbError> {
+    match command {
+        TargetCommands::Add {
+            name,
+            execute,
+            files,
+        } => target_add(name, execute.as_deref(), files),
+        TargetCommands::Remove { name } => target_remove(name),
+        TargetCommands::SetDefault { name } => set_default(name),
+    }
+}
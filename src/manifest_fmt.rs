@@ -0,0 +1,210 @@
+//! `gb fmt-manifest`: one layout for gb.toml, so that the diffs to a shared
+//! manifest only show what actually changed. comments are kept, next to the
+//! key or table they were written above.
+//!
+//! - `name`, `target`, `files` and `execute` come first in a table, every other
+//!   key after them in alphabetical order. tables stay where they are, the
+//!   order of the targets means something.
+//! - `key = value`, without indentation, and one blank line before a table
+//! - arrays on one line while they fit in 80 columns, otherwise one element
+//!   per line with a trailing comma. an array with comments in it is left as
+//!   it is.
+//!
+//! toml_edit repeats a comment above dotted keys like `default.target` in
+//! front of every other `default.` key, write a `[default]` table to comment
+//! on those.
+//!
+//! `--check` only tells whether gb.toml is formatted, failing if it isn't.
+
+use std::cmp::Ordering;
+
+use colored::Colorize;
+use toml_edit::{Array, Decor, Document, InlineTable, Item, Key, RawString, Table, Value};
+
+use crate::{Check, GbError, Level};
+
+/// the keys that go first, in this order
+const LEADING_KEYS: &[&str] = &["name", "target", "files", "execute"];
+
+const MAX_WIDTH: usize = 80;
+
+fn compare_keys(a: &Key, b: &Key) -> Ordering {
+    let rank = |key: &Key| {
+        LEADING_KEYS
+            .iter()
+            .position(|leading| *leading == key.get())
+            .unwrap_or(LEADING_KEYS.len())
+    };
+    rank(a).cmp(&rank(b)).then_with(|| a.get().cmp(b.get()))
+}
+
+fn raw(raw: Option<&RawString>) -> &str {
+    raw.and_then(|raw| raw.as_str()).unwrap_or_default()
+}
+
+/// the comments of a prefix without indentation, and at most one blank line
+/// between them. `blank_line` puts exactly one blank line in front.
+fn normalize_prefix(prefix: &str, blank_line: bool) -> String {
+    let mut lines = prefix.split('\n').map(str::trim).collect::<Vec<_>>();
+    // the last piece is the indentation of the key itself
+    lines.pop();
+    let mut normalized: Vec<&str> = Vec::new();
+    for line in lines {
+        let previous_blank = normalized.last().copied().unwrap_or_default().is_empty();
+        if line.is_empty() && previous_blank {
+            continue;
+        }
+        normalized.push(line);
+    }
+    while normalized.last().is_some_and(|last| last.is_empty()) {
+        normalized.pop();
+    }
+    if blank_line && normalized.first() != Some(&"") {
+        normalized.insert(0, "");
+    }
+    normalized
+        .into_iter()
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/// a trailing comment is kept one space after the value
+fn normalize_suffix(suffix: &str) -> String {
+    let suffix = suffix.trim();
+    if suffix.is_empty() {
+        String::new()
+    } else {
+        format!(" {suffix}")
+    }
+}
+
+fn has_comment(decor: &Decor) -> bool {
+    raw(decor.prefix()).contains('#') || raw(decor.suffix()).contains('#')
+}
+
+/// `width` is how much of the line the key and ` = ` take already
+fn normalize_array(array: &mut Array, width: usize, top_level: bool) {
+    for value in array.iter_mut() {
+        normalize_value(value, 0, false);
+    }
+    let commented = array.iter().any(|value| has_comment(value.decor()))
+        || raw(Some(array.trailing())).contains('#');
+    if commented {
+        return;
+    }
+    array.fmt();
+    let one_line = array.to_string().trim().len() + width <= MAX_WIDTH;
+    if one_line || !top_level || array.is_empty() {
+        return;
+    }
+    for value in array.iter_mut() {
+        value.decor_mut().set_prefix("\n    ");
+        value.decor_mut().set_suffix("");
+    }
+    array.set_trailing_comma(true);
+    array.set_trailing("\n");
+}
+
+fn normalize_inline_table(table: &mut InlineTable) {
+    for (_, value) in table.iter_mut() {
+        normalize_value(value, 0, false);
+    }
+    table.sort_values_by(|a, _, b, _| compare_keys(a, b));
+    table.fmt();
+}
+
+fn normalize_value(value: &mut Value, width: usize, top_level: bool) {
+    match value {
+        Value::Array(array) => normalize_array(array, width, top_level),
+        Value::InlineTable(table) => normalize_inline_table(table),
+        _ => {}
+    }
+}
+
+fn normalize_table(table: &mut Table, first: bool) {
+    if !table.is_dotted() {
+        let prefix = normalize_prefix(raw(table.decor().prefix()), !first);
+        table.decor_mut().set_prefix(prefix);
+        let suffix = normalize_suffix(raw(table.decor().suffix()));
+        table.decor_mut().set_suffix(suffix);
+    }
+    table.sort_values_by(|a, _, b, _| compare_keys(a, b));
+
+    // in `default.target`, the line's comments are in front of `default` and
+    // the dot follows right after it
+    let dotted = table.is_dotted();
+    for (mut key, item) in table.iter_mut() {
+        let dotted_table = item.as_table().is_some_and(Table::is_dotted);
+        if item.is_value() || dotted_table {
+            let prefix = if dotted {
+                String::new()
+            } else {
+                normalize_prefix(raw(key.decor().prefix()), false)
+            };
+            key.decor_mut().set_prefix(prefix);
+            key.decor_mut()
+                .set_suffix(if dotted_table { "" } else { " " });
+        }
+        let width = key.get().len() + 3;
+        match item {
+            Item::Value(value) => {
+                let suffix = normalize_suffix(raw(value.decor().suffix()));
+                value.decor_mut().set_prefix(" ");
+                value.decor_mut().set_suffix(suffix);
+                normalize_value(value, width, true);
+            }
+            Item::Table(table) => normalize_table(table, false),
+            Item::ArrayOfTables(tables) => {
+                for table in tables.iter_mut() {
+                    normalize_table(table, false);
+                }
+            }
+            Item::None => {}
+        }
+    }
+}
+
+/// formats `doc` in place, `gb fmt-manifest` and gb's own edits of gb.toml go through here
+pub fn normalize(doc: &mut Document) {
+    normalize_table(doc.as_table_mut(), true);
+    // comments after the last key stay, blank lines after them don't
+    let trailing = normalize_prefix(&format!("{}\n", raw(Some(doc.trailing()))), false);
+    doc.set_trailing(trailing);
+}
+
+/// the formatted text of a manifest
+pub fn format(manifest: &str) -> Result<String, GbError> {
+    let mut doc = manifest
+        .parse::<Document>()
+        .fatal("failed to parse manifest file")?;
+    normalize(&mut doc);
+    let formatted = doc.to_string();
+    Ok(format!("{}\n", formatted.trim_matches('\n')))
+}
+
+pub fn fmt_manifest(check: bool) -> Result<(), GbError> {
+    let manifest = std::fs::read_to_string("gb.toml").fatal("could not read gb.toml")?;
+    let formatted = format(&manifest)?;
+    if formatted == manifest {
+        eprintln!(
+            "  {}  {}",
+            "[fmt]".blue().bold(),
+            "gb.toml is already formatted".green().bold()
+        );
+        return Ok(());
+    }
+    if check {
+        return Err(GbError {
+            message: "gb.toml is not formatted, run `gb fmt-manifest`".to_owned(),
+            level: Level::Fatal,
+            source: None,
+        });
+    }
+    std::fs::write("gb.toml", formatted).fatal("could not write gb.toml")?;
+    eprintln!(
+        "  {}  {}",
+        "[fmt]".blue().bold(),
+        "Formatted gb.toml".green().bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Files named after the entity they declare. The dependency resolver finds
+//! a component's file as `<component>.vhd` next to the file using it, so an
+//! entity living in a differently named file is silently never found.
+//!
+//! ```toml
+//! [lint]
+//! file-naming = "lowercase"   # "entity" (the default), "lowercase" or "off"
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use toml_edit::Document;
+
+use crate::{report, GbError, Level};
+
+static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"--[^\n]*").unwrap());
+static ENTITY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bentity\s+([a-z][a-z0-9_]*)\s+is\b").unwrap());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Convention {
+    /// the stem is the entity name, in any case, since vhdl doesn't care
+    #[default]
+    Entity,
+    /// the stem is the entity name in lowercase
+    Lowercase,
+    Off,
+}
+
+impl Convention {
+    pub fn parse(doc: &Document) -> Result<Convention, GbError> {
+        let Some(naming) = doc.get("lint").and_then(|lint| lint.get("file-naming")) else {
+            return Ok(Convention::default());
+        };
+        match naming.as_str() {
+            Some("entity") => Ok(Convention::Entity),
+            Some("lowercase") => Ok(Convention::Lowercase),
+            Some("off") => Ok(Convention::Off),
+            _ => Err(GbError {
+                message: "`lint.file-naming` must be one of \"entity\", \"lowercase\" or \"off\""
+                    .to_owned(),
+                level: Level::Fatal,
+                source: None,
+            }),
+        }
+    }
+
+    fn accepts(self, stem: &str, entity: &str) -> bool {
+        match self {
+            Convention::Entity => stem.eq_ignore_ascii_case(entity),
+            Convention::Lowercase => stem == entity.to_lowercase(),
+            Convention::Off => true,
+        }
+    }
+}
+
+/// a file whose name doesn't follow the convention
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub file: PathBuf,
+    pub entity: String,
+    /// what the file should be called
+    pub expected: PathBuf,
+}
+
+/// the entities `path` declares, in source order
+pub fn entities(path: &Path) -> Vec<String> {
+    let Ok(code_src) = std::fs::read_to_string(path) else {
+        return vec![];
+    };
+    let code_src = COMMENT.replace_all(&code_src, "");
+    ENTITY
+        .captures_iter(&code_src)
+        .map(|captures| captures[1].to_owned())
+        .collect()
+}
+
+/// the first entity `path` declares, which is the one its name should follow
+fn primary_entity(path: &Path) -> Option<String> {
+    entities(path).into_iter().next()
+}
+
+/// the files among `files` not named after their entity. files without an
+/// entity, like packages, are fine with any name.
+pub fn mismatches(files: &[&str], convention: Convention) -> Vec<Mismatch> {
+    files
+        .iter()
+        .map(Path::new)
+        .filter_map(|file| {
+            let entity = primary_entity(file)?;
+            let stem = file.file_stem()?.to_str()?;
+            if convention.accepts(stem, &entity) {
+                return None;
+            }
+            let name = match convention {
+                Convention::Lowercase => entity.to_lowercase(),
+                _ => entity.clone(),
+            };
+            let mut expected = file.with_file_name(name);
+            if let Some(extension) = file.extension() {
+                expected.set_extension(extension);
+            }
+            Some(Mismatch {
+                file: file.to_owned(),
+                entity,
+                expected,
+            })
+        })
+        .collect()
+}
+
+pub fn warn(mismatches: &[Mismatch]) -> Result<(), GbError> {
+    for mismatch in mismatches {
+        report::emit(report::warning(format!(
+            "`{}` declares entity `{}`, so other files won't find it, rename it to `{}`",
+            mismatch.file.display(),
+            mismatch.entity,
+            mismatch.expected.display()
+        )))?;
+    }
+    Ok(())
+}
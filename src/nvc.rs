@@ -0,0 +1,259 @@
+//! nvc as the simulator, for `simulator = "nvc"` in gb.toml. which nvc runs
+//! is picked like ghdl's:
+//!
+//! ```toml
+//! [nvc]
+//! path = "/opt/nvc/bin/nvc"
+//! flags = ["--ieee-warnings=off"]   # passed before every nvc command
+//! ```
+//!
+//! `GB_NVC` takes precedence over `nvc.path`, without either gb runs the
+//! `nvc` found on the path.
+//!
+//! nvc's libraries are directories, the target's is `build/<profile>/work/`,
+//! or named after its `library`, and nvc is told to put it there instead of
+//! gb moving it afterwards. elaboration leaves the simulation in the library
+//! too, there's no executable. generics are given when elaborating, dotted
+//! ones like `dut.fifo.DEPTH` reach below the top entity. the options of
+//! `[target.X.sim]` are passed to `nvc -r` as they are, nvc knows
+//! `stop-time` and `stop-delta` by the same name as ghdl.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use once_cell::sync::OnceCell;
+use toml_edit::Document;
+
+use crate::{
+    profile,
+    simulator::Simulator,
+    wave::{WaveFormat, Waveform},
+    BuildOptions, Check, GbError, Level,
+};
+
+pub const ENV: &str = "GB_NVC";
+
+#[derive(Debug, Clone)]
+struct Config {
+    path: String,
+    flags: Vec<String>,
+}
+
+static CONFIGURED: OnceCell<Config> = OnceCell::new();
+
+/// reads `[nvc]` from gb.toml
+pub fn configure(doc: &Document) -> Result<(), GbError> {
+    let table = doc.get("nvc");
+    let path = match std::env::var(ENV) {
+        Ok(path) if !path.is_empty() => path,
+        _ => match table.and_then(|table| table.get("path")) {
+            Some(path) => path
+                .as_str()
+                .fatal("`nvc.path` must be the path to the nvc binary")?
+                .to_owned(),
+            None => "nvc".to_owned(),
+        },
+    };
+    let flags = match table.and_then(|table| table.get("flags")) {
+        Some(flags) => flags
+            .as_array()
+            .and_then(|flags| {
+                flags
+                    .iter()
+                    .map(|flag| flag.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .fatal("`nvc.flags` must be an array of strings")?,
+        None => vec![],
+    };
+    let _ = CONFIGURED.set(Config { path, flags });
+    Ok(())
+}
+
+/// the nvc gb runs
+pub fn path() -> &'static str {
+    CONFIGURED.get().map_or("nvc", |nvc| nvc.path.as_str())
+}
+
+/// nvc's name for a `std`, it wants the whole year
+fn standard(std: &str) -> &str {
+    match std {
+        "87" => "1987",
+        "93" | "93c" => "1993",
+        "00" => "2000",
+        "02" => "2002",
+        "08" => "2008",
+        "19" => "2019",
+        std => std,
+    }
+}
+
+/// the directory name of the library of `build`, nvc lowercases them
+fn library(build: &BuildOptions) -> String {
+    build.library.as_deref().unwrap_or("work").to_lowercase()
+}
+
+/// `nvc` with the options it wants before the command, `libraries` is where
+/// the libraries of the build are, seen from where nvc runs
+fn command(build: &BuildOptions, libraries: &Path) -> Command {
+    let mut nvc = Command::new(path());
+    let library = library(build);
+    nvc.args(CONFIGURED.get().map_or(&[][..], |nvc| nvc.flags.as_slice()))
+        .args(
+            build
+                .std
+                .iter()
+                .map(|std| format!("--std={}", standard(std))),
+        )
+        .arg(format!(
+            "--work={library}:{}",
+            libraries.join(&library).display()
+        ))
+        .args(
+            std::iter::once(libraries.to_path_buf())
+                .chain(build.library_paths.iter().cloned())
+                .flat_map(|path| ["-L".to_owned(), path.display().to_string()]),
+        );
+    nvc
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), GbError> {
+    std::fs::create_dir_all(to).fatal(format!("could not create `{}`", to.display()))?;
+    for entry in std::fs::read_dir(from)
+        .fatal(format!("could not read `{}`", from.display()))?
+        .flatten()
+    {
+        let path = entry.path();
+        let copy = to.join(entry.file_name());
+        if path.is_dir() {
+            copy_dir(&path, &copy)?;
+        } else {
+            std::fs::copy(&path, &copy).fatal(format!("could not copy `{}`", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// nvc as the simulator of the build
+pub struct Nvc;
+
+impl Simulator for Nvc {
+    fn name(&self) -> &'static str {
+        "nvc"
+    }
+
+    fn analyze(&self, files: &[&str], build: &BuildOptions, workdir: Option<&Path>) -> Command {
+        let libraries = workdir.map_or_else(profile::dir, Path::to_path_buf);
+        let mut nvc = command(build, &libraries);
+        nvc.arg("-a")
+            .args(
+                build
+                    .analyze_flags
+                    .iter()
+                    // `-P` are ghdl's library paths, which nvc already got as
+                    // `-L`, and `--warn-` are ghdl's warnings, of `[warnings]`
+                    // and `werror` or `strict`
+                    .filter(|flag| !flag.starts_with("-P") && !flag.starts_with("--warn-")),
+            )
+            .args(files);
+        nvc
+    }
+
+    fn elaborate(&self, unit: &str, build: &BuildOptions) -> Result<Command, GbError> {
+        if !build.foreign.is_empty() {
+            Err(GbError {
+                message: "C code linked into the simulation, `foreign` and `vpi`, needs ghdl"
+                    .to_owned(),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        let mut nvc = command(build, Path::new("."));
+        nvc.arg("-e")
+            .args(&build.elaborate_flags)
+            .args(
+                build
+                    .generics
+                    .iter()
+                    .map(|(name, value)| format!("-g{name}={value}")),
+            )
+            .arg(unit)
+            .current_dir(build.run_dir());
+        Ok(nvc)
+    }
+
+    fn run(
+        &self,
+        unit: &str,
+        waveform: Option<&Waveform>,
+        build: &BuildOptions,
+    ) -> Result<Command, GbError> {
+        let mut nvc = command(build, Path::new("."));
+        nvc.arg("-r");
+        if let Some(waveform) = waveform {
+            if waveform.path == Path::new("-") {
+                Err(GbError {
+                    message: "nvc can't write its waveform to gb while it runs, that needs ghdl"
+                        .to_owned(),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+            nvc.arg(format!("--wave={}", waveform.path.display()));
+            match waveform.format {
+                WaveFormat::Vcd => {
+                    nvc.arg("--format=vcd");
+                }
+                WaveFormat::Fst => {
+                    nvc.arg("--format=fst");
+                }
+                WaveFormat::Ghw => Err(GbError {
+                    message: "nvc doesn't write ghw waveforms, use a vcd or fst instead".to_owned(),
+                    level: Level::Fatal,
+                    source: None,
+                })?,
+            }
+        }
+        nvc.args(&build.run_flags)
+            .arg(unit)
+            .current_dir(build.run_dir());
+        Ok(nvc)
+    }
+
+    /// the file nvc keeps in every library directory
+    fn library_file(&self, build: &BuildOptions) -> String {
+        format!("{}/_NVC_LIB", library(build))
+    }
+
+    fn executable(&self, _unit: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// every library directory, the simulation nvc elaborates goes in there
+    fn isolate(&self, dir: &Path) -> Result<(), GbError> {
+        let built = profile::dir();
+        for library in std::fs::read_dir(&built)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.join("_NVC_LIB").is_file())
+        {
+            if let Some(name) = library.file_name() {
+                copy_dir(&library, &dir.join(name))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// nvc writes into `build/<profile>/` right away, which has to exist
+    fn restore(&self) -> Result<(), GbError> {
+        std::fs::create_dir_all(profile::dir()).fatal("could not create the build directory")
+    }
+
+    fn overrides_nested_generics(&self) -> bool {
+        true
+    }
+}
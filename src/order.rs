@@ -0,0 +1,179 @@
+//! Orders a target's files so that every file is analyzed after the files it
+//! depends on, whatever order gb.toml lists them in. ghdl's analyzer needs the
+//! units a file uses to already be in the library, and a file listed too early
+//! used to fail with nothing more than "unit not found".
+//!
+//! a file depends on the files of the components it declares and the contexts
+//! it references, like in `gb graph`, and on the listed files declaring the
+//! entities and packages it names from `work`, or whose entity or package it
+//! implements with an architecture or package body. files which don't depend
+//! on each other keep the order they were listed in, and a cycle, which ghdl
+//! couldn't analyze either way, is left as it is.
+//!
+//! a target without `execute` runs its top: the one entity of its files which
+//! none of the others instantiate, usually the testbench.
+
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{sources, tree, tree_sitter, GbError, Level};
+
+static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"--[^\n]*").unwrap());
+static DECLARATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(?:entity|package|context)\s+([a-z][a-z0-9_]*)\s+is\b").unwrap()
+});
+static REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\bwork\s*\.\s*([a-z][a-z0-9_]*)|\barchitecture\s+[a-z][a-z0-9_]*\s+of\s+([a-z][a-z0-9_]*)|\bpackage\s+body\s+([a-z][a-z0-9_]*)",
+    )
+    .unwrap()
+});
+static ENTITY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bentity\s+([a-z][a-z0-9_]*)\s+is\b").unwrap());
+fn names(path: &Path, regex: &Regex) -> Vec<String> {
+    let Ok(code_src) = std::fs::read_to_string(path) else {
+        return vec![];
+    };
+    let code_src = COMMENT.replace_all(&code_src, "");
+    regex
+        .captures_iter(&code_src)
+        .map(|captures| captures[1].to_lowercase())
+        .collect()
+}
+
+/// the units a file declares and the ones it uses, lowercased since vhdl
+/// doesn't care
+fn units(path: &Path) -> (Vec<String>, Vec<String>) {
+    let Ok(code_src) = std::fs::read_to_string(path) else {
+        return (vec![], vec![]);
+    };
+    let code_src = COMMENT.replace_all(&code_src, "");
+    let declared = DECLARATION
+        .captures_iter(&code_src)
+        .map(|captures| captures[1].to_lowercase())
+        .collect::<Vec<_>>();
+    let referenced = REFERENCE
+        .captures_iter(&code_src)
+        .filter_map(|captures| captures.iter().skip(1).flatten().next())
+        .map(|name| name.as_str().to_lowercase())
+        .filter(|name| !declared.contains(name))
+        .collect();
+    (declared, referenced)
+}
+
+/// for every one of `files`, the positions of the others it depends on. the
+/// incremental cache and `--jobs` go by these too, so that whatever order
+/// analyzes files in, it's one ghdl can analyze them in.
+pub fn dependencies<S: AsRef<str>>(files: &[S]) -> Vec<Vec<usize>> {
+    let normalized = files
+        .iter()
+        .map(|file| sources::normalize(file.as_ref().as_ref()))
+        .collect::<Vec<_>>();
+    let units = files
+        .iter()
+        .map(|file| units(file.as_ref().as_ref()))
+        .collect::<Vec<_>>();
+
+    files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let components = tree_sitter::direct_dependencies(file.as_ref())
+                .into_iter()
+                .map(|path: PathBuf| sources::normalize(&path))
+                .collect::<Vec<_>>();
+            let (_, referenced) = &units[index];
+            (0..files.len())
+                .filter(|other| *other != index)
+                .filter(|other| {
+                    components.contains(&normalized[*other])
+                        || units[*other].0.iter().any(|unit| referenced.contains(unit))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// `files`, reordered so that dependencies come first
+pub fn order(files: Vec<String>) -> Vec<String> {
+    let dependencies = dependencies(&files);
+
+    fn visit(
+        index: usize,
+        dependencies: &[Vec<usize>],
+        visited: &mut [bool],
+        order: &mut Vec<usize>,
+    ) {
+        if visited[index] {
+            return;
+        }
+        visited[index] = true;
+        for dependency in &dependencies[index] {
+            visit(*dependency, dependencies, visited, order);
+        }
+        order.push(index);
+    }
+
+    let mut visited = vec![false; files.len()];
+    let mut order = Vec::with_capacity(files.len());
+    for index in 0..files.len() {
+        visit(index, &dependencies, &mut visited, &mut order);
+    }
+    let mut files = files.into_iter().map(Some).collect::<Vec<_>>();
+    order
+        .into_iter()
+        .filter_map(|index| files[index].take())
+        .collect()
+}
+
+/// the file declaring the one entity of `files` nothing else instantiates
+pub fn top(target: &str, files: &[String]) -> Result<String, GbError> {
+    let entities = files
+        .iter()
+        .map(|file| names(file.as_ref(), &ENTITY))
+        .collect::<Vec<_>>();
+    let mut instantiated = tree::instances(&files.iter().map(String::as_str).collect::<Vec<_>>())
+        .into_values()
+        .flatten()
+        .map(|instance| instance.unit)
+        .collect::<Vec<_>>();
+    // a component declared in one file is the entity of the file named after it
+    for file in files {
+        for dependency in tree_sitter::direct_dependencies(file) {
+            if let Some(stem) = dependency.file_stem() {
+                instantiated.push(stem.to_string_lossy().to_lowercase());
+            }
+        }
+    }
+
+    let candidates = files
+        .iter()
+        .zip(&entities)
+        .flat_map(|(file, entities)| entities.iter().map(move |entity| (file, entity)))
+        .filter(|(_, entity)| !instantiated.contains(entity))
+        .collect::<Vec<_>>();
+    match candidates.as_slice() {
+        [(file, _)] => Ok((*file).clone()),
+        [] => Err(GbError {
+            message: format!(
+                "target `{target}` has no `execute` file, and gb couldn't find an entity in its files to run instead. Please set `execute = \"<YOUR_FILE>\"` in gb.toml"
+            ),
+            level: Level::Fatal,
+            source: None,
+        }),
+        candidates => Err(GbError {
+            message: format!(
+                "target `{target}` has no `execute` file, and more than one entity nothing instantiates: {}. set `execute` to the one to run",
+                candidates
+                    .iter()
+                    .map(|(file, entity)| format!("`{entity}` in `{file}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            level: Level::Fatal,
+            source: None,
+        }),
+    }
+}
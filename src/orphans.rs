@@ -0,0 +1,115 @@
+//! Cleans the work libraries in `build/<profile>/` of units whose file was removed
+//! from gb.toml or renamed. ghdl keeps them around otherwise, and elaborating
+//! against such a stale unit fails in ways only `gb clean` used to fix.
+//!
+//! a file counts as known while any target, `[test] files` or a testbench
+//! lists it, so targets sharing a library don't throw out each other's units.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::Document;
+
+use crate::{artifacts, cache, profile, sources, test, verbosity, Check, GbError};
+
+/// the source file of a `file . "<path>" ...` line, as analyzed from the project root
+fn source_of(line: &str) -> Option<PathBuf> {
+    let rest = line.strip_prefix(artifacts::FILE_PREFIX)?;
+    let path = artifacts::unrelocate(&rest[..rest.find('"')?]);
+    Some(sources::normalize(Path::new(path)))
+}
+
+/// `library` without the entries of files not in `known`, and those files
+fn prune_library(library: &str, known: &HashSet<PathBuf>) -> (String, Vec<PathBuf>, usize) {
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+    let mut units = 0;
+    let mut dropping = false;
+    for line in library.lines() {
+        if let Some(source) = source_of(line) {
+            dropping = !known.contains(&source);
+            if dropping {
+                removed.push(source);
+            }
+        } else if !line.starts_with(char::is_whitespace) {
+            dropping = false;
+        } else if dropping {
+            units += 1;
+        }
+        if !dropping {
+            kept.push(line);
+        }
+    }
+    (kept.join("\n") + "\n", removed, units)
+}
+
+/// the work libraries gb keeps for the selected profile
+fn libraries() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(profile::dir()) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.contains("-obj") && name.ends_with(".cf"))
+        })
+        .collect()
+}
+
+/// removes the units of files the manifest no longer mentions from every
+/// work library, along with their object files
+pub fn prune(doc: &Document) -> Result<(), GbError> {
+    let libraries = libraries();
+    if libraries.is_empty() {
+        return Ok(());
+    }
+    // another target being broken shouldn't stop this one, it's only cleanup
+    let Ok(known) = test::files_to_analyze(doc, &test::discover(doc)) else {
+        return Ok(());
+    };
+    let known = known
+        .iter()
+        .map(|file| sources::normalize(file.as_ref()))
+        .collect::<HashSet<_>>();
+
+    let mut orphans = HashSet::new();
+    let mut units = 0;
+    for library in libraries {
+        let contents =
+            std::fs::read_to_string(&library).fatal(format!("could not read {library:?}"))?;
+        let (pruned, removed, removed_units) = prune_library(&contents, &known);
+        if removed.is_empty() {
+            continue;
+        }
+        std::fs::write(&library, pruned).fatal(format!("could not clean up {library:?}"))?;
+        orphans.extend(removed);
+        units += removed_units;
+    }
+    if orphans.is_empty() {
+        return Ok(());
+    }
+
+    for orphan in &orphans {
+        // object files are named by stem, another file may share it
+        let shared = |stem| known.iter().any(|file| file.file_stem() == Some(stem));
+        if let Some(stem) = orphan.file_stem().filter(|stem| !shared(stem)) {
+            let object = profile::dir().join(stem).with_extension("o");
+            let _ = std::fs::remove_file(object);
+        }
+    }
+    // when a file comes back, it has to be analyzed again
+    cache::forget(&orphans)?;
+    verbosity::step(
+        "[clean]",
+        &format!(
+            "Removed {units} unit(s) of {} file(s) no longer in gb.toml",
+            orphans.len()
+        ),
+    );
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! `gb package`: the sources of a target bundled up, for handing in an
+//! assignment or passing a design on, as `build/package/<name>.tar.gz`, or a
+//! `.zip` with `--format zip`:
+//!
+//! ```toml
+//! [package]
+//! name = "lab3-jdoe"                       # the target's name without it
+//! version = "1.0.0"
+//! authors = ["Jane Doe <jdoe@example.edu>"]
+//! description = "traffic light controller"
+//! include = ["README.md", "docs/*.pdf"]    # anything else to bundle
+//! ```
+//!
+//! the bundle has the files of the target as gb resolves them, `files =
+//! "auto"` too, those of the targets it `depends` on, its `execute` file,
+//! gb.toml and gb.lock, and what `include` matches. they sit in a directory
+//! named like the bundle, next to a `package.json` with the metadata and the
+//! list of files. the archive is written by `tar`, or `zip`.
+
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+use serde::Serialize;
+use toml_edit::Document;
+
+use crate::{depends, sources, Check, GbError, Level};
+
+const DIR: &str = "build/package";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    #[value(name = "tar.gz")]
+    TarGz,
+    Zip,
+}
+
+#[derive(Debug, Serialize)]
+struct Metadata<'p> {
+    name: &'p str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'p str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<&'p str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'p str>,
+    target: &'p str,
+    gb_version: &'static str,
+    files: &'p [String],
+}
+
+fn string<'d>(doc: &'d Document, key: &str) -> Result<Option<&'d str>, GbError> {
+    match doc.get("package").and_then(|package| package.get(key)) {
+        Some(value) => Ok(Some(
+            value
+                .as_str()
+                .fatal(format!("`package.{key}` must be a string"))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn strings<'d>(doc: &'d Document, key: &str) -> Result<Vec<&'d str>, GbError> {
+    match doc.get("package").and_then(|package| package.get(key)) {
+        Some(value) => value
+            .as_array()
+            .and_then(|array| array.iter().map(|item| item.as_str()).collect())
+            .fatal(format!("`package.{key}` must be an array of strings")),
+        None => Ok(vec![]),
+    }
+}
+
+/// whether `file` stays inside the project, where the bundle can keep its path
+fn is_inside(file: &str) -> bool {
+    Path::new(file)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// every file that goes into the bundle, once each
+fn files(
+    doc: &Document,
+    target: &str,
+    target_files: &[String],
+    execute: Option<&str>,
+) -> Result<Vec<String>, GbError> {
+    let mut files = Vec::new();
+    for depend in depends::order(doc, target)? {
+        files.extend(crate::resolve_target_files(
+            &depend,
+            &doc["target"][depend.as_str()],
+        )?);
+    }
+    files.extend(target_files.iter().cloned());
+    files.extend(execute.map(str::to_owned));
+    files.push("gb.toml".to_owned());
+    if Path::new("gb.lock").exists() {
+        files.push("gb.lock".to_owned());
+    }
+    for pattern in strings(doc, "include")? {
+        if sources::is_glob(pattern) {
+            files.extend(sources::expand(pattern)?);
+        } else {
+            files.push(pattern.to_owned());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(sources::normalize(file.as_ref())));
+    for file in &files {
+        if !is_inside(file) {
+            Err(GbError {
+                message: format!("`{file}` is outside of the project, it can't be packaged"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        if !Path::new(file).is_file() {
+            Err(GbError {
+                message: format!("`{file}` doesn't exist, so it can't be packaged"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+    }
+    Ok(files)
+}
+
+pub fn package(
+    doc: &Document,
+    target: &str,
+    target_files: &[String],
+    execute: Option<&str>,
+    format: Format,
+) -> Result<(), GbError> {
+    let name = string(doc, "name")?.unwrap_or(target);
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        Err(GbError {
+            message: format!("`{name}` can't name a package, it's used as a file name"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let version = string(doc, "version")?;
+    let stem = match version {
+        Some(version) => format!("{name}-{version}"),
+        None => name.to_owned(),
+    };
+    let files = files(doc, target, target_files, execute)?;
+
+    // the bundle is put together in `build/package/<stem>/` and archived from there
+    let staging = PathBuf::from(DIR).join(&stem);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .fatal(format!("could not remove `{}`", staging.display()))?;
+    }
+    for file in &files {
+        let copy = staging.join(sources::normalize(file.as_ref()));
+        if let Some(parent) = copy.parent() {
+            std::fs::create_dir_all(parent)
+                .fatal(format!("could not create `{}`", parent.display()))?;
+        }
+        std::fs::copy(file, &copy).fatal(format!("could not copy `{file}`"))?;
+    }
+    let metadata = Metadata {
+        name,
+        version,
+        authors: strings(doc, "authors")?,
+        description: string(doc, "description")?,
+        target,
+        gb_version: env!("CARGO_PKG_VERSION"),
+        files: &files,
+    };
+    std::fs::write(
+        staging.join("package.json"),
+        serde_json::to_string_pretty(&metadata).fatal("could not serialize package.json")?,
+    )
+    .fatal("could not write package.json")?;
+
+    let (archive, mut command) = match format {
+        Format::TarGz => {
+            let archive = format!("{stem}.tar.gz");
+            let mut tar = Command::new("tar");
+            tar.arg("-czf").arg(&archive).arg(&stem);
+            (archive, tar)
+        }
+        Format::Zip => {
+            let archive = format!("{stem}.zip");
+            let mut zip = Command::new("zip");
+            zip.arg("-qr").arg(&archive).arg(&stem);
+            (archive, zip)
+        }
+    };
+    let archive_path = PathBuf::from(DIR).join(&archive);
+    if archive_path.exists() {
+        std::fs::remove_file(&archive_path)
+            .fatal(format!("could not remove `{}`", archive_path.display()))?;
+    }
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .current_dir(DIR)
+        .status()
+        .fatal(format!("could not run `{program}`, is it installed?"))?;
+    let _ = std::fs::remove_dir_all(&staging);
+    if !status.success() {
+        Err(GbError {
+            message: format!("`{program}` could not write `{}`", archive_path.display()),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    eprintln!(
+        "  {}  {}",
+        "[package]".blue().bold(),
+        format!(
+            "Packaged {} files into {}",
+            files.len(),
+            archive_path.display()
+        )
+        .green()
+        .bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Analyzing independent files at the same time (`--jobs`).
+//!
+//! ghdl rewrites the whole work library on every analysis, so workers can't
+//! share one. Instead every worker analyzes its group of files in a private
+//! `--workdir`, seeded with the library as it was before, and the units it
+//! added are merged back into the library in the project root afterwards.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{artifacts, filter, order, BuildOptions, Check, GbError, Level};
+
+const JOBS_DIR: &str = "build/jobs";
+
+/// groups `files` so that every file only depends on files of earlier groups,
+/// by the same dependencies the sequential order goes by
+fn levels<'f>(files: &[&'f str]) -> Vec<Vec<&'f str>> {
+    let dependencies = order::dependencies(files);
+
+    fn level_of(
+        file: usize,
+        dependencies: &[Vec<usize>],
+        levels: &mut [Option<usize>],
+        visiting: &mut HashSet<usize>,
+    ) -> usize {
+        if let Some(level) = levels[file] {
+            return level;
+        }
+        // a cycle can't be analyzed in any order, let ghdl complain about it
+        if !visiting.insert(file) {
+            return 0;
+        }
+        let level = dependencies[file]
+            .iter()
+            .map(|&dep| level_of(dep, dependencies, levels, visiting) + 1)
+            .max()
+            .unwrap_or(0);
+        levels[file] = Some(level);
+        level
+    }
+
+    let mut file_levels = vec![None; files.len()];
+    let mut grouped: Vec<Vec<&str>> = Vec::new();
+    for (pos, file) in files.iter().enumerate() {
+        let level = level_of(pos, &dependencies, &mut file_levels, &mut HashSet::new());
+        if grouped.len() <= level {
+            grouped.resize(level + 1, Vec::new());
+        }
+        grouped[level].push(file);
+    }
+    grouped
+}
+
+/// a work library, split into whatever comes before the first `file` entry
+/// and the entries, which are a `file ...:` line followed by its units.
+struct Library {
+    header: Vec<String>,
+    entries: Vec<Vec<String>>,
+}
+
+impl Library {
+    fn read(path: &Path) -> Result<Option<Library>, GbError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents =
+            std::fs::read_to_string(path).fatal(format!("could not read {}", path.display()))?;
+
+        let mut library = Library {
+            header: vec![],
+            entries: vec![],
+        };
+        for line in contents.lines() {
+            if line.starts_with("file ") {
+                library.entries.push(vec![line.to_owned()]);
+            } else if let Some(entry) = library.entries.last_mut() {
+                entry.push(line.to_owned());
+            } else {
+                library.header.push(line.to_owned());
+            }
+        }
+        Ok(Some(library))
+    }
+
+    /// `file <dir> "<name>"`, leaving out the timestamps which change on every analysis
+    fn key(entry: &[String]) -> String {
+        entry[0]
+            .split_whitespace()
+            .take(3)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// takes over the entries `other` added or changed compared to `before`
+    fn merge(&mut self, other: Library, before: &[Vec<String>]) {
+        for entry in other.entries {
+            if before.contains(&entry) {
+                continue;
+            }
+            let key = Self::key(&entry);
+            match self
+                .entries
+                .iter_mut()
+                .find(|existing| Self::key(existing) == key)
+            {
+                Some(existing) => *existing = entry,
+                None => self.entries.push(entry),
+            }
+        }
+    }
+
+    fn write(&self, path: &Path) -> Result<(), GbError> {
+        let mut lines = self.header.clone();
+        lines.extend(self.entries.iter().flatten().cloned());
+        std::fs::write(path, lines.join("\n") + "\n")
+            .fatal(format!("could not write {}", path.display()))
+    }
+}
+
+fn analyze_level(level: &[&str], build: &BuildOptions) -> Result<(), GbError> {
+    let workers = build.jobs.clamp(1, level.len());
+    let mut groups = vec![Vec::new(); workers];
+    for (pos, file) in level.iter().enumerate() {
+        groups[pos % workers].push(*file);
+    }
+
+    let mut children = Vec::new();
+    for (pos, group) in groups.iter().enumerate() {
+        let workdir = PathBuf::from(JOBS_DIR).join(pos.to_string());
+        std::fs::create_dir_all(&workdir).fatal("could not create a worker directory")?;
+        for work_library in artifacts::work_libraries(Path::new(".")) {
+            std::fs::copy(&work_library, workdir.join(&work_library))
+                .fatal("could not copy the work library for a worker")?;
+        }
+
+        let mut build = build.clone();
+        build
+            .analyze_flags
+            .push(format!("--workdir={}", workdir.display()));
+        let child = filter::spawn(
+            &mut crate::analyze_command(group, &build),
+            &build.output.analyze,
+        )
+        .fatal("couldn't spawn ghdl subprocess")?;
+        children.push((workdir, group, child));
+    }
+
+    let mut failed = false;
+    for (_, _, child) in &mut children {
+        let status = child
+            .wait()
+            .fatal("couldn't await ghdl analyze subprocess, is ghdl installed?")?;
+        failed |= !status.success();
+    }
+    if failed {
+        Err(GbError {
+            message: "GHDL didn't compile successfully.".to_owned(),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    // the standard in the name of the library is whichever ghdl went by
+    let mut work_libraries = children
+        .iter()
+        .flat_map(|(workdir, _, _)| artifacts::work_libraries(workdir))
+        .collect::<Vec<_>>();
+    work_libraries.sort();
+    work_libraries.dedup();
+    if work_libraries.is_empty() {
+        Err(GbError {
+            message: "a worker did not produce a work library".to_owned(),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    for work_library in &work_libraries {
+        let work_library = Path::new(work_library);
+        let before = Library::read(work_library)?;
+        let before_entries = before
+            .as_ref()
+            .map(|library| library.entries.clone())
+            .unwrap_or_default();
+        let mut merged = before;
+        for (workdir, _, _) in &children {
+            let Some(analyzed) = Library::read(&workdir.join(work_library))? else {
+                continue;
+            };
+            match &mut merged {
+                Some(merged) => merged.merge(analyzed, &before_entries),
+                None => merged = Some(analyzed),
+            }
+        }
+        if let Some(merged) = merged {
+            merged.write(work_library)?;
+        }
+    }
+
+    // put the objects where a serial analysis would have left them
+    for (workdir, group, _) in children {
+        for file in group {
+            let object = artifacts::object_file(file)?;
+            if workdir.join(&object).exists() {
+                std::fs::rename(workdir.join(&object), &object).fatal(format!(
+                    "could not move `{object:?}` out of its worker directory"
+                ))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// analyzes `files` with up to `build.jobs` ghdl processes at once, leaving the
+/// artifacts in the build directory just like a serial analysis does.
+pub fn analyze(files: Vec<&str>, build: &BuildOptions) -> Result<(), GbError> {
+    let result = levels(&files)
+        .iter()
+        .try_for_each(|level| analyze_level(level, build));
+    let _ = std::fs::remove_dir_all(JOBS_DIR);
+
+    if let Err(err) = result {
+        // the half merged libraries in the project root are of no use to anyone
+        for work_library in artifacts::work_libraries(Path::new(".")) {
+            let _ = std::fs::remove_file(work_library);
+        }
+        return Err(err);
+    }
+    crate::cleanup_build_dir(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{self, Design};
+
+    #[test]
+    fn packages_come_a_level_before_their_users() {
+        let design = Design::new("levels");
+        let names = levels(&design.files())
+            .into_iter()
+            .map(|level| level.into_iter().map(fixture::name).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                vec!["pkg.vhd", "other.vhd"],
+                vec!["user.vhd", "pkg_body.vhd"],
+                vec!["top.vhd"],
+            ]
+        );
+    }
+}
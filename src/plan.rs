@@ -0,0 +1,121 @@
+//! `gb plan`: everything `gb run` would do, without doing any of it, for
+//! build orchestrators which want to schedule the work themselves.
+
+use std::{path::PathBuf, process::Command};
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::{artifacts, sources, transcript, wave::Waveform, BuildOptions, Check, GbError};
+
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub target: String,
+    /// the steps, in the order they have to run
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Input {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Step {
+    /// `analyze`, `elaborate` or `run`
+    pub kind: &'static str,
+    pub inputs: Vec<Input>,
+    pub cwd: String,
+    pub command: Vec<String>,
+    /// where the artifacts of the step end up once gb has moved them into place
+    pub outputs: Vec<String>,
+}
+
+fn step(kind: &'static str, command: &Command, inputs: Vec<Input>, outputs: Vec<PathBuf>) -> Step {
+    Step {
+        kind,
+        inputs,
+        cwd: command
+            .get_current_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_else(|| ".".to_owned()),
+        command: transcript::command_argv(command),
+        outputs: outputs
+            .into_iter()
+            .map(|output| output.display().to_string())
+            .collect(),
+    }
+}
+
+/// `files` is the analysis order: ghdl needs a unit analyzed before anything using it
+pub fn plan(
+    target: &str,
+    files: &[&str],
+    build: &BuildOptions,
+    file_to_exec: &str,
+    waveform: Option<Waveform>,
+) -> Result<Plan, GbError> {
+    let mut steps = Vec::new();
+    for file in files {
+        let mut outputs = vec![crate::profile::dir().join(build.work_library_file())];
+        if artifacts::writes_objects() {
+            outputs.insert(0, crate::profile::dir().join(artifacts::object_file(file)?));
+        }
+        steps.push(step(
+            "analyze",
+            &crate::analyze_command(&[file], build),
+            vec![Input {
+                path: file.to_string(),
+                sha256: sources::fingerprint(file.as_ref())?,
+            }],
+            outputs,
+        ));
+    }
+
+    steps.push(step(
+        "elaborate",
+        &crate::elaborate_command(file_to_exec, build)?,
+        vec![],
+        crate::executable_path(file_to_exec)?.into_iter().collect(),
+    ));
+
+    let mut run_outputs = vec![PathBuf::from("build").join(target).join("run.log")];
+    run_outputs.extend(waveform.as_ref().map(Waveform::built_path));
+    steps.push(step(
+        "run",
+        &crate::run_command(file_to_exec, waveform, build)?,
+        vec![],
+        run_outputs,
+    ));
+
+    Ok(Plan {
+        target: target.to_owned(),
+        steps,
+    })
+}
+
+pub fn print(plan: &Plan, json: bool) -> Result<(), GbError> {
+    if json {
+        let json =
+            serde_json::to_string_pretty(plan).fatal("could not serialize the build plan")?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    for (pos, step) in plan.steps.iter().enumerate() {
+        println!(
+            "{} {} {}",
+            format!("{:>3}.", pos + 1).blue().bold(),
+            step.kind.green().bold(),
+            step.command.join(" ")
+        );
+        if step.cwd != "." {
+            println!("       in {}", step.cwd);
+        }
+        for output in &step.outputs {
+            println!("       -> {output}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! `gb probe`: "what was X at time T" as a one-liner.
+
+use std::path::Path;
+
+use colored::Colorize;
+
+use crate::{
+    vcd::{self, Vcd},
+    Check, GbError,
+};
+
+pub fn probe(vcd_path: &Path, at: &[String], signals: &[String]) -> Result<(), GbError> {
+    let times = at
+        .iter()
+        .map(|time| {
+            vcd::parse_time(time).fatal(format!(
+                "could not understand the time `{time}`, write it like `150ns`"
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let vcd = Vcd::load(vcd_path)?;
+    let matching = vcd.select(signals)?;
+    let name_width = matching
+        .iter()
+        .map(|signal| signal.name.len())
+        .max()
+        .unwrap_or_default();
+
+    for time in times {
+        let mut header = format!("@{}", vcd::format_time(time));
+        if time > vcd.end_time() {
+            header.push_str(&format!(
+                " (after the last change at {})",
+                vcd::format_time(vcd.end_time())
+            ));
+        }
+        println!("{}", header.blue().bold());
+        for signal in &matching {
+            let value = vcd.value_at(signal, time).unwrap_or("-");
+            println!("  {:name_width$}  {value}", signal.name);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! Build profiles: `debug`, which gb builds by default, `release`, picked with
+//! `--release`, or any other one picked with `--profile <name>`. every profile
+//! keeps its libraries, objects and executables in `build/<profile>/`, so
+//! switching between them doesn't throw the other one's build away.
+//!
+//! ```toml
+//! [profile.release]
+//! opt-level = 2               # -O2, for ghdl's llvm and gcc backends
+//! debug = false               # -g
+//! warnings = { hide = false } # --warn-no-hide
+//! flags = ["-frelaxed"]       # anything else, for analysis and elaboration
+//! ```
+//!
+//! a profile without a table in gb.toml adds no flags at all, mcode ghdl has
+//! no use for `-O` or `-g`.
+//!
+//! targets keep their run logs in `build/<target>/`, so no target can be
+//! named like a profile.
+
+use std::path::PathBuf;
+
+use once_cell::sync::OnceCell;
+use toml_edit::{Document, Item};
+
+use crate::{BuildOptions, Check, GbError, Level};
+
+pub const DEFAULT: &str = "debug";
+
+/// where `gb cover` builds, instrumented objects can't mix with others
+pub const COVERAGE: &str = "coverage";
+
+/// directories of `build/` gb already uses for other things
+const RESERVED: &[&str] = &[
+    COVERAGE, "doc", "fpga", "jobs", "lib", "package", "publish", "src", "synth", "test",
+];
+
+static SELECTED: OnceCell<String> = OnceCell::new();
+
+/// where the selected profile is built, `build/debug/` until one is selected
+pub fn dir() -> PathBuf {
+    PathBuf::from("build").join(SELECTED.get().map(String::as_str).unwrap_or(DEFAULT))
+}
+
+fn flags(name: &str, profile: &Item) -> Result<Vec<String>, GbError> {
+    let mut flags = Vec::new();
+    if let Some(level) = profile.get("opt-level") {
+        let level = level
+            .as_integer()
+            .filter(|level| (0..=3).contains(level))
+            .fatal(format!("`profile.{name}.opt-level` must be 0, 1, 2 or 3"))?;
+        flags.push(format!("-O{level}"));
+    }
+    if let Some(debug) = profile.get("debug") {
+        let debug = debug
+            .as_bool()
+            .fatal(format!("`profile.{name}.debug` must be true or false"))?;
+        if debug {
+            flags.push("-g".to_owned());
+        }
+    }
+    if let Some(warnings) = profile.get("warnings") {
+        let warnings = warnings.as_table_like().fatal(format!(
+            "`profile.{name}.warnings` must be a table like `{{ hide = false }}`"
+        ))?;
+        for (warning, enabled) in warnings.iter() {
+            let enabled = enabled.as_bool().fatal(format!(
+                "`profile.{name}.warnings.{warning}` must be true or false"
+            ))?;
+            flags.push(if enabled {
+                format!("--warn-{warning}")
+            } else {
+                format!("--warn-no-{warning}")
+            });
+        }
+    }
+    if let Some(extra) = profile.get("flags") {
+        let extra = extra.as_array().fatal(format!(
+            "`profile.{name}.flags` must be an array of strings"
+        ))?;
+        for flag in extra {
+            let flag = flag
+                .as_str()
+                .fatal(format!("`profile.{name}.flags` must only contain strings"))?;
+            flags.push(flag.to_owned());
+        }
+    }
+    Ok(flags)
+}
+
+/// whether `target` can be built into `build/<target>/` without writing
+/// into a profile's directory, or one gb uses for something else
+pub fn check_target_name(doc: &Document, target: &str) -> Result<(), GbError> {
+    let profile = target == DEFAULT
+        || target == "release"
+        || doc
+            .get("profile")
+            .and_then(|profiles| profiles.get(target))
+            .is_some();
+    if profile || RESERVED.contains(&target) {
+        Err(GbError {
+            message: format!(
+                "`{target}` can't be the name of a target, gb builds into build/{target}/ already"
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+/// whether every `[profile.<name>]` of gb.toml is valid, and no target is
+/// named like a directory of `build/` gb uses
+pub fn check(doc: &Document) -> Result<(), GbError> {
+    if let Some(targets) = doc.get("target").and_then(Item::as_table_like) {
+        for (target, _) in targets.iter() {
+            check_target_name(doc, target)?;
+        }
+    }
+    let Some(profiles) = doc
+        .get("profile")
+        .and_then(|profiles| profiles.as_table_like())
+    else {
+        return Ok(());
+    };
+    for (name, profile) in profiles.iter() {
+        flags(name, profile)?;
+    }
+    Ok(())
+}
+
+/// builds into `build/coverage/` instead of the selected profile's directory
+pub fn select_coverage() {
+    let _ = SELECTED.set(COVERAGE.to_owned());
+}
+
+/// picks the profile for this run and adds its flags to `build`
+pub fn select(
+    doc: &Document,
+    release: bool,
+    profile: Option<&str>,
+    build: &mut BuildOptions,
+) -> Result<(), GbError> {
+    let name = match (release, profile) {
+        (true, _) => "release",
+        (false, Some(profile)) => profile,
+        (false, None) => DEFAULT,
+    };
+    if RESERVED.contains(&name) || name.is_empty() || name.contains(['/', '\\', '.']) {
+        Err(GbError {
+            message: format!("`{name}` can't be the name of a profile"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let table = doc.get("profile").and_then(|profiles| profiles.get(name));
+    let built_in = name == DEFAULT || name == "release";
+    if table.is_none() && !built_in {
+        Err(GbError {
+            message: format!("there is no `[profile.{name}]` in gb.toml"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    if let Some(table) = table {
+        let flags = flags(name, table)?;
+        build.analyze_flags.extend(flags.iter().cloned());
+        build.elaborate_flags.extend(flags);
+    }
+    // gb only runs one build at a time, the first selection sticks
+    let _ = SELECTED.set(name.to_owned());
+    Ok(())
+}
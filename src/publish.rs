@@ -0,0 +1,283 @@
+//! `gb publish`: copies what a build produced somewhere the rest of the team
+//! can get at it, together with a `build-info.json` telling how it was built.
+//!
+//! ```toml
+//! [target.counter.publish]
+//! dest = "s3://our-bucket/counter"
+//! artifacts = ["build/counter/run.log", "build/release/*.vcd", "reports/*.rpt"]
+//! ```
+//!
+//! a destination is a directory, `s3://bucket/prefix` (through the `aws` cli)
+//! or `ssh://[user@]host/path` (through `ssh` and `scp`). `--dest` beats the
+//! one in gb.toml. without `artifacts`, the run log, the waveform and the
+//! executable of the target are published, whichever of them exist. nothing
+//! is built, run `gb run` first.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use colored::Colorize;
+use serde::Serialize;
+use toml_edit::Item;
+
+use crate::{sources, BuildOptions, Check, GbError, Level};
+
+#[derive(Debug)]
+enum Destination {
+    Dir(PathBuf),
+    S3(String),
+    Ssh { host: String, path: String },
+}
+
+impl Destination {
+    fn parse(dest: &str) -> Result<Destination, GbError> {
+        if dest.starts_with("s3://") {
+            return Ok(Destination::S3(dest.trim_end_matches('/').to_owned()));
+        }
+        if let Some(rest) = dest.strip_prefix("ssh://") {
+            let (host, path) = rest.split_once('/').fatal(format!(
+                "`{dest}` has no path, it should look like `ssh://host/path`"
+            ))?;
+            return Ok(Destination::Ssh {
+                host: host.to_owned(),
+                path: format!("/{path}"),
+            });
+        }
+        if let Some((scheme, _)) = dest
+            .split_once("://")
+            .filter(|(scheme, _)| *scheme != "file")
+        {
+            return Err(GbError {
+                message: format!(
+                    "can't publish to `{scheme}://`, only to a directory, `s3://` or `ssh://`"
+                ),
+                level: Level::Fatal,
+                source: None,
+            });
+        }
+        Ok(Destination::Dir(PathBuf::from(
+            dest.strip_prefix("file://").unwrap_or(dest),
+        )))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Input {
+    path: String,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BuildInfo {
+    target: String,
+    gb_version: &'static str,
+    published: String,
+    /// the commit the sources were at, `-dirty` when they had changes on top
+    commit: Option<String>,
+    std: Option<String>,
+    generics: Vec<(String, String)>,
+    sources: Vec<Input>,
+    artifacts: Vec<Input>,
+}
+
+fn git_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn commit() -> Option<String> {
+    let commit = git_output(&["rev-parse", "HEAD"])?;
+    let dirty = git_output(&["status", "--porcelain"]).is_some_and(|status| !status.is_empty());
+    Some(if dirty {
+        format!("{commit}-dirty")
+    } else {
+        commit
+    })
+}
+
+fn inputs(paths: &[PathBuf]) -> Result<Vec<Input>, GbError> {
+    paths
+        .iter()
+        .map(|path| {
+            Ok(Input {
+                path: path.display().to_string(),
+                sha256: sources::fingerprint(path)?,
+            })
+        })
+        .collect()
+}
+
+/// the files matched by the `artifacts` patterns, every pattern has to match something
+fn configured_artifacts(target: &str, artifacts: &Item) -> Result<Vec<PathBuf>, GbError> {
+    let patterns = artifacts.as_array().fatal(format!(
+        "`target.{target}.publish.artifacts` must be an array of paths"
+    ))?;
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let pattern = pattern.as_str().fatal(format!(
+            "`target.{target}.publish.artifacts` must only contain strings"
+        ))?;
+        let matches = glob::glob(pattern)
+            .fatal(format!("`{pattern}` is not a valid pattern"))?
+            .flatten()
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            Err(GbError {
+                message: format!("nothing to publish matches `{pattern}`, was it built yet?"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        files.extend(matches);
+    }
+    Ok(files)
+}
+
+fn copy_into(dir: &Path, files: &[PathBuf]) -> Result<(), GbError> {
+    std::fs::create_dir_all(dir).fatal(format!("could not create `{}`", dir.display()))?;
+    for file in files {
+        let name = file
+            .file_name()
+            .fatal(format!("`{}` is not a file", file.display()))?;
+        std::fs::copy(file, dir.join(name)).fatal(format!(
+            "could not copy `{}` to `{}`",
+            file.display(),
+            dir.display()
+        ))?;
+    }
+    Ok(())
+}
+
+fn run(command: &mut Command, tool: &str) -> Result<(), GbError> {
+    let status = command.status().fatal(format!(
+        "could not run `{tool}`, which gb needs to publish there"
+    ))?;
+    if !status.success() {
+        Err(GbError {
+            message: format!("`{tool}` failed to upload the artifacts"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+/// `outputs` are the run log, the waveform and the executable, which are
+/// published when the target has no `artifacts`
+pub fn publish(
+    target: &str,
+    target_info: &Item,
+    files: &[&str],
+    outputs: Vec<PathBuf>,
+    dest: Option<&str>,
+    build: &BuildOptions,
+) -> Result<(), GbError> {
+    let config = target_info.get("publish");
+    let dest = match dest {
+        Some(dest) => dest.to_owned(),
+        None => config
+            .and_then(|config| config.get("dest"))
+            .and_then(|dest| dest.as_str())
+            .map(ToOwned::to_owned)
+            .fatal(format!(
+                "nowhere to publish to, pass `--dest` or set `target.{target}.publish.dest`"
+            ))?,
+    };
+    let destination = Destination::parse(&dest)?;
+
+    let artifacts = match config.and_then(|config| config.get("artifacts")) {
+        Some(artifacts) => configured_artifacts(target, artifacts)?,
+        None => outputs
+            .into_iter()
+            .filter(|output| output.is_file())
+            .collect(),
+    };
+    if artifacts.is_empty() {
+        Err(GbError {
+            message: format!("{target} has nothing to publish yet, run it first"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    for (pos, artifact) in artifacts.iter().enumerate() {
+        let name = artifact.file_name();
+        if artifacts[..pos]
+            .iter()
+            .any(|other| other.file_name() == name)
+        {
+            Err(GbError {
+                message: format!(
+                    "more than one artifact is called `{}`, they'd overwrite each other",
+                    artifact.display()
+                ),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+    }
+
+    let info = BuildInfo {
+        target: target.to_owned(),
+        gb_version: env!("CARGO_PKG_VERSION"),
+        published: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        commit: commit(),
+        std: build.std.clone(),
+        generics: build.generics.clone(),
+        sources: inputs(&files.iter().map(PathBuf::from).collect::<Vec<_>>())?,
+        artifacts: inputs(&artifacts)?,
+    };
+    // everything goes out from one directory, so every destination gets the same
+    let staging = PathBuf::from("build/publish").join(target);
+    let _ = std::fs::remove_dir_all(&staging);
+    copy_into(&staging, &artifacts)?;
+    let info = serde_json::to_string_pretty(&info).fatal("could not serialize the build info")?;
+    std::fs::write(staging.join("build-info.json"), info)
+        .fatal("could not write build-info.json")?;
+
+    eprintln!(
+        "  {}  {}",
+        "[publish]".blue().bold(),
+        format!("Publishing {} artifact(s) to {dest}", artifacts.len())
+            .green()
+            .bold()
+    );
+    let mut staged = artifacts
+        .iter()
+        .filter_map(|artifact| artifact.file_name())
+        .map(|name| staging.join(name))
+        .collect::<Vec<_>>();
+    staged.push(staging.join("build-info.json"));
+    match destination {
+        Destination::Dir(dir) => copy_into(&dir, &staged)?,
+        Destination::S3(url) => run(
+            Command::new("aws")
+                .args(["s3", "cp", "--recursive"])
+                .arg(&staging)
+                .arg(format!("{url}/")),
+            "aws",
+        )?,
+        Destination::Ssh { host, path } => {
+            run(
+                Command::new("ssh").arg(&host).args(["mkdir", "-p", &path]),
+                "ssh",
+            )?;
+            run(
+                Command::new("scp")
+                    .args(&staged)
+                    .arg(format!("{host}:{path}/")),
+                "scp",
+            )?;
+        }
+    }
+    for (pos, artifact) in artifacts.iter().enumerate() {
+        eprintln!("  {}. {}", pos + 1, artifact.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,276 @@
+//! `gb wave --export-svg/--export-png`: draws the selected signals of a dump
+//! over a time range, for reports and pull requests.
+
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use colored::Colorize;
+
+use crate::{
+    vcd::{self, Signal, Vcd},
+    Check, GbError, Level,
+};
+
+const ROW_HEIGHT: f64 = 28.0;
+const WAVE_HEIGHT: f64 = 16.0;
+const PLOT_WIDTH: f64 = 900.0;
+const AXIS_HEIGHT: f64 = 30.0;
+const CHAR_WIDTH: f64 = 7.5;
+const MARGIN: f64 = 10.0;
+const TICKS: u64 = 10;
+
+/// escapes text for use inside svg elements and attributes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// binary vectors read better in hex, anything with `x`, `z`, ... stays as it is
+fn display_value(signal: &Signal, value: &str) -> String {
+    if signal.width == 1 || !value.chars().all(|c| c == '0' || c == '1') {
+        return value.to_owned();
+    }
+    let digits = value.len().div_ceil(4);
+    let padded = format!("{value:0>width$}", width = digits * 4);
+    let hex = padded
+        .as_bytes()
+        .chunks(4)
+        .map(|nibble| {
+            let nibble = nibble
+                .iter()
+                .fold(0, |acc, bit| acc * 2 + u32::from(*bit == b'1'));
+            char::from_digit(nibble, 16).unwrap_or('?')
+        })
+        .collect::<String>();
+    format!("0x{hex}")
+}
+
+/// the values a signal holds between `from` and `to`, as `(start, end, value)`
+fn segments<'v>(vcd: &'v Vcd, signal: &Signal, from: u64, to: u64) -> Vec<(u64, u64, &'v str)> {
+    let mut starts = Vec::new();
+    if let Some(value) = vcd.value_at(signal, from) {
+        starts.push((from, value));
+    }
+    starts.extend(
+        vcd.changes(signal)
+            .iter()
+            .filter(|(time, _)| *time > from && *time < to)
+            .map(|(time, value)| (*time, value.as_str())),
+    );
+
+    let ends = starts
+        .iter()
+        .skip(1)
+        .map(|(time, _)| *time)
+        .chain(std::iter::once(to));
+    starts
+        .iter()
+        .zip(ends)
+        .map(|((start, value), end)| (*start, end, *value))
+        .collect()
+}
+
+pub fn svg(vcd: &Vcd, signals: &[&Signal], from: u64, to: u64) -> String {
+    let label_width = signals
+        .iter()
+        .map(|signal| signal.name.len())
+        .max()
+        .unwrap_or_default() as f64
+        * CHAR_WIDTH
+        + 2.0 * MARGIN;
+    let width = label_width + PLOT_WIDTH + MARGIN;
+    let height = AXIS_HEIGHT + ROW_HEIGHT * signals.len() as f64 + MARGIN;
+    let x = |time: u64| label_width + (time - from) as f64 / (to - from) as f64 * PLOT_WIDTH;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="12">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{width}" height="{height}" fill="white"/>"#
+    );
+
+    // the time axis, with a faint grid line for every tick
+    for tick in 0..=TICKS {
+        let time = from + (to - from) * tick / TICKS;
+        let tick_x = x(time);
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{tick_x:.1}" y1="{}" x2="{tick_x:.1}" y2="{}" stroke="#ddd"/>"##,
+            AXIS_HEIGHT - 6.0,
+            height - MARGIN
+        );
+        let _ = writeln!(
+            svg,
+            r##"<text x="{tick_x:.1}" y="{}" text-anchor="middle" fill="#555">{}</text>"##,
+            AXIS_HEIGHT - 10.0,
+            vcd::format_time(time)
+        );
+    }
+
+    for (row, signal) in signals.iter().enumerate() {
+        let top = AXIS_HEIGHT + ROW_HEIGHT * row as f64 + (ROW_HEIGHT - WAVE_HEIGHT) / 2.0;
+        let bottom = top + WAVE_HEIGHT;
+        let middle = top + WAVE_HEIGHT / 2.0;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{MARGIN}" y="{:.1}" dominant-baseline="middle">{}</text>"#,
+            middle,
+            escape(&signal.name)
+        );
+
+        for (start, end, value) in segments(vcd, signal, from, to) {
+            let (x0, x1) = (x(start), x(end));
+            let unknown = !value.chars().all(|c| c == '0' || c == '1');
+            let colour = if unknown { "#c33" } else { "#282" };
+
+            if signal.width == 1 {
+                let path = match value {
+                    "1" => format!("M{x0:.1} {top:.1} H{x1:.1}"),
+                    "0" => format!("M{x0:.1} {bottom:.1} H{x1:.1}"),
+                    _ => format!("M{x0:.1} {middle:.1} H{x1:.1}"),
+                };
+                let _ = writeln!(
+                    svg,
+                    r#"<path d="{path} M{x0:.1} {top:.1} V{bottom:.1}" stroke="{colour}" fill="none"/>"#
+                );
+            } else {
+                // the usual bus shape, pinched at every change
+                let pinch = (x1 - x0).min(4.0) / 2.0;
+                let _ = writeln!(
+                    svg,
+                    r#"<path d="M{x0:.1} {middle:.1} L{:.1} {top:.1} H{:.1} L{x1:.1} {middle:.1} L{:.1} {bottom:.1} H{:.1} Z" stroke="{colour}" fill="none"/>"#,
+                    x0 + pinch,
+                    x1 - pinch,
+                    x1 - pinch,
+                    x0 + pinch
+                );
+                let text = display_value(signal, value);
+                // only label what fits
+                if text.len() as f64 * CHAR_WIDTH < x1 - x0 - 2.0 * pinch {
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{:.1}" y="{middle:.1}" text-anchor="middle" dominant-baseline="middle">{}</text>"#,
+                        (x0 + x1) / 2.0,
+                        escape(&text)
+                    );
+                }
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// there's no rasterizer in gb, so pngs go through whichever svg converter is installed
+fn svg_to_png(svg: &str, out: &Path) -> Result<(), GbError> {
+    let converters: [(&str, Vec<String>); 2] = [
+        (
+            "rsvg-convert",
+            vec![
+                "--format=png".to_owned(),
+                format!("--output={}", out.display()),
+            ],
+        ),
+        (
+            "magick",
+            vec!["svg:-".to_owned(), format!("png:{}", out.display())],
+        ),
+    ];
+
+    for (converter, args) in converters {
+        let Ok(mut child) = Command::new(converter)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+        else {
+            continue;
+        };
+        child
+            .stdin
+            .take()
+            .fatal("could not pipe the waveform to the converter")?
+            .write_all(svg.as_bytes())
+            .fatal(format!("could not pipe the waveform to {converter}"))?;
+        let status = child.wait().fatal(format!("failed to await {converter}"))?;
+        if !status.success() {
+            Err(GbError {
+                message: format!("{converter} could not convert the waveform to a png"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        return Ok(());
+    }
+
+    Err(GbError {
+        message: "exporting a png needs `rsvg-convert` (librsvg) or `magick` (imagemagick) installed, `--export-svg` works without either".to_owned(),
+        level: Level::Fatal,
+        source: None,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Svg,
+    Png,
+}
+
+pub fn export(
+    vcd_path: &Path,
+    out: &Path,
+    format: Format,
+    signals: &[String],
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(), GbError> {
+    let parse = |time: &str| {
+        vcd::parse_time(time).fatal(format!(
+            "could not understand the time `{time}`, write it like `150ns`"
+        ))
+    };
+    let vcd = Vcd::load(vcd_path)?;
+    let from = from.map(parse).transpose()?.unwrap_or(0);
+    let to = match to {
+        Some(to) => parse(to)?,
+        None => vcd.end_time(),
+    };
+    if to <= from {
+        Err(GbError {
+            message: format!(
+                "nothing to draw between {} and {}",
+                vcd::format_time(from),
+                vcd::format_time(to)
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let svg = svg(&vcd, &vcd.select(signals)?, from, to);
+    if let Some(parent) = out.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .fatal(format!("could not create `{}`", parent.display()))?;
+    }
+    match format {
+        Format::Svg => {
+            std::fs::write(out, svg).fatal(format!("could not write `{}`", out.display()))?
+        }
+        Format::Png => svg_to_png(&svg, out)?,
+    }
+
+    eprintln!(
+        "  {}  {}",
+        "[wave]".blue().bold(),
+        format!("Wrote {}", out.display()).green().bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! gb's own warnings and notes, the ones that don't stop a build. an error
+//! at `Level::Warning` or `Level::Info` goes through `emit`, which prints it
+//! and carries on, while `Error` and `Fatal` still abort.
+//!
+//! `--deny-warnings`, or `strict = true`, turns every warning into an error,
+//! for CI that wants gb.toml kept clean. ghdl's own warnings aren't gb's, see
+//! `--warnings-baseline` and `[output]` for those.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use colored::Colorize;
+
+use crate::{GbError, Level};
+
+static DENY_WARNINGS: AtomicBool = AtomicBool::new(false);
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn deny_warnings() {
+    DENY_WARNINGS.store(true, Ordering::Relaxed);
+}
+
+pub fn warning(message: impl Into<String>) -> GbError {
+    GbError {
+        message: message.into(),
+        level: Level::Warning,
+        source: None,
+    }
+}
+
+pub fn info(message: impl Into<String>) -> GbError {
+    GbError {
+        message: message.into(),
+        level: Level::Info,
+        source: None,
+    }
+}
+
+/// prints a warning or a note, and fails on anything worse, or on a warning
+/// with `--deny-warnings`
+pub fn emit(diagnostic: GbError) -> Result<(), GbError> {
+    match diagnostic.level {
+        Level::Fatal | Level::Error => Err(diagnostic),
+        Level::Warning if DENY_WARNINGS.load(Ordering::Relaxed) => Err(GbError {
+            message: format!("{} (warnings are denied)", diagnostic.message),
+            level: Level::Error,
+            source: diagnostic.source,
+        }),
+        Level::Warning => {
+            WARNINGS.fetch_add(1, Ordering::Relaxed);
+            eprintln!(
+                "{} {}: {}",
+                "[gb-warning]".yellow().bold(),
+                "[build]".blue().bold(),
+                diagnostic.message
+            );
+            Ok(())
+        }
+        Level::Info => {
+            eprintln!(
+                "{} {}: {}",
+                "[gb-info]".cyan().bold(),
+                "[build]".blue().bold(),
+                diagnostic.message
+            );
+            Ok(())
+        }
+    }
+}
+
+/// a reminder of the warnings at the end of a run, they scroll away otherwise
+pub fn summary() {
+    let warnings = WARNINGS.load(Ordering::Relaxed);
+    if warnings > 0 {
+        eprintln!(
+            "{} {}: {warnings} warning(s), `--deny-warnings` makes them fail the build",
+            "[gb-warning]".yellow().bold(),
+            "[build]".blue().bold(),
+        );
+    }
+}
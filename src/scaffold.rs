@@ -0,0 +1,560 @@
+//! `gb new` and `gb init`: a project that runs out of the box, with a starter
+//! entity named after the project and a testbench driving it. `--template`
+//! picks what the entity is:
+//!
+//! - `counter`, an 8 bit counter, the default of `gb new`
+//! - `alu`, an 8 bit alu adding, subtracting and combining bits
+//! - `uart`, a uart transmitter, 8N1 at a generic number of clocks per bit
+//! - `empty`, only a gb.toml to fill in, the default of `gb init`
+//!
+//! `gb init` on a terminal without `--template` asks instead, see `wizard`.
+
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::{gitignore, wizard, Check, GbError, Level};
+
+/// the entity has to be a vhdl identifier, so `my-project` becomes `my_project`
+pub fn entity_name(project: &str) -> Result<String, GbError> {
+    let entity = project.replace('-', "_").to_lowercase();
+    let valid = entity.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !entity.ends_with('_')
+        && !entity.contains("__")
+        && entity
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        Err(GbError {
+            message: format!(
+                "`{project}` can't be turned into a vhdl entity name, use letters, digits, `-` and `_`, starting with a letter"
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(entity)
+}
+
+/// what the starter entity of a new project is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Template {
+    /// an 8 bit counter
+    #[default]
+    Counter,
+    /// an 8 bit alu
+    Alu,
+    /// a uart transmitter
+    Uart,
+    /// no sources, only a gb.toml to fill in
+    Empty,
+}
+
+/// what goes into a new project, from the flags or from `gb init`'s questions
+pub struct Setup {
+    pub target: String,
+    pub entity: String,
+    /// `None` leaves it to ghdl
+    pub std: Option<String>,
+    pub viewer: String,
+    pub template: Template,
+}
+
+/// the options an empty gb.toml lists, to uncomment as needed
+const MORE_OPTIONS: &str = r#"# wave-format = "ghw"
+# vcd-viewer = "surfer" # overrides default.vcd-viewer for this target
+# library = "my_lib" # analyze into this library instead of `work`
+# generics = { WIDTH = 8 }
+# dump-start = "1ms" # only keep this part of the simulation in the vcd
+# dump-stop = "1.2ms"
+# sim = { stop-time = "100ns", ieee-asserts = "disable-at-0" }
+# memory-limit = "2GiB"
+# cpu-time-limit = "5min"
+"#;
+
+fn manifest(setup: &Setup) -> String {
+    let Setup {
+        target,
+        entity,
+        viewer,
+        ..
+    } = setup;
+    let empty = setup.template == Template::Empty;
+    let mut manifest = format!("default.target = \"{target}\"\ndefault.vcd-viewer = \"{viewer}\"");
+    if empty {
+        manifest.push_str(r#" # or "surfer", or a command like "myviewer {file}""#);
+    }
+    manifest.push_str(&format!("\n\n[target.{target}]\n"));
+    if empty {
+        manifest.push_str(
+            "files = []\n\n# execute = \"your-file-to-execute\"\n# vcd-name = \"your-vcd-name.vcd\"\n",
+        );
+    } else {
+        manifest.push_str(&format!(
+            "files = [\"src/{entity}.vhd\", \"src/{entity}_tb.vhd\"]\nexecute = \"src/{entity}_tb.vhd\"\nvcd-name = \"{entity}.vcd\"\n"
+        ));
+    }
+    match &setup.std {
+        Some(std) => manifest.push_str(&format!("std = \"{std}\"\n")),
+        None => manifest.push_str("# std = \"08\"\n"),
+    }
+    if empty {
+        manifest.push_str(MORE_OPTIONS);
+    }
+    manifest
+}
+
+fn counter(entity: &str) -> String {
+    format!(
+        r#"library ieee;
+use ieee.std_logic_1164.all;
+use ieee.numeric_std.all;
+
+-- counts up on every rising edge of `clk`, back to zero while `rst` is high
+entity {entity} is
+  port (
+    clk   : in  std_logic;
+    rst   : in  std_logic;
+    count : out std_logic_vector(7 downto 0)
+  );
+end entity {entity};
+
+architecture rtl of {entity} is
+  signal counter : unsigned(7 downto 0) := (others => '0');
+begin
+  process (clk)
+  begin
+    if rising_edge(clk) then
+      if rst = '1' then
+        counter <= (others => '0');
+      else
+        counter <= counter + 1;
+      end if;
+    end if;
+  end process;
+
+  count <= std_logic_vector(counter);
+end architecture rtl;
+"#
+    )
+}
+
+fn counter_testbench(entity: &str) -> String {
+    format!(
+        r#"library ieee;
+use ieee.std_logic_1164.all;
+use ieee.numeric_std.all;
+
+entity {entity}_tb is
+end entity {entity}_tb;
+
+architecture sim of {entity}_tb is
+  component {entity} is
+    port (
+      clk   : in  std_logic;
+      rst   : in  std_logic;
+      count : out std_logic_vector(7 downto 0)
+    );
+  end component;
+
+  signal clk   : std_logic := '0';
+  signal rst   : std_logic := '1';
+  signal count : std_logic_vector(7 downto 0);
+  signal done  : boolean := false;
+begin
+  dut : {entity}
+    port map (clk => clk, rst => rst, count => count);
+
+  -- the simulation ends once nothing is left to do, so stop the clock when done
+  clk <= not clk after 5 ns when not done else clk;
+
+  process
+  begin
+    wait for 20 ns;
+    rst <= '0';
+    for i in 1 to 10 loop
+      wait until rising_edge(clk);
+    end loop;
+    wait for 1 ns;
+    assert unsigned(count) = 10
+      report "expected the counter to reach 10" severity error;
+    report "simulation finished";
+    done <= true;
+    wait;
+  end process;
+end architecture sim;
+"#
+    )
+}
+
+fn alu(entity: &str) -> String {
+    format!(
+        r#"library ieee;
+use ieee.std_logic_1164.all;
+use ieee.numeric_std.all;
+
+-- an 8 bit alu, `op` picks a + b, a - b, a and b, a or b or a xor b
+entity {entity} is
+  port (
+    a      : in  std_logic_vector(7 downto 0);
+    b      : in  std_logic_vector(7 downto 0);
+    op     : in  std_logic_vector(2 downto 0);
+    result : out std_logic_vector(7 downto 0);
+    zero   : out std_logic
+  );
+end entity {entity};
+
+architecture rtl of {entity} is
+  signal r : std_logic_vector(7 downto 0);
+begin
+  with op select r <=
+    std_logic_vector(unsigned(a) + unsigned(b)) when "000",
+    std_logic_vector(unsigned(a) - unsigned(b)) when "001",
+    a and b when "010",
+    a or b when "011",
+    a xor b when "100",
+    (others => '0') when others;
+
+  result <= r;
+  zero   <= '1' when r = x"00" else '0';
+end architecture rtl;
+"#
+    )
+}
+
+fn alu_testbench(entity: &str) -> String {
+    format!(
+        r#"library ieee;
+use ieee.std_logic_1164.all;
+use ieee.numeric_std.all;
+
+entity {entity}_tb is
+end entity {entity}_tb;
+
+architecture sim of {entity}_tb is
+  component {entity} is
+    port (
+      a      : in  std_logic_vector(7 downto 0);
+      b      : in  std_logic_vector(7 downto 0);
+      op     : in  std_logic_vector(2 downto 0);
+      result : out std_logic_vector(7 downto 0);
+      zero   : out std_logic
+    );
+  end component;
+
+  signal a      : std_logic_vector(7 downto 0) := (others => '0');
+  signal b      : std_logic_vector(7 downto 0) := (others => '0');
+  signal op     : std_logic_vector(2 downto 0) := "000";
+  signal result : std_logic_vector(7 downto 0);
+  signal zero   : std_logic;
+begin
+  dut : {entity}
+    port map (a => a, b => b, op => op, result => result, zero => zero);
+
+  process
+    procedure check (x, y : natural; operation : std_logic_vector(2 downto 0); expected : natural) is
+    begin
+      a  <= std_logic_vector(to_unsigned(x, 8));
+      b  <= std_logic_vector(to_unsigned(y, 8));
+      op <= operation;
+      wait for 10 ns;
+      assert unsigned(result) = expected
+        report "wrong result for " & integer'image(x) & " and " & integer'image(y)
+        severity error;
+    end procedure;
+  begin
+    check(3, 4, "000", 7);
+    check(10, 3, "001", 7);
+    check(12, 10, "010", 8);
+    check(12, 10, "011", 14);
+    check(12, 10, "100", 6);
+    check(5, 5, "001", 0);
+    assert zero = '1' report "expected `zero` for 5 - 5" severity error;
+    report "simulation finished";
+    wait;
+  end process;
+end architecture sim;
+"#
+    )
+}
+
+fn uart(entity: &str) -> String {
+    format!(
+        r#"library ieee;
+use ieee.std_logic_1164.all;
+
+-- sends `data` on `tx`, 8N1, after `start` was high for a clock. `busy` stays
+-- high until the stop bit is out.
+entity {entity} is
+  generic (
+    CLKS_PER_BIT : positive := 4
+  );
+  port (
+    clk   : in  std_logic;
+    rst   : in  std_logic;
+    start : in  std_logic;
+    data  : in  std_logic_vector(7 downto 0);
+    tx    : out std_logic;
+    busy  : out std_logic
+  );
+end entity {entity};
+
+architecture rtl of {entity} is
+  type state_t is (idle, start_bit, data_bits, stop_bit);
+  signal state   : state_t := idle;
+  signal ticks   : natural range 0 to CLKS_PER_BIT - 1 := 0;
+  signal bit_idx : natural range 0 to 7 := 0;
+  signal shift   : std_logic_vector(7 downto 0) := (others => '0');
+begin
+  process (clk)
+  begin
+    if rising_edge(clk) then
+      if rst = '1' then
+        state <= idle;
+        ticks <= 0;
+        tx    <= '1';
+      else
+        case state is
+          when idle =>
+            tx <= '1';
+            if start = '1' then
+              shift <= data;
+              state <= start_bit;
+            end if;
+          when start_bit =>
+            tx <= '0';
+            if ticks = CLKS_PER_BIT - 1 then
+              ticks   <= 0;
+              bit_idx <= 0;
+              state   <= data_bits;
+            else
+              ticks <= ticks + 1;
+            end if;
+          when data_bits =>
+            tx <= shift(bit_idx);
+            if ticks = CLKS_PER_BIT - 1 then
+              ticks <= 0;
+              if bit_idx = 7 then
+                state <= stop_bit;
+              else
+                bit_idx <= bit_idx + 1;
+              end if;
+            else
+              ticks <= ticks + 1;
+            end if;
+          when stop_bit =>
+            tx <= '1';
+            if ticks = CLKS_PER_BIT - 1 then
+              ticks <= 0;
+              state <= idle;
+            else
+              ticks <= ticks + 1;
+            end if;
+        end case;
+      end if;
+    end if;
+  end process;
+
+  busy <= '0' when state = idle else '1';
+end architecture rtl;
+"#
+    )
+}
+
+fn uart_testbench(entity: &str) -> String {
+    format!(
+        r#"library ieee;
+use ieee.std_logic_1164.all;
+
+entity {entity}_tb is
+end entity {entity}_tb;
+
+architecture sim of {entity}_tb is
+  component {entity} is
+    generic (
+      CLKS_PER_BIT : positive := 4
+    );
+    port (
+      clk   : in  std_logic;
+      rst   : in  std_logic;
+      start : in  std_logic;
+      data  : in  std_logic_vector(7 downto 0);
+      tx    : out std_logic;
+      busy  : out std_logic
+    );
+  end component;
+
+  constant CLKS_PER_BIT : positive := 4;
+  constant PERIOD       : time := 10 ns;
+  constant BIT_TIME     : time := CLKS_PER_BIT * PERIOD;
+
+  signal clk   : std_logic := '0';
+  signal rst   : std_logic := '1';
+  signal start : std_logic := '0';
+  signal data  : std_logic_vector(7 downto 0) := (others => '0');
+  signal tx    : std_logic;
+  signal busy  : std_logic;
+  signal done  : boolean := false;
+begin
+  dut : {entity}
+    generic map (CLKS_PER_BIT => CLKS_PER_BIT)
+    port map (clk => clk, rst => rst, start => start, data => data, tx => tx, busy => busy);
+
+  -- the simulation ends once nothing is left to do, so stop the clock when done
+  clk <= not clk after PERIOD / 2 when not done else clk;
+
+  process
+    variable received : std_logic_vector(7 downto 0);
+  begin
+    wait until rising_edge(clk);
+    rst <= '0';
+    wait until rising_edge(clk);
+    data  <= x"A5";
+    start <= '1';
+    wait until rising_edge(clk);
+    start <= '0';
+
+    -- sample in the middle of every data bit, after the start bit
+    wait until tx = '0';
+    wait for BIT_TIME + BIT_TIME / 2;
+    for i in 0 to 7 loop
+      received(i) := tx;
+      wait for BIT_TIME;
+    end loop;
+    assert tx = '1' report "expected the stop bit" severity error;
+    assert received = x"A5" report "received the wrong byte" severity error;
+    report "simulation finished";
+    done <= true;
+    wait;
+  end process;
+end architecture sim;
+"#
+    )
+}
+
+/// the files of a project, relative to its directory
+fn files(setup: &Setup) -> Vec<(PathBuf, String)> {
+    let entity = &setup.entity;
+    let manifest = (PathBuf::from("gb.toml"), manifest(setup));
+    let sources = match setup.template {
+        Template::Counter => (counter(entity), counter_testbench(entity)),
+        Template::Alu => (alu(entity), alu_testbench(entity)),
+        Template::Uart => (uart(entity), uart_testbench(entity)),
+        Template::Empty => return vec![manifest],
+    };
+    vec![
+        manifest,
+        (PathBuf::from(format!("src/{entity}.vhd")), sources.0),
+        (PathBuf::from(format!("src/{entity}_tb.vhd")), sources.1),
+    ]
+}
+
+/// writes the files of `setup` into `dir`, which has to be free of them
+fn write(dir: &Path, setup: &Setup) -> Result<(), GbError> {
+    let files = files(setup);
+    if let Some((existing, _)) = files.iter().find(|(path, _)| dir.join(path).exists()) {
+        Err(GbError {
+            message: format!(
+                "`{}` already exists, gb won't overwrite it",
+                existing.display()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let src = dir.join("src");
+    std::fs::create_dir_all(&src).fatal(format!("could not create `{}`", src.display()))?;
+    for (path, contents) in files {
+        let path = dir.join(path);
+        std::fs::write(&path, contents).fatal(format!("could not write `{}`", path.display()))?;
+    }
+    gitignore::ensure_ignored_in(dir, gitignore::GB_ARTIFACTS)?;
+    Ok(())
+}
+
+pub fn new(project: &str, template: Template) -> Result<(), GbError> {
+    let dir = Path::new(project);
+    if dir.exists() {
+        Err(GbError {
+            message: format!("`{project}` already exists, use `gb init` inside of it instead"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    let name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .fatal(format!("`{project}` is not a usable project name"))?;
+    let setup = Setup {
+        target: name.to_owned(),
+        entity: entity_name(name)?,
+        std: None,
+        viewer: "gtkwave".to_owned(),
+        template,
+    };
+    write(dir, &setup)?;
+
+    eprintln!(
+        "  {}  {}",
+        "[new]".blue().bold(),
+        format!("Created project `{name}`, try `cd {project} && gb run`")
+            .green()
+            .bold()
+    );
+    Ok(())
+}
+
+/// sets up the current directory as a project, named after the directory.
+/// without a template, gb asks on a terminal and writes an empty gb.toml
+/// otherwise.
+pub fn init(template: Option<Template>) -> Result<(), GbError> {
+    if Path::new("gb.toml").exists() {
+        eprintln!("already inited!");
+        return Ok(());
+    }
+    let cwd = std::env::current_dir().fatal("cannot get the current directory")?;
+    let name = cwd
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("default-target");
+    let asked = template.is_none() && wizard::interactive();
+    let setup = match template {
+        _ if asked => wizard::ask(name)?,
+        Some(Template::Empty) | None => Setup {
+            target: "default-target".to_owned(),
+            entity: String::new(),
+            std: None,
+            viewer: "gtkwave".to_owned(),
+            template: Template::Empty,
+        },
+        Some(template) => Setup {
+            target: name.to_owned(),
+            // a directory name vhdl can't use still makes a fine target name
+            entity: entity_name(name).unwrap_or_else(|_| "top".to_owned()),
+            std: None,
+            viewer: "gtkwave".to_owned(),
+            template,
+        },
+    };
+    write(Path::new("."), &setup)?;
+
+    let target = &setup.target;
+    if setup.template != Template::Empty {
+        eprintln!(
+            "  {}  {}",
+            "[init]".blue().bold(),
+            format!("Created target `{target}`, try `gb run`")
+                .green()
+                .bold()
+        );
+    } else if asked {
+        eprintln!(
+            "  {}  {}",
+            "[init]".blue().bold(),
+            format!("Created target `{target}`, list its files in gb.toml")
+                .green()
+                .bold()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Named scenarios: canned variations of a target's simulation, e.g.
+//!
+//! ```toml
+//! [[target.counter.scenario]]
+//! name = "reset-glitch"
+//! stop-time = "2us"
+//! generics = { WIDTH = 4, GLITCH = true }
+//! env = { SEED = "42" }
+//! ```
+//!
+//! run with `gb run counter --scenario reset-glitch`.
+
+use toml_edit::{Item, Value};
+
+use crate::{BuildOptions, Check, GbError, Level};
+
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    pub name: String,
+    /// top level generics, passed as `-gNAME=VALUE`
+    pub generics: Vec<(String, String)>,
+    /// passed as `--stop-time`, like `100ns`
+    pub stop_time: Option<String>,
+    /// environment variables the simulation runs with
+    pub env: Vec<(String, String)>,
+}
+
+/// strings are taken as they are, everything else the way it's written in the manifest
+fn value_to_string(value: &Value) -> String {
+    match value.as_str() {
+        Some(string) => string.to_owned(),
+        None => value.to_string().trim().to_owned(),
+    }
+}
+
+/// a table like `{ NAME = "value" }` as name and value pairs
+pub fn key_values(item: Option<&Item>, what: &str) -> Result<Vec<(String, String)>, GbError> {
+    let Some(item) = item else {
+        return Ok(vec![]);
+    };
+    let table = item.as_table_like().fatal(format!(
+        "`{what}` must be a table, like `{what} = {{ NAME = \"value\" }}`"
+    ))?;
+    table
+        .iter()
+        .map(|(key, item)| {
+            let value = item
+                .as_value()
+                .fatal(format!("`{what}.{key}` must be a plain value"))?;
+            Ok((key.to_owned(), value_to_string(value)))
+        })
+        .collect()
+}
+
+/// every scenario declared on the target, in manifest order
+pub fn scenarios(target: &str, target_info: &Item) -> Result<Vec<Scenario>, GbError> {
+    let Some(item) = target_info.get("scenario") else {
+        return Ok(vec![]);
+    };
+    let tables = item.as_array_of_tables().fatal(format!(
+        "`target.{target}.scenario` must be written as `[[target.{target}.scenario]]` tables"
+    ))?;
+
+    let mut scenarios = Vec::new();
+    for table in tables.iter() {
+        let name = table
+            .get("name")
+            .and_then(|name| name.as_str())
+            .fatal(format!("every scenario of `{target}` needs a `name`"))?;
+        let stop_time = match table.get("stop-time") {
+            Some(stop_time) => Some(
+                stop_time
+                    .as_str()
+                    .fatal(format!(
+                        "`stop-time` of scenario `{name}` must be a string, like \"100ns\""
+                    ))?
+                    .to_owned(),
+            ),
+            None => None,
+        };
+        scenarios.push(Scenario {
+            name: name.to_owned(),
+            generics: key_values(table.get("generics"), "generics")?,
+            stop_time,
+            env: key_values(table.get("env"), "env")?,
+        });
+    }
+    Ok(scenarios)
+}
+
+pub fn find(target: &str, target_info: &Item, name: &str) -> Result<Scenario, GbError> {
+    let scenarios = scenarios(target, target_info)?;
+    if let Some(scenario) = scenarios.iter().find(|scenario| scenario.name == name) {
+        return Ok(scenario.clone());
+    }
+
+    if scenarios.is_empty() {
+        eprintln!("target `{target}` has no scenarios");
+    } else {
+        eprintln!("target `{target}` has the following scenarios");
+        for (pos, scenario) in scenarios.iter().enumerate() {
+            eprintln!("  {}. {}", pos + 1, scenario.name)
+        }
+    }
+    Err(GbError {
+        message: format!("no scenario named `{name}` in target `{target}`"),
+        level: Level::Fatal,
+        source: None,
+    })
+}
+
+impl Scenario {
+    pub fn apply(&self, build: &mut BuildOptions) {
+        for (name, value) in &self.generics {
+            build.set_generic(name, value);
+        }
+        build.run_flags.extend(
+            self.stop_time
+                .iter()
+                .map(|stop_time| format!("--stop-time={stop_time}")),
+        );
+        build.run_env.extend(self.env.iter().cloned());
+    }
+}
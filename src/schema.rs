@@ -0,0 +1,168 @@
+//! The keys gb.toml knows, so that a typo like `vcd-veiwer` gets a warning
+//! instead of silently doing nothing. a key gb starts reading has to be
+//! added here as well.
+//!
+//! besides unknown keys, this warns about a `[default]` every target
+//! overrides, which never takes effect, and notes target keys repeating
+//! their default.
+
+use toml_edit::{Document, Item};
+
+use crate::{report, GbError};
+
+/// the top level keys and tables
+const MANIFEST: &[&str] = &[
+    "default",
+    "dependencies",
+    "fmt",
+    "fpga",
+    "ghdl",
+    "include",
+    "libraries",
+    "lint",
+    "nvc",
+    "output",
+    "package",
+    "profile",
+    "simulator",
+    "strict",
+    "target",
+    "test",
+    "warnings",
+    "werror",
+    "workspace",
+];
+
+/// keys of `[default]` which a target can set for itself as well
+const SHARED: &[&str] = &[
+    "cpu-time-limit",
+    "memory-limit",
+    "std",
+    "timeout",
+    "uses",
+    "vcd-stream",
+    "vcd-viewer",
+];
+
+/// keys only `[default]` has
+const DEFAULT: &[&str] = &["jobs", "target"];
+
+/// keys only a target has
+const TARGET: &[&str] = &[
+    "analyze-flags",
+    "contexts",
+    "depends",
+    "dump-start",
+    "dump-stop",
+    "elab-flags",
+    "exclude",
+    "execute",
+    "extends",
+    "files",
+    "foreign",
+    "generics",
+    "golden",
+    "golden-vcd",
+    "gtkw",
+    "gtkwave-script",
+    "hooks",
+    "ieee",
+    "library",
+    "publish",
+    "run-flags",
+    "scenario",
+    "sim",
+    "synth",
+    "vcd-name",
+    "vpi",
+    "wave-format",
+    "wave-name",
+];
+
+/// how many single character edits turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn unknown(key: &str, place: &str, known: &[&[&str]]) -> GbError {
+    let suggestion = known
+        .iter()
+        .flat_map(|keys| keys.iter())
+        .map(|candidate| (distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min();
+    match suggestion {
+        Some((_, candidate)) => report::warning(format!(
+            "gb doesn't know `{key}` {place}, did you mean `{candidate}`?"
+        )),
+        None => report::warning(format!("gb doesn't know `{key}` {place}, it's ignored")),
+    }
+}
+
+/// warnings and notes about gb.toml, none of which stop a build by themselves
+pub fn findings(doc: &Document) -> Vec<GbError> {
+    let mut findings = Vec::new();
+    for (key, _) in doc.iter() {
+        if !MANIFEST.contains(&key) {
+            findings.push(unknown(key, "in gb.toml", &[MANIFEST]));
+        }
+    }
+    let default = doc.get("default").and_then(Item::as_table_like);
+    for (key, _) in default.iter().flat_map(|default| default.iter()) {
+        if !SHARED.contains(&key) && !DEFAULT.contains(&key) {
+            findings.push(unknown(key, "in `[default]`", &[SHARED, DEFAULT]));
+        }
+    }
+
+    let targets = doc
+        .get("target")
+        .and_then(Item::as_table_like)
+        .map(|targets| targets.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    for (target, info) in &targets {
+        let Some(info) = info.as_table_like() else {
+            continue;
+        };
+        for (key, value) in info.iter() {
+            if !SHARED.contains(&key) && !TARGET.contains(&key) {
+                findings.push(unknown(
+                    key,
+                    &format!("in target `{target}`"),
+                    &[SHARED, TARGET],
+                ));
+                continue;
+            }
+            let same_as_default = default
+                .and_then(|default| default.get(key))
+                .is_some_and(|default| default.to_string().trim() == value.to_string().trim());
+            if same_as_default {
+                findings.push(report::info(format!(
+                    "`{key}` of target `{target}` is the same as in `[default]`, it can go"
+                )));
+            }
+        }
+    }
+
+    if !targets.is_empty() {
+        for (key, _) in default.iter().flat_map(|default| default.iter()) {
+            let overridden =
+                SHARED.contains(&key) && targets.iter().all(|(_, info)| info.get(key).is_some());
+            if overridden {
+                findings.push(report::warning(format!(
+                    "every target sets its own `{key}`, so `default.{key}` is never used"
+                )));
+            }
+        }
+    }
+    findings
+}
@@ -0,0 +1,288 @@
+//! `gb shell`: a prompt taking gb commands without the `gb`, e.g. `run`,
+//! `test` or `wave counter`, with tab completion over commands, targets and
+//! entities. `target <name>` switches the target used when a command doesn't
+//! name one, for the rest of the session.
+//!
+//! everything runs in the one process, so the parsed sources of tree-sitter
+//! stay around between commands. gb.toml is read again for every command, so
+//! edits to it are picked up right away.
+
+use std::{path::Path, sync::Mutex};
+
+use clap::Parser;
+use colored::Colorize;
+use inquire::{autocompletion::Replacement, Autocomplete, CustomUserError, InquireError, Text};
+use toml_edit::Document;
+
+use crate::{naming, order, sources, wave, Check, Cli, Commands, GbError, Level};
+
+/// the target picked with `target <name>`, which beats `gb use` and `default.target`
+static SESSION_TARGET: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn session_target() -> Option<String> {
+    SESSION_TARGET.lock().ok()?.clone()
+}
+
+/// what the shell understands besides gb's own commands
+const SHELL_COMMANDS: &[&str] = &["describe", "target", "help", "exit"];
+
+const GB_COMMANDS: &[&str] = &[
+    "run",
+    "test",
+    "cover",
+    "wave",
+    "lint",
+    "analyze",
+    "compile",
+    "elab",
+    "synth",
+    "pnr",
+    "flash",
+    "plan",
+    "probe",
+    "vcd",
+    "grep",
+    "entities",
+    "clean",
+    "list",
+    "lsp-config",
+    "graph",
+    "tree",
+    "fmt",
+    "fmt-manifest",
+    "doc",
+    "doctor",
+];
+
+fn read_manifest() -> Result<Document, GbError> {
+    std::fs::read_to_string("gb.toml")
+        .fatal("manifest file `gb.toml` not found in the current directory")?
+        .parse::<Document>()
+        .fatal("failed to parse manifest file")
+}
+
+fn targets(doc: &Document) -> Vec<String> {
+    doc.get("target")
+        .and_then(|targets| targets.as_table_like())
+        .map(|targets| targets.iter().map(|(name, _)| name.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Clone)]
+struct Completer {
+    targets: Vec<String>,
+    entities: Vec<String>,
+}
+
+impl Completer {
+    fn new(doc: &Document) -> Completer {
+        let mut entities = sources::find_vhdl_sources(Path::new("."))
+            .iter()
+            .flat_map(|source| naming::entities(source))
+            .collect::<Vec<_>>();
+        entities.sort();
+        entities.dedup();
+        Completer {
+            targets: targets(doc),
+            entities,
+        }
+    }
+
+    /// everything the last word of `input` could become
+    fn candidates(&self, input: &str) -> Vec<String> {
+        let words = input.split_whitespace().count();
+        let typing_new_word = input.is_empty() || input.ends_with(char::is_whitespace);
+        let first_word = words == 0 || (words == 1 && !typing_new_word);
+        let last = if typing_new_word {
+            ""
+        } else {
+            input.split_whitespace().last().unwrap_or_default()
+        };
+
+        if first_word {
+            return GB_COMMANDS
+                .iter()
+                .chain(SHELL_COMMANDS)
+                .filter(|command| command.starts_with(last))
+                .map(|command| command.to_string())
+                .collect();
+        }
+        // vhdl names are case insensitive
+        let last = last.to_lowercase();
+        self.targets
+            .iter()
+            .chain(&self.entities)
+            .filter(|name| name.to_lowercase().starts_with(&last))
+            .cloned()
+            .collect()
+    }
+
+    /// `input` with its last word replaced by `word`
+    fn replace_last(input: &str, word: &str) -> String {
+        if input.is_empty() || input.ends_with(char::is_whitespace) {
+            return format!("{input}{word}");
+        }
+        let start = input
+            .rfind(char::is_whitespace)
+            .map(|space| space + 1)
+            .unwrap_or(0);
+        format!("{}{word}", &input[..start])
+    }
+}
+
+impl Autocomplete for Completer {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        Ok(self
+            .candidates(input)
+            .iter()
+            .map(|word| Completer::replace_last(input, word))
+            .collect())
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        if highlighted_suggestion.is_some() {
+            return Ok(highlighted_suggestion);
+        }
+        let candidates = self.candidates(input);
+        Ok(match candidates.as_slice() {
+            [only] => Some(Completer::replace_last(input, only) + " "),
+            _ => None,
+        })
+    }
+}
+
+fn help() {
+    eprintln!("any gb command works without the `gb`, like `run`, `test` or `wave <target>`");
+    eprintln!(
+        "  {}  sets the target used when a command doesn't name one",
+        "target <name>".bold()
+    );
+    eprintln!(
+        "  {}  shows the files and settings of a target",
+        "describe [target]".bold()
+    );
+    eprintln!("  {}  leaves the shell, as does ctrl-c", "exit".bold());
+}
+
+fn switch_target(doc: &Document, target: &str) -> Result<(), GbError> {
+    let targets = targets(doc);
+    if !targets.iter().any(|known| known == target) {
+        eprintln!("gb.toml has the following targets");
+        for (pos, known) in targets.iter().enumerate() {
+            eprintln!("  {}. {known}", pos + 1);
+        }
+        Err(GbError {
+            message: format!("there is no target named `{target}`"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    if let Ok(mut session) = SESSION_TARGET.lock() {
+        *session = Some(target.to_owned());
+    }
+    Ok(())
+}
+
+fn describe(doc: &Document, target: Option<&str>) -> Result<(), GbError> {
+    let target = match target {
+        Some(target) => target.to_owned(),
+        None => crate::default_target(doc)?.fatal("no target to describe, pass one")?,
+    };
+    let target_info = doc
+        .get("target")
+        .and_then(|targets| targets.get(&target))
+        .fatal(format!("there is no target named `{target}`"))?;
+
+    eprintln!("{}", format!("target `{target}`").bold());
+    let files = crate::resolve_target_files(&target, target_info)?;
+    eprintln!("files");
+    for (pos, file) in files.iter().enumerate() {
+        eprintln!("  {}. {file}", pos + 1);
+    }
+    match target_info.get("execute").and_then(|file| file.as_str()) {
+        Some(execute) => eprintln!("executes {execute}"),
+        None => {
+            if let Ok(top) = order::top(&target, &files) {
+                eprintln!("executes {top}, nothing instantiates it");
+            }
+        }
+    }
+    if let Some(waveform) = wave::from_manifest(&target, target_info)? {
+        eprintln!("dumps {}", waveform.built_path().display());
+    }
+    if let Some(std) = target_info.get("std").and_then(|std| std.as_str()) {
+        eprintln!("std {std}");
+    }
+    Ok(())
+}
+
+/// runs one line of input, false when the shell should stop
+fn execute(line: &str) -> Result<bool, GbError> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        [] => {}
+        ["exit" | "quit"] => return Ok(false),
+        ["help"] => help(),
+        ["target"] => {
+            let doc = read_manifest()?;
+            match crate::default_target(&doc)? {
+                Some(target) => eprintln!("{target}"),
+                None => eprintln!("no target is set"),
+            }
+        }
+        ["target", target] => switch_target(&read_manifest()?, target)?,
+        ["describe"] => describe(&read_manifest()?, None)?,
+        ["describe", target] => describe(&read_manifest()?, Some(target))?,
+        _ => {
+            let cli = match Cli::try_parse_from(std::iter::once("gb").chain(words)) {
+                Ok(cli) => cli,
+                Err(err) => {
+                    let _ = err.print();
+                    return Ok(true);
+                }
+            };
+            if let Commands::Shell = cli.command {
+                eprintln!("already in the shell");
+                return Ok(true);
+            }
+            crate::validate(&cli.command, &cli.options)?;
+        }
+    }
+    Ok(true)
+}
+
+pub fn shell() -> Result<(), GbError> {
+    eprintln!(
+        "  {}  {}",
+        "[shell]".blue().bold(),
+        "gb shell, `help` lists what it understands".green().bold()
+    );
+    loop {
+        let doc = read_manifest()?;
+        let prompt = match crate::default_target(&doc)? {
+            Some(target) => format!("gb ({target})>"),
+            None => "gb>".to_owned(),
+        };
+        let line = match Text::new(&prompt)
+            .with_autocomplete(Completer::new(&doc))
+            .prompt()
+        {
+            Ok(line) => line,
+            // esc drops the line, ctrl-c leaves
+            Err(InquireError::OperationCanceled) => continue,
+            Err(InquireError::OperationInterrupted) => break,
+            Err(err) => return Err(err).fatal("could not read from the terminal"),
+        };
+        match execute(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            // a failing command shouldn't end the session
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Runtime options of the simulation, so a free running testbench can be
+//! bounded without touching its code:
+//!
+//! ```toml
+//! [target.counter.sim]
+//! stop-time = "100ns"
+//! ieee-asserts = "disable-at-0"
+//! max-stack-alloc = 1024
+//! ```
+//!
+//! each key becomes a `--key=value` option of `ghdl -r`, `true` a bare `--key`.
+//!
+//! anything gb has no key for yet can be passed to ghdl as it is:
+//!
+//! ```toml
+//! [target.counter]
+//! analyze-flags = ["-frelaxed"]
+//! elab-flags = ["-Wl,-lm"]
+//! run-flags = ["--trace-signals"]
+//! ```
+//!
+//! `run-flags` go after the options of `[target.X.sim]`.
+
+use toml_edit::Item;
+
+use crate::{Check, GbError, Level};
+
+/// the runtime options ghdl knows, and what their values look like
+const OPTIONS: &[(&str, &str)] = &[
+    ("stop-time", "a time like \"100ns\""),
+    ("stop-delta", "a number of delta cycles"),
+    (
+        "ieee-asserts",
+        "\"enable\", \"disable\" or \"disable-at-0\"",
+    ),
+    (
+        "assert-level",
+        "\"note\", \"warning\", \"error\", \"failure\" or \"none\"",
+    ),
+    (
+        "backtrace-severity",
+        "\"note\", \"warning\", \"error\", \"failure\" or \"none\"",
+    ),
+    ("max-stack-alloc", "a size in KB"),
+    ("unbuffered", "true"),
+    ("disp-tree", "\"none\", \"inst\", \"proc\" or \"port\""),
+];
+
+/// `analyze-flags`, `elab-flags` or `run-flags` of a target, in manifest order
+pub fn passthrough(target: &str, target_info: &Item, key: &str) -> Result<Vec<String>, GbError> {
+    let Some(flags) = target_info.get(key) else {
+        return Ok(vec![]);
+    };
+    flags
+        .as_array()
+        .and_then(|flags| {
+            flags
+                .iter()
+                .map(|flag| flag.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()
+        })
+        .fatal(format!(
+            "`target.{target}.{key}` must be an array of strings"
+        ))
+}
+
+/// the `ghdl -r` options of a `[target.X.sim]` table, in manifest order
+pub fn run_flags(target: &str, target_info: &Item) -> Result<Vec<String>, GbError> {
+    let Some(sim) = target_info.get("sim") else {
+        return Ok(vec![]);
+    };
+    let sim = sim
+        .as_table_like()
+        .fatal(format!("`target.{target}.sim` must be a table"))?;
+
+    let mut flags = Vec::new();
+    for (key, item) in sim.iter() {
+        let Some((_, expected)) = OPTIONS.iter().find(|(option, _)| *option == key) else {
+            return Err(GbError {
+                message: format!(
+                    "unknown simulation option `{key}` in `target.{target}.sim`, expected one of {}",
+                    OPTIONS
+                        .iter()
+                        .map(|(option, _)| *option)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                level: Level::Fatal,
+                source: None,
+            });
+        };
+        let invalid = || format!("`target.{target}.sim.{key}` must be {expected}");
+        let value = item.as_value().fatal(invalid())?;
+        let flag = if let Some(value) = value.as_str() {
+            format!("--{key}={value}")
+        } else if let Some(value) = value.as_integer() {
+            format!("--{key}={value}")
+        } else if value.as_bool() == Some(true) {
+            format!("--{key}")
+        } else if value.as_bool() == Some(false) {
+            continue;
+        } else {
+            return Err(GbError {
+                message: invalid(),
+                level: Level::Fatal,
+                source: None,
+            });
+        };
+        flags.push(flag);
+    }
+    Ok(flags)
+}
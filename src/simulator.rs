@@ -0,0 +1,117 @@
+//! The simulator gb drives, ghdl unless gb.toml picks nvc:
+//!
+//! ```toml
+//! simulator = "nvc"
+//!
+//! [nvc]
+//! path = "/opt/nvc/bin/nvc"
+//! flags = ["--ieee-warnings=off"]   # passed to every nvc command
+//! ```
+//!
+//! analysis, elaboration and the run all go through the `Simulator`, which
+//! knows its command line and where it leaves the libraries: ghdl writes them
+//! next to gb.toml for gb to move into `build/<profile>/`, nvc is pointed at
+//! a library directory in there right away. synthesis, coverage and C code
+//! linked into the simulation are ghdl's alone.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use once_cell::sync::OnceCell;
+use toml_edit::Document;
+
+use crate::{ghdl, nvc, wave::Waveform, BuildOptions, Check, GbError, Level};
+
+pub trait Simulator: Send + Sync {
+    /// what `simulator` in gb.toml calls it
+    fn name(&self) -> &'static str;
+
+    /// analyzes `files` into the library of `build`, which goes into `workdir`
+    /// when it's given, and `build/<profile>/` otherwise
+    fn analyze(&self, files: &[&str], build: &BuildOptions, workdir: Option<&Path>) -> Command;
+
+    /// elaborates the design unit `unit`, in `build/<profile>/` or the
+    /// `workdir` of `build`
+    fn elaborate(&self, unit: &str, build: &BuildOptions) -> Result<Command, GbError>;
+
+    /// runs the elaborated `unit`, where it was elaborated
+    fn run(
+        &self,
+        unit: &str,
+        waveform: Option<&Waveform>,
+        build: &BuildOptions,
+    ) -> Result<Command, GbError>;
+
+    /// the file analysis writes for the library of `build`, relative to the
+    /// directory the libraries go to, which is stale when a source is newer
+    fn library_file(&self, build: &BuildOptions) -> String;
+
+    /// the executable elaborating `unit` leaves, if the simulator makes one
+    fn executable(&self, unit: &str) -> Option<PathBuf>;
+
+    /// moves what analysis left next to gb.toml into `build/<profile>/`
+    fn collect(&self, _files: &[&str]) -> Result<(), GbError> {
+        Ok(())
+    }
+
+    /// brings back what `collect` moved, for analysis to add to it
+    fn restore(&self) -> Result<(), GbError> {
+        Ok(())
+    }
+
+    /// copies the libraries in `build/<profile>/` to `dir`, for a simulation
+    /// elaborated and run there to stay out of the way of the others
+    fn isolate(&self, dir: &Path) -> Result<(), GbError>;
+
+    /// whether `parallel` can split an analysis among several processes
+    fn analyzes_in_parallel(&self) -> bool {
+        false
+    }
+
+    /// whether a generic below the top entity, like `dut.fifo.DEPTH`, can be
+    /// overridden
+    fn overrides_nested_generics(&self) -> bool {
+        false
+    }
+}
+
+static GHDL: ghdl::Ghdl = ghdl::Ghdl;
+static NVC: nvc::Nvc = nvc::Nvc;
+
+static SELECTED: OnceCell<&'static dyn Simulator> = OnceCell::new();
+
+/// reads `simulator`, and the table of the simulator it picks
+pub fn configure(doc: &Document) -> Result<(), GbError> {
+    let simulator: &'static dyn Simulator = match doc.get("simulator") {
+        None => &GHDL,
+        Some(name) => match name.as_str() {
+            Some("ghdl") => &GHDL,
+            Some("nvc") => &NVC,
+            _ => Err(GbError {
+                message: "`simulator` must be \"ghdl\" or \"nvc\"".to_owned(),
+                level: Level::Fatal,
+                source: None,
+            })?,
+        },
+    };
+    // synthesis goes through ghdl whichever simulator there is
+    ghdl::configure(doc)?;
+    nvc::configure(doc)?;
+    let _ = SELECTED.set(simulator);
+    Ok(())
+}
+
+/// the simulator of the build, ghdl until gb.toml was read
+pub fn get() -> &'static dyn Simulator {
+    SELECTED.get().copied().unwrap_or(&GHDL)
+}
+
+/// fails unless the build runs on ghdl, for what only ghdl can do
+pub fn require_ghdl(what: &str) -> Result<(), GbError> {
+    let simulator = get().name();
+    (simulator == GHDL.name()).then_some(()).fatal(format!(
+        "{what} needs ghdl, but gb.toml sets `simulator = \"{simulator}\"`"
+    ))
+}
@@ -0,0 +1,97 @@
+//! Finding vhdl sources on disk.
+
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{vendor, Check, GbError, Level};
+
+/// directories that gb (or git) writes into, which never hold project sources
+pub const IGNORED_DIRS: &[&str] = &["build", ".gb", ".git"];
+
+pub fn is_vhdl_source(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "vhd" || ext == "vhdl")
+}
+
+/// drops `.` components, so that `./src/a.vhd` and `src/a.vhd` compare equal
+pub fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+fn find_vhdl_sources_into(dir: &Path, sources: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            let ignored = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| IGNORED_DIRS.contains(&name))
+                || vendor::is_vendored(&path);
+            if !ignored {
+                find_vhdl_sources_into(&path, sources);
+            }
+        } else if is_vhdl_source(&path) {
+            sources.push(normalize(&path));
+        }
+    }
+}
+
+/// whether a `files` entry is a pattern like `src/**/*.vhd` rather than a path
+pub fn is_glob(entry: &str) -> bool {
+    entry.contains(['*', '?', '['])
+}
+
+/// the files a pattern matches, sorted by path, leaving out build output.
+/// a pattern has to match something, it's a typo otherwise.
+pub fn expand(pattern: &str) -> Result<Vec<String>, GbError> {
+    let mut matches = glob::glob(pattern)
+        .fatal(format!("`{pattern}` is not a valid pattern"))?
+        .flatten()
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            !path.components().any(|component| {
+                component
+                    .as_os_str()
+                    .to_str()
+                    .is_some_and(|name| IGNORED_DIRS.contains(&name))
+            }) && !path.ancestors().any(vendor::is_vendored)
+        })
+        .map(|path| normalize(&path))
+        .collect::<Vec<_>>();
+    if matches.is_empty() {
+        Err(GbError {
+            message: format!("`{pattern}` doesn't match any files"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    matches.sort();
+    Ok(matches
+        .into_iter()
+        .map(|path| path.display().to_string())
+        .collect())
+}
+
+/// every vhdl source below `root`, skipping build output, sorted by path
+pub fn find_vhdl_sources(root: &Path) -> Vec<PathBuf> {
+    let mut sources = Vec::new();
+    find_vhdl_sources_into(root, &mut sources);
+    sources.sort();
+    sources
+}
+
+/// the sha256 of a file's contents, as lowercase hex
+pub fn fingerprint(path: &Path) -> Result<String, GbError> {
+    let contents =
+        std::fs::read(path).fatal(format!("could not read `{}` to hash it", path.display()))?;
+    Ok(Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
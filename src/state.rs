@@ -0,0 +1,52 @@
+//! Per-directory state which should never be committed, like the
+//! target a developer has locked in with `gb use`. Everything lives
+//! under `.gb/`, which ignores itself so that nobody has to remember
+//! to touch their .gitignore.
+
+use std::path::Path;
+
+use toml_edit::{value, Document};
+
+use crate::{Check, GbError};
+
+const STATE_DIR: &str = ".gb";
+const STATE_FILE: &str = ".gb/state";
+
+fn load_state() -> Result<Document, GbError> {
+    if !Path::new(STATE_FILE).exists() {
+        return Ok(Document::new());
+    }
+    std::fs::read_to_string(STATE_FILE)
+        .fatal("could not read `.gb/state`")?
+        .parse::<Document>()
+        .fatal("failed to parse `.gb/state`, consider deleting it")
+}
+
+fn store_state(state: &Document) -> Result<(), GbError> {
+    std::fs::create_dir_all(STATE_DIR).fatal("could not create the `.gb` directory")?;
+    // the whole directory is local to this checkout, so it ignores itself
+    std::fs::write(Path::new(STATE_DIR).join(".gitignore"), "*\n")
+        .fatal("could not write `.gb/.gitignore`")?;
+    std::fs::write(STATE_FILE, state.to_string()).fatal("could not write `.gb/state`")
+}
+
+/// the target chosen with `gb use`, which takes precedence over `default.target`
+pub fn local_default_target() -> Result<Option<String>, GbError> {
+    Ok(load_state()?
+        .get("target")
+        .and_then(|target| target.as_str())
+        .map(ToOwned::to_owned))
+}
+
+pub fn set_local_default_target(target: Option<&str>) -> Result<(), GbError> {
+    let mut state = load_state()?;
+    match target {
+        Some(target) => {
+            state["target"] = value(target);
+        }
+        None => {
+            state.remove("target");
+        }
+    }
+    store_state(&state)
+}
@@ -0,0 +1,175 @@
+//! `gb wave --stream`: the viewer gets the value changes while the simulation
+//! is still running, instead of the finished dump, so a long simulation can
+//! be looked at from the start. ghdl writes the vcd to its stdout, gb keeps a
+//! copy of it in the usual place and passes it on to the viewer as it comes.
+//!
+//! gtkwave follows a dump through `shmidcat`, which comes with it. any other
+//! viewer needs a command reading the vcd from its stdin:
+//!
+//! ```toml
+//! [default]
+//! vcd-stream = "myviewer --follow -"
+//! ```
+//!
+//! the simulation's reports still go to the terminal, but no `run.log` is
+//! written for a streamed run. what a testbench writes to `output` through
+//! textio ends up in the middle of the dump, use `report` instead.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    process::{Child, Command, Stdio},
+};
+
+use crate::{
+    exit, hooks, verbosity,
+    wave::{self, Waveform},
+    BuildOptions, Check, GbError, Level,
+};
+
+/// the viewer, started with its stdin open for the dump
+fn start_viewer(
+    viewer: Option<&str>,
+    vcd_stream: Option<&str>,
+    gtkwave: &wave::Gtkwave,
+) -> Result<Vec<Child>, GbError> {
+    if let Some(vcd_stream) = vcd_stream {
+        let mut words = vcd_stream.split_whitespace();
+        let program = words.next().fatal("`vcd-stream` is empty")?;
+        let child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+            .fatal(format!(
+                "could not run `{program}`, the `vcd-stream` command"
+            ))?;
+        return Ok(vec![child]);
+    }
+
+    let program = viewer
+        .and_then(|viewer| viewer.split_whitespace().next())
+        .fatal(
+            "neither `vcd-stream` nor a `vcd-viewer` is set, so there is nothing to stream to",
+        )?;
+    if !wave::is_gtkwave(program) {
+        return Err(GbError {
+            message: format!(
+                "gb can't stream into `{program}`, set `vcd-stream` to a command reading the vcd from stdin"
+            ),
+            level: Level::Fatal,
+            source: None,
+        });
+    }
+    // shmidcat shares the dump with gtkwave as it grows
+    let mut shmidcat = Command::new("shmidcat")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .fatal("could not run `shmidcat`, which gtkwave needs to follow a running simulation")?;
+    let shared = shmidcat.stdout.take().fatal("shmidcat has no stdout")?;
+    let gtkwave = Command::new(program)
+        .args(["-v", "-I"])
+        .args(gtkwave.args()?)
+        .stdin(shared)
+        .spawn()
+        .fatal(format!("could not run `{program}`"))?;
+    Ok(vec![shmidcat, gtkwave])
+}
+
+/// runs the simulation, writing its dump to `waveform` and into the viewer at once
+pub fn stream(
+    file_to_exec: &str,
+    waveform: &Waveform,
+    viewer: Option<&str>,
+    vcd_stream: Option<&str>,
+    gtkwave: &wave::Gtkwave,
+    build: &BuildOptions,
+    step: &str,
+) -> Result<(), GbError> {
+    verbosity::step(step, "Executing Solution, streaming the waveform...");
+    build.hooks.run(hooks::Stage::PreRun, build)?;
+    let mut viewers = start_viewer(viewer, vcd_stream, gtkwave)?;
+    let mut into_viewer = viewers[0].stdin.take();
+
+    let dump = waveform.built_path();
+    let mut file =
+        std::fs::File::create(&dump).fatal(format!("could not create `{}`", dump.display()))?;
+    let mut command = crate::run_command(file_to_exec, Some(Waveform::vcd("-")), build)?;
+    command.stdout(Stdio::piped());
+
+    exit::during(exit::Phase::Simulation, || {
+        verbosity::echo(&command);
+        let mut simulation = command
+            .spawn()
+            .fatal("couldn't spawn ghdl run subprocess, is ghdl installed?")?;
+        let mut out = simulation.stdout.take().fatal("ghdl has no stdout")?;
+        // the dump is passed on from another thread, while this one keeps
+        // an eye on the timeout
+        let dump = dump.clone();
+        let passing = std::thread::spawn(move || -> Result<_, GbError> {
+            let mut buffer = [0; 64 * 1024];
+            loop {
+                let read = match out.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                    Err(error) => Err(error).fatal("could not read the dump from ghdl")?,
+                };
+                file.write_all(&buffer[..read])
+                    .fatal(format!("could not write `{}`", dump.display()))?;
+                // a closed viewer doesn't stop the simulation, the file still gets all of it
+                if let Some(viewer) = &mut into_viewer {
+                    if viewer
+                        .write_all(&buffer[..read])
+                        .and_then(|()| viewer.flush())
+                        .is_err()
+                    {
+                        into_viewer = None;
+                    }
+                }
+            }
+            Ok(into_viewer)
+        });
+        let (status, timed_out) = build
+            .limits
+            .wait(&mut simulation)
+            .fatal("failed to await the simulation")?;
+        let into_viewer = passing
+            .join()
+            .ok()
+            .fatal("passing the dump on to the viewer panicked")??;
+        // the viewer stays open to look at the whole run
+        drop(into_viewer);
+        if timed_out {
+            exit::during(exit::Phase::Timeout, || {
+                Err(GbError {
+                    message: build.limits.timed_out(),
+                    level: Level::Fatal,
+                    source: None,
+                })
+            })?;
+        }
+        if let Some(explanation) = build.limits.explain(&status, &[]) {
+            exit::during(exit::Phase::Runtime, || {
+                Err(GbError {
+                    message: explanation,
+                    level: Level::Fatal,
+                    source: None,
+                })
+            })?;
+        }
+        if !status.success() {
+            Err(GbError {
+                message: format!("the simulation did not finish successfully ({status})"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        Ok(())
+    })?;
+
+    for mut viewer in viewers {
+        viewer.wait().fatal("failed to await the viewer")?;
+    }
+    build.dump_window.apply(waveform)?;
+    build.hooks.run(hooks::Stage::PostRun, build)
+}
@@ -0,0 +1,200 @@
+//! `gb synth`: synthesizes a target's design with ghdl, into a netlist in
+//! `build/synth/`.
+//!
+//! ```toml
+//! [target.cpu.synth]
+//! top = "cpu"         # the entity to synthesize, not the testbench
+//! format = "verilog"  # or "json", or "vhdl"
+//! yosys = true        # go through yosys and its ghdl plugin
+//! ```
+//!
+//! without yosys, `ghdl --synth` writes the netlist itself, which it can do
+//! as verilog or vhdl. a json netlist, like nextpnr reads, needs yosys.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+use toml_edit::Item;
+
+use crate::{exit, ghdl, profile, verbosity, BuildOptions, Check, GbError, Level};
+
+/// what the netlist is written as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Verilog,
+    Json,
+    Vhdl,
+}
+
+impl Format {
+    fn parse(target: &str, format: &Item) -> Result<Format, GbError> {
+        match format.as_str() {
+            Some("verilog") => Ok(Format::Verilog),
+            Some("json") => Ok(Format::Json),
+            Some("vhdl") => Ok(Format::Vhdl),
+            _ => Err(GbError {
+                message: format!(
+                    "`target.{target}.synth.format` must be \"verilog\", \"json\" or \"vhdl\""
+                ),
+                level: Level::Fatal,
+                source: None,
+            }),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Verilog => "v",
+            Format::Json => "json",
+            Format::Vhdl => "vhd",
+        }
+    }
+}
+
+/// a target's `synth` table
+#[derive(Debug, Clone)]
+pub struct Synth {
+    pub top: String,
+    pub format: Format,
+    pub yosys: bool,
+}
+
+impl Synth {
+    pub fn from_manifest(target: &str, target_info: &Item) -> Result<Option<Synth>, GbError> {
+        let Some(synth) = target_info.get("synth") else {
+            return Ok(None);
+        };
+        let synth = synth
+            .as_table_like()
+            .fatal(format!("`target.{target}.synth` must be a table"))?;
+        let top = synth
+            .get("top")
+            .and_then(|top| top.as_str())
+            .fatal(format!(
+                "`target.{target}.synth.top` must name the entity to synthesize"
+            ))?;
+        let format = match synth.get("format") {
+            Some(format) => Format::parse(target, format)?,
+            None => Format::default(),
+        };
+        let yosys = match synth.get("yosys") {
+            Some(yosys) => yosys.as_bool().fatal(format!(
+                "`target.{target}.synth.yosys` must be true or false"
+            ))?,
+            None => false,
+        };
+        let problem = match (format, yosys) {
+            (Format::Json, false) => Some("a json netlist needs yosys, set `yosys = true`"),
+            (Format::Vhdl, true) => Some("yosys doesn't write vhdl, take out `yosys = true`"),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            Err(GbError {
+                message: format!("{problem} in `target.{target}.synth`"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        Ok(Some(Synth {
+            top: top.to_owned(),
+            format,
+            yosys,
+        }))
+    }
+
+    /// where the netlist ends up
+    pub fn netlist(&self) -> PathBuf {
+        PathBuf::from("build")
+            .join("synth")
+            .join(&self.top)
+            .with_extension(self.format.extension())
+    }
+}
+
+/// `ghdl --synth`, run next to the analyzed libraries
+fn ghdl_command(synth: &Synth, build: &BuildOptions) -> Command {
+    let mut command = ghdl::command("--synth");
+    command
+        .current_dir(profile::dir())
+        .args(build.common_flags());
+    if synth.format == Format::Verilog {
+        command.arg("--out=verilog");
+    }
+    command.arg(&synth.top);
+    command
+}
+
+/// yosys loading its ghdl plugin, which reads the design the same way, and
+/// then running `then` on it
+pub fn yosys_command(top: &str, build: &BuildOptions, then: &str) -> Command {
+    let arguments = ghdl::flags()
+        .iter()
+        .cloned()
+        .chain(build.common_flags())
+        .chain([top.to_owned()])
+        .collect::<Vec<_>>();
+    let mut command = Command::new("yosys");
+    command
+        .current_dir(profile::dir())
+        .args(["-q", "-m", "ghdl", "-p"])
+        .arg(format!("ghdl {}; {then}", arguments.join(" ")));
+    command
+}
+
+pub fn synth(synth: &Synth, build: &BuildOptions, step: &str) -> Result<(), GbError> {
+    verbosity::step(step, "Synthesizing...");
+    let netlist = synth.netlist();
+    let dir = netlist.parent().unwrap_or(Path::new("build"));
+    std::fs::create_dir_all(dir).fatal(format!("could not create `{}`", dir.display()))?;
+
+    exit::during(exit::Phase::Synthesis, || {
+        if synth.yosys {
+            // yosys runs in the profile's directory, so it needs the full path
+            let cwd = std::env::current_dir().fatal("cannot get the current directory")?;
+            let write = match synth.format {
+                Format::Json => "write_json",
+                _ => "write_verilog",
+            };
+            let then = format!("{write} {}", cwd.join(&netlist).display());
+            let mut command = yosys_command(&synth.top, build, &then);
+            verbosity::echo(&command);
+            let status = command
+                .status()
+                .fatal("couldn't spawn yosys, is it installed, with its ghdl plugin?")?;
+            if !status.success() {
+                Err(GbError {
+                    message: format!("yosys couldn't synthesize `{}` ({status})", synth.top),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+            return Ok(());
+        }
+        let mut command = ghdl_command(synth, build);
+        verbosity::echo(&command);
+        let output = command
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .fatal("couldn't spawn ghdl --synth subprocess, is ghdl installed?")?;
+        if !output.status.success() {
+            Err(GbError {
+                message: format!("ghdl couldn't synthesize `{}`", synth.top),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        std::fs::write(&netlist, output.stdout)
+            .fatal(format!("could not write `{}`", netlist.display()))
+    })?;
+
+    eprintln!(
+        "  {}  {}",
+        "[synth]".blue().bold(),
+        format!("Wrote {}", netlist.display()).green().bold()
+    );
+    Ok(())
+}
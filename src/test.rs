@@ -0,0 +1,666 @@
+//! `gb test`: find the testbenches of a project, run every one of them and
+//! report which ones failed.
+//!
+//! Testbenches are the files listed in `[test] testbenches`, or if that isn't
+//! set, every `*_tb.vhd` file in the project. All of them are analyzed together
+//! with the files of every target (and `[test] files`), then each testbench is
+//! elaborated and run on its own.
+//!
+//! `--repeat N` runs every testbench N times to catch the flaky ones, each
+//! round with the next seed (from `--seed`, 1 by default). a seed reaches the
+//! simulation as `GB_SEED`, and as a generic when the testbench takes one:
+//!
+//! ```toml
+//! [test]
+//! seed-generic = "SEED"
+//! ```
+//!
+//! a failing run keeps its log as `run-seed-<seed>.log`, so `gb test --seed
+//! <seed>` reproduces it.
+//!
+//! `--report junit:build/report.xml` or `--report tap` also writes what came
+//! of every testbench for CI, see `test_report`.
+//!
+//! testbenches run `--jobs` at a time, each elaborated and run in
+//! `build/test/<testbench>/` with its own copy of the libraries, where its
+//! logs go too.
+//!
+//! `gb test <pattern>` only runs the testbenches with the pattern in their
+//! name, or matching it as a glob like `uart_*`. `gb test --failed` runs
+//! those that failed the last time they ran, which
+//! `build/.gb-test-state.json` remembers.
+//!
+//! `--golden` has the testbenches with a `golden-vcd` dump their waveform to
+//! `build/test/<testbench>/run.vcd` and fails them unless it matches, see
+//! `golden`.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use toml_edit::Document;
+
+use crate::{
+    diagnostics::Diagnostic,
+    exit,
+    golden::{self, Golden},
+    orphans, simulator, sources, test_report, transcript, verbosity, wave, BuildOptions, Check,
+    GbError, Level,
+};
+
+#[derive(Debug, Clone)]
+pub struct TestBench {
+    /// the design unit, which is the file stem just like for `execute`
+    pub name: String,
+    pub file: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    pub bench: TestBench,
+    /// the seed of the run, under `--repeat` or `--seed`
+    pub seed: Option<u64>,
+    pub passed: bool,
+    /// the assertion (or elaboration) messages explaining a failure
+    pub failures: Vec<String>,
+    pub duration: Duration,
+    /// how many assertions the run reported, by severity
+    pub assertions: BTreeMap<String, usize>,
+    pub log: PathBuf,
+}
+
+fn string_array<'d>(doc: &'d Document, table: &str, key: &str) -> Option<Vec<&'d str>> {
+    doc.get(table)?
+        .get(key)?
+        .as_array()
+        .map(|array| array.iter().filter_map(|item| item.as_str()).collect())
+}
+
+fn is_testbench(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.ends_with("_tb"))
+}
+
+pub fn discover(doc: &Document) -> Vec<TestBench> {
+    let files = match string_array(doc, "test", "testbenches") {
+        Some(files) => files.into_iter().map(ToOwned::to_owned).collect(),
+        None => sources::find_vhdl_sources(".".as_ref())
+            .into_iter()
+            .filter(|path| is_testbench(path))
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>(),
+    };
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let name = Path::new(&file).file_stem()?.to_string_lossy().into_owned();
+            Some(TestBench { name, file })
+        })
+        .collect()
+}
+
+/// everything that needs analyzing for the testbenches to elaborate: the files of
+/// every target in manifest order, then `[test] files`, then the testbenches.
+pub fn files_to_analyze(doc: &Document, benches: &[TestBench]) -> Result<Vec<String>, GbError> {
+    let mut files = Vec::new();
+    for target in crate::list_targets(doc) {
+        files.extend(crate::resolve_target_files(target, &doc["target"][target])?);
+    }
+    let test_files = string_array(doc, "test", "files").unwrap_or_default();
+
+    let mut seen = HashSet::new();
+    Ok(files
+        .into_iter()
+        .chain(test_files.into_iter().map(ToOwned::to_owned))
+        .chain(benches.iter().map(|bench| bench.file.clone()))
+        .filter(|file| seen.insert(sources::normalize(file.as_ref())))
+        .collect())
+}
+
+/// assertion messages with a severity that should fail a test. ghdl only exits
+/// with an error for `failure`, an `error` lets the simulation carry on.
+pub fn is_failure(line: &str) -> bool {
+    line.contains("(assertion error)") || line.contains("(assertion failure)")
+}
+
+/// how often the testbenches run and with which seeds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Seeds {
+    pub first: Option<u64>,
+    pub repeat: Option<usize>,
+    pub until_failure: bool,
+}
+
+impl Seeds {
+    /// the seed every round runs with, none for a plain `gb test`
+    fn rounds(&self) -> Vec<Option<u64>> {
+        match (self.repeat, self.first) {
+            (None, first) => vec![first],
+            (Some(repeat), first) => {
+                let first = first.unwrap_or(1);
+                (0..repeat as u64)
+                    .map(|round| Some(first.wrapping_add(round)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// which of the testbenches run
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// a part of their name, or a glob matching it
+    pub pattern: Option<String>,
+    /// only those that failed last time
+    pub failed: bool,
+}
+
+impl Selection {
+    fn matches(&self, bench: &TestBench) -> Result<bool, GbError> {
+        match &self.pattern {
+            None => Ok(true),
+            Some(pattern) if pattern.contains(['*', '?', '[']) => Ok(glob::Pattern::new(pattern)
+                .fatal(format!("`{pattern}` is not a valid pattern"))?
+                .matches(&bench.name)),
+            Some(pattern) => Ok(bench.name.contains(pattern.as_str())),
+        }
+    }
+
+    fn select(&self, benches: &[TestBench]) -> Result<Vec<TestBench>, GbError> {
+        let state = if self.failed {
+            Some(load_state().fatal(format!(
+                "`--failed` needs an earlier `gb test`, but `{STATE_FILE}` doesn't exist"
+            ))?)
+        } else {
+            None
+        };
+        let mut selected = Vec::new();
+        for bench in benches {
+            let failed = state
+                .as_ref()
+                .is_none_or(|state| state.results.get(&bench.name) == Some(&false));
+            if failed && self.matches(bench)? {
+                selected.push(bench.clone());
+            }
+        }
+        Ok(selected)
+    }
+}
+
+const STATE_FILE: &str = "build/.gb-test-state.json";
+
+/// what came of every testbench the last time it ran
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    /// testbench name to whether it passed
+    results: BTreeMap<String, bool>,
+}
+
+fn load_state() -> Option<State> {
+    std::fs::read_to_string(STATE_FILE)
+        .ok()
+        .and_then(|state| serde_json::from_str(&state).ok())
+}
+
+/// remembers `results`, keeping what's there for the testbenches that didn't run
+fn store_state<'r>(results: impl IntoIterator<Item = (&'r str, bool)>) -> Result<(), GbError> {
+    let mut state = load_state().unwrap_or_default();
+    for (name, passed) in results {
+        state.results.insert(name.to_owned(), passed);
+    }
+    let state =
+        serde_json::to_string_pretty(&state).fatal("could not serialize the test results")?;
+    std::fs::create_dir_all("build").fatal("could not create the build directory")?;
+    std::fs::write(STATE_FILE, state).fatal(format!("could not write `{STATE_FILE}`"))
+}
+
+/// `build` with the seed passed on, as `GB_SEED` and `[test] seed-generic`
+fn seeded(doc: &Document, build: &BuildOptions, seed: Option<u64>) -> BuildOptions {
+    let mut build = build.clone();
+    if let Some(seed) = seed {
+        build.run_env.push(("GB_SEED".to_owned(), seed.to_string()));
+        let generic = doc
+            .get("test")
+            .and_then(|test| test.get("seed-generic"))
+            .and_then(|generic| generic.as_str());
+        if let Some(generic) = generic {
+            build.set_generic(generic, &seed.to_string());
+        }
+    }
+    build
+}
+
+fn run_bench(
+    bench: &TestBench,
+    build: &BuildOptions,
+    seed: Option<u64>,
+    golden: Option<&Golden>,
+) -> Result<TestOutcome, GbError> {
+    let dir = PathBuf::from("build/test").join(&bench.name);
+    let log = match seed {
+        Some(seed) => dir.join(format!("run-seed-{seed}.log")),
+        None => dir.join("run.log"),
+    };
+    let started = Instant::now();
+    let outcome = |passed, failures, assertions| TestOutcome {
+        bench: bench.clone(),
+        seed,
+        passed,
+        failures,
+        duration: started.elapsed(),
+        assertions,
+        log: log.clone(),
+    };
+
+    simulator::get().isolate(&dir)?;
+    let mut build = build.clone();
+    build.workdir = Some(dir.clone());
+    let build = &build;
+
+    let mut elaborate = crate::elaborate_command(&bench.file, build)?;
+    verbosity::echo(&elaborate);
+    let elaborated = elaborate
+        .output()
+        .fatal("couldn't spawn ghdl elaborate subprocess, is ghdl installed?")?;
+    if !elaborated.status.success() {
+        let failures = String::from_utf8_lossy(&elaborated.stderr)
+            .lines()
+            .map(ToOwned::to_owned)
+            .chain(std::iter::once("elaboration failed".to_owned()))
+            .collect();
+        return Ok(outcome(false, failures, BTreeMap::new()));
+    }
+
+    // absolute, the simulation runs in `dir`
+    let dump = std::env::current_dir()
+        .fatal("could not find the current directory")?
+        .join(dir.join("run.vcd"));
+    let waveform = golden.map(|_| wave::Waveform::vcd(&dump));
+    let mut command = crate::run_command(&bench.file, waveform, build)?;
+    let transcript = transcript::run_teed(&mut command, &log, None, &build.limits)?;
+    let mut failures = transcript
+        .lines
+        .iter()
+        .filter(|line| is_failure(line))
+        .cloned()
+        .collect::<Vec<_>>();
+    if transcript.timed_out {
+        failures.push(build.limits.timed_out());
+    } else if let Some(explanation) = build
+        .limits
+        .explain(&transcript.status, &transcript.lines)
+        .filter(|_| failures.is_empty())
+    {
+        failures.push(explanation);
+    }
+    if !transcript.status.success() && failures.is_empty() {
+        failures.push(format!("the simulation exited with {}", transcript.status));
+    }
+    if let Some(golden) = golden.filter(|_| failures.is_empty()) {
+        let mismatches = golden.compare(&dump)?;
+        if !mismatches.is_empty() {
+            failures.extend(mismatches);
+            failures.push(format!(
+                "the waveform doesn't match `{}`, if `{}` is right now, copy it over",
+                golden.vcd.display(),
+                dir.join("run.vcd").display()
+            ));
+        }
+    }
+    let mut assertions = BTreeMap::new();
+    for line in &transcript.lines {
+        if let Some(diagnostic) = Diagnostic::parse(line).filter(|d| d.time.is_some()) {
+            *assertions.entry(diagnostic.severity).or_default() += 1;
+        }
+    }
+    Ok(outcome(failures.is_empty(), failures, assertions))
+}
+
+/// runs `benches`, up to `build.jobs` of them at once, calling `finished`
+/// with each outcome as it comes. the outcomes are in the order of `benches`.
+fn run_benches<'g>(
+    benches: &[TestBench],
+    build: &BuildOptions,
+    seed: Option<u64>,
+    golden_of: &(dyn Fn(&TestBench) -> Option<&'g Golden> + Sync),
+    finished: &(dyn Fn(&TestOutcome) + Sync),
+) -> Result<Vec<TestOutcome>, GbError> {
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..build.jobs.clamp(1, benches.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(bench) = benches.get(index) else {
+                    break;
+                };
+                let outcome = run_bench(bench, build, seed, golden_of(bench));
+                match &outcome {
+                    Ok(outcome) => finished(outcome),
+                    // nothing new gets started after an error
+                    Err(_) => next.store(benches.len(), Ordering::SeqCst),
+                }
+                if let Ok(mut outcomes) = outcomes.lock() {
+                    outcomes.push((index, outcome));
+                }
+            });
+        }
+    });
+    let mut outcomes = outcomes
+        .into_inner()
+        .ok()
+        .fatal("a testbench panicked while running")?;
+    outcomes.sort_by_key(|(index, _)| *index);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+pub fn run_tests(
+    doc: &Document,
+    build: &BuildOptions,
+    seeds: Seeds,
+    selection: &Selection,
+    golden: bool,
+    reports: &[test_report::Report],
+) -> Result<(), GbError> {
+    let all = discover(doc);
+    if all.is_empty() {
+        Err(GbError {
+            message:
+                "no testbenches found, name them `*_tb.vhd` or list them in `[test] testbenches`"
+                    .to_owned(),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let missing = all
+        .iter()
+        .filter(|bench| !Path::new(&bench.file).exists())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        eprintln!("The following testbenches are listed in `[test]`, but were not found");
+        for (pos, bench) in missing.iter().enumerate() {
+            eprintln!("  {}. {}", pos + 1, bench.file)
+        }
+        Err(GbError {
+            message: "There were missing testbenches.".to_owned(),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let goldens = if golden {
+        let goldens = golden::goldens(doc, &all)?;
+        if goldens.is_empty() {
+            Err(GbError {
+                message: "`--golden` was passed, but no target sets a `golden-vcd`".to_owned(),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        goldens
+    } else {
+        vec![]
+    };
+    let golden_of = |bench: &TestBench| {
+        goldens
+            .iter()
+            .find(|(name, _)| *name == bench.name)
+            .map(|(_, golden)| golden)
+    };
+
+    let benches = selection.select(&all)?;
+    if benches.is_empty() && selection.failed {
+        eprintln!("no testbench failed the last time it ran");
+        return Ok(());
+    }
+    if let Some(pattern) = selection.pattern.as_ref().filter(|_| benches.is_empty()) {
+        Err(GbError {
+            message: format!("no testbench matches `{pattern}`"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let files = files_to_analyze(doc, &benches)?;
+    orphans::prune(doc)?;
+    crate::analyze_vhdl(files.iter().map(String::as_str).collect(), build, " [1/2] ")?;
+
+    let rounds = seeds.rounds();
+    if seeds.repeat.is_some() {
+        return repeat(
+            doc,
+            build,
+            &benches,
+            &rounds,
+            seeds.until_failure,
+            &golden_of,
+            reports,
+        );
+    }
+
+    verbosity::step(
+        " [2/2] ",
+        &format!("Running {} testbenches...", benches.len()),
+    );
+    let build = seeded(doc, build, rounds[0]);
+    let outcomes = run_benches(&benches, &build, rounds[0], &golden_of, &|outcome| {
+        let status = if outcome.passed {
+            "ok".green().bold()
+        } else {
+            "FAILED".red().bold()
+        };
+        eprintln!(
+            "  {}  {} ... {status} ({:.2?})",
+            "[test]".blue().bold(),
+            outcome.bench.name,
+            outcome.duration
+        );
+    })?;
+
+    store_state(
+        outcomes
+            .iter()
+            .map(|outcome| (outcome.bench.name.as_str(), outcome.passed)),
+    )?;
+    test_report::write(reports, &outcomes)?;
+    exit::during(exit::Phase::Tests, || report(&outcomes))
+}
+
+/// what the runs of one testbench came to under `--repeat`
+struct Runs<'b> {
+    bench: &'b TestBench,
+    passed: usize,
+    /// the seeds it failed with, and what the first of those runs reported
+    failed: Vec<u64>,
+    first_failure: Option<TestOutcome>,
+}
+
+fn repeat<'g>(
+    doc: &Document,
+    build: &BuildOptions,
+    benches: &[TestBench],
+    rounds: &[Option<u64>],
+    until_failure: bool,
+    golden_of: &(dyn Fn(&TestBench) -> Option<&'g Golden> + Sync),
+    reports: &[test_report::Report],
+) -> Result<(), GbError> {
+    verbosity::step(
+        " [2/2] ",
+        &format!(
+            "Running {} testbenches {} times...",
+            benches.len(),
+            rounds.len()
+        ),
+    );
+    let mut runs = benches
+        .iter()
+        .map(|bench| Runs {
+            bench,
+            passed: 0,
+            failed: Vec::new(),
+            first_failure: None,
+        })
+        .collect::<Vec<_>>();
+    let mut outcomes = Vec::new();
+    for (round, seed) in rounds.iter().enumerate() {
+        let seed = seed.expect("every repeated round has a seed");
+        let build = seeded(doc, build, Some(seed));
+        let mut failures = 0;
+        let round_outcomes = run_benches(benches, &build, Some(seed), golden_of, &|_| {})?;
+        for (runs, outcome) in runs.iter_mut().zip(round_outcomes) {
+            if !reports.is_empty() {
+                outcomes.push(outcome.clone());
+            }
+            if outcome.passed {
+                runs.passed += 1;
+                // only the logs of failures are worth keeping around
+                let _ = std::fs::remove_file(&outcome.log);
+            } else {
+                failures += 1;
+                runs.failed.push(seed);
+                runs.first_failure.get_or_insert(outcome);
+            }
+        }
+        let status = if failures == 0 {
+            "ok".green().bold()
+        } else {
+            format!("{failures} FAILED").red().bold()
+        };
+        eprintln!(
+            "  {}  round {}/{} (seed {seed}) ... {status}",
+            "[test]".blue().bold(),
+            round + 1,
+            rounds.len()
+        );
+        if until_failure && failures > 0 {
+            break;
+        }
+    }
+
+    // a testbench counts as failed when any of its runs did
+    store_state(
+        runs.iter()
+            .map(|runs| (runs.bench.name.as_str(), runs.failed.is_empty())),
+    )?;
+    test_report::write(reports, &outcomes)?;
+    exit::during(exit::Phase::Tests, || summarize(&runs))
+}
+
+fn summarize(runs: &[Runs]) -> Result<(), GbError> {
+    let flaky = runs
+        .iter()
+        .filter(|runs| runs.passed > 0 && !runs.failed.is_empty())
+        .collect::<Vec<_>>();
+    let broken = runs
+        .iter()
+        .filter(|runs| runs.passed == 0 && !runs.failed.is_empty())
+        .collect::<Vec<_>>();
+
+    for (title, group) in [("flaky:", &flaky), ("failing with every seed:", &broken)] {
+        if group.is_empty() {
+            continue;
+        }
+        eprintln!();
+        eprintln!("{title}");
+        for runs in group {
+            let seeds = runs
+                .failed
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            eprintln!(
+                "  {} failed {} of {} runs, seeds: {}",
+                runs.bench.name.bold(),
+                runs.failed.len(),
+                runs.passed + runs.failed.len(),
+                seeds.join(", ")
+            );
+            if let Some(outcome) = &runs.first_failure {
+                eprintln!("    first failure (log: {})", outcome.log.display());
+                for failure in &outcome.failures {
+                    eprintln!("      {failure}");
+                }
+            }
+        }
+    }
+
+    eprintln!();
+    let stable = runs.len() - flaky.len() - broken.len();
+    let result = if flaky.is_empty() && broken.is_empty() {
+        "ok".green().bold()
+    } else {
+        "FAILED".red().bold()
+    };
+    eprintln!(
+        "test result: {result}. {stable} stable; {} flaky; {} failing",
+        flaky.len(),
+        broken.len()
+    );
+
+    if !flaky.is_empty() || !broken.is_empty() {
+        Err(GbError {
+            message: format!(
+                "{} of {} testbenches are flaky and {} always fail",
+                flaky.len(),
+                runs.len(),
+                broken.len()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
+
+fn report(outcomes: &[TestOutcome]) -> Result<(), GbError> {
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| !outcome.passed)
+        .collect::<Vec<_>>();
+
+    if !failed.is_empty() {
+        eprintln!();
+        eprintln!("failures:");
+        for outcome in &failed {
+            eprintln!(
+                "  {} (log: {})",
+                outcome.bench.name.bold(),
+                outcome.log.display()
+            );
+            for failure in &outcome.failures {
+                eprintln!("    {failure}");
+            }
+        }
+    }
+
+    eprintln!();
+    let result = if failed.is_empty() {
+        "ok".green().bold()
+    } else {
+        "FAILED".red().bold()
+    };
+    eprintln!(
+        "test result: {result}. {} passed; {} failed",
+        outcomes.len() - failed.len(),
+        failed.len()
+    );
+
+    if !failed.is_empty() {
+        Err(GbError {
+            message: format!("{} of {} testbenches failed", failed.len(), outcomes.len()),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(())
+}
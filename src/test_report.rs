@@ -0,0 +1,168 @@
+//! `gb test --report`: the outcome of every testbench in a format CI systems
+//! render, next to gb's own summary:
+//!
+//! ```sh
+//! gb test --report junit:build/report.xml --report tap
+//! ```
+//!
+//! a report without a path is printed to stdout, which gb's own output stays
+//! out of. a run with `--repeat` reports every run of a testbench on its own,
+//! with the seed it ran with in its name.
+
+use std::{collections::BTreeMap, fmt::Write as _, path::PathBuf, time::Duration};
+
+use crate::{test::TestOutcome, Check, GbError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Junit,
+    Tap,
+}
+
+/// a `--report format[:path]`
+#[derive(Debug, Clone)]
+pub struct Report {
+    format: Format,
+    path: Option<PathBuf>,
+}
+
+pub fn parse(report: &str) -> Result<Report, String> {
+    let (format, path) = match report.split_once(':') {
+        Some((format, path)) => (format, Some(PathBuf::from(path))),
+        None => (report, None),
+    };
+    let format = match format {
+        "junit" => Format::Junit,
+        "tap" => Format::Tap,
+        _ => return Err(format!("expected junit or tap, got `{format}`")),
+    };
+    Ok(Report { format, path })
+}
+
+fn name(outcome: &TestOutcome) -> String {
+    match outcome.seed {
+        Some(seed) => format!("{} (seed {seed})", outcome.bench.name),
+        None => outcome.bench.name.clone(),
+    }
+}
+
+/// `2 error, 1 note`
+fn assertions(counts: &BTreeMap<String, usize>) -> String {
+    if counts.is_empty() {
+        return "none".to_owned();
+    }
+    counts
+        .iter()
+        .map(|(severity, count)| format!("{count} {severity}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+fn junit(outcomes: &[TestOutcome]) -> String {
+    let failures = outcomes.iter().filter(|outcome| !outcome.passed).count();
+    let total = outcomes.iter().map(|outcome| outcome.duration).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites>\n  <testsuite name=\"gb test\" tests=\"{}\" failures=\"{failures}\" time=\"{}\">",
+        outcomes.len(),
+        seconds(total)
+    );
+    for outcome in outcomes {
+        let _ = writeln!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\" time=\"{}\">",
+            escape(&name(outcome)),
+            escape(&outcome.bench.name),
+            escape(&outcome.bench.file),
+            seconds(outcome.duration)
+        );
+        if !outcome.passed {
+            let message = outcome
+                .failures
+                .first()
+                .map(String::as_str)
+                .unwrap_or("failed");
+            let _ = writeln!(
+                xml,
+                "      <failure message=\"{}\">{}</failure>",
+                escape(message),
+                escape(&outcome.failures.join("\n"))
+            );
+        }
+        let _ = writeln!(
+            xml,
+            "      <system-out>assertions: {}\nlog: {}</system-out>",
+            escape(&assertions(&outcome.assertions)),
+            escape(&outcome.log.display().to_string())
+        );
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// a yaml string, quoted the way TAP 13's diagnostics need
+fn yaml(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn tap(outcomes: &[TestOutcome]) -> String {
+    let mut tap = format!("TAP version 13\n1..{}\n", outcomes.len());
+    for (number, outcome) in outcomes.iter().enumerate() {
+        let status = if outcome.passed { "ok" } else { "not ok" };
+        let _ = writeln!(tap, "{status} {} - {}", number + 1, name(outcome));
+        tap.push_str("  ---\n");
+        let _ = writeln!(tap, "  duration_ms: {}", outcome.duration.as_millis());
+        let _ = writeln!(
+            tap,
+            "  assertions: {}",
+            yaml(&assertions(&outcome.assertions))
+        );
+        let _ = writeln!(tap, "  log: {}", yaml(&outcome.log.display().to_string()));
+        if !outcome.passed {
+            tap.push_str("  failures:\n");
+            for failure in &outcome.failures {
+                let _ = writeln!(tap, "    - {}", yaml(failure));
+            }
+        }
+        tap.push_str("  ...\n");
+    }
+    tap
+}
+
+/// writes every report about `outcomes`
+pub fn write(reports: &[Report], outcomes: &[TestOutcome]) -> Result<(), GbError> {
+    for report in reports {
+        let written = match report.format {
+            Format::Junit => junit(outcomes),
+            Format::Tap => tap(outcomes),
+        };
+        match &report.path {
+            Some(path) => {
+                if let Some(parent) = path
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                {
+                    std::fs::create_dir_all(parent)
+                        .fatal(format!("could not create `{}`", parent.display()))?;
+                }
+                std::fs::write(path, written)
+                    .fatal(format!("could not write the report `{}`", path.display()))?;
+            }
+            None => print!("{written}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Tees the output of a simulation into a log file while still streaming it
+//! to the terminal, so that a run can be looked at after the scrollback is gone.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{filter::OutputFilter, limits::Limits, Check, GbError};
+
+/// the program and arguments of a command, as they will be passed to it
+pub fn command_argv(command: &Command) -> Vec<String> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// renders a command the way you would type it into a shell
+pub fn describe_command(command: &Command) -> String {
+    command_argv(command).join(" ")
+}
+
+fn timestamp() -> String {
+    humantime::format_rfc3339_millis(SystemTime::now()).to_string()
+}
+
+/// what a teed process did: how it exited and every line it printed
+pub struct Transcript {
+    pub status: ExitStatus,
+    pub lines: Vec<String>,
+    /// whether it was killed for running past the timeout
+    pub timed_out: bool,
+}
+
+/// where a stream is echoed to live, if anywhere, and what of it is shown there
+type Echo = Option<(Box<dyn Write + Send>, OutputFilter)>;
+
+struct Sinks {
+    log: File,
+    lines: Vec<String>,
+}
+
+fn pump(
+    stream: impl Read + Send + 'static,
+    tag: &'static str,
+    sinks: Arc<Mutex<Sinks>>,
+    mut echo: Echo,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if let Some((echo, filter)) = &mut echo {
+                filter.show(&line, echo);
+            }
+            if let Ok(mut sinks) = sinks.lock() {
+                let _ = writeln!(sinks.log, "[{}] [{tag}] {line}", timestamp());
+                sinks.lines.push(line);
+            }
+        }
+    })
+}
+
+/// runs `command` to completion, writing a timestamped copy of its output,
+/// headed by the resolved command, to `log_path`, which only ever holds the
+/// latest run. when `echo` is set the output
+/// is also streamed live to the terminal, through the given filter. it's
+/// killed when it runs past the timeout of `limits`.
+pub fn run_teed(
+    command: &mut Command,
+    log_path: &Path,
+    echo: Option<&OutputFilter>,
+    limits: &Limits,
+) -> Result<Transcript, GbError> {
+    crate::verbosity::echo(command);
+    if let Some(parent) = log_path.parent() {
+        std::fs::create_dir_all(parent).fatal("could not create the directory for the run log")?;
+    }
+    let mut log = File::create(log_path).fatal(format!(
+        "could not create the run log at `{}`",
+        log_path.display()
+    ))?;
+
+    let cwd = command
+        .get_current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|| ".".to_owned());
+    writeln!(log, "# started: {}", timestamp()).fatal("could not write to the run log")?;
+    writeln!(log, "# cwd: {cwd}").fatal("could not write to the run log")?;
+    writeln!(log, "# command: {}", describe_command(command))
+        .fatal("could not write to the run log")?;
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .fatal("couldn't spawn ghdl run subprocess, is ghdl installed?")?;
+
+    let sinks = Arc::new(Mutex::new(Sinks {
+        log,
+        lines: Vec::new(),
+    }));
+    let stdout = child
+        .stdout
+        .take()
+        .fatal("could not capture simulation stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .fatal("could not capture simulation stderr")?;
+    let (echo_stdout, echo_stderr): (Echo, Echo) = match echo {
+        Some(filter) if filter.is_json() => (
+            Some((Box::new(std::io::stderr()), filter.clone())),
+            Some((Box::new(std::io::stderr()), filter.clone())),
+        ),
+        Some(filter) => (
+            Some((Box::new(std::io::stdout()), filter.clone())),
+            Some((Box::new(std::io::stderr()), filter.clone())),
+        ),
+        None => (None, None),
+    };
+    let pumps = [
+        pump(stdout, "stdout", sinks.clone(), echo_stdout),
+        pump(stderr, "stderr", sinks.clone(), echo_stderr),
+    ];
+
+    let (status, timed_out) = limits.wait(&mut child).fatal("couldn't await ghdl run subprocess, is ghdl installed correctly, and do you have run permissions?")?;
+    for pump in pumps {
+        let _ = pump.join();
+    }
+
+    let mut sinks = sinks.lock().ok().fatal("the run log was poisoned")?;
+    if timed_out {
+        let _ = writeln!(sinks.log, "# {}", limits.timed_out());
+    }
+    let _ = writeln!(sinks.log, "# finished: {} ({status})", timestamp());
+    Ok(Transcript {
+        status,
+        lines: std::mem::take(&mut sinks.lines),
+        timed_out,
+    })
+}
@@ -0,0 +1,154 @@
+//! `gb tree`: the instantiation hierarchy of a target, from the unit it
+//! executes down, like `cargo tree` prints crates:
+//!
+//! ```text
+//! cpu_tb
+//! └── dut: cpu
+//!     ├── alu0: alu
+//!     └── regs: register_file
+//! ```
+//!
+//! `--invert alu` turns it around and prints everything instantiating `alu`,
+//! up to the units nothing instantiates. instantiations are picked out of the
+//! architectures of the target's files, both of components and of entities,
+//! and a unit none of them declares, like a vendor primitive, is a leaf.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{GbError, Level};
+
+static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"--[^\n]*").unwrap());
+static ARCHITECTURE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\barchitecture\s+[a-z][a-z0-9_]*\s+of\s+([a-z][a-z0-9_]*)\s+is\b").unwrap()
+});
+static INSTANCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\b([a-z][a-z0-9_]*)\s*:\s*(?:component\s+|entity\s+(?:[a-z][a-z0-9_]*\s*\.\s*)?)?([a-z][a-z0-9_]*)\s*(?:\(\s*[a-z][a-z0-9_]*\s*\)\s*)?(?:generic|port)\s+map\b",
+    )
+    .unwrap()
+});
+
+/// a unit instantiated by an architecture, lowercased since vhdl doesn't care
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub label: String,
+    pub unit: String,
+}
+
+/// what the architectures of `files` instantiate, by the entity they belong to
+pub fn instances(files: &[&str]) -> HashMap<String, Vec<Instance>> {
+    let mut instances: HashMap<String, Vec<Instance>> = HashMap::new();
+    for file in files {
+        let Ok(code_src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let code_src = COMMENT.replace_all(&code_src, "");
+        let architectures = ARCHITECTURE.captures_iter(&code_src).collect::<Vec<_>>();
+        for (index, architecture) in architectures.iter().enumerate() {
+            // an architecture goes on until the next one starts
+            let start = architecture.get(0).map_or(0, |found| found.end());
+            let end = architectures
+                .get(index + 1)
+                .and_then(|next| next.get(0))
+                .map_or(code_src.len(), |next| next.start());
+            let entity = architecture[1].to_lowercase();
+            instances.entry(entity).or_default().extend(
+                INSTANCE
+                    .captures_iter(&code_src[start..end])
+                    .map(|captures| Instance {
+                        label: captures[1].to_lowercase(),
+                        unit: captures[2].to_lowercase(),
+                    }),
+            );
+        }
+    }
+    instances
+}
+
+const BRANCH: &str = "├── ";
+const LAST: &str = "└── ";
+const PIPE: &str = "│   ";
+const SPACE: &str = "    ";
+
+/// the edges of the tree, from a unit to the `(label, unit)` pairs under it
+type Edges = HashMap<String, Vec<(String, String)>>;
+
+/// prints what's under `unit`, `path` holds the units above it to stop at a
+/// cycle
+fn print_children(
+    unit: &str,
+    edges: &Edges,
+    inverted: bool,
+    prefix: &str,
+    depth: Option<usize>,
+    path: &mut Vec<String>,
+) {
+    if depth == Some(path.len()) {
+        return;
+    }
+    let children = edges.get(unit).map(Vec::as_slice).unwrap_or_default();
+    path.push(unit.to_owned());
+    for (index, (label, child)) in children.iter().enumerate() {
+        let last = index + 1 == children.len();
+        let cycle = path.contains(child);
+        let line = if inverted {
+            format!("{child} (as {label})")
+        } else {
+            format!("{label}: {child}")
+        };
+        println!(
+            "{prefix}{}{line}{}",
+            if last { LAST } else { BRANCH },
+            if cycle { " (cycle)" } else { "" }
+        );
+        if !cycle {
+            let prefix = format!("{prefix}{}", if last { SPACE } else { PIPE });
+            print_children(child, edges, inverted, &prefix, depth, path);
+        }
+    }
+    path.pop();
+}
+
+/// prints the hierarchy under `root`, or with `invert`, above it
+pub fn tree(files: &[&str], root: &str, depth: Option<usize>, invert: bool) -> Result<(), GbError> {
+    let instances = instances(files);
+    let root = root.to_lowercase();
+    let mut edges = Edges::new();
+    if invert {
+        for (parent, instances) in &instances {
+            for instance in instances {
+                edges
+                    .entry(instance.unit.clone())
+                    .or_default()
+                    .push((instance.label.clone(), parent.clone()));
+            }
+        }
+        for parents in edges.values_mut() {
+            parents.sort();
+        }
+        if !edges.contains_key(&root) && !instances.contains_key(&root) {
+            Err(GbError {
+                message: format!("nothing in the target's files instantiates `{root}`"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+    } else {
+        for (entity, instances) in instances {
+            edges.insert(
+                entity,
+                instances
+                    .into_iter()
+                    .map(|instance| (instance.label, instance.unit))
+                    .collect(),
+            );
+        }
+    }
+
+    println!("{root}");
+    print_children(&root, &edges, invert, "", depth, &mut vec![]);
+    Ok(())
+}
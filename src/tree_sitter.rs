@@ -6,6 +6,8 @@ use std::{
 use once_cell::sync::Lazy;
 use tree_sitter::{Language, Parser, Query, QueryCursor};
 
+use crate::contexts;
+
 extern "C" {
     fn tree_sitter_vhdl() -> Language;
 }
@@ -42,7 +44,29 @@ fn get_components_of<P: AsRef<std::path::Path>>(
         .collect())
 }
 
-pub fn generate_sources_for<P: AsRef<std::path::Path>>(path: P) -> HashSet<std::path::PathBuf> {
+/// components live next to the file declaring them, in `<component>.vhd`
+fn component_files(path: &std::path::Path, components: &[String]) -> Vec<std::path::PathBuf> {
+    components
+        .iter()
+        .map(|comp| path.with_file_name(comp).with_extension("vhd"))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// the files of the components `path` declares and the contexts it references,
+/// without following them any further
+pub fn direct_dependencies<P: AsRef<std::path::Path>>(path: P) -> Vec<std::path::PathBuf> {
+    let path = path.as_ref();
+    let mut dependencies = get_components_of(path)
+        .map(|components| component_files(path, &components))
+        .unwrap_or_default();
+    dependencies.extend(contexts::dependencies(path));
+    dependencies
+}
+
+/// maps every file reachable from `path` to the files of the components it declares
+/// and of the contexts it references
+fn dependency_map(path: &std::path::Path) -> HashMap<std::path::PathBuf, Vec<std::path::PathBuf>> {
     fn generate_sources_inner(
         path: &std::path::Path,
         set: &mut HashMap<std::path::PathBuf, Vec<std::path::PathBuf>>,
@@ -55,12 +79,10 @@ pub fn generate_sources_for<P: AsRef<std::path::Path>>(path: P) -> HashSet<std::
             return;
         };
 
-        let paths = components
-            .iter()
-            .map(|comp| path.with_file_name(comp).with_extension("vhd"))
-            .filter(|path| path.exists());
+        let mut paths = component_files(path, &components);
+        paths.extend(contexts::dependencies(path));
 
-        set.insert(path.to_owned(), paths.clone().collect());
+        set.insert(path.to_owned(), paths.clone());
         // set all the direct dependencies of the current path
 
         for path in paths {
@@ -69,12 +91,165 @@ pub fn generate_sources_for<P: AsRef<std::path::Path>>(path: P) -> HashSet<std::
     }
 
     let mut map = HashMap::new();
-    generate_sources_inner(path.as_ref(), &mut map);
+    generate_sources_inner(path, &mut map);
+    map
+}
 
+pub fn generate_sources_for<P: AsRef<std::path::Path>>(path: P) -> HashSet<std::path::PathBuf> {
     let mut set = HashSet::new();
-    for (k, v) in map {
+    for (k, v) in dependency_map(path.as_ref()) {
         set.insert(k);
         set.extend(v);
     }
     set
 }
+
+/// like `generate_sources_for`, but ordered so that every file comes after the
+/// files it depends on, which is the order ghdl needs to analyze them in.
+pub fn generate_ordered_sources_for<P: AsRef<std::path::Path>>(path: P) -> Vec<std::path::PathBuf> {
+    fn visit(
+        path: &std::path::Path,
+        map: &HashMap<std::path::PathBuf, Vec<std::path::PathBuf>>,
+        visited: &mut HashSet<std::path::PathBuf>,
+        order: &mut Vec<std::path::PathBuf>,
+    ) {
+        if !visited.insert(path.to_owned()) {
+            return;
+        }
+        for dependency in map.get(path).into_iter().flatten() {
+            visit(dependency, map, visited, order);
+        }
+        order.push(path.to_owned());
+    }
+
+    let map = dependency_map(path.as_ref());
+    let mut order = Vec::new();
+    visit(path.as_ref(), &map, &mut HashSet::new(), &mut order);
+    order
+}
+
+/// a node found by `find_nodes`
+#[derive(Debug, Clone)]
+pub struct NodeMatch {
+    /// 1-based, like editors count them
+    pub line: usize,
+    pub column: usize,
+    pub kind: String,
+    pub name: String,
+}
+
+/// the text naming `node`: its `name` field if the grammar has one for it,
+/// otherwise the first identifier inside of it.
+fn node_name<'s>(node: tree_sitter::Node, code_src: &'s str) -> Option<&'s str> {
+    if node.kind() == "identifier" {
+        return Some(&code_src[node.byte_range()]);
+    }
+    if let Some(name) = node.child_by_field_name("name") {
+        return Some(&code_src[name.byte_range()]);
+    }
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+    children
+        .into_iter()
+        .find_map(|child| node_name(child, code_src))
+}
+
+/// every node of `kind` (or every identifier, without a kind) in `path` whose
+/// name matches `pattern`, in source order.
+pub fn find_nodes<P: AsRef<std::path::Path>>(
+    path: P,
+    kind: Option<&str>,
+    pattern: &glob::Pattern,
+) -> Result<Vec<NodeMatch>, Box<dyn std::error::Error>> {
+    let code_src = std::fs::read_to_string(path)?;
+    let mut parser = VHDL_TREE_SITTER.lock()?;
+    let tree = parser
+        .parse(&code_src, None)
+        .ok_or("tree-sitter could not parse the file")?;
+
+    // vhdl identifiers are case insensitive
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+    let kind = kind.unwrap_or("identifier");
+
+    let mut matches = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.kind() == kind {
+            if let Some(name) = node_name(node, &code_src) {
+                if pattern.matches_with(name, options) {
+                    let start = node.start_position();
+                    matches.push(NodeMatch {
+                        line: start.row + 1,
+                        column: start.column + 1,
+                        kind: node.kind().to_owned(),
+                        name: name.to_owned(),
+                    });
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+        stack.extend(children.into_iter().rev());
+    }
+    Ok(matches)
+}
+
+/// a token of a source file, as tree-sitter split it up
+#[derive(Debug, Clone)]
+pub struct Leaf {
+    /// byte offsets into the source
+    pub start: usize,
+    pub end: usize,
+    /// 0-based, a comment or string can end on a later row than it starts on
+    pub row: usize,
+    pub end_row: usize,
+    /// a reserved word, like `entity` or `downto`, whatever its case
+    pub keyword: bool,
+    pub comment: bool,
+}
+
+/// every token of `code_src` in source order, failing when it doesn't parse
+pub fn leaves(code_src: &str) -> Result<Vec<Leaf>, Box<dyn std::error::Error>> {
+    let mut parser = VHDL_TREE_SITTER.lock()?;
+    let tree = parser
+        .parse(code_src, None)
+        .ok_or("tree-sitter could not parse the file")?;
+    if tree.root_node().has_error() {
+        return Err("it has syntax errors".into());
+    }
+
+    let mut leaves = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.child_count() == 0 {
+            let text = &code_src[node.byte_range()];
+            // reserved words are the unnamed tokens spelled like a word
+            let keyword = !node.is_named()
+                && !text.is_empty()
+                && text.chars().all(|c| c.is_ascii_alphabetic())
+                && node.kind().eq_ignore_ascii_case(text);
+            let end = node.end_position();
+            leaves.push(Leaf {
+                start: node.start_byte(),
+                end: node.end_byte(),
+                row: node.start_position().row,
+                // a comment can take the line break after it along
+                end_row: if end.column == 0 && end.row > 0 {
+                    end.row - 1
+                } else {
+                    end.row
+                },
+                keyword,
+                comment: node.kind().contains("comment") || text.starts_with("--"),
+            });
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children = node.children(&mut cursor).collect::<Vec<_>>();
+        stack.extend(children.into_iter().rev());
+    }
+    Ok(leaves)
+}
@@ -0,0 +1,206 @@
+//! `gb self update`: replaces the running gb with the latest release from
+//! GitHub, for everyone who installed it from a release archive rather than
+//! a package manager. Downloads go through `curl` and archives through `tar`,
+//! which every platform gb is released for ships with.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use colored::Colorize;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{Check, GbError, Level};
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/andystopia/gb/releases/latest";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+fn curl() -> Command {
+    let mut curl = Command::new("curl");
+    curl.args(["--fail", "--silent", "--show-error", "--location"]);
+    curl
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>, GbError> {
+    let output = curl()
+        .arg(url)
+        .output()
+        .fatal("could not run `curl`, which gb uses to download releases")?;
+    if !output.status.success() {
+        Err(GbError {
+            message: format!(
+                "downloading {url} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(output.stdout)
+}
+
+/// `v0.1.11` or `0.1.11` as numbers, anything after a `-` doesn't count
+fn version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split('-')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// what cargo-dist calls the archive for the platform this gb was built for
+fn archive_name() -> String {
+    let target = env!("GB_TARGET");
+    let extension = if target.contains("windows") {
+        "zip"
+    } else {
+        "tar.xz"
+    };
+    format!("gb-{target}.{extension}")
+}
+
+fn find_binary(dir: &Path) -> Option<PathBuf> {
+    let name = format!("gb{}", std::env::consts::EXE_SUFFIX);
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(found) = find_binary(&path) {
+                return Some(found);
+            }
+        } else if path.file_name().is_some_and(|file| file == name.as_str()) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// puts `new` where the running executable is. windows won't let a running
+/// executable be overwritten, but it can be renamed out of the way.
+fn replace_executable(new: &Path) -> Result<(), GbError> {
+    let current = std::env::current_exe().fatal("could not find the running gb executable")?;
+    let staged = current.with_extension("new");
+    std::fs::copy(new, &staged).fatal(format!(
+        "could not write next to `{}`, is it writable?",
+        current.display()
+    ))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .fatal("could not make the new gb executable")?;
+    }
+    if cfg!(windows) {
+        let old = current.with_extension("old.exe");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(&current, &old).fatal("could not move the running gb out of the way")?;
+    }
+    std::fs::rename(&staged, &current).fatal("could not put the new gb in place")
+}
+
+pub fn update(check: bool) -> Result<(), GbError> {
+    let release = fetch(LATEST_RELEASE)?;
+    let release: Release =
+        serde_json::from_slice(&release).fatal("could not understand GitHub's release listing")?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    if version(&release.tag_name) <= version(current) {
+        eprintln!(
+            "  {}  {}",
+            "[self]".blue().bold(),
+            format!("gb {current} is up to date").green().bold()
+        );
+        return Ok(());
+    }
+    eprintln!(
+        "  {}  {}",
+        "[self]".blue().bold(),
+        format!("gb {} is available, this is {current}", release.tag_name)
+            .green()
+            .bold()
+    );
+    if check {
+        return Ok(());
+    }
+
+    let name = archive_name();
+    let url = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.as_str())
+            .fatal(format!(
+                "release {} has no `{name}`, update gb some other way",
+                release.tag_name
+            ))
+    };
+    let archive = fetch(url(&name)?)?;
+    let checksum = fetch(url(&format!("{name}.sha256"))?)?;
+
+    // the checksum file reads `<hex>  <file name>`
+    let expected = String::from_utf8_lossy(&checksum)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let actual = Sha256::digest(&archive)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    if expected != actual {
+        Err(GbError {
+            message: format!("the checksum of `{name}` does not match, refusing to install it"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+
+    let scratch = std::env::temp_dir().join(format!("gb-update-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch).fatal("could not create a directory for the download")?;
+    let result = (|| {
+        let downloaded = scratch.join(&name);
+        std::fs::write(&downloaded, &archive).fatal("could not save the download")?;
+        let status = Command::new("tar")
+            .arg("-xf")
+            .arg(&downloaded)
+            .arg("-C")
+            .arg(&scratch)
+            .status()
+            .fatal("could not run `tar` to unpack the release")?;
+        if !status.success() {
+            Err(GbError {
+                message: format!("could not unpack `{name}`"),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        let binary = find_binary(&scratch).fatal(format!("`{name}` does not contain gb"))?;
+        replace_executable(&binary)
+    })();
+    let _ = std::fs::remove_dir_all(&scratch);
+    result?;
+
+    eprintln!(
+        "  {}  {}",
+        "[self]".blue().bold(),
+        format!("Updated gb to {}", release.tag_name).green().bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,330 @@
+//! A small reader for the VCD files ghdl writes with `--vcd`, so gb can answer
+//! questions about a simulation without going through a waveform viewer.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{Check, GbError, Level};
+
+fn malformed(message: impl std::fmt::Display) -> GbError {
+    GbError {
+        message: format!("malformed vcd file: {message}"),
+        level: Level::Fatal,
+        source: None,
+    }
+}
+
+/// parses a time like `150ns`, `1.5 us` or `20fs` into femtoseconds
+pub fn parse_time(time: &str) -> Option<u64> {
+    let time = time.trim();
+    let split = time
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(time.len());
+    let (magnitude, unit) = time.split_at(split);
+    let scale: u64 = match unit.trim() {
+        "fs" => 1,
+        "ps" => 1_000,
+        "ns" => 1_000_000,
+        "us" => 1_000_000_000,
+        "ms" => 1_000_000_000_000,
+        "s" | "sec" => 1_000_000_000_000_000,
+        _ => return None,
+    };
+    let magnitude = magnitude.trim();
+    match magnitude.parse::<u64>() {
+        Ok(whole) => whole.checked_mul(scale),
+        Err(_) => {
+            let fractional = magnitude.parse::<f64>().ok()?;
+            (fractional >= 0.0).then(|| (fractional * scale as f64).round() as u64)
+        }
+    }
+}
+
+/// formats femtoseconds with the largest unit that keeps it a whole number
+pub fn format_time(fs: u64) -> String {
+    const UNITS: [(u64, &str); 5] = [
+        (1_000_000_000_000_000, "s"),
+        (1_000_000_000_000, "ms"),
+        (1_000_000_000, "us"),
+        (1_000_000, "ns"),
+        (1_000, "ps"),
+    ];
+    UNITS
+        .iter()
+        .find(|(scale, _)| fs != 0 && fs.is_multiple_of(*scale))
+        .map(|(scale, unit)| format!("{}{unit}", fs / scale))
+        .unwrap_or_else(|| format!("{fs}fs"))
+}
+
+#[derive(Debug, Clone)]
+pub struct Signal {
+    /// the full hierarchical name, scopes joined with `.`
+    pub name: String,
+    pub width: u32,
+    pub id: String,
+}
+
+impl Signal {
+    /// the name without its scopes and range, `count` for `top.dut.count[7:0]`
+    pub fn leaf(&self) -> &str {
+        let name = self.name.rsplit('.').next().unwrap_or(&self.name);
+        name.split_once('[').map_or(name, |(leaf, _)| leaf).trim()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Vcd {
+    /// femtoseconds per vcd time unit
+    pub timescale: u64,
+    pub signals: Vec<Signal>,
+    /// value changes per identifier code, ordered by time (in femtoseconds)
+    changes: HashMap<String, Vec<(u64, String)>>,
+    end_time: u64,
+}
+
+impl Vcd {
+    pub fn parse(text: &str) -> Result<Vcd, GbError> {
+        let mut vcd = Vcd {
+            timescale: 1,
+            ..Default::default()
+        };
+        let mut scopes: Vec<&str> = Vec::new();
+        let mut time = 0;
+        let mut tokens = text.split_whitespace();
+
+        // everything up to the next `$end`
+        fn until_end<'t>(tokens: &mut impl Iterator<Item = &'t str>) -> Vec<&'t str> {
+            tokens.take_while(|token| *token != "$end").collect()
+        }
+
+        while let Some(token) = tokens.next() {
+            match token {
+                "$timescale" => {
+                    let timescale = until_end(&mut tokens).concat();
+                    vcd.timescale = parse_time(&timescale)
+                        .ok_or_else(|| malformed(format!("unknown timescale `{timescale}`")))?;
+                }
+                "$scope" => {
+                    let scope = until_end(&mut tokens);
+                    scopes.push(scope.get(1).ok_or_else(|| malformed("unnamed scope"))?);
+                }
+                "$upscope" => {
+                    until_end(&mut tokens);
+                    scopes.pop();
+                }
+                "$var" => {
+                    let var = until_end(&mut tokens);
+                    let [_kind, width, id, reference, ..] = var[..] else {
+                        return Err(malformed(format!(
+                            "incomplete variable `{}`",
+                            var.join(" ")
+                        )));
+                    };
+                    let name = scopes
+                        .iter()
+                        .copied()
+                        .chain(std::iter::once(reference))
+                        .collect::<Vec<_>>()
+                        .join(".");
+                    vcd.signals.push(Signal {
+                        name,
+                        width: width
+                            .parse()
+                            .map_err(|_| malformed(format!("bad width for `{reference}`")))?,
+                        id: id.to_owned(),
+                    });
+                }
+                "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => {}
+                keyword if keyword.starts_with('$') => {
+                    // $date, $version, $comment, $enddefinitions, ...
+                    until_end(&mut tokens);
+                }
+                stamp if stamp.starts_with('#') => {
+                    time = stamp[1..]
+                        .parse::<u64>()
+                        .map_err(|_| malformed(format!("bad timestamp `{stamp}`")))?
+                        .saturating_mul(vcd.timescale);
+                    vcd.end_time = vcd.end_time.max(time);
+                }
+                vector if vector.starts_with(['b', 'B', 'r', 'R', 's', 'S']) => {
+                    let id = tokens
+                        .next()
+                        .ok_or_else(|| malformed(format!("`{vector}` is missing an identifier")))?;
+                    vcd.record(id, time, &vector[1..]);
+                }
+                scalar => {
+                    let mut chars = scalar.chars();
+                    let value = chars.next().unwrap_or_default();
+                    vcd.record(chars.as_str(), time, &value.to_string());
+                }
+            }
+        }
+        Ok(vcd)
+    }
+
+    pub fn load(path: &Path) -> Result<Vcd, GbError> {
+        let dump = std::fs::read_to_string(path)
+            .fatal(format!("could not read the waveform `{}`", path.display()))?;
+        Vcd::parse(&dump)
+    }
+
+    /// the signals matching any of the glob `patterns`, in dump order. a
+    /// pattern without a `.` is also matched against a signal's own name,
+    /// without its range, so `clk` finds `top.clk`
+    pub fn select(&self, patterns: &[String]) -> Result<Vec<&Signal>, GbError> {
+        let compiled = patterns
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .fatal(format!("`{pattern}` is not a valid signal pattern"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let matching = self
+            .signals
+            .iter()
+            .filter(|signal| {
+                compiled.iter().any(|pattern| {
+                    pattern.matches(&signal.name)
+                        || (!pattern.as_str().contains('.') && pattern.matches(signal.leaf()))
+                })
+            })
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            Err(GbError {
+                message: format!("no signal in the waveform matches {}", patterns.join(", ")),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        Ok(matching)
+    }
+
+    fn record(&mut self, id: &str, time: u64, value: &str) {
+        self.changes
+            .entry(id.to_owned())
+            .or_default()
+            .push((time, value.to_owned()));
+    }
+
+    /// the time of the last value change in the dump, in femtoseconds
+    pub fn end_time(&self) -> u64 {
+        self.end_time
+    }
+
+    /// all the value changes of a signal, as `(femtoseconds, value)` pairs
+    pub fn changes(&self, signal: &Signal) -> &[(u64, String)] {
+        self.changes
+            .get(&signal.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// the value a signal holds at `time` femtoseconds, if it was ever assigned by then
+    pub fn value_at(&self, signal: &Signal, time: u64) -> Option<&str> {
+        let changes = self.changes(signal);
+        let after = changes.partition_point(|(changed, _)| *changed <= time);
+        after.checked_sub(1).map(|last| changes[last].1.as_str())
+    }
+}
+
+/// the identifier a value change line is about, `1!` or `b0101 !`
+fn changed_id(line: &str) -> Option<&str> {
+    if line.starts_with(['b', 'B', 'r', 'R', 's', 'S']) {
+        line.split_whitespace().nth(1)
+    } else {
+        line.get(1..).map(str::trim)
+    }
+}
+
+/// cuts a dump down to the changes between `start` and `stop` (in femtoseconds),
+/// going through it line by line so a huge dump never has to fit in memory.
+/// what every signal holds at `start` is dumped right at the start of the window.
+pub fn trim(path: &Path, start: Option<u64>, stop: Option<u64>) -> Result<(), GbError> {
+    let start = start.unwrap_or(0);
+    let stop = stop.unwrap_or(u64::MAX);
+    let dump = std::fs::File::open(path)
+        .fatal(format!("could not read the waveform `{}`", path.display()))?;
+    let trimmed_path = path.with_extension("vcd.trimmed");
+    let trimmed =
+        std::fs::File::create(&trimmed_path).fatal("could not write the trimmed waveform")?;
+    let mut trimmed = BufWriter::new(trimmed);
+    let mut write = |line: &str| writeln!(trimmed, "{line}");
+
+    let mut lines = BufReader::new(dump).lines();
+    let mut timescale = Vec::new();
+    let mut in_timescale = false;
+    for line in lines.by_ref() {
+        let line = line.fatal("could not read the waveform")?;
+        write(&line).fatal("could not write the trimmed waveform")?;
+        for token in line.split_whitespace() {
+            match token {
+                "$timescale" => in_timescale = true,
+                "$end" => in_timescale = false,
+                token if in_timescale => timescale.push(token.to_owned()),
+                _ => {}
+            }
+        }
+        if line.contains("$enddefinitions") {
+            break;
+        }
+    }
+    let timescale = match timescale.concat() {
+        timescale if timescale.is_empty() => 1,
+        timescale => parse_time(&timescale)
+            .ok_or_else(|| malformed(format!("unknown timescale `{timescale}`")))?,
+    };
+
+    // the last value of every signal before the window, in dump order
+    let mut values: Vec<String> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut in_window = false;
+    for line in lines {
+        let line = line.fatal("could not read the waveform")?;
+        let trimmed_line = line.trim();
+        if let Some(stamp) = trimmed_line.strip_prefix('#') {
+            let time = stamp
+                .parse::<u64>()
+                .map_err(|_| malformed(format!("bad timestamp `{trimmed_line}`")))?
+                .saturating_mul(timescale);
+            if time > stop {
+                break;
+            }
+            if !in_window && time >= start {
+                in_window = true;
+                write(&format!("#{}", start / timescale))
+                    .fatal("could not write the trimmed waveform")?;
+                write("$dumpvars").fatal("could not write the trimmed waveform")?;
+                for value in &values {
+                    write(value).fatal("could not write the trimmed waveform")?;
+                }
+                write("$end").fatal("could not write the trimmed waveform")?;
+                if time == start {
+                    continue;
+                }
+            }
+        }
+        if in_window {
+            write(&line).fatal("could not write the trimmed waveform")?;
+        } else if !trimmed_line.is_empty() && !trimmed_line.starts_with(['$', '#']) {
+            let Some(id) = changed_id(trimmed_line) else {
+                continue;
+            };
+            match positions.get(id) {
+                Some(&position) => values[position] = trimmed_line.to_owned(),
+                None => {
+                    positions.insert(id.to_owned(), values.len());
+                    values.push(trimmed_line.to_owned());
+                }
+            }
+        }
+    }
+    trimmed
+        .flush()
+        .fatal("could not write the trimmed waveform")?;
+    drop(trimmed);
+    std::fs::rename(&trimmed_path, path)
+        .fatal("could not replace the waveform with its trimmed version")
+}
@@ -0,0 +1,129 @@
+//! `gb vcd export`: the value changes of a dump as csv or json, for scripts
+//! and plots that shouldn't have to read vcd themselves:
+//!
+//! ```sh
+//! gb vcd export --signals clk,data_out --format csv build/debug/top.vcd
+//! ```
+//!
+//! csv has a row for every time one of the signals changes, with what each
+//! of them holds then, an empty cell for a signal not assigned yet:
+//!
+//! ```text
+//! time_fs,top.clk,top.data_out[7:0]
+//! 0,0,00000000
+//! 10000000,1,00000001
+//! ```
+//!
+//! json has every signal with its own changes, as `[time_fs, value]` pairs.
+//! times are in femtoseconds either way, whatever the dump's timescale.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{
+    vcd::{Signal, Vcd},
+    Check, GbError,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct Dump<'v> {
+    end_time_fs: u64,
+    signals: Vec<Changes<'v>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Changes<'v> {
+    name: &'v str,
+    width: u32,
+    changes: Vec<(u64, &'v str)>,
+}
+
+/// a csv cell, quoted when it has to be
+fn cell(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn csv(vcd: &Vcd, signals: &[&Signal]) -> String {
+    let mut out = std::iter::once("time_fs".to_owned())
+        .chain(signals.iter().map(|signal| cell(&signal.name)))
+        .collect::<Vec<_>>()
+        .join(",");
+    out.push('\n');
+
+    let mut times = signals
+        .iter()
+        .flat_map(|signal| vcd.changes(signal).iter().map(|(time, _)| *time))
+        .collect::<Vec<_>>();
+    times.sort_unstable();
+    times.dedup();
+    for time in times {
+        let row = std::iter::once(time.to_string())
+            .chain(
+                signals
+                    .iter()
+                    .map(|signal| cell(vcd.value_at(signal, time).unwrap_or_default())),
+            )
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push_str(&row);
+        out.push('\n');
+    }
+    out
+}
+
+fn json(vcd: &Vcd, signals: &[&Signal]) -> Result<String, GbError> {
+    let dump = Dump {
+        end_time_fs: vcd.end_time(),
+        signals: signals
+            .iter()
+            .map(|signal| Changes {
+                name: &signal.name,
+                width: signal.width,
+                changes: vcd
+                    .changes(signal)
+                    .iter()
+                    .map(|(time, value)| (*time, value.as_str()))
+                    .collect(),
+            })
+            .collect(),
+    };
+    // on one line, pretty printing puts every time and value on a line of its own
+    let mut out = serde_json::to_string(&dump).fatal("could not serialize the dump")?;
+    out.push('\n');
+    Ok(out)
+}
+
+pub fn export(
+    dump: &Path,
+    signals: &[String],
+    format: Format,
+    out: Option<&Path>,
+) -> Result<(), GbError> {
+    let vcd = Vcd::load(dump)?;
+    let selected = vcd.select(signals)?;
+    let exported = match format {
+        Format::Csv => csv(&vcd, &selected),
+        Format::Json => json(&vcd, &selected)?,
+    };
+    match out {
+        Some(out) => {
+            std::fs::write(out, exported).fatal(format!("could not write `{}`", out.display()))
+        }
+        None => {
+            print!("{exported}");
+            Ok(())
+        }
+    }
+}
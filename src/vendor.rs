@@ -0,0 +1,188 @@
+//! `gb vendor`: copies every git dependency, and those of the dependencies,
+//! into `vendor/<name>/` at the commit gb.lock pins it to, like `cargo
+//! vendor`. checked in, the project then builds without reaching the network
+//! or gb's cache:
+//!
+//! ```text
+//! vendor/
+//! ├── .gitignore        # the libraries gb analyzes in there
+//! └── uart/
+//!     ├── .gb-vendor    # where the copy came from
+//!     ├── gb.toml
+//!     └── src/uart.vhd
+//! ```
+//!
+//! a dependency is built from its copy whenever the copy's commit is the one
+//! gb.lock has, and fetched as usual otherwise, e.g. after `gb update`, until
+//! `gb vendor` runs again. path dependencies are already on disk and aren't
+//! copied, but their git dependencies are. a copy of a dependency gb.toml no
+//! longer has is removed, anything else in `vendor/` is left alone.
+
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use toml_edit::{value, Document};
+
+use crate::{deps, lock, manifest, report, sources, Check, GbError};
+
+const DIR: &str = "vendor";
+
+/// the file marking a copy of a dependency, with where it came from
+const MARKER: &str = ".gb-vendor";
+
+const MARKER_HEADER: &str =
+    "# copied by `gb vendor`, gb builds from here while gb.lock pins this commit";
+
+/// whether `dir` is a dependency `gb vendor` copied, whose sources aren't the
+/// project's
+pub fn is_vendored(dir: &Path) -> bool {
+    dir.join(MARKER).is_file()
+}
+
+fn marker_commit(dir: &Path) -> Option<String> {
+    let marker = std::fs::read_to_string(dir.join(MARKER)).ok()?;
+    let marker = marker.parse::<Document>().ok()?;
+    marker
+        .get("commit")
+        .and_then(|commit| commit.as_str())
+        .map(str::to_owned)
+}
+
+/// the copy of `name` in `vendor/`, if there's one at `commit`
+pub fn vendored(name: &str, commit: &str) -> Result<Option<PathBuf>, GbError> {
+    let dir = lock::root()?.join(DIR).join(name);
+    match marker_commit(&dir) {
+        Some(vendored) if vendored == commit => Ok(Some(dir)),
+        Some(vendored) => {
+            report::emit(report::warning(format!(
+                "`vendor/{name}` is at {vendored}, but gb.lock pins {commit}, run `gb vendor` to copy it again"
+            )))?;
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+/// copies the files of `from` into `to`, leaving out git's and gb's own
+fn copy_dir(from: &Path, to: &Path) -> Result<(), GbError> {
+    std::fs::create_dir_all(to).fatal(format!("could not create `{}`", to.display()))?;
+    let entries = std::fs::read_dir(from).fatal(format!("could not read `{}`", from.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let skipped = name
+            .to_str()
+            .is_some_and(|name| sources::IGNORED_DIRS.contains(&name) || name == MARKER);
+        if skipped {
+            continue;
+        }
+        if path.is_dir() {
+            copy_dir(&path, &to.join(&name))?;
+        } else {
+            std::fs::copy(&path, to.join(&name)).fatal(format!(
+                "could not copy `{}` to `{}`",
+                path.display(),
+                to.display()
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// the gb.toml of the dependency in `dir`, resolved the way building it does
+fn read_manifest(dir: &Path) -> Result<Document, GbError> {
+    let manifest = dir.join("gb.toml");
+    let mut doc = std::fs::read_to_string(&manifest)
+        .fatal(format!("could not read `{}`", manifest.display()))?
+        .parse::<Document>()
+        .fatal(format!("failed to parse `{}`", manifest.display()))?;
+    deps::in_dir(dir, || manifest::resolve(&mut doc))?;
+    Ok(doc)
+}
+
+/// copies the git dependencies of the project in `base`, and theirs, adding
+/// their names to `vendored`
+fn vendor_within(doc: &Document, base: &Path, vendored: &mut Vec<String>) -> Result<(), GbError> {
+    let root = lock::root()?;
+    for dependency in deps::dependencies(doc)? {
+        if vendored.contains(&dependency.name) {
+            continue;
+        }
+        let deps::Source::Git { url, rev } = &dependency.source else {
+            let dir = dependency.dir(base)?;
+            vendor_within(&read_manifest(&dir)?, &dir, vendored)?;
+            continue;
+        };
+        vendored.push(dependency.name.clone());
+
+        let commit = lock::commit(&dependency.name, url, rev.as_deref())?;
+        let dir = dependency.dir(base)?;
+        let copy = root.join(DIR).join(&dependency.name);
+        // `dir` is the copy already when it's at the locked commit
+        if dir != copy {
+            let _ = std::fs::remove_dir_all(&copy);
+            copy_dir(&dir, &copy)?;
+            let mut marker = Document::new();
+            marker["git"] = value(url.as_str());
+            marker["commit"] = value(&commit);
+            std::fs::write(copy.join(MARKER), format!("{MARKER_HEADER}\n{marker}"))
+                .fatal(format!("could not write `{}`", copy.join(MARKER).display()))?;
+        }
+        eprintln!(
+            "  {}  {}",
+            "[vendor]".blue().bold(),
+            format!(
+                "Vendored `{}` at {} into vendor/{}",
+                dependency.name,
+                commit.get(..7).unwrap_or(&commit),
+                dependency.name
+            )
+            .green()
+            .bold()
+        );
+
+        vendor_within(&read_manifest(&copy)?, &copy, vendored)?;
+    }
+    Ok(())
+}
+
+pub fn vendor(doc: &Document) -> Result<(), GbError> {
+    let mut vendored = Vec::new();
+    vendor_within(doc, Path::new("."), &mut vendored)?;
+    lock::save()?;
+
+    let dir = Path::new(DIR);
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if is_vendored(&path) && !vendored.contains(&name) {
+                std::fs::remove_dir_all(&path)
+                    .fatal(format!("could not remove `{}`", path.display()))?;
+                eprintln!(
+                    "  {}  {}",
+                    "[vendor]".blue().bold(),
+                    format!("Removed vendor/{name}, which gb.toml no longer depends on")
+                        .green()
+                        .bold()
+                );
+            }
+        }
+    }
+
+    if vendored.is_empty() {
+        eprintln!("there are no git dependencies to vendor");
+        return Ok(());
+    }
+    // the dependencies are analyzed where they are, which is in here now
+    std::fs::write(dir.join(".gitignore"), "build/\n")
+        .fatal("could not write vendor/.gitignore")?;
+    eprintln!(
+        "  {}  {}",
+        "[vendor]".blue().bold(),
+        "Check vendor/ in, gb builds the dependencies from it from now on"
+            .green()
+            .bold()
+    );
+    Ok(())
+}
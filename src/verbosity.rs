@@ -0,0 +1,74 @@
+//! How much gb says while it builds. `-q` leaves out the step banners, like
+//! `[1/3]  Analyzing Solution...`, so only ghdl's output, warnings and errors
+//! are left. `-v` also prints every ghdl command before it runs, the way it
+//! could be typed into a shell to reproduce it:
+//!
+//! ```text
+//!   [cmd]  (in build/debug) GB_SEED=3 ghdl -r --std=08 counter_tb
+//! ```
+
+use std::{
+    process::Command,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use colored::Colorize;
+
+use crate::transcript;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set(quiet: bool, verbose: bool) {
+    let verbosity = match (quiet, verbose) {
+        (true, _) => Verbosity::Quiet,
+        (false, true) => Verbosity::Verbose,
+        (false, false) => Verbosity::Normal,
+    };
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn get() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// a step banner, left out with `-q`
+pub fn step(tag: &str, message: &str) {
+    if get() > Verbosity::Quiet {
+        eprintln!("  {}  {}", tag.blue().bold(), message.green().bold());
+    }
+}
+
+/// with `-v`, the command about to run, with the environment and directory
+/// gb gives it
+pub fn echo(command: &Command) {
+    if get() < Verbosity::Verbose {
+        return;
+    }
+    let mut line = String::new();
+    if let Some(dir) = command.get_current_dir() {
+        line.push_str(&format!("(in {}) ", dir.display()));
+    }
+    for (name, value) in command.get_envs() {
+        if let Some(value) = value {
+            line.push_str(&format!(
+                "{}={} ",
+                name.to_string_lossy(),
+                value.to_string_lossy()
+            ));
+        }
+    }
+    line.push_str(&transcript::describe_command(command));
+    eprintln!("  {}  {}", "[cmd]".blue().bold(), line.dimmed());
+}
@@ -0,0 +1,75 @@
+//! Which of ghdl's analysis warnings are on, and whether they fail the build:
+//!
+//! ```toml
+//! werror = true
+//!
+//! [warnings]
+//! disable = ["binding"]          # --warn-no-binding
+//! enable = ["runtime-error"]     # --warn-runtime-error
+//! ```
+//!
+//! the names are those of ghdl's `--warn-<name>` options, which ghdl checks
+//! itself. every analysis gets these, of the targets, `gb test` and the
+//! dependencies alike.
+
+use toml_edit::Document;
+
+use crate::{Check, GbError, Level};
+
+fn names(doc: &Document, key: &str) -> Result<Vec<String>, GbError> {
+    let Some(names) = doc.get("warnings").and_then(|warnings| warnings.get(key)) else {
+        return Ok(vec![]);
+    };
+    let names = names
+        .as_array()
+        .and_then(|names| {
+            names
+                .iter()
+                .map(|name| name.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()
+        })
+        .fatal(format!(
+            "`warnings.{key}` must be an array of warning names"
+        ))?;
+    if let Some(name) = names.iter().find(|name| {
+        name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    }) {
+        Err(GbError {
+            message: format!(
+                "`{name}` in `warnings.{key}` is not a warning name, like \"binding\""
+            ),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(names)
+}
+
+/// whether `werror = true`
+pub fn werror(doc: &Document) -> Result<bool, GbError> {
+    match doc.get("werror") {
+        Some(werror) => werror.as_bool().fatal("`werror` must be true or false"),
+        None => Ok(false),
+    }
+}
+
+/// the `--warn-` flags of `[warnings]`, disabled ones first
+pub fn analyze_flags(doc: &Document) -> Result<Vec<String>, GbError> {
+    let disable = names(doc, "disable")?;
+    let enable = names(doc, "enable")?;
+    if let Some(name) = disable.iter().find(|name| enable.contains(name)) {
+        Err(GbError {
+            message: format!("warning `{name}` is both disabled and enabled in `[warnings]`"),
+            level: Level::Fatal,
+            source: None,
+        })?;
+    }
+    Ok(disable
+        .iter()
+        .map(|name| format!("--warn-no-{name}"))
+        .chain(enable.iter().map(|name| format!("--warn-{name}")))
+        .collect())
+}
@@ -0,0 +1,153 @@
+//! `gb watch`: re-run a target every time one of its sources changes.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use colored::Colorize;
+
+use crate::{local, manifest, sources, Check, Commands, GbError, GlobalOptions};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// the files of the target being watched, or every vhdl source in the
+/// project when the manifest can't say which those are (yet).
+fn watched_files(target: Option<&str>) -> Vec<PathBuf> {
+    let target_files = || -> Option<Vec<PathBuf>> {
+        let (doc, origins) = manifest::load().ok()?;
+        let target = match target {
+            Some(target) => target.to_owned(),
+            None => crate::default_target(&doc).ok()??,
+        };
+        let target_info = doc.get("target")?.get(&target)?;
+        let files = crate::resolve_target_files(&target, target_info).ok()?;
+        // the files gb.toml includes are as much a part of it
+        Some(
+            files
+                .into_iter()
+                .map(PathBuf::from)
+                .chain(origins.files)
+                .collect(),
+        )
+    };
+
+    let mut watched = target_files().unwrap_or_else(|| sources::find_vhdl_sources(Path::new(".")));
+    // the manifest, what's laid over it, and `gb use` switching targets
+    watched.push(PathBuf::from("gb.toml"));
+    watched.push(PathBuf::from(local::FILE));
+    watched.push(PathBuf::from(".gb/state"));
+    watched
+}
+
+fn snapshot(watched: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    let mut snapshot = BTreeMap::new();
+    for path in watched {
+        if let Ok(modified) = std::fs::metadata(path).and_then(|meta| meta.modified()) {
+            snapshot.insert(path.clone(), modified);
+        }
+    }
+    snapshot
+}
+
+/// blocks until something in `watched` differs from `before`, and then stays quiet for `debounce`
+fn wait_for_change(
+    watched: &[PathBuf],
+    before: &BTreeMap<PathBuf, SystemTime>,
+    debounce: Duration,
+) {
+    while snapshot(watched) == *before {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    let mut last = snapshot(watched);
+    loop {
+        std::thread::sleep(debounce);
+        let now = snapshot(watched);
+        if now == last {
+            return;
+        }
+        last = now;
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+/// runs the `--run-on-success` hook. a failing hook is reported, but
+/// it doesn't stop the watch loop.
+fn run_hook(hook: &str) -> Result<(), GbError> {
+    eprintln!(
+        "  {}  {}",
+        "[watch]".blue().bold(),
+        format!("Running `{hook}`...").green().bold()
+    );
+    let status = shell(hook)
+        .status()
+        .fatal(format!("could not spawn the run-on-success hook `{hook}`"))?;
+    if !status.success() {
+        eprintln!(
+            "  {}  {}",
+            "[watch]".blue().bold(),
+            format!("`{hook}` exited with {status}").yellow().bold()
+        );
+    }
+    Ok(())
+}
+
+pub fn watch(
+    target: Option<&str>,
+    run_on_success: Option<&str>,
+    clear: bool,
+    debounce: Duration,
+    options: &GlobalOptions,
+) -> Result<(), GbError> {
+    let run = Commands::Run {
+        target: target.map(ToOwned::to_owned),
+        vcd: None,
+        ghw: None,
+        fst: None,
+        scenario: None,
+        generics: vec![],
+        jobs: None,
+        sim_args: vec![],
+    };
+
+    loop {
+        if clear {
+            // clear the screen and move the cursor back to the top
+            eprint!("\x1B[2J\x1B[1;1H");
+        }
+        let watched = watched_files(target);
+        let before = snapshot(&watched);
+
+        match crate::validate(&run, options) {
+            Ok(()) => {
+                if let Some(hook) = run_on_success {
+                    run_hook(hook)?;
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+
+        eprintln!(
+            "  {}  {}",
+            "[watch]".blue().bold(),
+            format!("Waiting for changes to {} files...", watched.len())
+                .green()
+                .bold()
+        );
+        wait_for_change(&watched, &before, debounce);
+    }
+}
@@ -0,0 +1,261 @@
+//! The waveform a simulation dumps: ghdl can write VCD (`--vcd`), its own GHW
+//! (`--wave`), which keeps the full vhdl types, and FST (`--fst`), which is
+//! much smaller. gtkwave reads all three.
+
+use std::path::{Path, PathBuf};
+
+use crate::{vcd, Check, GbError, Level};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveFormat {
+    Vcd,
+    Ghw,
+    Fst,
+}
+
+impl WaveFormat {
+    const ALL: [WaveFormat; 3] = [WaveFormat::Vcd, WaveFormat::Ghw, WaveFormat::Fst];
+
+    /// also the name used for `wave-format` in gb.toml
+    pub fn extension(self) -> &'static str {
+        match self {
+            WaveFormat::Vcd => "vcd",
+            WaveFormat::Ghw => "ghw",
+            WaveFormat::Fst => "fst",
+        }
+    }
+
+    /// the `ghdl -r` option writing this format
+    fn option(self) -> &'static str {
+        match self {
+            WaveFormat::Vcd => "--vcd",
+            WaveFormat::Ghw => "--wave",
+            WaveFormat::Fst => "--fst",
+        }
+    }
+
+    fn parse(format: &str) -> Option<WaveFormat> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.extension().eq_ignore_ascii_case(format))
+    }
+
+    fn of_path(path: &Path) -> Option<WaveFormat> {
+        Self::parse(path.extension()?.to_str()?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Waveform {
+    pub format: WaveFormat,
+    /// relative to `build/<profile>/`, where the simulation runs
+    pub path: PathBuf,
+}
+
+impl Waveform {
+    pub fn vcd(path: impl Into<PathBuf>) -> Waveform {
+        Waveform {
+            format: WaveFormat::Vcd,
+            path: path.into(),
+        }
+    }
+
+    pub fn run_flag(&self) -> String {
+        format!("{}={}", self.format.option(), self.path.to_string_lossy())
+    }
+
+    /// where the dump ends up once the simulation wrote it
+    pub fn built_path(&self) -> PathBuf {
+        crate::profile::dir().join(&self.path)
+    }
+}
+
+/// the waveform asked for on the command line with `--vcd`, `--wave` or `--fst`
+pub fn from_flags(
+    vcd: Option<&PathBuf>,
+    ghw: Option<&PathBuf>,
+    fst: Option<&PathBuf>,
+) -> Option<Waveform> {
+    [
+        (vcd, WaveFormat::Vcd),
+        (ghw, WaveFormat::Ghw),
+        (fst, WaveFormat::Fst),
+    ]
+    .into_iter()
+    .find_map(|(path, format)| {
+        path.map(|path| Waveform {
+            format,
+            path: path.clone(),
+        })
+    })
+}
+
+/// the waveform a target dumps according to gb.toml: `wave-name` and
+/// `wave-format`, or the older `vcd-name`.
+pub fn from_manifest(
+    target: &str,
+    target_info: &toml_edit::Item,
+) -> Result<Option<Waveform>, GbError> {
+    let key = |key: &str| -> Result<Option<&str>, GbError> {
+        target_info
+            .get(key)
+            .map(|value| {
+                value
+                    .as_str()
+                    .fatal(format!("`{key}` of {target} must be a string"))
+            })
+            .transpose()
+    };
+
+    let format = match key("wave-format")? {
+        Some(format) => Some(WaveFormat::parse(format).fatal(format!(
+            "unknown `wave-format = \"{format}\"` in {target}, expected one of {}",
+            WaveFormat::ALL.map(WaveFormat::extension).join(", ")
+        ))?),
+        None => None,
+    };
+
+    match (key("wave-name")?, format) {
+        (Some(name), format) => {
+            let path = PathBuf::from(name);
+            let format = format
+                .or_else(|| WaveFormat::of_path(&path))
+                .fatal(format!(
+                    "can't tell the format of `{name}` in {target}, set `wave-format`"
+                ))?;
+            Ok(Some(Waveform { format, path }))
+        }
+        (None, Some(format)) => Ok(Some(Waveform {
+            format,
+            path: PathBuf::from(format!("{target}.{}", format.extension())),
+        })),
+        (None, None) => Ok(key("vcd-name")?.map(Waveform::vcd)),
+    }
+}
+
+/// `gb probe` and the image export read the dump themselves, which only works
+/// for vcd, so targets dumping something else get a `<target>.vcd` on the side.
+pub fn readable(waveform: Option<Waveform>, target: &str) -> Waveform {
+    match waveform {
+        Some(waveform) if waveform.format == WaveFormat::Vcd => waveform,
+        _ => Waveform::vcd(format!("{target}.vcd")),
+    }
+}
+
+/// whether `program` is gtkwave, which gb knows a few more tricks for
+pub fn is_gtkwave(program: &str) -> bool {
+    Path::new(program)
+        .file_stem()
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("gtkwave"))
+}
+
+/// what gtkwave opens along with the dump: `gtkw`, a save file keeping the
+/// signals and layout picked the last time, and `gtkwave-script`, a tcl
+/// script it runs on startup
+#[derive(Debug, Clone, Default)]
+pub struct Gtkwave {
+    save: Option<PathBuf>,
+    script: Option<PathBuf>,
+}
+
+impl Gtkwave {
+    pub fn from_manifest(target: &str, target_info: &toml_edit::Item) -> Result<Gtkwave, GbError> {
+        let path = |key: &str| -> Result<Option<PathBuf>, GbError> {
+            target_info
+                .get(key)
+                .map(|path| {
+                    path.as_str()
+                        .map(PathBuf::from)
+                        .fatal(format!("`{key}` of {target} must be a path"))
+                })
+                .transpose()
+        };
+        Ok(Gtkwave {
+            save: path("gtkw")?,
+            script: path("gtkwave-script")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.save.is_none() && self.script.is_none()
+    }
+
+    /// the options passing both on. a save file that doesn't exist yet is
+    /// where gtkwave saves the layout to, but the script has to be there.
+    pub fn args(&self) -> Result<Vec<String>, GbError> {
+        let mut args = Vec::new();
+        if let Some(save) = &self.save {
+            args.push(format!("--save={}", save.display()));
+        }
+        if let Some(script) = &self.script {
+            if !script.is_file() {
+                Err(GbError {
+                    message: format!("the `gtkwave-script` `{}` doesn't exist", script.display()),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+            args.push(format!("--script={}", script.display()));
+        }
+        Ok(args)
+    }
+}
+
+/// `dump-start` and `dump-stop` of a target, in femtoseconds. ghdl has no way
+/// to dump only part of a simulation, so the dump is trimmed once it's written.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DumpWindow {
+    pub start: Option<u64>,
+    pub stop: Option<u64>,
+}
+
+impl DumpWindow {
+    pub fn from_manifest(
+        target: &str,
+        target_info: &toml_edit::Item,
+    ) -> Result<DumpWindow, GbError> {
+        let time = |key: &str| -> Result<Option<u64>, GbError> {
+            target_info
+                .get(key)
+                .map(|time| {
+                    time.as_str().and_then(vcd::parse_time).fatal(format!(
+                        "`{key}` of {target} must be a time, like \"1.5us\""
+                    ))
+                })
+                .transpose()
+        };
+        let window = DumpWindow {
+            start: time("dump-start")?,
+            stop: time("dump-stop")?,
+        };
+        if let (Some(start), Some(stop)) = (window.start, window.stop) {
+            if start > stop {
+                Err(GbError {
+                    message: format!("`dump-start` of {target} is after its `dump-stop`"),
+                    level: Level::Fatal,
+                    source: None,
+                })?;
+            }
+        }
+        Ok(window)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start.is_none() && self.stop.is_none()
+    }
+
+    /// trims the dump a simulation just wrote to the window
+    pub fn apply(&self, waveform: &Waveform) -> Result<(), GbError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        if waveform.format != WaveFormat::Vcd {
+            eprintln!(
+                "`dump-start` and `dump-stop` only apply to vcd dumps, `{}` is kept whole",
+                waveform.path.display()
+            );
+            return Ok(());
+        }
+        vcd::trim(&waveform.built_path(), self.start, self.stop)
+    }
+}
@@ -0,0 +1,123 @@
+//! `gb init` on a terminal, without `--template`: asks what the project should
+//! look like instead of writing a gb.toml that needs editing right away.
+//!
+//! ```text
+//!   [init]  project name [blinky]:
+//!   [init]  default target [blinky]:
+//!   [init]  vhdl standard, or `ghdl` for its default [ghdl]: 08
+//!   [init]  waveform viewer [gtkwave]: surfer
+//!   [init]  sample testbench, counter, alu, uart or none [counter]:
+//! ```
+//!
+//! enter takes the default in brackets. piped into, or with `--template`,
+//! `gb init` doesn't ask anything.
+
+use std::io::{BufRead, IsTerminal};
+
+use clap::ValueEnum;
+use colored::Colorize;
+
+use crate::{
+    scaffold::{self, Setup, Template},
+    Check, GbError, Level, VHDL_STANDARDS,
+};
+
+/// whether someone is there to answer
+pub fn interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// asks until `valid` takes the answer, printing why it didn't otherwise
+fn question(
+    question: &str,
+    default: &str,
+    valid: impl Fn(&str) -> Result<(), String>,
+) -> Result<String, GbError> {
+    loop {
+        eprint!("  {}  {question} [{default}]: ", "[init]".blue().bold());
+        let mut answer = String::new();
+        let read = std::io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .fatal("could not read the answer")?;
+        if read == 0 {
+            eprintln!();
+            Err(GbError {
+                message: "gb init was cancelled, nothing was written".to_owned(),
+                level: Level::Fatal,
+                source: None,
+            })?;
+        }
+        let answer = match answer.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match valid(answer) {
+            Ok(()) => return Ok(answer.to_owned()),
+            Err(problem) => eprintln!("      {problem}"),
+        }
+    }
+}
+
+/// target names become toml keys and directories under build/, and the
+/// project name becomes the target name
+fn target_name(target: &str) -> Result<(), String> {
+    let valid = target
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "`{target}` won't do, use letters, digits, `-` and `_`"
+        ))
+    }
+}
+
+fn template(answer: &str) -> Result<Template, String> {
+    match answer {
+        "none" | "no" | "n" => Ok(Template::Empty),
+        "yes" | "y" => Ok(Template::Counter),
+        answer => Template::from_str(answer, true)
+            .map_err(|_| format!("`{answer}` isn't one of counter, alu, uart or none")),
+    }
+}
+
+/// asks for everything gb.toml needs, `directory` names the project unless
+/// told otherwise
+pub fn ask(directory: &str) -> Result<Setup, GbError> {
+    let project = question("project name", directory, target_name)?;
+    let target = question("default target", &project, target_name)?;
+    let std = question("vhdl standard, or `ghdl` for its default", "ghdl", |std| {
+        if std == "ghdl" || VHDL_STANDARDS.contains(&std) {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected `ghdl` or one of {}",
+                VHDL_STANDARDS.join(", ")
+            ))
+        }
+    })?;
+    // a command like `myviewer {file}` works too, but it goes into a toml string
+    let viewer = question("waveform viewer", "gtkwave", |viewer| {
+        if viewer.contains(['"', '\\']) {
+            Err("edit quotes and backslashes into gb.toml afterwards".to_owned())
+        } else {
+            Ok(())
+        }
+    })?;
+    let sample = question(
+        "sample testbench, counter, alu, uart or none",
+        "counter",
+        |answer| template(answer).map(drop),
+    )?;
+
+    Ok(Setup {
+        // a project name vhdl can't use still makes a fine target name
+        entity: scaffold::entity_name(&project).unwrap_or_else(|_| "top".to_owned()),
+        target,
+        std: (std != "ghdl").then_some(std),
+        viewer,
+        template: template(&sample).unwrap_or_default(),
+    })
+}
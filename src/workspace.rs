@@ -0,0 +1,131 @@
+//! Workspaces: a gb.toml listing member projects, each a directory with its
+//! own gb.toml and its own `build/`.
+//!
+//! ```toml
+//! [workspace]
+//! members = ["core", "periph", "tb"]
+//! ```
+//!
+//! `gb run -p periph` runs from anywhere in the workspace as if it was run in
+//! `periph/`. a member can use what another one builds with `uses = ["core"]`,
+//! on a target or in `[default]`: the default target of `core` is analyzed into
+//! a library named `core`, in `core/build/lib/`, which is then available as
+//! `library core;`.
+
+use std::path::{Path, PathBuf};
+
+use toml_edit::Document;
+
+use crate::{deps, BuildOptions, Check, GbError, Level};
+
+fn read_manifest(dir: &Path) -> Result<Document, GbError> {
+    let manifest = dir.join("gb.toml");
+    std::fs::read_to_string(&manifest)
+        .fatal(format!("could not read `{}`", manifest.display()))?
+        .parse::<Document>()
+        .fatal(format!("failed to parse `{}`", manifest.display()))
+}
+
+/// the members listed in a `[workspace]` table
+pub fn members(doc: &Document) -> Result<Option<Vec<String>>, GbError> {
+    let Some(workspace) = doc.get("workspace") else {
+        return Ok(None);
+    };
+    let members = workspace
+        .get("members")
+        .and_then(|members| members.as_array())
+        .fatal("`workspace.members` must be an array of member directories")?
+        .iter()
+        .map(|member| {
+            member
+                .as_str()
+                .map(ToOwned::to_owned)
+                .fatal("`workspace.members` must only contain strings")
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(members))
+}
+
+/// the closest directory, from the current one upwards, whose gb.toml has a `[workspace]`
+fn find_root() -> Result<Option<(PathBuf, Vec<String>)>, GbError> {
+    let cwd = std::env::current_dir().fatal("cannot get the current directory")?;
+    for dir in cwd.ancestors() {
+        if !dir.join("gb.toml").exists() {
+            continue;
+        }
+        if let Some(members) = members(&read_manifest(dir)?)? {
+            return Ok(Some((dir.to_owned(), members)));
+        }
+    }
+    Ok(None)
+}
+
+fn unknown_member(member: &str, members: &[String]) -> GbError {
+    eprintln!("the workspace has the following members");
+    for (pos, known) in members.iter().enumerate() {
+        eprintln!("  {}. {known}", pos + 1);
+    }
+    GbError {
+        message: format!("`{member}` is not a member of the workspace"),
+        level: Level::Fatal,
+        source: None,
+    }
+}
+
+/// the directory of `member`
+fn member_dir(member: &str) -> Result<PathBuf, GbError> {
+    let (root, members) = find_root()?.fatal(format!(
+        "`{member}` was asked for, but there is no gb.toml with a `[workspace]` here or above"
+    ))?;
+    if !members.iter().any(|known| known == member) {
+        return Err(unknown_member(member, &members));
+    }
+    Ok(root.join(member))
+}
+
+/// `-p <member>`: carries on as if gb was started in the member's directory
+pub fn enter(member: &str) -> Result<(), GbError> {
+    let dir = member_dir(member)?;
+    std::env::set_current_dir(&dir).fatal(format!(
+        "could not enter the workspace member `{}`",
+        dir.display()
+    ))
+}
+
+/// the error for running a target at the root of a workspace
+pub fn pick_member(members: &[String]) -> GbError {
+    eprintln!("this is a workspace with the following members");
+    for (pos, member) in members.iter().enumerate() {
+        eprintln!("  {}. {member}", pos + 1);
+    }
+    GbError {
+        message: "pick a member to build with `-p <member>`".to_owned(),
+        level: Level::Fatal,
+        source: None,
+    }
+}
+
+/// the members a target uses, from its `uses` or the one in `[default]`
+pub fn uses(doc: &Document, target_info: &toml_edit::Item) -> Result<Vec<String>, GbError> {
+    let Some(uses) = target_info
+        .get("uses")
+        .or_else(|| doc.get("default").and_then(|default| default.get("uses")))
+    else {
+        return Ok(vec![]);
+    };
+    uses.as_array()
+        .fatal("`uses` must be an array of workspace members")?
+        .iter()
+        .map(|member| {
+            member
+                .as_str()
+                .map(ToOwned::to_owned)
+                .fatal("`uses` must only contain strings")
+        })
+        .collect()
+}
+
+/// builds `member` as a library, see `deps::library`
+pub fn library(member: &str, build: &BuildOptions) -> Result<Vec<PathBuf>, GbError> {
+    deps::library(member, &member_dir(member)?, build)
+}